@@ -0,0 +1,139 @@
+//! Write reduction of `RedisCache` on a steady-state refresh workload.
+//!
+//! Every round refreshes all keys through `cachified`, but only a few values
+//! actually change. With the default `JsonCodec`, unchanged values only get
+//! their metadata rewritten. A codec that can't compare encoded values shows
+//! the baseline where every refresh rewrites the whole entry.
+//!
+//! Requires a Redis instance, `redis://localhost:6379` unless `REDIS_URL` is set:
+//!
+//! ```text
+//! cargo bench --bench redis_writes --features redis
+//! ```
+
+use cachified::codec::{Codec, JsonCodec};
+use cachified::{cachified, CacheEntry, CachifiedOptionsBuilder, RedisCache};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const KEYS: usize = 1_000;
+const ROUNDS: usize = 20;
+/// Every this many keys, one value changes per round
+const CHANGE_EVERY: usize = 20;
+const VALUE_SIZE: usize = 4 * 1024;
+
+/// JSON codec that doesn't let the cache compare encoded values
+#[derive(Clone)]
+struct FullWriteCodec;
+
+impl Codec<String> for FullWriteCodec {
+    fn encode(&self, entry: &CacheEntry<String>) -> cachified::Result<Vec<u8>> {
+        JsonCodec.encode(entry)
+    }
+
+    fn decode(&self, data: Vec<u8>) -> cachified::Result<CacheEntry<String>> {
+        JsonCodec.decode(data)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+    let mut conn = redis::Client::open(url.as_str())?.get_multiplexed_async_connection().await?;
+
+    println!("{KEYS} keys, {ROUNDS} rounds, 1 in {CHANGE_EVERY} values changing per round, {VALUE_SIZE} byte values");
+    println!("{:<12} {:>10} {:>12} {:>16}", "codec", "time", "full writes", "metadata writes");
+
+    let cache = RedisCache::with_prefix(&url, "cachified-bench-json:".to_string()).await?;
+    let stats = run(cache, &mut conn).await?;
+    println!("{:<12} {stats}", "json");
+
+    let cache = RedisCache::with_prefix(&url, "cachified-bench-full:".to_string())
+        .await?
+        .with_codec(FullWriteCodec);
+    let stats = run(cache, &mut conn).await?;
+    println!("{:<12} {stats}", "full-write");
+
+    Ok(())
+}
+
+struct Stats {
+    elapsed: Duration,
+    full_writes: u64,
+    metadata_writes: u64,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>8.0?} {:>12} {:>16}",
+            self.elapsed, self.full_writes, self.metadata_writes
+        )
+    }
+}
+
+/// Populate the cache, then refresh every key for a number of rounds
+async fn run<K>(cache: RedisCache<String, K>, conn: &mut redis::aio::MultiplexedConnection) -> cachified::Result<Stats>
+where
+    K: Codec<String> + Clone + 'static,
+{
+    use cachified::Cache;
+
+    cache.clear().await;
+    refresh_all(&cache, 0).await?;
+
+    let before = command_calls(conn).await?;
+    let start = Instant::now();
+    for round in 1..=ROUNDS {
+        refresh_all(&cache, round).await?;
+    }
+    let elapsed = start.elapsed();
+    let after = command_calls(conn).await?;
+    cache.clear().await;
+
+    let delta = |command: &str| after.get(command).unwrap_or(&0) - before.get(command).unwrap_or(&0);
+    Ok(Stats {
+        elapsed,
+        full_writes: delta("set"),
+        metadata_writes: delta("setrange"),
+    })
+}
+
+async fn refresh_all<K>(cache: &RedisCache<String, K>, round: usize) -> cachified::Result<()>
+where
+    K: Codec<String> + Clone + 'static,
+{
+    for key in 0..KEYS {
+        // Values only change for the keys whose turn it is in this round
+        let version = if key % CHANGE_EVERY == round % CHANGE_EVERY { round } else { 0 };
+        let value = format!("{key}:{version}:{}", "x".repeat(VALUE_SIZE));
+
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), format!("key-{key}"))
+                .ttl(Duration::from_secs(600))
+                .force_fresh(true)
+                .get_fresh_value_once(move || async move { Ok(value) }),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Get how often Redis executed each command, including from scripts
+async fn command_calls(conn: &mut redis::aio::MultiplexedConnection) -> cachified::Result<HashMap<String, u64>> {
+    let info: String = redis::cmd("INFO")
+        .arg("commandstats")
+        .query_async(conn)
+        .await
+        .map_err(cachified::CachifiedError::cache_source)?;
+
+    Ok(info
+        .lines()
+        .filter_map(|line| {
+            let (command, stats) = line.strip_prefix("cmdstat_")?.split_once(':')?;
+            let calls = stats.split(',').find_map(|stat| stat.strip_prefix("calls="))?;
+            Some((command.to_string(), calls.parse().ok()?))
+        })
+        .collect())
+}
@@ -0,0 +1,60 @@
+use cachified::{cachified, cachified_map_entry, CachifiedOptionsBuilder, MokaCache};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("=== Collection Values Example ===");
+
+    // Example 1: Reading single fields of a cached map
+    println!("\n1. Per-field access to a cached map:");
+    let prices: MokaCache<HashMap<String, u32>> = MokaCache::new(1000);
+    let fetches = Arc::new(AtomicUsize::new(0));
+
+    for product in ["apple", "pear", "plum"] {
+        let fetches = fetches.clone();
+        let price = cachified_map_entry(
+            CachifiedOptionsBuilder::new(prices.clone(), "prices")
+                .ttl(Duration::from_secs(60))
+                .get_fresh_value(move || {
+                    let fetches = fetches.clone();
+                    async move {
+                        fetches.fetch_add(1, Ordering::SeqCst);
+                        println!("   Fetching all prices...");
+                        Ok(HashMap::from([
+                            ("apple".to_string(), 120),
+                            ("pear".to_string(), 95),
+                        ]))
+                    }
+                }),
+            product,
+        ).await?;
+
+        match price {
+            Some(price) => println!("   {}: {} cents", product, price),
+            None => println!("   {}: not for sale", product),
+        }
+    }
+    println!("   The map was fetched {} time(s)", fetches.load(Ordering::SeqCst));
+
+    // Example 2: Indexing a cached Vec after a regular cachified call
+    println!("\n2. Indexing a cached Vec:");
+    let leaderboards: MokaCache<Vec<String>> = MokaCache::new(1000);
+
+    for rank in [0, 2] {
+        let leaderboard: Vec<String> = cachified(
+            CachifiedOptionsBuilder::new(leaderboards.clone(), "leaderboard")
+                .ttl(Duration::from_secs(60))
+                .get_fresh_value(|| async {
+                    println!("   Fetching leaderboard...");
+                    Ok(vec!["marvin".to_string(), "ferris".to_string(), "corro".to_string()])
+                })
+        ).await?;
+
+        println!("   Rank {}: {:?}", rank + 1, leaderboard.get(rank));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use cachified::{CachifiedDefaults, MokaCache, Result};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[derive(Clone, Debug)]
+struct User {
+    id: u64,
+    name: String,
+}
+
+/// A repository whose reads go through a shared cache
+#[derive(Clone)]
+struct UserRepository {
+    cache: CachifiedDefaults<User, MokaCache<User>>,
+}
+
+impl UserRepository {
+    fn new() -> Self {
+        Self {
+            cache: CachifiedDefaults::new(MokaCache::new(1000))
+                .ttl(Duration::from_secs(300)) // 5 minutes
+                .stale_while_revalidate(Duration::from_secs(60))
+                .fallback_to_cache(true),
+        }
+    }
+
+    async fn get_user(&self, id: u64) -> Result<User> {
+        self.cache
+            .get(format!("user:{id}"), move || async move {
+                println!("   Loading user {id} from the database...");
+                sleep(Duration::from_millis(200)).await; // Simulate a slow query
+                Ok(User {
+                    id,
+                    name: format!("User {id}"),
+                })
+            })
+            .await
+    }
+}
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let users = UserRepository::new();
+
+    println!("=== Repository Demo ===");
+
+    println!("\n1. First lookup loads the user:");
+    let user = users.get_user(1).await?;
+    println!("   Got: {user:?}");
+
+    println!("\n2. Second lookup is served from the cache:");
+    let user = users.get_user(1).await?;
+    println!("   Got: {user:?}");
+
+    println!("\n3. Clones of the repository share the cache:");
+    let handle = users.clone();
+    let user = tokio::spawn(async move { handle.get_user(1).await }).await??;
+    println!("   Got user {} ({})", user.id, user.name);
+
+    println!("\n4. Other users are loaded separately:");
+    let user = users.get_user(2).await?;
+    println!("   Got: {user:?}");
+
+    Ok(())
+}
@@ -61,15 +61,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n4. Validation Failure Recovery:");
     
     // First, let's put invalid data in cache manually (simulating corrupted cache)
-    string_cache.set("corrupted-data", cachified::CacheEntry {
-        value: "".to_string(), // Empty string - will fail validation
-        metadata: cachified::CacheMetadata {
-            created_time: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap(),
-            ttl: Some(Duration::from_secs(300)),
-        }
-    }).await?;
+    // Empty string - will fail validation
+    string_cache.set(
+        "corrupted-data",
+        cachified::CacheEntry::new("".to_string(), Some(Duration::from_secs(300))),
+    ).await?;
     
     // Now try to get it with validation - should fetch fresh value
     let recovered_value: String = cachified(
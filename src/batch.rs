@@ -0,0 +1,230 @@
+//! Batched caching for warming many keys at once.
+//!
+//! This module provides [`cachified_many`] and [`cachified_many_keyed`], which
+//! read all keys in a single batch, fetch fresh values for the misses with a
+//! single call and write them back in a single batch.
+
+use crate::{
+    check_async, passes_check, staged, Cache, CacheEntry, CachifiedError, CachifiedOptions, CachifiedOptionsBuilder,
+    Result, Stage, WritePolicy,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+/// Get many values at once, fetching all missing ones in a single batch
+///
+/// The entries are read and written with the cache and the TTL, write and
+/// validation options of `options`, like by `cachified`. The key of `options`
+/// isn't used, as every value has its own key from `keys`. Options that only
+/// concern serving a single value, such as `stale_while_revalidate` serving or
+/// `fallback_to_cache`, are ignored.
+///
+/// All keys are read with one [`Cache::get_batch_if_fresh`] call, which is a
+/// single round trip on backends that batch reads. Freshness is computed
+/// client-side from the returned entries at the time read from the clock of
+/// `options`, which is also the creation time of the written entries. Cached
+/// values failing the cached value validator count as missing. The keys that
+/// are missing or expired are passed to `get_fresh_values` in one call, which
+/// must return exactly one value per key in the same order. Fresh values are
+/// checked with the fresh value validator and written in one
+/// [`Cache::set_many`] call.
+///
+/// # Returns
+///
+/// Returns one value per key, in the same order as `keys`, or an error if
+/// `get_fresh_values` fails, returns the wrong number of values or returns a
+/// value failing validation.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_many, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let names: Vec<String> = cachified_many(
+///     CachifiedOptionsBuilder::new(&cache, "users").ttl(Duration::from_secs(60)),
+///     &["user-1", "user-2"],
+///     |missing| async move {
+///         // A single upstream call for all missing keys
+///         Ok(missing.iter().map(|key| format!("name of {}", key)).collect())
+///     },
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_many<T, C, F, Fut>(
+    options: CachifiedOptionsBuilder<T, C>,
+    keys: &[&str],
+    get_fresh_values: F,
+) -> Result<Vec<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+    F: FnOnce(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    // Delegates to the keyed variant, which only differs in how fresh values are matched to keys
+    let values = cachified_many_keyed(options, keys, |missing| async move {
+        let fresh_values = get_fresh_values(missing.clone()).await?;
+        if fresh_values.len() != missing.len() {
+            return Err(CachifiedError::fresh_value(format!(
+                "Batch fresh value function returned {} values for {} keys",
+                fresh_values.len(),
+                missing.len()
+            )));
+        }
+        Ok(missing.into_iter().zip(fresh_values).collect())
+    })
+    .await?;
+
+    Ok(values.into_iter().flatten().collect())
+}
+
+/// Get many values at once, fetching all missing ones in a single batch keyed by cache key
+///
+/// Like [`cachified_many`], but `get_fresh_values` returns the fresh values
+/// keyed by cache key, which suits upstream batch APIs that don't return a
+/// value for every requested key, e.g. a database query by ids. Keys missing
+/// from the returned map are `None` in the result and aren't cached, so they
+/// are requested again by the next call. Entries for keys that weren't
+/// requested are ignored.
+///
+/// # Returns
+///
+/// Returns one optional value per key, in the same order as `keys`, or an
+/// error if `get_fresh_values` fails or returns a value failing validation.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_many_keyed, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let names: Vec<Option<String>> = cachified_many_keyed(
+///     CachifiedOptionsBuilder::new(&cache, "users").ttl(Duration::from_secs(60)),
+///     &["user-1", "user-404"],
+///     |missing| async move {
+///         // Unknown users are left out
+///         Ok(missing
+///             .into_iter()
+///             .filter(|key| key != "user-404")
+///             .map(|key| {
+///                 let name = format!("name of {}", key);
+///                 (key, name)
+///             })
+///             .collect())
+///     },
+/// ).await?;
+/// assert_eq!(names, vec![Some("name of user-1".to_string()), None]);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_many_keyed<T, C, F, Fut>(
+    options: CachifiedOptionsBuilder<T, C>,
+    keys: &[&str],
+    get_fresh_values: F,
+) -> Result<Vec<Option<T>>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+    F: FnOnce(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<HashMap<String, T>>>,
+{
+    let options = options.build(());
+    let served = async {
+        let write_policy = WritePolicy::from_options(&options);
+        let now = options.clock.now();
+        let mut values = read_fresh(&options, keys, now).await?;
+
+        let misses: Vec<usize> = (0..keys.len()).filter(|&index| values[index].is_none()).collect();
+
+        if !misses.is_empty() {
+            let missing_keys = misses.iter().map(|&index| keys[index].to_string()).collect();
+            let fresh_values = get_fresh_values(missing_keys)
+                .await
+                .map_err(|e| e.with_stage(Stage::GetFreshValue))?;
+
+            let mut entries = Vec::with_capacity(misses.len());
+            for index in misses {
+                let value = fresh_values.get(keys[index]).cloned();
+                if let Some(value) = &value {
+                    check_fresh(&options, value).await?;
+                    entries.extend(fresh_entry(&write_policy, keys[index], value.clone(), now));
+                }
+                values[index] = value;
+            }
+            write_entries(&options.cache, entries).await;
+        }
+
+        Ok(values)
+    };
+    served.await.map_err(|e| staged(e, options.error_stage))
+}
+
+/// Read the values of all keys that are cached, not expired at `now` and pass the cached value validators
+async fn read_fresh<T, C>(options: &CachifiedOptions<T, (), C>, keys: &[&str], now: Duration) -> Result<Vec<Option<T>>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    let entries = options.cache.get_batch_if_fresh(keys, now).await;
+    let mut values = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let value = match entry {
+            Some(entry) if passes_check(&options.check_cached_value, &options.check_value_async, &entry.value).await? => {
+                Some(entry.value)
+            }
+            _ => None,
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Check a fresh value against the fresh value validators
+async fn check_fresh<T, C>(options: &CachifiedOptions<T, (), C>, value: &T) -> Result<()>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    if let Some(validator) = &options.check_fresh_value {
+        validator.check(value).map_err(|e| e.with_stage(Stage::ValidateFreshValue))?;
+    }
+    check_async(&options.check_value_async, value, Stage::ValidateFreshValue).await
+}
+
+/// Build the entry to write for a fresh value, or `None` if the write policy doesn't cache it
+fn fresh_entry<T: Clone>(
+    write_policy: &WritePolicy<T>,
+    key: &str,
+    value: T,
+    now: Duration,
+) -> Option<(String, CacheEntry<T>)> {
+    let metadata = write_policy.metadata(&value, now, None)?;
+    Some((key.to_string(), CacheEntry::with_metadata(value, metadata)))
+}
+
+/// Write the entries of fresh values in a single batch
+///
+/// Write failures are ignored like for single values, the values are still
+/// returned to the caller.
+async fn write_entries<T, C>(cache: &C, entries: Vec<(String, CacheEntry<T>)>)
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    if !entries.is_empty() {
+        let _ = cache.set_many(entries).await;
+    }
+}
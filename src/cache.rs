@@ -2,17 +2,63 @@
 //!
 //! This module provides the cache abstraction and concrete implementations.
 //! The main implementations include Moka (in-memory) and Redis (distributed).
+//! [`MemcachedCache`] stores entries in Memcached with the "memcached" feature.
+//! [`DynamoDbCache`] stores entries in a DynamoDB table with the "dynamodb" feature.
+//! [`HashMapCache`] is a dependency-free in-memory alternative to Moka.
 
-use crate::{CacheEntry, Result};
+use crate::{CacheEntry, CacheMetadata, CachifiedError, Result};
 use async_trait::async_trait;
+use std::time::Duration;
 
 #[cfg(feature = "moka")]
-use moka::future::Cache as MokaFutureCache;
+use moka::future::{Cache as MokaFutureCache, CacheBuilder as MokaCacheBuilder};
+/// Why an entry was removed from a [`MokaCache`], passed to eviction listeners
 #[cfg(feature = "moka")]
+pub use moka::notification::RemovalCause;
 use std::sync::Arc;
 
 #[cfg(feature = "redis")]
-use redis::{aio::MultiplexedConnection, AsyncCommands};
+use crate::codec::{Codec, JsonCodec};
+#[cfg(feature = "redis")]
+use redis::AsyncCommands;
+#[cfg(feature = "redis")]
+use std::collections::HashSet;
+
+mod buffered;
+pub use buffered::BufferedCache;
+#[cfg(feature = "dynamodb")]
+mod dynamodb;
+#[cfg(feature = "dynamodb")]
+pub use dynamodb::DynamoDbCache;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "fs")]
+pub use fs::FileSystemCache;
+mod hash_map;
+pub use hash_map::HashMapCache;
+#[cfg(feature = "memcached")]
+mod memcached;
+#[cfg(feature = "memcached")]
+pub use memcached::MemcachedCache;
+mod scoped;
+pub use scoped::ScopedCache;
+mod tiered;
+pub use tiered::TieredCache;
+
+#[cfg(feature = "redis")]
+mod redis_connection;
+#[cfg(feature = "redis")]
+use redis_connection::RedisConnection;
+#[cfg(feature = "redis")]
+pub use redis_connection::{ReconnectPolicy, RedisConnectionState};
+
+#[cfg(all(feature = "redis", feature = "serde"))]
+mod redis_payload;
+
+#[cfg(all(feature = "redis-cluster", feature = "serde"))]
+mod redis_cluster;
+#[cfg(all(feature = "redis-cluster", feature = "serde"))]
+pub use redis_cluster::RedisClusterCache;
 
 /// Cache trait that defines the interface for cache implementations.
 ///
@@ -35,6 +81,53 @@ where
     /// Returns `Some(CacheEntry<T>)` if the key exists, `None` otherwise.
     async fn get(&self, key: &str) -> Option<CacheEntry<T>>;
 
+    /// Get a cache entry by key, distinguishing read errors from misses
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` if the key doesn't exist, or an error if the entry
+    /// couldn't be read (e.g. because of a connection or deserialization error).
+    /// The default implementation delegates to [`Cache::get`] and therefore
+    /// never returns an error.
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        Ok(self.get(key).await)
+    }
+
+    /// Get multiple cache entries at once
+    ///
+    /// The result has one element per key, in the same order. Backends that
+    /// support it fetch all entries in a single round trip; the default
+    /// implementation calls [`Cache::get`] for each key.
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            entries.push(self.get(key).await);
+        }
+        entries
+    }
+
+    /// Get multiple cache entries at once, keeping only those fresh at the given time
+    ///
+    /// Freshness is computed client-side from the entries returned by
+    /// [`Cache::get_many`], so this costs a single round trip on backends
+    /// that batch reads. Missing and expired entries are `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The cache keys to look up
+    /// * `now` - The current time as duration since UNIX_EPOCH
+    async fn get_batch_if_fresh(&self, keys: &[&str], now: Duration) -> Vec<Option<CacheEntry<T>>> {
+        self.get_many(keys)
+            .await
+            .into_iter()
+            .map(|entry| entry.filter(|entry| !entry.is_expired(now)))
+            .collect()
+    }
+
     /// Set a cache entry
     ///
     /// # Arguments
@@ -47,6 +140,88 @@ where
     /// Returns `Ok(())` if successful, or an error if the operation fails.
     async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()>;
 
+    /// Set multiple cache entries
+    ///
+    /// The default implementation sets the entries one by one. Backends with
+    /// a network round trip per operation should override it to batch them.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of every write, in the order of `entries`.
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+        let mut results = Vec::with_capacity(entries.len());
+        for (key, entry) in entries {
+            results.push(self.set(&key, entry).await);
+        }
+        results
+    }
+
+    /// Set a cache entry only if the stored entry has the expected version
+    ///
+    /// This is a compare-and-set on [`CacheMetadata::version`](crate::CacheMetadata::version)
+    /// that protects against overwriting an entry that was written concurrently.
+    ///
+    /// The default implementation reads and then writes the entry, which is not
+    /// atomic. Caches shared between tasks or processes should override it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    /// * `entry` - The cache entry to store
+    /// * `expected_version` - The version the stored entry must have, or `None`
+    ///   if no entry may be stored
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the entry was written and `Ok(false)` if the stored
+    /// version didn't match.
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let current_version = self.try_get(key).await?.map(|entry| entry.metadata.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+
+        self.set(key, entry).await?;
+        Ok(true)
+    }
+
+    /// Atomically read, modify and write a cache entry
+    ///
+    /// `update` receives the stored entry, or `None` if the key is missing, and
+    /// returns the entry to store. The write uses [`Cache::set_if_version`], so
+    /// if another writer changed the entry in the meantime, `update` is called
+    /// again with the new entry. The version of the written entry is set
+    /// automatically. Gives up with an error after [`MAX_UPDATE_ATTEMPTS`]
+    /// conflicting attempts.
+    ///
+    /// This is only atomic if the cache implements `set_if_version` atomically,
+    /// which `MokaCache` and `RedisCache` do.
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry that was written.
+    async fn update<F>(&self, key: &str, mut update: F) -> Result<CacheEntry<T>>
+    where
+        F: FnMut(Option<CacheEntry<T>>) -> CacheEntry<T> + Send,
+        Self: Sized,
+    {
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let current = self.try_get(key).await?;
+            let expected_version = current.as_ref().map(|entry| entry.metadata.version);
+
+            let mut entry = update(current);
+            entry.metadata.version = expected_version.map_or(0, |version| version + 1);
+
+            if self.set_if_version(key, entry.clone(), expected_version).await? {
+                return Ok(entry);
+            }
+        }
+
+        Err(CachifiedError::cache(format!(
+            "Entry was changed concurrently {MAX_UPDATE_ATTEMPTS} times while updating it"
+        )))
+    }
+
     /// Remove a cache entry by key
     ///
     /// # Arguments
@@ -58,12 +233,331 @@ where
     async fn clear(&self);
 
     /// Get the current number of entries in the cache
+    ///
+    /// Implementations must return an exact count that reflects every
+    /// previously awaited `set`, `remove` and `clear` call. Entries whose
+    /// metadata has expired but which are still physically stored are counted,
+    /// see [`Cache::len_valid`] to exclude them. Backends that can't count
+    /// their entries, e.g. because Redis is unreachable, return 0.
     async fn len(&self) -> usize;
 
     /// Check if the cache is empty
+    ///
+    /// Must be consistent with [`Cache::len`], which the default implementation uses.
     async fn is_empty(&self) -> bool {
         self.len().await == 0
     }
+
+    /// Get the number of entries that haven't expired at `now`
+    ///
+    /// [`Cache::len`] counts what is physically stored, including entries
+    /// whose metadata TTL has passed but which the backend hasn't dropped yet,
+    /// e.g. because Moka only evicts by size. This counts the logically valid
+    /// entries instead. The default implementation reads the metadata of every
+    /// key from [`Cache::keys`], so it is O(n).
+    ///
+    /// If the keys can't be listed, e.g. by `MemcachedCache`, which can't
+    /// enumerate keys at all, or by Redis while it is unreachable, the default
+    /// implementation falls back to [`Cache::len`], which counts expired
+    /// entries too.
+    async fn len_valid(&self, now: Duration) -> usize {
+        let Ok(keys) = self.keys().await else {
+            return self.len().await;
+        };
+
+        let mut valid = 0;
+        for key in keys {
+            if self.get_metadata(&key).await.is_some_and(|metadata| !metadata.is_expired(now)) {
+                valid += 1;
+            }
+        }
+        valid
+    }
+
+    /// Check if the cache holds no entries that haven't expired at `now`
+    ///
+    /// Must be consistent with [`Cache::len_valid`], which the default implementation uses.
+    async fn is_empty_valid(&self, now: Duration) -> bool {
+        self.len_valid(now).await == 0
+    }
+
+    /// Check if a cache entry exists for the given key
+    ///
+    /// Must be consistent with [`Cache::get`], which the default implementation uses.
+    async fn contains_key(&self, key: &str) -> bool {
+        self.get(key).await.is_some()
+    }
+
+    /// Get the metadata of a cache entry by key
+    ///
+    /// Use this to check the age or expiry of an entry. The default
+    /// implementation reads the whole entry with [`Cache::get`].
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(CacheMetadata)` if the key exists, `None` otherwise.
+    async fn get_metadata(&self, key: &str) -> Option<CacheMetadata> {
+        self.get(key).await.map(|entry| entry.metadata)
+    }
+
+    /// Extend the life of a cache entry without fetching its value again
+    ///
+    /// The TTL of the entry is set so that it expires `ttl` after `now`, while
+    /// its creation time and thereby its age stay untouched. The default
+    /// implementation rewrites the entry with [`Cache::set_if_version`] and a
+    /// bumped version. Backends with native TTLs such as Redis override it to
+    /// only rewrite the metadata and renew the expiry.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key to touch
+    /// * `ttl` - How long the entry should live after `now`
+    /// * `now` - The current time as duration since UNIX_EPOCH
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the entry was touched and `Ok(false)` if the key
+    /// doesn't exist or the entry was replaced concurrently.
+    async fn touch(&self, key: &str, ttl: Duration, now: Duration) -> Result<bool> {
+        touch_entry(self, key, ttl, now).await
+    }
+
+    /// Store a value with the given TTL, created now
+    ///
+    /// This is a shortcut for [`Cache::set`] when using the cache as a plain
+    /// key-value store outside of `cachified`.
+    async fn put(&self, key: &str, value: T, ttl: Option<Duration>) -> Result<()> {
+        self.set(key, CacheEntry::new(value, ttl)).await
+    }
+
+    /// Get a value by key if it isn't expired at the given time
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key to look up
+    /// * `now` - The current time as duration since UNIX_EPOCH
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(value)` if the key exists and the entry is fresh, `None` otherwise.
+    async fn get_value(&self, key: &str, now: Duration) -> Option<T> {
+        self.get(key)
+            .await
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.value)
+    }
+
+    /// Get the keys of all entries in the cache
+    ///
+    /// This is an O(n) operation and not meant for hot paths. Backends that
+    /// can't enumerate their keys return an error, which is the default.
+    async fn keys(&self) -> Result<Vec<String>> {
+        Err(CachifiedError::cache("Listing keys is not supported by this cache"))
+    }
+
+    /// Remove all entries whose key starts with `prefix`
+    ///
+    /// Use this to invalidate a logical group of entries, e.g. all entries of
+    /// a user under `user:123:`. The default implementation lists all keys with
+    /// [`Cache::keys`] and removes the matching ones, which makes it an O(n)
+    /// operation. Backends that can't list their keys return an error.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed entries.
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        let mut removed = 0;
+        for key in self.keys().await? {
+            if key.starts_with(prefix) {
+                self.remove(&key).await;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove all entries that are expired at the given time
+    ///
+    /// Entries are only removed if they are still expired when read, so this
+    /// is safe to run while other calls write to the cache. The default
+    /// implementation lists all keys with [`Cache::keys`], which makes it an
+    /// O(n) operation meant for background cleanup, e.g. with
+    /// [`spawn_janitor`](crate::janitor::spawn_janitor).
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time as duration since UNIX_EPOCH
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed entries, or an error if the cache can't
+    /// list its keys.
+    async fn clear_expired(&self, now: Duration) -> Result<usize> {
+        let keys = self.keys().await?;
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        Ok(remove_expired(self, &keys, now).await)
+    }
+
+    /// Get the name of the backend, e.g. for tracing and metrics
+    fn backend(&self) -> &'static str {
+        "custom"
+    }
+
+    /// Get a view of this cache that namespaces all keys under `prefix`
+    ///
+    /// See [`ScopedCache`] for details.
+    fn scoped(&self, prefix: impl Into<String>) -> ScopedCache<Self>
+    where
+        Self: Clone + Sized,
+    {
+        ScopedCache::new(self.clone(), prefix.into())
+    }
+}
+
+/// Number of times [`Cache::update`] retries after a concurrent write
+pub const MAX_UPDATE_ATTEMPTS: usize = 16;
+
+/// Implement `Cache<T>` for a pointer type by forwarding to the pointee
+macro_rules! forward_cache_impl {
+    ($(impl<$($lt:lifetime,)? T, C> for $ptr:ty;)*) => {$(
+        #[async_trait]
+        impl<$($lt,)? T, C> Cache<T> for $ptr
+        where
+            T: Clone + Send + Sync + 'static,
+            C: Cache<T> + ?Sized,
+        {
+            async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+                (**self).get(key).await
+            }
+
+            async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+                (**self).try_get(key).await
+            }
+
+            async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+                (**self).get_many(keys).await
+            }
+
+            async fn get_batch_if_fresh(&self, keys: &[&str], now: Duration) -> Vec<Option<CacheEntry<T>>> {
+                (**self).get_batch_if_fresh(keys, now).await
+            }
+
+            async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+                (**self).set(key, entry).await
+            }
+
+            async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+                (**self).set_many(entries).await
+            }
+
+            async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+                (**self).set_if_version(key, entry, expected_version).await
+            }
+
+            async fn remove(&self, key: &str) {
+                (**self).remove(key).await
+            }
+
+            async fn clear(&self) {
+                (**self).clear().await
+            }
+
+            async fn len(&self) -> usize {
+                (**self).len().await
+            }
+
+            async fn is_empty(&self) -> bool {
+                (**self).is_empty().await
+            }
+
+            async fn len_valid(&self, now: Duration) -> usize {
+                (**self).len_valid(now).await
+            }
+
+            async fn is_empty_valid(&self, now: Duration) -> bool {
+                (**self).is_empty_valid(now).await
+            }
+
+            async fn contains_key(&self, key: &str) -> bool {
+                (**self).contains_key(key).await
+            }
+
+            async fn get_metadata(&self, key: &str) -> Option<CacheMetadata> {
+                (**self).get_metadata(key).await
+            }
+
+            async fn touch(&self, key: &str, ttl: Duration, now: Duration) -> Result<bool> {
+                (**self).touch(key, ttl, now).await
+            }
+
+            async fn keys(&self) -> Result<Vec<String>> {
+                (**self).keys().await
+            }
+
+            async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+                (**self).remove_prefix(prefix).await
+            }
+
+            async fn clear_expired(&self, now: Duration) -> Result<usize> {
+                (**self).clear_expired(now).await
+            }
+
+            fn backend(&self) -> &'static str {
+                (**self).backend()
+            }
+        }
+    )*};
+}
+
+/// Remove the entries of the given keys that are expired at `now`
+///
+/// Returns the number of removed entries.
+pub(crate) async fn remove_expired<T, C>(cache: &C, keys: &[&str], now: Duration) -> usize
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + ?Sized,
+{
+    let entries = cache.get_many(keys).await;
+    let mut removed = 0;
+
+    for (key, entry) in keys.iter().zip(entries) {
+        if entry.is_some_and(|entry| entry.is_expired(now)) {
+            cache.remove(key).await;
+            removed += 1;
+        }
+    }
+
+    removed
+}
+
+/// Touch an entry by rewriting it as a whole, see [`Cache::touch`]
+pub(crate) async fn touch_entry<T, C>(cache: &C, key: &str, ttl: Duration, now: Duration) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + ?Sized,
+{
+    let Some(mut entry) = cache.try_get(key).await? else {
+        return Ok(false);
+    };
+
+    let version = entry.metadata.version;
+    entry.metadata = touched(entry.metadata, ttl, now);
+    cache.set_if_version(key, entry, Some(version)).await
+}
+
+/// Get the metadata of a touched entry, expiring `ttl` after `now` with a bumped version
+pub(crate) fn touched(mut metadata: CacheMetadata, ttl: Duration, now: Duration) -> CacheMetadata {
+    metadata.version += 1;
+    metadata.ttl = Some(metadata.age(now) + ttl);
+    metadata
+}
+
+// References and smart pointers to caches are caches too, which allows sharing
+// one cache as `Arc<dyn Cache<T>>` or passing a `&'static` cache to `cachified`.
+forward_cache_impl! {
+    impl<'a, T, C> for &'a C;
+    impl<T, C> for Arc<C>;
+    impl<T, C> for Box<C>;
 }
 
 /// Moka-based cache implementation
@@ -71,6 +565,9 @@ where
 /// This is a high-performance in-memory cache implementation that uses the Moka library
 /// for concurrent caching with automatic cleanup.
 ///
+/// [`Cache::len`] runs Moka's pending maintenance before counting, so it is
+/// exact right after awaited writes, unlike Moka's own `entry_count`.
+///
 /// # Examples
 ///
 /// ```rust
@@ -107,15 +604,194 @@ where
     /// let cache: MokaCache<String> = MokaCache::new(1000);
     /// ```
     pub fn new(max_capacity: u64) -> Self {
-        let inner = MokaFutureCache::builder()
+        let inner = Self::builder()
+            .max_capacity(max_capacity)
+            .build();
+
+        Self::from_moka(inner)
+    }
+
+    /// Create a new MokaCache that evicts entries once their total weight exceeds a byte budget
+    ///
+    /// Instead of limiting the number of entries, every entry is weighed with
+    /// `weigher` and entries are evicted once the sum of all weights exceeds
+    /// `max_bytes`. This keeps memory usage bounded when values vary widely in size.
+    /// The weigher should return an approximation of the entry's size in bytes,
+    /// which saturates at `u32::MAX`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_bytes` - Maximum total weight of all entries
+    /// * `weigher` - Function returning the weight of an entry given its key and value
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::MokaCache;
+    ///
+    /// // Keep at most 64 MiB of string data, weighing entries by key and value length
+    /// # #[cfg(feature = "moka")]
+    /// let cache: MokaCache<String> = MokaCache::with_byte_capacity(64 * 1024 * 1024, |key, value: &String| {
+    ///     (key.len() + value.len()).try_into().unwrap_or(u32::MAX)
+    /// });
+    /// ```
+    pub fn with_byte_capacity<W>(max_bytes: u64, weigher: W) -> Self
+    where
+        W: Fn(&str, &T) -> u32 + Send + Sync + 'static,
+    {
+        let inner = Self::builder()
+            .max_capacity(max_bytes)
+            .weigher(move |key: &String, entry: &CacheEntry<T>| weigher(key, &entry.value))
+            .build();
+
+        Self::from_moka(inner)
+    }
+
+    /// Create a new MokaCache that notifies a listener whenever an entry is removed
+    ///
+    /// The listener receives the key, the removed entry and the cause of the
+    /// removal, e.g. to spill entries evicted for capacity to a slower store or
+    /// to monitor the eviction rate. It runs on the task that triggered the
+    /// removal, so it should return quickly.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_capacity` - Maximum number of entries the cache can hold
+    /// * `listener` - Function called with every removed entry
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cache::RemovalCause, MokaCache};
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// let cache: MokaCache<String> = MokaCache::with_eviction_listener(1000, |key, _entry, cause| {
+    ///     if cause == RemovalCause::Size {
+    ///         println!("Evicted {key} for capacity");
+    ///     }
+    /// });
+    /// ```
+    pub fn with_eviction_listener<F>(max_capacity: u64, listener: F) -> Self
+    where
+        F: Fn(&str, CacheEntry<T>, RemovalCause) + Send + Sync + 'static,
+    {
+        let inner = Self::builder()
+            .max_capacity(max_capacity)
+            .eviction_listener(move |key: Arc<String>, entry, cause| listener(&key, entry, cause))
+            .build();
+
+        Self::from_moka(inner)
+    }
+
+    /// Create a new MokaCache that evicts entries once their metadata expires
+    ///
+    /// Moka itself knows nothing about the TTL stored with each entry, so
+    /// without an expiry policy expired entries stay in memory until they are
+    /// evicted for capacity. This cache uses [`MetadataExpiry`] instead, which
+    /// derives the lifetime in Moka from the metadata of every written entry.
+    /// Entries are kept through their stale-while-revalidate window. To keep
+    /// them longer, e.g. for `fallback_to_cache`, pass a [`MetadataExpiry`]
+    /// with a stale retention to [`MokaCache::builder`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cache::MetadataExpiry, MokaCache};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// let cache: MokaCache<String> = MokaCache::with_metadata_expiry(1000);
+    ///
+    /// // The same, keeping expired entries around for another hour
+    /// # #[cfg(feature = "moka")]
+    /// let retaining: MokaCache<String> = MokaCache::from_moka(
+    ///     MokaCache::builder()
+    ///         .max_capacity(1000)
+    ///         .expire_after(MetadataExpiry::new().with_stale_retention(Duration::from_secs(3600)))
+    ///         .build(),
+    /// );
+    /// ```
+    pub fn with_metadata_expiry(max_capacity: u64) -> Self {
+        let inner = Self::builder()
             .max_capacity(max_capacity)
+            .expire_after(MetadataExpiry::new())
             .build();
 
+        Self::from_moka(inner)
+    }
+
+    /// Get a Moka cache builder for full control over eviction and expiration
+    ///
+    /// This also gives access to options not covered by the constructors,
+    /// such as combining an eviction listener with a weigher.
+    /// Build the cache and wrap it with [`MokaCache::from_moka`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::MokaCache;
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// let cache: MokaCache<String> = MokaCache::from_moka(
+    ///     MokaCache::builder()
+    ///         .max_capacity(1000)
+    ///         .time_to_idle(Duration::from_secs(600))
+    ///         .build(),
+    /// );
+    /// ```
+    pub fn builder() -> MokaCacheBuilder<String, CacheEntry<T>, MokaFutureCache<String, CacheEntry<T>>> {
+        MokaFutureCache::builder()
+    }
+
+    /// Create a MokaCache from an existing Moka cache
+    ///
+    /// This adopts a cache that was built and tuned elsewhere, e.g. with
+    /// eviction listeners or a custom weigher. The same works with `From`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{CacheEntry, MokaCache};
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # {
+    /// let inner = moka::future::Cache::builder()
+    ///     .max_capacity(1000)
+    ///     .eviction_listener(|key, _value: CacheEntry<String>, cause| {
+    ///         println!("Evicted {key}: {cause:?}");
+    ///     })
+    ///     .build();
+    ///
+    /// let cache = MokaCache::from_moka(inner);
+    /// # }
+    /// ```
+    pub fn from_moka(inner: MokaFutureCache<String, CacheEntry<T>>) -> Self {
         Self {
             inner: Arc::new(inner),
         }
     }
 
+    /// Create a MokaCache from an existing Moka cache
+    ///
+    /// This is an alias of [`MokaCache::from_moka`].
+    pub fn from_inner(inner: MokaFutureCache<String, CacheEntry<T>>) -> Self {
+        Self::from_moka(inner)
+    }
+
+    /// Convert this cache back into the underlying Moka cache
+    ///
+    /// Moka caches are cheap handles to shared storage, so the returned cache
+    /// keeps sharing its entries with any remaining clones of this one.
+    pub fn into_inner(self) -> MokaFutureCache<String, CacheEntry<T>> {
+        Arc::unwrap_or_clone(self.inner)
+    }
+
     /// Get the underlying Moka cache for advanced operations
     ///
     /// This provides access to additional Moka-specific functionality
@@ -125,6 +801,74 @@ where
     }
 }
 
+/// Moka expiry policy deriving the lifetime of entries from their metadata
+///
+/// Every written entry is evicted by Moka once its TTL, its stale-while-revalidate
+/// window and the stale retention have passed, so memory isn't held by values
+/// `cachified` won't serve anymore. Entries without a TTL never expire.
+/// Expiry is measured against the system clock, regardless of the clock
+/// configured for `cachified` calls.
+///
+/// Use it with [`MokaCache::with_metadata_expiry`] or pass it to
+/// `expire_after` of [`MokaCache::builder`]. Moka's own `time_to_live` and
+/// `time_to_idle` can be set alongside it, in which case the earliest of
+/// them evicts an entry.
+#[cfg(feature = "moka")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetadataExpiry {
+    stale_retention: Duration,
+}
+
+#[cfg(feature = "moka")]
+impl MetadataExpiry {
+    /// Create a policy evicting entries once their metadata expires
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep expired entries for `retention` after they expire before evicting them
+    pub fn with_stale_retention(mut self, retention: Duration) -> Self {
+        self.stale_retention = retention;
+        self
+    }
+
+    /// Get the time from now until an entry can be evicted, or `None` if it never expires
+    fn time_to_eviction<T>(&self, entry: &CacheEntry<T>) -> Option<Duration> {
+        let expires_at = entry.expires_at()?;
+        let stale_until = entry.metadata.stale_until().unwrap_or(expires_at);
+        let evict_at = stale_until.max(expires_at + self.stale_retention);
+        Some(evict_at.saturating_sub(crate::current_time()))
+    }
+}
+
+#[cfg(feature = "moka")]
+impl<T> moka::Expiry<String, CacheEntry<T>> for MetadataExpiry {
+    fn expire_after_create(&self, _key: &String, entry: &CacheEntry<T>, _created_at: std::time::Instant) -> Option<Duration> {
+        self.time_to_eviction(entry)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &CacheEntry<T>,
+        _updated_at: std::time::Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        // Rewritten entries carry new metadata, e.g. after a refresh or `touch`
+        self.time_to_eviction(entry)
+    }
+}
+
+#[cfg(feature = "moka")]
+impl<T> From<MokaFutureCache<String, CacheEntry<T>>> for MokaCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn from(inner: MokaFutureCache<String, CacheEntry<T>>) -> Self {
+        Self::from_moka(inner)
+    }
+}
+
 #[cfg(feature = "moka")]
 #[async_trait]
 impl<T> Cache<T> for MokaCache<T>
@@ -140,6 +884,26 @@ where
         Ok(())
     }
 
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        use moka::ops::compute::{CompResult, Op};
+
+        // Moka runs the closure while holding a lock on the key
+        let result = self
+            .inner
+            .entry(key.to_string())
+            .and_compute_with(|current| {
+                let current_version = current.map(|current| current.into_value().metadata.version);
+                std::future::ready(if current_version == expected_version {
+                    Op::Put(entry)
+                } else {
+                    Op::Nop
+                })
+            })
+            .await;
+
+        Ok(matches!(result, CompResult::Inserted(_) | CompResult::ReplacedWith(_)))
+    }
+
     async fn remove(&self, key: &str) {
         self.inner.invalidate(key).await;
     }
@@ -149,8 +913,27 @@ where
     }
 
     async fn len(&self) -> usize {
+        // `entry_count` is only updated after Moka's pending maintenance tasks
+        // have run, so flush them first to honour the `Cache::len` contract.
+        self.inner.run_pending_tasks().await;
         self.inner.entry_count() as usize
     }
+
+    async fn len_valid(&self, now: Duration) -> usize {
+        self.inner.iter().filter(|(_, entry)| !entry.is_expired(now)).count()
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.inner.iter().map(|(key, _)| key.as_ref().clone()).collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        "moka"
+    }
 }
 
 /// Redis-based cache implementation
@@ -158,6 +941,46 @@ where
 /// This is a distributed cache implementation that uses Redis for
 /// storing cache entries. Requires the "redis" feature to be enabled.
 ///
+/// Entries are serialized with a [`Codec`], which defaults to [`JsonCodec`].
+/// Use [`RedisCache::with_codec`] to store entries differently, e.g. with
+/// [`RawBytesCodec`](crate::codec::RawBytesCodec) for byte values.
+///
+/// # Caching `Option` values
+///
+/// Values are always stored inside an entry together with their metadata, so
+/// an `Option<T>` value of `None` is stored as a JSON `null` value, not as a
+/// missing key. Reading it back yields `Some(entry)` with a `None` value, and
+/// [`Cache::contains_key`] reports the key as present. Only keys that were
+/// never written, were removed or expired in Redis are misses. The TTL of a
+/// stored `None` applies exactly like for any other value, which makes
+/// `RedisCache<Option<T>>` suitable for caching negative lookups.
+///
+/// With a deterministic codec such as [`JsonCodec`], the value and the
+/// metadata of an entry are stored in separate sections. Writing an entry
+/// whose value encodes to the stored bytes, e.g. when a refresh fetched an
+/// unchanged value, then only rewrites its metadata server-side, so unchanged
+/// values don't cause write amplification on replicas and in the AOF.
+/// [`Cache::get_metadata`] and [`Cache::touch`] only read and rewrite the
+/// metadata section as well, so the value never leaves Redis.
+///
+/// # Compatibility of stored entries
+///
+/// Before the sections were introduced, entries were stored as the plain
+/// output of the codec. Those entries can still be read, and are rewritten in
+/// sections on their next write, so existing Redis data needs no migration.
+/// Entries written in sections can't be read by earlier versions of this
+/// crate though, so during a rolling deployment, let old and new instances
+/// use different prefixes. Codecs that aren't deterministic, such as
+/// [`EncryptedCodec`](crate::codec::EncryptedCodec), still store their plain output.
+///
+/// When an operation fails because the connection broke, the connection is
+/// rebuilt according to the [`ReconnectPolicy`] and the operation is retried
+/// once if the first attempt succeeds. Otherwise, the connection is rebuilt in
+/// the background. Operations failing that way or issued while reconnecting
+/// fail immediately: reads behave like misses and writes return an error, both
+/// of which `cachified` tolerates.
+/// Clones share the same connection.
+///
 /// # Examples
 ///
 /// ```rust,no_run
@@ -172,9 +995,11 @@ where
 /// ```
 #[cfg(feature = "redis")]
 #[derive(Clone)]
-pub struct RedisCache<T> {
-    connection: MultiplexedConnection,
+pub struct RedisCache<T, K = JsonCodec> {
+    connection: Arc<RedisConnection>,
+    reconnect_policy: ReconnectPolicy,
     prefix: String,
+    codec: K,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -202,14 +1027,7 @@ where
     /// # }
     /// ```
     pub async fn new(redis_url: &str) -> Result<Self> {
-        let client = redis::Client::open(redis_url)?;
-        let connection = client.get_multiplexed_async_connection().await?;
-        
-        Ok(Self {
-            connection,
-            prefix: "cachified:".to_string(),
-            _phantom: std::marker::PhantomData,
-        })
+        Self::with_prefix(redis_url, "cachified:".to_string()).await
     }
 
     /// Create a new RedisCache with a custom key prefix
@@ -220,89 +1038,712 @@ where
     /// * `prefix` - Custom prefix for all cache keys
     pub async fn with_prefix(redis_url: &str, prefix: String) -> Result<Self> {
         let client = redis::Client::open(redis_url)?;
-        let connection = client.get_multiplexed_async_connection().await?;
-        
-        Ok(Self {
-            connection,
+        Self::from_client(client, prefix).await
+    }
+
+    /// Create a new RedisCache from an existing client
+    ///
+    /// Use this when the client needs configuration that can't be expressed
+    /// in a URL, such as custom TLS settings. The client is kept to reconnect
+    /// when the connection breaks.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Configured Redis client
+    /// * `prefix` - Prefix for all cache keys
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[cfg(feature = "redis")]
+    /// use cachified::RedisCache;
+    ///
+    /// # #[cfg(feature = "redis")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = redis::Client::open("redis://localhost:6379")?;
+    /// let cache: RedisCache<String> = RedisCache::from_client(client, "cachified:".to_string()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn from_client(client: redis::Client, prefix: String) -> Result<Self> {
+        let connection = RedisConnection::connect(client).await?;
+        Ok(Self::from_redis_connection(connection, prefix))
+    }
+
+    /// Create a new RedisCache from an established connection
+    ///
+    /// Without a client the connection can't be rebuilt, so operations keep
+    /// failing once it breaks. Prefer [`RedisCache::from_client`] where possible.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - Established multiplexed connection
+    /// * `prefix` - Prefix for all cache keys
+    pub fn from_connection(connection: redis::aio::MultiplexedConnection, prefix: String) -> Self {
+        Self::from_redis_connection(RedisConnection::from_connection(connection), prefix)
+    }
+
+    fn from_redis_connection(connection: RedisConnection, prefix: String) -> Self {
+        Self {
+            connection: Arc::new(connection),
+            reconnect_policy: ReconnectPolicy::default(),
             prefix,
+            codec: JsonCodec,
             _phantom: std::marker::PhantomData,
-        })
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+impl<T, K> RedisCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Use a different codec for serializing entries
+    ///
+    /// Entries written with one codec generally can't be read with another,
+    /// so use a separate prefix when switching codecs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[cfg(feature = "redis")]
+    /// use cachified::{codec::RawBytesCodec, RedisCache};
+    ///
+    /// # #[cfg(feature = "redis")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = RedisCache::<Vec<u8>>::new("redis://localhost:6379")
+    ///     .await?
+    ///     .with_codec(RawBytesCodec);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_codec<K2>(self, codec: K2) -> RedisCache<T, K2> {
+        RedisCache {
+            connection: self.connection,
+            reconnect_policy: self.reconnect_policy,
+            prefix: self.prefix,
+            codec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get a cache for another value type that shares this cache's connection and prefix
+    ///
+    /// This avoids opening a connection per value type, e.g. when registering
+    /// several logical caches in a [`CacheRegistry`](crate::registry::CacheRegistry).
+    pub fn for_type<U>(&self) -> RedisCache<U, K>
+    where
+        K: Clone,
+    {
+        RedisCache {
+            connection: self.connection.clone(),
+            reconnect_policy: self.reconnect_policy,
+            prefix: self.prefix.clone(),
+            codec: self.codec.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Set how a dropped connection is re-established
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Get the state of the connection, e.g. for health checks
+    pub fn connection_state(&self) -> RedisConnectionState {
+        self.connection.state()
+    }
+
+    /// Get the raw payload encoded by the codec for a key without deserializing it
+    ///
+    /// This is meant for migration tooling that needs to read entries stored
+    /// in an old format, which would fail to deserialize into `T`. For entries
+    /// stored with their value and metadata in separate sections, this is the
+    /// value encoded with placeholder metadata.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key (without prefix)
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Some(bytes))` if the key exists, `Ok(None)` otherwise.
+    pub async fn get_raw(&self, key: &str) -> Result<Option<Vec<u8>>>
+    where
+        K: Codec<T>,
+    {
+        self.get_stored(key)
+            .await?
+            .map(|data| redis_payload::codec_payload::<T, _>(&self.codec, data))
+            .transpose()
+    }
+
+    /// Get the bytes stored for a key
+    async fn get_stored(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let full_key = &self.full_key(key);
+
+        self.run(move |mut conn| async move { conn.get::<&str, Option<Vec<u8>>>(full_key).await })
+            .await
+    }
+
+    /// Run an operation on the connection, reconnecting if it is broken
+    async fn run<R, F, Fut>(&self, operation: F) -> Result<R>
+    where
+        F: Fn(redis::aio::MultiplexedConnection) -> Fut,
+        Fut: std::future::Future<Output = redis::RedisResult<R>>,
+    {
+        self.connection.run(&self.reconnect_policy, operation).await
+    }
+
+    /// Get the full key with prefix
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Get all full keys with this cache's prefix using non-blocking `SCAN`
+    async fn scan_full_keys(&self) -> Result<Vec<String>> {
+        let mut scan = PrefixScan::new(&self.prefix);
+
+        while let Some(command) = scan.next_command() {
+            let command = &command;
+            let step = self.run(move |mut conn| async move { command.query_async(&mut conn).await }).await?;
+            scan.advance(step);
+        }
+
+        Ok(scan.into_keys())
+    }
+
+    /// Count the entries that [`Cache::clear`] would delete, without deleting them
+    ///
+    /// Use this to sanity-check the prefix before clearing a shared Redis instance.
+    /// Only keys that start with exactly this cache's prefix are counted.
+    pub async fn clear_dry_run(&self) -> Result<usize> {
+        self.scan_prefix(&self.prefix, true).await
+    }
+
+    /// Unlink all keys starting with `full_prefix`, or only count them if `dry_run` is set
+    ///
+    /// Keys are scanned with one round trip per `SCAN` step and each step's
+    /// keys are unlinked with a single `UNLINK`, so Redis is never blocked for
+    /// longer than one step, unlike with `KEYS` or a script looping over `SCAN`.
+    /// Keys are compared against the prefix again so that only exact-prefix
+    /// matches are ever touched.
+    ///
+    /// Returns the number of matching keys when counting, and the number of
+    /// unlinked keys otherwise. `SCAN` may return a key more than once, so
+    /// counted keys are deduplicated, while `UNLINK` only counts a key the
+    /// first time it is deleted anyway.
+    async fn scan_prefix(&self, full_prefix: &str, dry_run: bool) -> Result<usize> {
+        let mut scan = PrefixScan::new(full_prefix);
+        let mut unlinked = 0;
+
+        while let Some(command) = scan.next_command() {
+            let command = &command;
+            let step = self.run(move |mut conn| async move { command.query_async(&mut conn).await }).await?;
+            let keys = scan.advance(step);
+
+            if !dry_run && !keys.is_empty() {
+                let keys = &keys;
+                let count: usize = self
+                    .run(move |mut conn| async move { redis::cmd("UNLINK").arg(keys).query_async(&mut conn).await })
+                    .await?;
+                unlinked += count;
+            }
+        }
+
+        Ok(if dry_run { scan.found() } else { unlinked })
+    }
+}
+
+/// Lua script that writes an entry unless the stored value is byte-identical
+///
+/// Arguments are the encoded entry, its expiry in milliseconds, where zero means
+/// no expiry, and the length of the prefix identifying its value, where zero
+/// means it can't be compared. If the stored entry starts with the same
+/// prefix, only the metadata after it and the expiry are rewritten. This
+/// avoids rewriting values that rarely change on every refresh, which would
+/// otherwise be propagated to replicas and the AOF. Returns whether the value
+/// was written.
+#[cfg(feature = "redis")]
+static SET_IF_CHANGED_SCRIPT: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(
+        r"
+local prefix_length = tonumber(ARGV[3])
+if prefix_length > 0
+    and redis.call('GETRANGE', KEYS[1], 0, prefix_length - 1) == string.sub(ARGV[1], 1, prefix_length) then
+    redis.call('SETRANGE', KEYS[1], prefix_length, string.sub(ARGV[1], prefix_length + 1))
+    if tonumber(ARGV[2]) > 0 then
+        redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    else
+        redis.call('PERSIST', KEYS[1])
+    end
+    return 0
+end
+if tonumber(ARGV[2]) > 0 then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+else
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+return 1
+",
+    )
+});
+
+/// Lua script that writes an entry if the stored payload is the expected one
+///
+/// Arguments are whether the key is expected to be missing, the expected
+/// payload, the new payload and its expiry in milliseconds, where zero means no
+/// expiry. Returns whether the entry was written.
+///
+/// The version is encoded by the codec, which the script can't decode, so
+/// callers compare the version of the payload they read and the script only
+/// writes if that payload is still stored. This works on shared multiplexed
+/// connections and through Redis Cluster, unlike `WATCH`.
+#[cfg(feature = "redis")]
+static SET_IF_PAYLOAD_SCRIPT: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(
+        r"
+local current = redis.call('GET', KEYS[1])
+if ARGV[1] == '1' then
+    if current then
+        return 0
+    end
+elseif current ~= ARGV[2] then
+    return 0
+end
+if tonumber(ARGV[4]) > 0 then
+    redis.call('SET', KEYS[1], ARGV[3], 'PX', ARGV[4])
+else
+    redis.call('SET', KEYS[1], ARGV[3])
+end
+return 1
+",
+    )
+});
+
+/// Lua snippet finding the metadata section of an entry stored in sections
+///
+/// `ARGV[1]` is the header of entries stored in sections. Returns `false` if
+/// the entry is missing or not stored in sections, otherwise sets `offset` to
+/// the start of its metadata section.
+#[cfg(feature = "redis")]
+const METADATA_OFFSET_LUA: &str = r"
+local header = redis.call('GETRANGE', KEYS[1], 0, 7)
+if #header < 8 or string.sub(header, 1, 4) ~= ARGV[1] then
+    return false
+end
+local b1, b2, b3, b4 = string.byte(header, 5, 8)
+local offset = 8 + ((b1 * 256 + b2) * 256 + b3) * 256 + b4
+";
+
+/// Lua script reading only the metadata section of an entry stored in sections
+///
+/// Its only argument is the header of entries stored in sections. Returns
+/// everything from the start of the metadata section, or `nil` if the entry
+/// is missing or not stored in sections.
+#[cfg(feature = "redis")]
+static GET_METADATA_SCRIPT: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(&[METADATA_OFFSET_LUA, "return redis.call('GETRANGE', KEYS[1], offset, -1)"].concat())
+});
+
+/// Lua script rewriting only the metadata section of an entry stored in sections
+///
+/// Arguments are the header of entries stored in sections, the expected
+/// metadata section, the new one and the new expiry in milliseconds, where zero
+/// means no expiry. The metadata is only rewritten if the expected one is
+/// still stored. Returns whether it was, or `nil` if the entry is missing or
+/// not stored in sections.
+#[cfg(feature = "redis")]
+static TOUCH_SCRIPT: std::sync::LazyLock<redis::Script> = std::sync::LazyLock::new(|| {
+    redis::Script::new(
+        &[
+            METADATA_OFFSET_LUA,
+            r"
+if redis.call('GETRANGE', KEYS[1], offset, offset + #ARGV[2] - 1) ~= ARGV[2] then
+    return 0
+end
+redis.call('SETRANGE', KEYS[1], offset, ARGV[3])
+if tonumber(ARGV[4]) > 0 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[4])
+else
+    redis.call('PERSIST', KEYS[1])
+end
+return 1
+",
+        ]
+        .concat(),
+    )
+});
+
+/// Number of keys Redis should look at per `SCAN` iteration
+#[cfg(feature = "redis")]
+const SCAN_COUNT: usize = 500;
+
+/// Non-blocking `SCAN` over all keys starting with a prefix, one step at a time
+///
+/// Run every command from [`PrefixScan::next_command`] and pass its reply to
+/// [`PrefixScan::advance`] until there is no next command. `SCAN` may return a
+/// key more than once, e.g. while Redis rehashes, so keys are deduplicated.
+/// Keys are compared against the prefix again, so that only exact-prefix
+/// matches are ever returned.
+#[cfg(feature = "redis")]
+struct PrefixScan<'a> {
+    full_prefix: &'a str,
+    pattern: String,
+    /// Cursor of the next step, `None` once the scan is complete
+    cursor: Option<u64>,
+    found: HashSet<String>,
+}
+
+#[cfg(feature = "redis")]
+impl<'a> PrefixScan<'a> {
+    fn new(full_prefix: &'a str) -> Self {
+        Self {
+            full_prefix,
+            pattern: format!("{}*", escape_pattern(full_prefix)),
+            cursor: Some(0),
+            found: HashSet::new(),
+        }
+    }
+
+    /// Get the `SCAN` command of the next step, or `None` once the scan is complete
+    fn next_command(&self) -> Option<redis::Cmd> {
+        let mut command = redis::cmd("SCAN");
+        command
+            .arg(self.cursor?)
+            .arg("MATCH")
+            .arg(&self.pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT);
+        Some(command)
+    }
+
+    /// Record the reply of a step and get the matching keys it found first
+    fn advance(&mut self, (next_cursor, batch): (u64, Vec<String>)) -> Vec<String> {
+        self.cursor = (next_cursor != 0).then_some(next_cursor);
+        batch
+            .into_iter()
+            .filter(|key| key.starts_with(self.full_prefix) && self.found.insert(key.clone()))
+            .collect()
+    }
+
+    /// Get the number of matching keys found so far
+    fn found(&self) -> usize {
+        self.found.len()
+    }
+
+    /// Get all matching keys that were found
+    fn into_keys(self) -> Vec<String> {
+        self.found.into_iter().collect()
+    }
+}
+
+/// Get how long Redis or Memcached should keep an entry, or `None` if it doesn't expire
+///
+/// The backend keeps the entry through its stale-while-revalidate window, so it can
+/// still be served stale after its logical expiry tracked in the metadata.
+#[cfg(any(feature = "redis", feature = "memcached"))]
+fn backend_expiry(metadata: &crate::CacheMetadata) -> Option<Duration> {
+    metadata
+        .ttl
+        .map(|ttl| ttl.saturating_add(metadata.swr.unwrap_or_default()))
+}
+
+/// Longest expiry Redis accepts in milliseconds, as it adds the current time to it
+#[cfg(feature = "redis")]
+const MAX_EXPIRE_MILLIS: u128 = (i64::MAX / 2) as u128;
+
+/// Get the expiry in milliseconds to store an entry in Redis with, where zero means no expiry
+///
+/// Expiries are rounded up, so an entry expiring within a millisecond, or
+/// already expired, is still stored with the shortest possible expiry instead
+/// of none. Expiries too long for Redis count as none.
+#[cfg(feature = "redis")]
+fn expire_millis(metadata: &crate::CacheMetadata) -> u64 {
+    let Some(expiry) = backend_expiry(metadata) else {
+        return 0;
+    };
+
+    let millis = expiry.as_millis() + u128::from(expiry.subsec_nanos() % 1_000_000 != 0);
+    if millis > MAX_EXPIRE_MILLIS {
+        return 0;
     }
+    (millis as u64).max(1)
+}
 
-    /// Get the full key with prefix
-    fn full_key(&self, key: &str) -> String {
-        format!("{}{}", self.prefix, key)
+/// Get the expiry in seconds to store an entry in Memcached with, where zero means no expiry
+///
+/// Expiries are rounded up to whole seconds, so an entry expiring within a
+/// second, or already expired, is still stored with the shortest possible
+/// expiry instead of none.
+#[cfg(feature = "memcached")]
+fn expire_seconds(metadata: &crate::CacheMetadata) -> u64 {
+    backend_expiry(metadata).map_or(0, |expiry| {
+        (expiry.as_secs() + u64::from(expiry.subsec_nanos() != 0)).max(1)
+    })
+}
+
+/// Escape glob special characters so a key prefix matches literally in `SCAN MATCH`
+#[cfg(feature = "redis")]
+fn escape_pattern(prefix: &str) -> String {
+    let mut escaped = String::with_capacity(prefix.len());
+    for c in prefix.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
 #[cfg(all(feature = "redis", feature = "serde"))]
 #[async_trait]
-impl<T> Cache<T> for RedisCache<T>
+impl<T, K> Cache<T> for RedisCache<T, K>
 where
-    T: Clone + Send + Sync + 'static + serde::Serialize + serde::de::DeserializeOwned,
+    T: Clone + Send + Sync + 'static,
+    K: Codec<T> + Clone + 'static,
 {
     async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
-        let mut conn = self.connection.clone();
-        let full_key = self.full_key(key);
-        
-        match conn.get::<String, String>(full_key).await {
-            Ok(data) => {
-                match serde_json::from_str::<CacheEntry<T>>(&data) {
-                    Ok(entry) => Some(entry),
-                    Err(_) => None,
+        self.try_get(key).await.ok().flatten()
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        match self.get_stored(key).await? {
+            Some(data) => Ok(Some(redis_payload::decode(&self.codec, data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        if keys.is_empty() {
+            return Vec::new();
+        }
+
+        // MGET fetches all entries in a single round trip
+        let full_keys = &keys.iter().map(|key| self.full_key(key)).collect::<Vec<_>>();
+        let payloads = self
+            .run(move |mut conn| async move {
+                redis::cmd("MGET")
+                    .arg(full_keys)
+                    .query_async::<Vec<Option<Vec<u8>>>>(&mut conn)
+                    .await
+            })
+            .await
+            .unwrap_or_else(|_| vec![None; keys.len()]);
+
+        payloads
+            .into_iter()
+            .map(|payload| payload.and_then(|data| redis_payload::decode(&self.codec, data).ok()))
+            .collect()
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        let full_key = &self.full_key(key);
+        let expire_millis = expire_millis(&entry.metadata);
+        let encoded = &redis_payload::encode(&self.codec, entry)?;
+
+        // Set with expiry if specified, only rewriting the metadata if the stored value is identical
+        self.run(move |mut conn| async move {
+            SET_IF_CHANGED_SCRIPT
+                .key(full_key)
+                .arg(&encoded.data)
+                .arg(expire_millis)
+                .arg(encoded.prefix_length)
+                .invoke_async::<bool>(&mut conn)
+                .await
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+        let mut results = Vec::with_capacity(entries.len());
+        let mut pipe = redis::pipe();
+
+        // Entries that fail to encode are reported without being sent.
+        // The script is invoked by its hash, so its source isn't sent with every entry.
+        for (key, entry) in entries {
+            let expire_millis = expire_millis(&entry.metadata);
+            match redis_payload::encode(&self.codec, entry) {
+                Ok(encoded) => {
+                    pipe.cmd("EVALSHA")
+                        .arg(SET_IF_CHANGED_SCRIPT.get_hash())
+                        .arg(1)
+                        .arg(self.full_key(&key))
+                        .arg(encoded.data)
+                        .arg(expire_millis)
+                        .arg(encoded.prefix_length)
+                        .ignore();
+                    results.push(Ok(()));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        // All writes are sent in a single round trip and fail together
+        let pipe = &pipe;
+        if let Err(e) = self
+            .run(move |mut conn| async move {
+                match pipe.query_async::<()>(&mut conn).await {
+                    // The script isn't cached by Redis yet, e.g. after a restart
+                    Err(e) if e.kind() == redis::ErrorKind::NoScriptError => {
+                        SET_IF_CHANGED_SCRIPT.prepare_invoke().load_async(&mut conn).await?;
+                        pipe.query_async::<()>(&mut conn).await
+                    }
+                    result => result,
                 }
+            })
+            .await
+        {
+            let error = CachifiedError::cache_source(e);
+            for result in results.iter_mut().filter(|result| result.is_ok()) {
+                *result = Err(error.clone());
             }
-            Err(_) => None,
         }
+
+        results
     }
 
-    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
-        let mut conn = self.connection.clone();
-        let full_key = self.full_key(key);
-        
-        let data = serde_json::to_string(&entry)?;
-        
-        // Set with TTL if specified
-        if let Some(ttl) = entry.metadata.ttl {
-            let expire_seconds = ttl.as_secs();
-            if expire_seconds > 0 {
-                conn.set_ex::<String, String, ()>(full_key, data, expire_seconds).await?;
-            } else {
-                conn.set::<String, String, ()>(full_key, data).await?;
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let full_key = &self.full_key(key);
+        let expire_millis = expire_millis(&entry.metadata);
+        let data = &redis_payload::encode(&self.codec, entry)?.data;
+
+        // The write is conditioned on the payload the version was read from,
+        // so it runs on the shared connection instead of a WATCHed one
+        let current = self.get_stored(key).await?;
+        let current_version = current
+            .clone()
+            .map(|data| redis_payload::decode(&self.codec, data))
+            .transpose()?
+            .map(|current| current.metadata.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+
+        let current = &current;
+        self.run(move |mut conn| async move {
+            SET_IF_PAYLOAD_SCRIPT
+                .key(full_key)
+                .arg(if current.is_none() { 1 } else { 0 })
+                .arg(current.as_deref().unwrap_or_default())
+                .arg(data)
+                .arg(expire_millis)
+                .invoke_async::<bool>(&mut conn)
+                .await
+        })
+        .await
+    }
+
+    async fn get_metadata(&self, key: &str) -> Option<CacheMetadata> {
+        // Only the metadata section is read, the value never leaves Redis
+        if let Some(header) = redis_payload::section_header::<T, _>(&self.codec) {
+            let full_key = &self.full_key(key);
+            let header = &header;
+            let stored = self
+                .run(move |mut conn| async move {
+                    GET_METADATA_SCRIPT
+                        .key(full_key)
+                        .arg(header)
+                        .invoke_async::<Option<Vec<u8>>>(&mut conn)
+                        .await
+                })
+                .await
+                .ok()?;
+            if let Some(stored) = stored {
+                return redis_payload::split_metadata(&stored).ok().map(|(metadata, _)| metadata);
             }
-        } else {
-            conn.set::<String, String, ()>(full_key, data).await?;
         }
-        
-        Ok(())
+
+        self.get(key).await.map(|entry| entry.metadata)
+    }
+
+    async fn touch(&self, key: &str, ttl: Duration, now: Duration) -> Result<bool> {
+        let Some(header) = redis_payload::section_header::<T, _>(&self.codec) else {
+            return touch_entry(self, key, ttl, now).await;
+        };
+        let full_key = &self.full_key(key);
+        let header = &header;
+
+        let stored = self
+            .run(move |mut conn| async move {
+                GET_METADATA_SCRIPT
+                    .key(full_key)
+                    .arg(header)
+                    .invoke_async::<Option<Vec<u8>>>(&mut conn)
+                    .await
+            })
+            .await?;
+        let Some(stored) = stored else {
+            // Missing, or stored before entries were split into sections
+            return touch_entry(self, key, ttl, now).await;
+        };
+
+        // Only the metadata section is rewritten, conditioned on the one that was read
+        let (metadata, section) = redis_payload::split_metadata(&stored)?;
+        let metadata = touched(metadata, ttl, now);
+        let expire_millis = expire_millis(&metadata);
+        let new_section = &redis_payload::encode_metadata(&metadata)?;
+        let touched = self
+            .run(move |mut conn| async move {
+                TOUCH_SCRIPT
+                    .key(full_key)
+                    .arg(header)
+                    .arg(section)
+                    .arg(new_section)
+                    .arg(expire_millis)
+                    .invoke_async::<Option<bool>>(&mut conn)
+                    .await
+            })
+            .await?;
+
+        Ok(touched.unwrap_or(false))
     }
 
     async fn remove(&self, key: &str) {
-        let mut conn = self.connection.clone();
-        let full_key = self.full_key(key);
-        let _ = conn.del::<String, ()>(full_key).await;
+        let full_key = &self.full_key(key);
+        let _ = self
+            .run(move |mut conn| async move { conn.del::<&str, ()>(full_key).await })
+            .await;
     }
 
     async fn clear(&self) {
-        let mut conn = self.connection.clone();
-        let pattern = format!("{}*", self.prefix);
-        
-        // Get all keys matching the pattern
-        if let Ok(keys) = conn.keys::<String, Vec<String>>(pattern).await {
-            if !keys.is_empty() {
-                let _ = conn.del::<Vec<String>, ()>(keys).await;
-            }
-        }
+        // Scanned step by step so only this cache's keys are deleted without blocking Redis
+        let _ = self.scan_prefix(&self.prefix, false).await;
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        // The same scan as `clear`, restricted to the longer prefix
+        self.scan_prefix(&self.full_key(prefix), false).await
     }
 
     async fn len(&self) -> usize {
-        let mut conn = self.connection.clone();
-        let pattern = format!("{}*", self.prefix);
-        
-        match conn.keys::<String, Vec<String>>(pattern).await {
-            Ok(keys) => keys.len(),
-            Err(_) => 0,
-        }
+        // Counted with SCAN, as `KEYS` would block Redis on large keyspaces
+        self.clear_dry_run().await.unwrap_or(0)
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        // EXISTS only checks the key, so stored `null` values are present too
+        let full_key = &self.full_key(key);
+        self.run(move |mut conn| async move { conn.exists::<&str, bool>(full_key).await })
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let keys = self.scan_full_keys().await?;
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        "redis"
     }
 }
 
@@ -316,13 +1757,10 @@ mod tests {
     use std::time::Duration;
 
     fn create_test_entry() -> CacheEntry<String> {
-        CacheEntry {
-            value: "test-value".to_string(),
-            metadata: CacheMetadata {
-                created_time: Duration::from_secs(1000),
-                ttl: Some(Duration::from_secs(300)),
-            },
-        }
+        CacheEntry::with_metadata(
+            "test-value".to_string(),
+            CacheMetadata::with_time(Duration::from_secs(1000), Some(Duration::from_secs(300))),
+        )
     }
 
     #[cfg(feature = "moka")]
@@ -345,6 +1783,88 @@ mod tests {
             assert!(cache.get("test-key").await.is_none());
         }
 
+        #[tokio::test]
+        async fn test_moka_cache_metadata_expiry() {
+            let cache: MokaCache<String> = MokaCache::with_metadata_expiry(100);
+            let expired = || {
+                CacheEntry::builder("value".to_string())
+                    .ttl(Duration::from_secs(60))
+                    .created_ago(Duration::from_secs(120))
+                    .build()
+            };
+
+            cache.set("valid", CacheEntry::new("value".to_string(), Some(Duration::from_secs(300)))).await.unwrap();
+            cache.set("forever", CacheEntry::new("value".to_string(), None)).await.unwrap();
+            cache.set("expired", expired()).await.unwrap();
+            let mut stale = expired();
+            stale.metadata.swr = Some(Duration::from_secs(300));
+            cache.set("stale", stale).await.unwrap();
+
+            assert!(cache.get("valid").await.is_some());
+            assert!(cache.get("forever").await.is_some());
+            assert!(cache.get("expired").await.is_none());
+            assert!(cache.get("stale").await.is_some());
+
+            // Refreshing an expired entry gives it a new lifetime
+            cache.set("expired", CacheEntry::new("value".to_string(), Some(Duration::from_secs(300)))).await.unwrap();
+            assert!(cache.get("expired").await.is_some());
+
+            let retaining: MokaCache<String> = MokaCache::from_moka(
+                MokaCache::builder()
+                    .max_capacity(100)
+                    .expire_after(MetadataExpiry::new().with_stale_retention(Duration::from_secs(300)))
+                    .build(),
+            );
+            retaining.set("expired", expired()).await.unwrap();
+            assert!(retaining.get("expired").await.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_eviction_listener() {
+            let removed = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let listener_removed = removed.clone();
+            let cache: MokaCache<String> = MokaCache::with_eviction_listener(100, move |key, entry, cause| {
+                listener_removed.lock().unwrap().push((key.to_string(), entry.value, cause));
+            });
+
+            cache.set("key", create_test_entry()).await.unwrap();
+            cache.set("key", CacheEntry::new("new-value".to_string(), None)).await.unwrap();
+            cache.remove("key").await;
+            cache.inner().run_pending_tasks().await;
+
+            assert_eq!(
+                *removed.lock().unwrap(),
+                vec![
+                    ("key".to_string(), "test-value".to_string(), RemovalCause::Replaced),
+                    ("key".to_string(), "new-value".to_string(), RemovalCause::Explicit),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_from_moka() {
+            let removed = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let listener_removed = removed.clone();
+            let inner = moka::future::Cache::builder()
+                .max_capacity(100)
+                .eviction_listener(move |key: Arc<String>, _entry: CacheEntry<String>, cause| {
+                    listener_removed.lock().unwrap().push((key.to_string(), cause));
+                })
+                .build();
+            let cache = MokaCache::from_moka(inner);
+
+            // The listener of the adopted cache sees removals through the wrapper
+            cache.set("key", create_test_entry()).await.unwrap();
+            assert_eq!(cache.get("key").await.unwrap().value, "test-value");
+            cache.remove("key").await;
+            cache.inner().run_pending_tasks().await;
+            assert_eq!(*removed.lock().unwrap(), vec![("key".to_string(), RemovalCause::Explicit)]);
+
+            // The old name still works
+            let aliased: MokaCache<String> = MokaCache::from_inner(cache.into_inner());
+            assert!(aliased.get("key").await.is_none());
+        }
+
         #[tokio::test]
         async fn test_moka_cache_clear() {
             let cache: MokaCache<String> = MokaCache::new(100);
@@ -369,6 +1889,22 @@ mod tests {
             assert!(cache.get("key3").await.is_none());
         }
 
+        #[tokio::test]
+        async fn test_moka_cache_len_is_exact_after_writes() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+            let entry = create_test_entry();
+
+            for i in 0..50 {
+                cache.set(&format!("key{i}"), entry.clone()).await.unwrap();
+            }
+            assert_eq!(cache.len().await, 50);
+
+            cache.remove("key0").await;
+            assert_eq!(cache.len().await, 49);
+            cache.clear().await;
+            assert!(cache.is_empty().await);
+        }
+
         #[tokio::test]
         async fn test_cache_clone() {
             let cache: MokaCache<String> = MokaCache::new(100);
@@ -383,12 +1919,216 @@ mod tests {
             assert!(retrieved.is_some());
             assert_eq!(retrieved.unwrap().value, "test-value");
         }
+
+        #[tokio::test]
+        async fn test_moka_cache_byte_capacity() {
+            let cache: MokaCache<String> =
+                MokaCache::with_byte_capacity(100, |_, value: &String| value.len() as u32);
+
+            for i in 0..10 {
+                cache
+                    .set(&format!("key{i}"), CacheEntry::new("x".repeat(20), None))
+                    .await
+                    .unwrap();
+            }
+
+            // At most five 20 byte values fit into the budget
+            assert!(cache.len().await <= 5);
+            assert!(cache.inner().weighted_size() <= 100);
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_put_and_get_value() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+
+            cache
+                .put("key", "value".to_string(), Some(Duration::from_secs(60)))
+                .await
+                .unwrap();
+            let created = cache.get("key").await.unwrap().metadata.created_time;
+
+            assert_eq!(cache.get_value("key", created).await, Some("value".to_string()));
+            assert_eq!(cache.get_value("key", created + Duration::from_secs(60)).await, None);
+            assert_eq!(cache.get_value("missing", created).await, None);
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_get_batch_if_fresh() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+            cache.set("fresh", create_test_entry()).await.unwrap();
+            cache
+                .set(
+                    "expired",
+                    CacheEntry::with_metadata(
+                        "old".to_string(),
+                        CacheMetadata::with_time(Duration::from_secs(0), Some(Duration::from_secs(1))),
+                    ),
+                )
+                .await
+                .unwrap();
+
+            let now = Duration::from_secs(1100);
+            let entries = cache.get_batch_if_fresh(&["fresh", "missing", "expired"], now).await;
+
+            assert_eq!(entries.len(), 3);
+            assert_eq!(entries[0].as_ref().unwrap().value, "test-value");
+            assert!(entries[1].is_none());
+            assert!(entries[2].is_none());
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_keys() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+            let entry = create_test_entry();
+
+            cache.set("key1", entry.clone()).await.unwrap();
+            cache.set("key2", entry).await.unwrap();
+
+            let mut keys = cache.keys().await.unwrap();
+            keys.sort();
+            assert_eq!(keys, vec!["key1".to_string(), "key2".to_string()]);
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_from_and_into_inner() {
+            let evicted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let listener_evicted = evicted.clone();
+            let inner = MokaFutureCache::builder()
+                .eviction_listener(move |_key, _value: CacheEntry<String>, _cause| {
+                    listener_evicted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                })
+                .build();
+
+            let cache = MokaCache::from(inner);
+            cache.set("key", create_test_entry()).await.unwrap();
+            cache.remove("key").await;
+            cache.inner().run_pending_tasks().await;
+            assert_eq!(evicted.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+            let clone = cache.clone();
+            clone.set("shared", create_test_entry()).await.unwrap();
+            let inner = cache.into_inner();
+            assert!(inner.contains_key("shared"));
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_concurrent_updates() {
+            let cache: MokaCache<u32> = MokaCache::new(100);
+
+            let updates: Vec<_> = (0..10)
+                .map(|_| {
+                    let cache = cache.clone();
+                    tokio::spawn(async move {
+                        cache
+                            .update("counter", |current| {
+                                CacheEntry::new(current.map_or(0, |entry| entry.value) + 1, None)
+                            })
+                            .await
+                    })
+                })
+                .collect();
+            for update in updates {
+                update.await.unwrap().unwrap();
+            }
+
+            let entry = cache.get("counter").await.unwrap();
+            assert_eq!(entry.value, 10);
+            assert_eq!(entry.metadata.version, 9);
+        }
+
+        #[tokio::test]
+        async fn test_moka_cache_clear_expired() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+            cache.set("fresh", create_test_entry()).await.unwrap();
+            cache.set("forever", CacheEntry::new("value".to_string(), None)).await.unwrap();
+            cache
+                .set(
+                    "expired",
+                    CacheEntry::with_metadata(
+                        "old".to_string(),
+                        CacheMetadata::with_time(Duration::from_secs(0), Some(Duration::from_secs(1))),
+                    ),
+                )
+                .await
+                .unwrap();
+
+            let removed = cache.clear_expired(Duration::from_secs(1100)).await.unwrap();
+
+            assert_eq!(removed, 1);
+            assert!(cache.get("expired").await.is_none());
+            assert!(cache.get("fresh").await.is_some());
+            assert!(cache.get("forever").await.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_cache_pointer_impls() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+            cache.set("key", create_test_entry()).await.unwrap();
+
+            // Forwarding works through references, boxes and shared trait objects
+            let borrowed = &cache;
+            assert!(borrowed.contains_key("key").await);
+
+            let boxed: Box<dyn Cache<String>> = Box::new(cache.clone());
+            assert_eq!(boxed.get("key").await.unwrap().value, "test-value");
+
+            let shared: Arc<dyn Cache<String>> = Arc::new(cache.clone());
+            shared.remove("key").await;
+            assert!(cache.is_empty().await);
+            assert_eq!(shared.backend(), "moka");
+        }
+
+        #[test]
+        fn test_moka_cache_backend() {
+            let cache: MokaCache<String> = MokaCache::new(100);
+
+            assert_eq!(Cache::<String>::backend(&cache), "moka");
+            assert_eq!(Cache::<String>::backend(&cache.scoped("scope:")), "moka");
+        }
+
+        crate::cache_conformance_tests!(
+            moka_conformance,
+            MokaCache::<String>::new(100),
+            "conformance-value".to_string()
+        );
     }
 
     #[cfg(all(feature = "redis", feature = "serde"))]
     mod redis_tests {
         use super::*;
 
+        #[test]
+        fn test_prefix_scan_deduplicates_exact_prefix_matches() {
+            let mut scan = PrefixScan::new("app:*");
+            assert!(scan.next_command().is_some());
+
+            // `MATCH` only filters by pattern, so keys without the exact prefix are dropped too
+            let keys = scan.advance((7, vec!["app:*1".to_string(), "app:x1".to_string(), "app:*2".to_string()]));
+            assert_eq!(keys, vec!["app:*1", "app:*2"]);
+            let keys = scan.advance((0, vec!["app:*2".to_string(), "app:*3".to_string()]));
+            assert_eq!(keys, vec!["app:*3"]);
+
+            assert!(scan.next_command().is_none());
+            assert_eq!(scan.found(), 3);
+        }
+
+        #[test]
+        fn test_expire_millis_rounds_up() {
+            let expiring = |ttl: Option<Duration>, swr: Option<Duration>| {
+                expire_millis(&CacheMetadata::new(ttl).with_swr(swr))
+            };
+
+            assert_eq!(expiring(None, None), 0);
+            // Short and past expiries never turn into no expiry at all
+            assert_eq!(expiring(Some(Duration::ZERO), None), 1);
+            assert_eq!(expiring(Some(Duration::from_micros(300)), None), 1);
+            assert_eq!(expiring(Some(Duration::from_micros(1_500)), None), 2);
+            assert_eq!(expiring(Some(Duration::from_millis(300)), None), 300);
+            assert_eq!(expiring(Some(Duration::from_millis(300)), Some(Duration::from_millis(200))), 500);
+            // TTLs meant as "forever" are stored without expiry
+            assert_eq!(expiring(Some(Duration::MAX), Some(Duration::from_secs(1))), 0);
+        }
+
         // Note: These tests require a running Redis instance
         // They are ignored by default to avoid failing CI/CD
         
@@ -411,5 +2151,306 @@ mod tests {
             cache.remove("test-key").await;
             assert!(cache.get("test-key").await.is_none());
         }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_get_raw() {
+            let cache: RedisCache<String> = RedisCache::new("redis://localhost:6379")
+                .await
+                .expect("Failed to connect to Redis");
+
+            let entry = create_test_entry();
+            cache.set("raw-key", entry.clone()).await.unwrap();
+
+            let raw = cache.get_raw("raw-key").await.unwrap().unwrap();
+            let decoded: CacheEntry<String> = serde_json::from_slice(&raw).unwrap();
+            assert_eq!(decoded.value, entry.value);
+
+            assert!(cache.get_raw("missing-raw-key").await.unwrap().is_none());
+            cache.remove("raw-key").await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_raw_bytes_codec() {
+            let cache = RedisCache::<Vec<u8>>::with_prefix(
+                "redis://localhost:6379",
+                "cachified-raw:".to_string(),
+            )
+            .await
+            .expect("Failed to connect to Redis")
+            .with_codec(crate::codec::RawBytesCodec);
+
+            let value: Vec<u8> = (0..=255).collect();
+            cache.set("bytes", CacheEntry::new(value.clone(), None)).await.unwrap();
+
+            // Stored verbatim instead of as a JSON array
+            let raw = cache.get_raw("bytes").await.unwrap().unwrap();
+            assert!(raw.ends_with(&value));
+
+            let entry = cache.get("bytes").await.unwrap();
+            assert_eq!(entry.value, value);
+            cache.remove("bytes").await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_get_many() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-many:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+
+            cache.set("a", create_test_entry()).await.unwrap();
+            cache.set("c", create_test_entry()).await.unwrap();
+
+            let entries = cache.get_many(&["a", "b", "c"]).await;
+            assert!(entries[0].is_some());
+            assert!(entries[1].is_none());
+            assert!(entries[2].is_some());
+            assert!(cache.get_many(&[]).await.is_empty());
+            cache.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_option_values() {
+            let cache: RedisCache<Option<String>> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-option:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            let ttl = Some(Duration::from_secs(60));
+
+            cache.put("none", None, ttl).await.unwrap();
+            cache.put("some", Some("value".to_string()), ttl).await.unwrap();
+
+            // A stored `None` is present, unlike a key that was never written
+            let entry = cache.get("none").await.expect("stored None should be present");
+            assert_eq!(entry.value, None);
+            assert_eq!(entry.metadata.ttl, ttl);
+            assert!(cache.contains_key("none").await);
+            assert_eq!(cache.get("some").await.unwrap().value, Some("value".to_string()));
+
+            assert!(cache.get("missing").await.is_none());
+            assert!(!cache.contains_key("missing").await);
+
+            cache.remove("none").await;
+            assert!(!cache.contains_key("none").await);
+            cache.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_skips_identical_writes() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-identical:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            let clock = crate::clock::MockClock::new(Duration::from_secs(1_000));
+            let refresh = |value: &'static str| {
+                crate::cachified(
+                    crate::CachifiedOptionsBuilder::new(cache.clone(), "key")
+                        .ttl(Duration::from_secs(60))
+                        .clock(clock.clone())
+                        .get_fresh_value(move || async move { Ok(value.to_string()) }),
+                )
+            };
+            refresh("value").await.unwrap();
+
+            // Mark the stored key behind the metadata so a rewrite of the value would be visible
+            let client = redis::Client::open("redis://localhost:6379").unwrap();
+            let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+            conn.append::<&str, &str, ()>("cachified-identical:key", &"#".repeat(64)).await.unwrap();
+
+            // Refreshing an unchanged value only rewrites the metadata
+            clock.advance(Duration::from_secs(120));
+            refresh("value").await.unwrap();
+            let last: String = conn.getrange("cachified-identical:key", -1, -1).await.unwrap();
+            assert_eq!(last, "#");
+            let entry = cache.get("key").await.unwrap();
+            assert_eq!(entry.metadata.created_time, crate::clock::Clock::now(&clock));
+            assert_eq!(entry.metadata.version, 1);
+
+            clock.advance(Duration::from_secs(120));
+            refresh("changed").await.unwrap();
+            let last: String = conn.getrange("cachified-identical:key", -1, -1).await.unwrap();
+            assert_ne!(last, "#");
+            assert_eq!(cache.get("key").await.unwrap().value, "changed");
+            cache.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_metadata_and_touch_skip_the_value() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-touch:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            let created = Duration::from_secs(1_000);
+            let metadata = CacheMetadata::with_time(created, Some(Duration::from_secs(60)));
+            cache.set("key", CacheEntry::with_metadata("value".to_string(), metadata.clone())).await.unwrap();
+
+            // Break the encoded value, so any operation reading it fails
+            let client = redis::Client::open("redis://localhost:6379").unwrap();
+            let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+            conn.setrange::<&str, &str, ()>("cachified-touch:key", 8, "X").await.unwrap();
+            assert!(cache.try_get("key").await.is_err());
+
+            assert_eq!(cache.get_metadata("key").await, Some(metadata));
+            let now = created + Duration::from_secs(30);
+            assert!(cache.touch("key", Duration::from_secs(3600), now).await.unwrap());
+            assert!(!cache.touch("missing", Duration::from_secs(3600), now).await.unwrap());
+
+            let touched = cache.get_metadata("key").await.unwrap();
+            assert_eq!(touched.ttl, Some(Duration::from_secs(3630)));
+            assert_eq!(touched.version, 1);
+            let expiry: i64 = conn.ttl("cachified-touch:key").await.unwrap();
+            assert!(expiry > 3600);
+            // The broken value was neither read nor written back
+            let value: String = conn.getrange("cachified-touch:key", 8, 8).await.unwrap();
+            assert_eq!(value, "X");
+            cache.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_keeps_entries_through_swr_window() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-swr:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            let metadata = CacheMetadata::new(Some(Duration::from_secs(60))).with_swr(Some(Duration::from_secs(300)));
+            cache.set("key", CacheEntry::with_metadata("value".to_string(), metadata)).await.unwrap();
+
+            // Redis expires the key after the stale window, not the logical TTL
+            let client = redis::Client::open("redis://localhost:6379").unwrap();
+            let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+            let ttl: i64 = conn.ttl("cachified-swr:key").await.unwrap();
+            assert!(ttl > 60);
+            assert!(ttl <= 360);
+            assert_eq!(cache.get("key").await.unwrap().metadata.ttl, Some(Duration::from_secs(60)));
+            cache.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_expires_sub_second_ttls() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-short:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            cache.put("key", "value".to_string(), Some(Duration::from_millis(300))).await.unwrap();
+
+            let client = redis::Client::open("redis://localhost:6379").unwrap();
+            let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+            let ttl: i64 = conn.pttl("cachified-short:key").await.unwrap();
+            assert!(ttl > 0);
+            assert!(ttl <= 300);
+
+            tokio::time::sleep(Duration::from_millis(400)).await;
+            assert!(!cache.contains_key("key").await);
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_from_client_and_connection() {
+            let client = redis::Client::open("redis://localhost:6379").unwrap();
+            let connection = client.get_multiplexed_async_connection().await.unwrap();
+
+            let from_client: RedisCache<String> =
+                RedisCache::from_client(client, "cachified-injected:".to_string()).await.unwrap();
+            let from_connection: RedisCache<String> =
+                RedisCache::from_connection(connection, "cachified-injected:".to_string());
+
+            from_client.set("key", create_test_entry()).await.unwrap();
+            assert_eq!(from_connection.get("key").await.unwrap().value, "test-value");
+
+            // Compare-and-set runs on the shared connection, so it works either way
+            assert!(from_client.set_if_version("other", create_test_entry(), None).await.unwrap());
+            assert!(from_connection.set_if_version("another", create_test_entry(), None).await.unwrap());
+            assert!(!from_connection.set_if_version("another", create_test_entry(), None).await.unwrap());
+            from_client.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_connection_state() {
+            let cache: RedisCache<String> = RedisCache::new("redis://localhost:6379")
+                .await
+                .expect("Failed to connect to Redis")
+                .with_reconnect_policy(ReconnectPolicy {
+                    max_attempts: 3,
+                    ..ReconnectPolicy::default()
+                });
+
+            assert_eq!(cache.connection_state(), RedisConnectionState::Connected);
+            assert!(cache.get("missing-key").await.is_none());
+            assert_eq!(cache.connection_state(), RedisConnectionState::Connected);
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_clear_is_scoped() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-clear:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            // Shares the prefix as a substring, but not as an exact prefix
+            let other: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "other-cachified-clear:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+
+            cache.set("a", create_test_entry()).await.unwrap();
+            cache.set("b", create_test_entry()).await.unwrap();
+            other.set("a", create_test_entry()).await.unwrap();
+
+            assert_eq!(cache.clear_dry_run().await.unwrap(), 2);
+            // A dry run doesn't delete anything
+            assert!(cache.contains_key("a").await);
+
+            cache.clear().await;
+            assert_eq!(cache.clear_dry_run().await.unwrap(), 0);
+            assert!(other.contains_key("a").await);
+            other.clear().await;
+        }
+
+        #[tokio::test]
+        #[ignore = "requires running Redis instance"]
+        async fn test_redis_cache_len_and_clear_scan_many_keys() {
+            let cache: RedisCache<String> =
+                RedisCache::with_prefix("redis://localhost:6379", "cachified-scan:".to_string())
+                    .await
+                    .expect("Failed to connect to Redis");
+            cache.clear().await;
+
+            // Several times the SCAN count, so both need multiple cursor iterations
+            let entries: Vec<_> = (0..5_000).map(|i| (format!("key-{i}"), create_test_entry())).collect();
+            assert!(cache.set_many(entries).await.iter().all(Result::is_ok));
+            assert_eq!(cache.len().await, 5_000);
+
+            cache.clear().await;
+            assert_eq!(cache.len().await, 0);
+            assert!(cache.is_empty().await);
+        }
+
+        crate::cache_conformance_tests!(
+            #[ignore = "requires running Redis instance"]
+            redis_conformance,
+            RedisCache::<String>::with_prefix(
+                "redis://localhost:6379",
+                // Unique prefix per test so concurrently running tests don't interfere
+                format!(
+                    "cachified-conformance:{}:",
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos()
+                )
+            )
+            .await
+            .expect("Failed to connect to Redis"),
+            "conformance-value".to_string()
+        );
     }
 }
@@ -0,0 +1,424 @@
+//! Caches that buffer writes and flush them in batches.
+
+use super::Cache;
+use crate::{CacheEntry, CachifiedError, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+/// A cache that buffers writes and flushes them to the inner cache in batches
+///
+/// Writes are collected in memory and written with a single
+/// [`Cache::set_many`] call once `max_entries` writes are buffered, every
+/// `flush_interval`, on [`BufferedCache::flush`] and when the last clone is
+/// dropped. With a [`RedisCache`](crate::RedisCache) as inner cache, a flush
+/// is one pipelined round trip instead of one per write. Later writes of a key
+/// replace buffered ones, so only the last of them reaches the inner cache.
+///
+/// Reads check the buffer before the inner cache, so a writer always sees its
+/// own writes. Other processes sharing the inner cache only see them after the
+/// next flush. Removals, [`Cache::set_if_version`], `keys` and `len` flush or
+/// wait for running flushes first, so they stay consistent with buffered writes.
+///
+/// # Durability
+///
+/// Buffered writes only live in memory. They are lost if the process exits
+/// before they are flushed, and a failed flush drops them. Flushing on drop
+/// spawns a task on the current Tokio runtime, which doesn't run if the
+/// runtime is shutting down; call [`BufferedCache::flush`] before shutdown
+/// to make sure all writes reach the inner cache.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cache::BufferedCache, Cache, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let backend: MokaCache<String> = MokaCache::new(1000);
+/// let cache = BufferedCache::new(backend.clone(), 100, Duration::from_millis(50));
+///
+/// cache.put("event-1", "created".to_string(), None).await?;
+/// // Buffered writes are visible through the buffered cache right away
+/// assert!(cache.contains_key("event-1").await);
+///
+/// cache.flush().await?;
+/// assert!(backend.contains_key("event-1").await);
+/// # Ok(())
+/// # }
+/// ```
+pub struct BufferedCache<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    shared: Arc<Shared<T, C>>,
+}
+
+impl<T, C> Clone for BufferedCache<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// State shared by all clones of a [`BufferedCache`]
+struct Shared<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    inner: Arc<C>,
+    buffer: Mutex<Buffer<T>>,
+    /// Held while flushing, so flushes run one at a time
+    flush_lock: tokio::sync::Mutex<()>,
+    max_entries: usize,
+}
+
+/// Writes that haven't reached the inner cache yet
+struct Buffer<T> {
+    /// Writes waiting for the next flush
+    pending: HashMap<String, CacheEntry<T>>,
+    /// Writes of the running flush, still served until it completes
+    flushing: HashMap<String, CacheEntry<T>>,
+}
+
+impl<T> Buffer<T> {
+    fn get(&self, key: &str) -> Option<&CacheEntry<T>> {
+        self.pending.get(key).or_else(|| self.flushing.get(key))
+    }
+}
+
+impl<T, C> BufferedCache<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    /// Wrap a cache, flushing once `max_entries` writes are buffered and every `flush_interval`
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime, since the periodic flush
+    /// runs in a spawned task.
+    pub fn new(inner: C, max_entries: usize, flush_interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            inner: Arc::new(inner),
+            buffer: Mutex::new(Buffer {
+                pending: HashMap::new(),
+                flushing: HashMap::new(),
+            }),
+            flush_lock: tokio::sync::Mutex::new(()),
+            max_entries,
+        });
+
+        tokio::spawn(flush_periodically(Arc::downgrade(&shared), flush_interval));
+        Self { shared }
+    }
+
+    /// Write all buffered entries to the inner cache
+    ///
+    /// Waits for a flush that is already running before starting a new one.
+    ///
+    /// # Returns
+    ///
+    /// Returns the first error of the batch write. The entries of a failed
+    /// flush are dropped, not retried.
+    pub async fn flush(&self) -> Result<()> {
+        self.shared.flush().await
+    }
+
+    /// Get the number of writes that haven't reached the inner cache yet
+    pub fn buffered(&self) -> usize {
+        let buffer = self.shared.buffer();
+        buffer.pending.len() + buffer.flushing.len()
+    }
+
+    /// Get the wrapped cache
+    pub fn inner(&self) -> &C {
+        &self.shared.inner
+    }
+
+    /// Buffer writes and flush if the buffer is full
+    async fn buffer_writes(&self, entries: impl IntoIterator<Item = (String, CacheEntry<T>)>) -> Result<()> {
+        let full = {
+            let mut buffer = self.shared.buffer();
+            buffer.pending.extend(entries);
+            buffer.pending.len() >= self.shared.max_entries
+        };
+
+        if full {
+            self.flush().await
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T, C> Shared<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    fn buffer(&self) -> MutexGuard<'_, Buffer<T>> {
+        self.buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let _flushing = self.flush_lock.lock().await;
+
+        let entries: Vec<_> = {
+            let mut buffer = self.buffer();
+            buffer.flushing = std::mem::take(&mut buffer.pending);
+            buffer
+                .flushing
+                .iter()
+                .map(|(key, entry)| (key.clone(), entry.clone()))
+                .collect()
+        };
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let results = self.inner.set_many(entries).await;
+        self.buffer().flushing.clear();
+        results.into_iter().collect()
+    }
+}
+
+impl<T, C> Drop for Shared<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    fn drop(&mut self) {
+        let pending = std::mem::take(&mut self.buffer().pending);
+        if pending.is_empty() {
+            return;
+        }
+
+        // Writes are lost if there is no runtime left to flush them on
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let inner = self.inner.clone();
+            runtime.spawn(async move {
+                inner.set_many(pending.into_iter().collect()).await;
+            });
+        }
+    }
+}
+
+/// Flush the buffer every `interval` until the cache is dropped
+async fn flush_periodically<T, C>(shared: Weak<Shared<T, C>>, interval: Duration)
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes immediately
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+        // Failed writes are dropped, there is no caller to report them to
+        let _ = shared.flush().await;
+    }
+}
+
+#[async_trait]
+impl<T, C> Cache<T> for BufferedCache<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        let buffered = self.shared.buffer().get(key).cloned();
+        match buffered {
+            Some(entry) => Some(entry),
+            None => self.shared.inner.get(key).await,
+        }
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        let buffered = self.shared.buffer().get(key).cloned();
+        match buffered {
+            Some(entry) => Ok(Some(entry)),
+            None => self.shared.inner.try_get(key).await,
+        }
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        let mut entries: Vec<_> = {
+            let buffer = self.shared.buffer();
+            keys.iter().map(|key| buffer.get(key).cloned()).collect()
+        };
+
+        // Only the keys that aren't buffered are read from the inner cache
+        let missing: Vec<usize> = (0..keys.len()).filter(|index| entries[*index].is_none()).collect();
+        if !missing.is_empty() {
+            let missing_keys: Vec<&str> = missing.iter().map(|index| keys[*index]).collect();
+            let read = self.shared.inner.get_many(&missing_keys).await;
+            for (index, entry) in missing.into_iter().zip(read) {
+                entries[index] = entry;
+            }
+        }
+
+        entries
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        self.buffer_writes([(key.to_string(), entry)]).await
+    }
+
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+        let count = entries.len();
+        match self.buffer_writes(entries).await {
+            Ok(()) => (0..count).map(|_| Ok(())).collect(),
+            Err(e) => {
+                let error = CachifiedError::cache_source(e);
+                (0..count).map(|_| Err(error.clone())).collect()
+            }
+        }
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        // The version has to be compared against the latest write
+        self.flush().await?;
+        self.shared.inner.set_if_version(key, entry, expected_version).await
+    }
+
+    async fn remove(&self, key: &str) {
+        // A running flush could otherwise write the entry again after removing it
+        let _flushing = self.shared.flush_lock.lock().await;
+        self.shared.buffer().pending.remove(key);
+        self.shared.inner.remove(key).await
+    }
+
+    async fn clear(&self) {
+        let _flushing = self.shared.flush_lock.lock().await;
+        self.shared.buffer().pending.clear();
+        self.shared.inner.clear().await
+    }
+
+    async fn len(&self) -> usize {
+        let _ = self.flush().await;
+        self.shared.inner.len().await
+    }
+
+    async fn len_valid(&self, now: Duration) -> usize {
+        let _ = self.flush().await;
+        self.shared.inner.len_valid(now).await
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        let buffered = self.shared.buffer().get(key).is_some();
+        buffered || self.shared.inner.contains_key(key).await
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        self.flush().await?;
+        // Entries buffered since the flush would otherwise survive the removal
+        let _flushing = self.shared.flush_lock.lock().await;
+        self.shared.buffer().pending.retain(|key, _| !key.starts_with(prefix));
+        self.shared.inner.remove_prefix(prefix).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.flush().await?;
+        self.shared.inner.keys().await
+    }
+
+    fn backend(&self) -> &'static str {
+        self.shared.inner.backend()
+    }
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::MokaCache;
+
+    crate::cache_conformance_tests!(
+        buffered_conformance,
+        BufferedCache::new(MokaCache::<String>::new(100), 10, Duration::from_secs(60)),
+        "value".to_string()
+    );
+
+    #[tokio::test]
+    async fn test_buffered_cache_flushes_when_full() {
+        let backend: MokaCache<String> = MokaCache::new(100);
+        let cache = BufferedCache::new(backend.clone(), 3, Duration::from_secs(60));
+
+        cache.put("a", "1".to_string(), None).await.unwrap();
+        cache.put("b", "2".to_string(), None).await.unwrap();
+        // Reads see buffered writes before they reach the backend
+        assert_eq!(cache.get("a").await.unwrap().value, "1");
+        assert!(backend.get("a").await.is_none());
+        assert_eq!(cache.buffered(), 2);
+
+        cache.put("c", "3".to_string(), None).await.unwrap();
+        assert_eq!(cache.buffered(), 0);
+        assert_eq!(backend.get("c").await.unwrap().value, "3");
+    }
+
+    #[tokio::test]
+    async fn test_buffered_cache_flushes_periodically_and_on_drop() {
+        let backend: MokaCache<String> = MokaCache::new(100);
+        let cache = BufferedCache::new(backend.clone(), 100, Duration::from_millis(50));
+
+        cache.put("periodic", "value".to_string(), None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(backend.contains_key("periodic").await);
+
+        cache.put("dropped", "value".to_string(), None).await.unwrap();
+        drop(cache);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(backend.contains_key("dropped").await);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_cache_remove_discards_buffered_write() {
+        let backend: MokaCache<String> = MokaCache::new(100);
+        let cache = BufferedCache::new(backend.clone(), 100, Duration::from_secs(60));
+
+        cache.put("key", "value".to_string(), None).await.unwrap();
+        cache.remove("key").await;
+        cache.flush().await.unwrap();
+
+        assert!(cache.get("key").await.is_none());
+        assert!(backend.get("key").await.is_none());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_buffered_cache_set_many_keeps_flush_error_source() {
+        use crate::testing::FailingCache;
+        use std::error::Error;
+
+        let backend = FailingCache::new(MokaCache::<String>::new(100));
+        backend.fail_writes(true);
+        let cache = BufferedCache::new(backend, 2, Duration::from_secs(60));
+
+        let entries = vec![
+            ("a".to_string(), CacheEntry::new("1".to_string(), None)),
+            ("b".to_string(), CacheEntry::new("2".to_string(), None)),
+        ];
+        let results = cache.set_many(entries).await;
+
+        assert_eq!(results.len(), 2);
+        for result in results {
+            let error = result.unwrap_err();
+            assert!(matches!(error, CachifiedError::CacheSource(_)));
+            assert!(error.source().unwrap().to_string().contains("Injected write failure"));
+        }
+    }
+}
@@ -0,0 +1,510 @@
+//! Cache storing entries as items of an Amazon DynamoDB table.
+
+use super::Cache;
+use crate::codec::{Codec, JsonCodec};
+use crate::jitter::ExponentialBackoff;
+use crate::{current_time, CacheEntry, CachifiedError, Result};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, Select, WriteRequest};
+use aws_sdk_dynamodb::Client;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Partition key attribute holding the prefixed cache key
+const KEY_ATTRIBUTE: &str = "pk";
+
+/// Attribute holding the entry serialized with the codec
+const ENTRY_ATTRIBUTE: &str = "entry";
+
+/// Attribute holding the version of the entry, for conditional writes
+const VERSION_ATTRIBUTE: &str = "version";
+
+/// Attribute holding the UNIX timestamp in seconds DynamoDB's TTL deletes the item after
+const TTL_ATTRIBUTE: &str = "expires_at";
+
+/// Most delete requests DynamoDB accepts in a single `BatchWriteItem`
+const MAX_BATCH_WRITE: usize = 25;
+
+/// Most times a `BatchWriteItem` is sent until all of its requests were processed
+const MAX_BATCH_WRITE_ATTEMPTS: u32 = 8;
+
+/// Backoff between sending unprocessed requests again, as DynamoDB recommends
+const UNPROCESSED_BACKOFF: ExponentialBackoff = ExponentialBackoff {
+    initial: Duration::from_millis(50),
+    max: Duration::from_secs(5),
+};
+
+/// DynamoDB cache implementation
+///
+/// A durable cache shared between processes without running a server, e.g.
+/// for AWS Lambda. Entries are serialized with a [`Codec`], [`JsonCodec`] by
+/// default, and stored under a key prefix. Requires the "dynamodb" feature
+/// to be enabled.
+///
+/// The table needs a string partition key named `pk` and no sort key.
+/// Enable DynamoDB's TTL on the `expires_at` attribute to have items deleted
+/// once their TTL and their stale-while-revalidate window have passed, like [`RedisCache`](crate::RedisCache)
+/// lets Redis expire them. Since DynamoDB deletes expired items lazily, items
+/// past that time are treated as missing when read.
+///
+/// Reads are strongly consistent. [`Cache::set_if_version`] is atomic, using
+/// a condition on the stored version. [`Cache::clear`], [`Cache::remove_prefix`],
+/// [`Cache::len`] and [`Cache::keys`] scan the whole table, which is slow and
+/// costly on large tables.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "dynamodb")]
+/// use cachified::{cachified, CachifiedOptionsBuilder, DynamoDbCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "dynamodb")]
+/// # async fn example(client: aws_sdk_dynamodb::Client) -> Result<(), Box<dyn std::error::Error>> {
+/// // The client is usually created from `aws_config::load_from_env()`
+/// let cache: DynamoDbCache<String> = DynamoDbCache::new(client, "cachified");
+///
+/// let value: String = cachified(
+///     CachifiedOptionsBuilder::new(cache, "greeting")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DynamoDbCache<T, K = JsonCodec> {
+    client: Client,
+    table: String,
+    prefix: String,
+    codec: K,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> DynamoDbCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Create a new DynamoDbCache storing entries in the given table
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Preconfigured DynamoDB client
+    /// * `table` - Name of the table, which must already exist
+    pub fn new(client: Client, table: impl Into<String>) -> Self {
+        Self::with_prefix(client, table, "cachified:".to_string())
+    }
+
+    /// Create a new DynamoDbCache with a custom key prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Preconfigured DynamoDB client
+    /// * `table` - Name of the table, which must already exist
+    /// * `prefix` - Custom prefix for all cache keys
+    pub fn with_prefix(client: Client, table: impl Into<String>, prefix: String) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            prefix,
+            codec: JsonCodec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, K> DynamoDbCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Use a different codec for serializing entries
+    ///
+    /// Entries written with one codec generally can't be read with another,
+    /// so use a separate prefix when switching codecs.
+    pub fn with_codec<K2>(self, codec: K2) -> DynamoDbCache<T, K2> {
+        DynamoDbCache {
+            client: self.client,
+            table: self.table,
+            prefix: self.prefix,
+            codec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get the underlying DynamoDB client
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Get the full key with prefix
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Scan for the keys of all items starting with `full_prefix`
+    ///
+    /// Returns the full keys, including the prefix.
+    async fn scan_full_keys(&self, full_prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut start_key = None;
+        loop {
+            let output = self
+                .client
+                .scan()
+                .table_name(&self.table)
+                .consistent_read(true)
+                .filter_expression("begins_with(#pk, :prefix)")
+                .projection_expression("#pk")
+                .expression_attribute_names("#pk", KEY_ATTRIBUTE)
+                .expression_attribute_values(":prefix", AttributeValue::S(full_prefix.to_string()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(CachifiedError::cache_source)?;
+
+            keys.extend(
+                output
+                    .items
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|mut item| match item.remove(KEY_ATTRIBUTE) {
+                        Some(AttributeValue::S(key)) => Some(key),
+                        _ => None,
+                    }),
+            );
+
+            start_key = output.last_evaluated_key;
+            if start_key.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Delete all items starting with `full_prefix` in batches
+    ///
+    /// Returns the number of deleted items.
+    async fn delete_prefix(&self, full_prefix: &str) -> Result<usize> {
+        let keys = self.scan_full_keys(full_prefix).await?;
+
+        for batch in keys.chunks(MAX_BATCH_WRITE) {
+            let mut requests = batch
+                .iter()
+                .map(|key| {
+                    let delete = DeleteRequest::builder()
+                        .key(KEY_ATTRIBUTE, AttributeValue::S(key.clone()))
+                        .build()
+                        .map_err(CachifiedError::cache_source)?;
+                    Ok(WriteRequest::builder().delete_request(delete).build())
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // Throttled requests come back as unprocessed and are sent again after a backoff
+            for attempt in 0.. {
+                if attempt > 0 {
+                    tokio::time::sleep(UNPROCESSED_BACKOFF.delay(attempt - 1)).await;
+                }
+                let output = self
+                    .client
+                    .batch_write_item()
+                    .request_items(&self.table, requests)
+                    .send()
+                    .await
+                    .map_err(CachifiedError::cache_source)?;
+                requests = output
+                    .unprocessed_items
+                    .and_then(|mut unprocessed| unprocessed.remove(&self.table))
+                    .unwrap_or_default();
+                if requests.is_empty() {
+                    break;
+                }
+                if attempt + 1 >= MAX_BATCH_WRITE_ATTEMPTS {
+                    return Err(CachifiedError::cache(format!(
+                        "{} delete requests were still unprocessed after {MAX_BATCH_WRITE_ATTEMPTS} attempts",
+                        requests.len()
+                    )));
+                }
+            }
+        }
+
+        Ok(keys.len())
+    }
+}
+
+impl<T, K> DynamoDbCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+    K: Codec<T>,
+{
+    /// Build the item storing an entry
+    fn item(&self, key: &str, entry: &CacheEntry<T>) -> Result<HashMap<String, AttributeValue>> {
+        let data = self.codec.encode(entry)?;
+
+        let mut item = HashMap::from([
+            (KEY_ATTRIBUTE.to_string(), AttributeValue::S(self.full_key(key))),
+            (ENTRY_ATTRIBUTE.to_string(), AttributeValue::B(Blob::new(data))),
+            (VERSION_ATTRIBUTE.to_string(), AttributeValue::N(entry.metadata.version.to_string())),
+        ]);
+        if let Some(stale_until) = entry.metadata.stale_until() {
+            // Round up, so DynamoDB never deletes an item early
+            let expires_at = stale_until.as_secs() + u64::from(stale_until.subsec_nanos() > 0);
+            item.insert(TTL_ATTRIBUTE.to_string(), AttributeValue::N(expires_at.to_string()));
+        }
+        Ok(item)
+    }
+}
+
+#[async_trait]
+impl<T, K> Cache<T> for DynamoDbCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+    K: Codec<T> + Clone + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        self.try_get(key).await.ok().flatten()
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(KEY_ATTRIBUTE, AttributeValue::S(self.full_key(key)))
+            .projection_expression("#entry")
+            .expression_attribute_names("#entry", ENTRY_ATTRIBUTE)
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(CachifiedError::cache_source)?;
+
+        let Some(AttributeValue::B(data)) = output.item.and_then(|mut item| item.remove(ENTRY_ATTRIBUTE)) else {
+            return Ok(None);
+        };
+        let entry: CacheEntry<T> = self.codec.decode(data.into_inner())?;
+
+        // DynamoDB deletes expired items lazily, possibly days later
+        if entry.metadata.stale_until().is_some_and(|stale_until| stale_until <= current_time()) {
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(self.item(key, &entry)?))
+            .send()
+            .await
+            .map_err(CachifiedError::cache_source)?;
+        Ok(())
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(self.item(key, &entry)?));
+
+        let request = match expected_version {
+            Some(version) => request
+                .condition_expression("#version = :version")
+                .expression_attribute_names("#version", VERSION_ATTRIBUTE)
+                .expression_attribute_values(":version", AttributeValue::N(version.to_string())),
+            // Expired items that DynamoDB hasn't deleted yet count as missing, like on reads
+            None => request
+                .condition_expression("attribute_not_exists(#pk) OR #expires_at <= :now")
+                .expression_attribute_names("#pk", KEY_ATTRIBUTE)
+                .expression_attribute_names("#expires_at", TTL_ATTRIBUTE)
+                .expression_attribute_values(":now", AttributeValue::N(current_time().as_secs().to_string())),
+        };
+
+        match request.send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => Ok(false),
+            Err(e) => Err(CachifiedError::cache_source(e)),
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let _ = self
+            .client
+            .delete_item()
+            .table_name(&self.table)
+            .key(KEY_ATTRIBUTE, AttributeValue::S(self.full_key(key)))
+            .send()
+            .await;
+    }
+
+    async fn clear(&self) {
+        let _ = self.delete_prefix(&self.prefix).await;
+    }
+
+    async fn len(&self) -> usize {
+        let mut count = 0;
+        let mut start_key = None;
+        loop {
+            let Ok(output) = self
+                .client
+                .scan()
+                .table_name(&self.table)
+                .consistent_read(true)
+                .select(Select::Count)
+                .filter_expression("begins_with(#pk, :prefix)")
+                .expression_attribute_names("#pk", KEY_ATTRIBUTE)
+                .expression_attribute_values(":prefix", AttributeValue::S(self.prefix.clone()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+            else {
+                return 0;
+            };
+
+            count += usize::try_from(output.count).unwrap_or(0);
+            start_key = output.last_evaluated_key;
+            if start_key.is_none() {
+                return count;
+            }
+        }
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        self.delete_prefix(&self.full_key(prefix)).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let keys = self.scan_full_keys(&self.prefix).await?;
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        "dynamodb"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+    use aws_sdk_dynamodb::types::{AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType};
+    use std::time::Duration;
+
+    const TABLE: &str = "cachified-test";
+
+    /// Connect to DynamoDB Local on port 8000, creating the test table if needed
+    async fn local_client() -> Client {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url("http://localhost:8000")
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("local", "local", None, None, "cachified-test"))
+            .build();
+        let client = Client::from_conf(config);
+
+        let created = client
+            .create_table()
+            .table_name(TABLE)
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(KEY_ATTRIBUTE)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()
+                    .unwrap(),
+            )
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(KEY_ATTRIBUTE)
+                    .key_type(KeyType::Hash)
+                    .build()
+                    .unwrap(),
+            )
+            .billing_mode(BillingMode::PayPerRequest)
+            .send()
+            .await;
+        // The table usually exists already from an earlier test
+        if let Err(e) = created {
+            assert!(
+                e.as_service_error().is_some_and(|e| e.is_resource_in_use_exception()),
+                "Failed to create table: {e:?}"
+            );
+        }
+
+        client
+    }
+
+    fn create_test_entry() -> CacheEntry<String> {
+        CacheEntry::new("test-value".to_string(), Some(Duration::from_secs(300)))
+    }
+
+    #[test]
+    fn test_item_attributes() {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .build();
+        let cache: DynamoDbCache<String> = DynamoDbCache::new(Client::from_conf(config), TABLE);
+
+        let entry = CacheEntry::with_metadata(
+            "value".to_string(),
+            crate::CacheMetadata::with_time(Duration::from_millis(1_000_500), Some(Duration::from_secs(60)))
+                .with_swr(Some(Duration::from_secs(30)))
+                .with_version(3),
+        );
+        let item = cache.item("key", &entry).unwrap();
+
+        assert_eq!(item[KEY_ATTRIBUTE], AttributeValue::S("cachified:key".to_string()));
+        assert_eq!(item[VERSION_ATTRIBUTE], AttributeValue::N("3".to_string()));
+        // Created at 1000.5s, expired after 60s and stale for another 30s, rounded up
+        assert_eq!(item[TTL_ATTRIBUTE], AttributeValue::N("1091".to_string()));
+
+        let forever = cache.item("key", &CacheEntry::new("value".to_string(), None)).unwrap();
+        assert!(!forever.contains_key(TTL_ATTRIBUTE));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running DynamoDB Local instance"]
+    async fn test_dynamodb_cache_basic_operations() {
+        let cache: DynamoDbCache<String> =
+            DynamoDbCache::with_prefix(local_client().await, TABLE, "cachified-basic:".to_string());
+
+        cache.set("test-key", create_test_entry()).await.unwrap();
+        assert_eq!(cache.get("test-key").await.unwrap().value, "test-value");
+
+        cache.remove("test-key").await;
+        assert!(cache.get("test-key").await.is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running DynamoDB Local instance"]
+    async fn test_dynamodb_cache_expired_items_are_missing() {
+        let cache: DynamoDbCache<String> =
+            DynamoDbCache::with_prefix(local_client().await, TABLE, "cachified-expired:".to_string());
+        let expired = CacheEntry::builder("value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(120))
+            .build();
+
+        cache.set("expired", expired).await.unwrap();
+        assert!(cache.get("expired").await.is_none());
+        // A write expecting no entry replaces the expired item
+        assert!(cache.set_if_version("expired", create_test_entry(), None).await.unwrap());
+        assert!(cache.get("expired").await.is_some());
+        cache.clear().await;
+    }
+
+    crate::cache_conformance_tests!(
+        #[ignore = "requires running DynamoDB Local instance"]
+        dynamodb_conformance,
+        DynamoDbCache::<String>::with_prefix(
+            local_client().await,
+            TABLE,
+            // Unique prefix per test so concurrently running tests don't interfere
+            format!("cachified-conformance-{}:", crate::current_time().as_nanos()),
+        ),
+        "conformance-value".to_string()
+    );
+}
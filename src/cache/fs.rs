@@ -0,0 +1,386 @@
+//! Cache storing entries as JSON files in a directory.
+
+use super::Cache;
+use crate::clock::{Clock, SystemClock};
+use crate::{CacheEntry, CachifiedError, Result};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Extension of entry files
+const ENTRY_EXTENSION: &str = "json";
+
+/// Counter making the names of temporary files unique within the process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// File system based cache implementation
+///
+/// A persistent cache for CLI tools and local development that survives
+/// restarts without a Redis server. Requires the "fs" feature to be enabled.
+///
+/// Every entry is stored as a JSON file in the root directory, named after
+/// the SHA-256 hash of its key, so keys can contain any characters without
+/// escaping the directory. Writes go to a temporary file that is renamed over
+/// the entry file, so readers never see partially written entries.
+///
+/// Expired entries are deleted when read, like Redis drops entries once their
+/// TTL has passed, unless they are still within the stale-while-revalidate
+/// window stored with them. Use [`FileSystemCache::with_stale_retention`] to
+/// keep them around longer, and [`FileSystemCache::with_clock`] when the
+/// cachified calls use a clock other than the system clock.
+///
+/// [`Cache::clear`] and [`Cache::len`] consider all entry files in the root
+/// directory, so don't share it with other files ending in `.json`.
+/// [`Cache::set_if_version`] isn't atomic across processes.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "fs")]
+/// use cachified::{cachified, CachifiedOptionsBuilder, FileSystemCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "fs")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = FileSystemCache::new(".cache/api");
+///
+/// let value: String = cachified(
+///     CachifiedOptionsBuilder::new(cache, "releases")
+///         .ttl(Duration::from_secs(3600))
+///         .get_fresh_value(|| async { Ok("v1.0.0".to_string()) })
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FileSystemCache<T> {
+    root: PathBuf,
+    stale_retention: Duration,
+    clock: Arc<dyn Clock>,
+    /// Temporary root directory, deleted when the last clone is dropped
+    #[cfg(any(test, feature = "testing"))]
+    _temp_dir: Option<Arc<tempfile::TempDir>>,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+/// An entry file, which stores the key to detect hash collisions and list keys
+#[derive(Serialize)]
+struct StoredEntryRef<'a, T> {
+    key: &'a str,
+    entry: &'a CacheEntry<T>,
+}
+
+#[derive(Deserialize)]
+struct StoredEntry<T> {
+    key: String,
+    entry: CacheEntry<T>,
+}
+
+/// An entry file without its entry, for listing keys
+#[derive(Deserialize)]
+struct StoredKey {
+    key: String,
+}
+
+impl<T> FileSystemCache<T> {
+    /// Create a new file system cache storing entries in `root`
+    ///
+    /// The directory is created on the first write if it doesn't exist.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            stale_retention: Duration::ZERO,
+            clock: Arc::new(SystemClock),
+            #[cfg(any(test, feature = "testing"))]
+            _temp_dir: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Create a file system cache in a new temporary directory
+    ///
+    /// The directory is deleted when the last clone of the cache is dropped,
+    /// which makes this useful for tests. Requires the "testing" feature to
+    /// be enabled.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn temporary() -> Result<Self> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("cachified-")
+            .tempdir()
+            .map_err(io_error)?;
+
+        Ok(Self {
+            root: temp_dir.path().to_path_buf(),
+            stale_retention: Duration::ZERO,
+            clock: Arc::new(SystemClock),
+            _temp_dir: Some(Arc::new(temp_dir)),
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Keep expired entries for `retention` after they expire before deleting them on read
+    ///
+    /// Entries written by `cachified` with stale-while-revalidate are kept
+    /// through their stale window regardless.
+    pub fn with_stale_retention(mut self, retention: Duration) -> Self {
+        self.stale_retention = retention;
+        self
+    }
+
+    /// Set the clock deciding whether entries are expired when read (default: [`SystemClock`])
+    ///
+    /// Pass the clock given to the cachified calls using this cache, e.g. a
+    /// [`MockClock`](crate::clock::MockClock), so entries aren't deleted
+    /// while they are still fresh by that clock.
+    pub fn with_clock<K>(mut self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Get the directory the entries are stored in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hash = Sha256::digest(key.as_bytes());
+        let name: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.root.join(name).with_extension(ENTRY_EXTENSION)
+    }
+
+    /// Check whether an entry is past its expiry, the stale retention and its own stale window
+    fn is_evictable(&self, entry: &CacheEntry<T>, now: Duration) -> bool {
+        entry.expires_at().is_some_and(|expires_at| {
+            let stale_until = entry.metadata.stale_until().unwrap_or(expires_at);
+            now >= stale_until.max(expires_at + self.stale_retention)
+        })
+    }
+
+    /// List the paths of all entry files
+    async fn entry_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut dir = match tokio::fs::read_dir(&self.root).await {
+            Ok(dir) => dir,
+            // Nothing was written yet
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut paths = Vec::new();
+        while let Some(file) = dir.next_entry().await.map_err(io_error)? {
+            let path = file.path();
+            if path.extension().is_some_and(|extension| extension == ENTRY_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+}
+
+#[async_trait]
+impl<T> Cache<T> for FileSystemCache<T>
+where
+    T: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        self.try_get(key).await.ok().flatten()
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        let path = self.path(key);
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let stored: StoredEntry<T> = serde_json::from_slice(&data)?;
+        if stored.key != key {
+            // Another key with the same hash, which is practically impossible
+            return Ok(None);
+        }
+
+        if self.is_evictable(&stored.entry, self.clock.now()) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Ok(None);
+        }
+
+        Ok(Some(stored.entry))
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        let data = serde_json::to_vec(&StoredEntryRef { key, entry: &entry })?;
+        tokio::fs::create_dir_all(&self.root).await.map_err(io_error)?;
+
+        let path = self.path(key);
+        let temp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        tokio::fs::write(&temp_path, data).await.map_err(io_error)?;
+
+        if let Err(e) = tokio::fs::rename(&temp_path, &path).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(io_error(e));
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) {
+        let _ = tokio::fs::remove_file(self.path(key)).await;
+    }
+
+    async fn clear(&self) {
+        if let Ok(paths) = self.entry_paths().await {
+            for path in paths {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+    }
+
+    async fn len(&self) -> usize {
+        self.entry_paths().await.map_or(0, |paths| paths.len())
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        self.get(key).await.is_some()
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for path in self.entry_paths().await? {
+            match tokio::fs::read(&path).await {
+                Ok(data) => keys.push(serde_json::from_slice::<StoredKey>(&data)?.key),
+                // Removed while listing
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+        Ok(keys)
+    }
+
+    fn backend(&self) -> &'static str {
+        "fs"
+    }
+}
+
+fn io_error(error: std::io::Error) -> CachifiedError {
+    CachifiedError::cache_source(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::cache_conformance_tests!(
+        fs_conformance,
+        FileSystemCache::<String>::temporary().unwrap(),
+        "value".to_string()
+    );
+
+    #[tokio::test]
+    async fn test_fs_cache_keeps_io_error_as_source() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+
+        // The root can't be created below a file
+        let cache: FileSystemCache<String> = FileSystemCache::new(dir.path().join("file").join("nested"));
+        let error = cache.put("key", "value".to_string(), None).await.unwrap_err();
+        assert!(error.downcast_source_ref::<std::io::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache_persists_across_instances() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache: FileSystemCache<String> = FileSystemCache::new(dir.path().join("nested"));
+        cache.put("../../etc/passwd", "value".to_string(), None).await.unwrap();
+
+        // Keys don't escape the root directory
+        let files: Vec<_> = std::fs::read_dir(cache.root()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+
+        let reopened: FileSystemCache<String> = FileSystemCache::new(dir.path().join("nested"));
+        assert_eq!(reopened.get("../../etc/passwd").await.unwrap().value, "value");
+        assert_eq!(reopened.keys().await.unwrap(), vec!["../../etc/passwd".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache_deletes_expired_entries_on_read() {
+        let cache: FileSystemCache<String> = FileSystemCache::temporary().unwrap();
+        let expired = CacheEntry::builder("value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(120))
+            .build();
+
+        cache.set("expired", expired.clone()).await.unwrap();
+        assert_eq!(cache.len().await, 1);
+        assert!(cache.get("expired").await.is_none());
+        assert_eq!(cache.len().await, 0);
+
+        // Within the retention, expired entries are kept for serving them stale
+        let retaining = cache.clone().with_stale_retention(Duration::from_secs(300));
+        retaining.set("expired", expired).await.unwrap();
+        assert!(retaining.get("expired").await.is_some());
+        assert_eq!(retaining.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache_expires_entries_by_its_clock() {
+        let clock = crate::clock::MockClock::new(Duration::from_secs(1_000));
+        let cache = FileSystemCache::temporary().unwrap().with_clock(clock.clone());
+        let entry = CacheEntry::with_metadata(
+            "value".to_string(),
+            crate::CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60))),
+        );
+
+        cache.set("key", entry).await.unwrap();
+        assert!(cache.get("key").await.is_some());
+
+        clock.advance(Duration::from_secs(60));
+        assert!(cache.get("key").await.is_none());
+        assert_eq!(cache.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache_keeps_entries_through_their_stale_window() {
+        let clock = crate::clock::MockClock::new(Duration::from_secs(1_000));
+        let cache = FileSystemCache::temporary().unwrap().with_clock(clock.clone());
+
+        let value: String = crate::cachified(
+            crate::CachifiedOptionsBuilder::new(cache.clone(), "key")
+                .ttl(Duration::from_secs(10))
+                .stale_while_revalidate(Duration::from_secs(600))
+                .clock(clock.clone())
+                .get_fresh_value(|| async { Ok("value".to_string()) }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(value, "value");
+
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(cache.get("key").await.unwrap().value, "value");
+
+        clock.advance(Duration::from_secs(600));
+        assert!(cache.get("key").await.is_none());
+        assert_eq!(cache.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache_temporary_directory_is_removed() {
+        let cache: FileSystemCache<String> = FileSystemCache::temporary().unwrap();
+        let root = cache.root().to_path_buf();
+        cache.put("key", "value".to_string(), None).await.unwrap();
+        assert!(root.exists());
+
+        drop(cache);
+        assert!(!root.exists());
+    }
+}
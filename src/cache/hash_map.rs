@@ -0,0 +1,369 @@
+//! Dependency-free in-memory cache backed by a `HashMap`.
+
+use super::Cache;
+use crate::clock::{Clock, SystemClock};
+use crate::{CacheEntry, Result};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+/// In-memory cache implementation without extra dependencies
+///
+/// A small cache for tests, examples and tools that don't want to pull in
+/// Moka, which also makes the crate usable with `default-features = false`.
+/// Clones share the same entries.
+///
+/// Expired entries are removed when read, like Redis drops entries once their
+/// TTL has passed, unless they are still within the stale-while-revalidate
+/// window stored with them. Use [`HashMapCache::with_stale_retention`] to keep
+/// them around longer, e.g. for soft purging, and [`HashMapCache::with_clock`]
+/// when the cachified calls use a clock other than the system clock. With a
+/// maximum size set
+/// with [`HashMapCache::with_max_size`], writing a new key to a full cache
+/// evicts the key that was inserted first.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::{cachified, CachifiedOptionsBuilder, HashMapCache};
+/// use std::time::Duration;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = HashMapCache::with_max_size(1000);
+///
+/// let value: String = cachified(
+///     CachifiedOptionsBuilder::new(cache, "greeting")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HashMapCache<T> {
+    inner: Arc<RwLock<Entries<T>>>,
+    max_size: Option<usize>,
+    stale_retention: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+/// The stored entries along with the order they were inserted in
+struct Entries<T> {
+    /// Entries by key, with the sequence number of their insertion
+    entries: HashMap<String, (u64, CacheEntry<T>)>,
+    /// Keys by the sequence number of their insertion, oldest first
+    insertion_order: BTreeMap<u64, String>,
+    next_sequence: u64,
+}
+
+impl<T> Entries<T> {
+    fn insert(&mut self, key: &str, entry: CacheEntry<T>, max_size: Option<usize>) {
+        // Replacing an entry keeps its place in the insertion order
+        if let Some((_, stored)) = self.entries.get_mut(key) {
+            *stored = entry;
+            return;
+        }
+
+        if let Some(max_size) = max_size {
+            while self.entries.len() >= max_size {
+                let Some((_, oldest)) = self.insertion_order.pop_first() else {
+                    break;
+                };
+                self.entries.remove(&oldest);
+            }
+            if max_size == 0 {
+                return;
+            }
+        }
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.entries.insert(key.to_string(), (sequence, entry));
+        self.insertion_order.insert(sequence, key.to_string());
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((sequence, _)) = self.entries.remove(key) {
+            self.insertion_order.remove(&sequence);
+        }
+    }
+}
+
+impl<T> HashMapCache<T> {
+    /// Create a new, unbounded cache
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Entries {
+                entries: HashMap::new(),
+                insertion_order: BTreeMap::new(),
+                next_sequence: 0,
+            })),
+            max_size: None,
+            stale_retention: Duration::ZERO,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Create a new cache holding at most `max_size` entries
+    ///
+    /// Once the cache is full, writing a new key evicts the oldest one.
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..Self::new()
+        }
+    }
+
+    /// Keep expired entries for `retention` after they expire before removing them on read
+    ///
+    /// Entries written by `cachified` with stale-while-revalidate are kept
+    /// through their stale window regardless.
+    pub fn with_stale_retention(mut self, retention: Duration) -> Self {
+        self.stale_retention = retention;
+        self
+    }
+
+    /// Set the clock deciding whether entries are expired when read (default: [`SystemClock`])
+    ///
+    /// Pass the clock given to the cachified calls using this cache, e.g. a
+    /// [`MockClock`](crate::clock::MockClock), so entries aren't removed
+    /// while they are still fresh by that clock.
+    pub fn with_clock<K>(mut self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Entries<T>> {
+        // Entries are always left consistent, so a panic elsewhere doesn't matter
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Entries<T>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check whether an entry is past its expiry, the stale retention and its own stale window
+    fn is_evictable(&self, entry: &CacheEntry<T>, now: Duration) -> bool {
+        entry.expires_at().is_some_and(|expires_at| {
+            let stale_until = entry.metadata.stale_until().unwrap_or(expires_at);
+            now >= stale_until.max(expires_at + self.stale_retention)
+        })
+    }
+}
+
+impl<T> Default for HashMapCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<T> Cache<T> for HashMapCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        let now = self.clock.now();
+        {
+            let entries = self.read();
+            match entries.entries.get(key) {
+                None => return None,
+                Some((_, entry)) if !self.is_evictable(entry, now) => return Some(entry.clone()),
+                Some(_) => {}
+            }
+        }
+
+        // The entry may have been replaced since it was read
+        let mut entries = self.write();
+        match entries.entries.get(key) {
+            Some((_, entry)) if self.is_evictable(entry, now) => {
+                entries.remove(key);
+                None
+            }
+            stored => stored.map(|(_, entry)| entry.clone()),
+        }
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        self.write().insert(key, entry, self.max_size);
+        Ok(())
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let mut entries = self.write();
+        let current_version = entries.entries.get(key).map(|(_, stored)| stored.metadata.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+
+        entries.insert(key, entry, self.max_size);
+        Ok(true)
+    }
+
+    async fn remove(&self, key: &str) {
+        self.write().remove(key);
+    }
+
+    async fn clear(&self) {
+        let mut entries = self.write();
+        entries.entries.clear();
+        entries.insertion_order.clear();
+    }
+
+    async fn len(&self) -> usize {
+        self.read().entries.len()
+    }
+
+    async fn len_valid(&self, now: Duration) -> usize {
+        self.read()
+            .entries
+            .values()
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .count()
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        let mut entries = self.write();
+        let keys: Vec<String> = entries
+            .entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        for key in &keys {
+            entries.remove(key);
+        }
+        Ok(keys.len())
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.read().entries.keys().cloned().collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        "hash_map"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::CacheMetadata;
+
+    fn create_test_entry() -> CacheEntry<String> {
+        CacheEntry::with_metadata(
+            "test-value".to_string(),
+            CacheMetadata::with_time(Duration::from_secs(1000), None),
+        )
+    }
+
+    crate::cache_conformance_tests!(
+        hash_map_conformance,
+        HashMapCache::<String>::new(),
+        "conformance-value".to_string()
+    );
+
+    #[tokio::test]
+    async fn test_hash_map_cache_basic_operations() {
+        let cache: HashMapCache<String> = HashMapCache::new();
+        let entry = create_test_entry();
+
+        // Test set and get
+        cache.set("test-key", entry.clone()).await.unwrap();
+        let retrieved = cache.get("test-key").await;
+        assert!(retrieved.is_some());
+        assert_eq!(retrieved.unwrap().value, "test-value");
+
+        // Test remove
+        cache.remove("test-key").await;
+        assert!(cache.get("test-key").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hash_map_cache_clear() {
+        let cache: HashMapCache<String> = HashMapCache::new();
+        let entry = create_test_entry();
+
+        // Add multiple entries
+        cache.set("key1", entry.clone()).await.unwrap();
+        cache.set("key2", entry.clone()).await.unwrap();
+        cache.set("key3", entry).await.unwrap();
+        assert_eq!(cache.len().await, 3);
+
+        // Clear all
+        cache.clear().await;
+
+        // Verify entries are gone
+        assert!(cache.get("key1").await.is_none());
+        assert!(cache.get("key2").await.is_none());
+        assert!(cache.get("key3").await.is_none());
+        assert!(cache.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_hash_map_cache_evicts_oldest_entry() {
+        let cache: HashMapCache<String> = HashMapCache::with_max_size(2);
+        let entry = create_test_entry();
+
+        cache.set("first", entry.clone()).await.unwrap();
+        cache.set("second", entry.clone()).await.unwrap();
+        // Replacing an entry doesn't move it to the back
+        cache.set("first", entry.clone()).await.unwrap();
+        cache.set("third", entry).await.unwrap();
+
+        assert_eq!(cache.len().await, 2);
+        assert!(cache.get("first").await.is_none());
+        assert!(cache.get("second").await.is_some());
+        assert!(cache.get("third").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hash_map_cache_removes_expired_entries_on_read() {
+        let cache: HashMapCache<String> = HashMapCache::new();
+        let expired = CacheEntry::builder("value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(120))
+            .build();
+
+        cache.set("expired", expired.clone()).await.unwrap();
+        assert_eq!(cache.len().await, 1);
+        assert!(cache.get("expired").await.is_none());
+        assert_eq!(cache.len().await, 0);
+
+        // Within the retention, expired entries are kept for serving them stale
+        let retaining = HashMapCache::new().with_stale_retention(Duration::from_secs(300));
+        retaining.set("expired", expired).await.unwrap();
+        assert!(retaining.get("expired").await.is_some());
+        assert_eq!(retaining.len().await, 1);
+
+        // Entries are kept through their own stale window as well
+        let mut stale = CacheEntry::builder("value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(120))
+            .build();
+        stale.metadata.swr = Some(Duration::from_secs(300));
+        cache.set("stale", stale).await.unwrap();
+        assert!(cache.get("stale").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hash_map_cache_expires_entries_by_its_clock() {
+        let clock = crate::clock::MockClock::new(Duration::from_secs(1_000));
+        let cache = HashMapCache::new().with_clock(clock.clone());
+        let entry = CacheEntry::with_metadata(
+            "value".to_string(),
+            CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60))),
+        );
+
+        cache.set("key", entry).await.unwrap();
+        assert!(cache.get("key").await.is_some());
+
+        clock.advance(Duration::from_secs(60));
+        assert!(cache.get("key").await.is_none());
+        assert_eq!(cache.len().await, 0);
+    }
+}
@@ -0,0 +1,656 @@
+//! Cache storing entries in Memcached, spoken to over its text protocol.
+//!
+//! The protocol is spoken directly instead of through a client crate: the
+//! `memcache` crate is blocking, and the cache only needs a handful of
+//! commands, including `gets` and `cas` for [`Cache::set_if_version`].
+//! Commands run concurrently on a pool of Tokio connections managed by `bb8`.
+
+use super::{expire_seconds, Cache};
+use crate::codec::{Codec, JsonCodec};
+use crate::{current_time, CacheEntry, CachifiedError, Result};
+use async_trait::async_trait;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+
+/// Longest key Memcached accepts, in bytes
+const MAX_KEY_LENGTH: usize = 250;
+
+/// Longest expiry Memcached treats as relative, anything longer is a UNIX timestamp
+const MAX_RELATIVE_EXPIRY: u64 = 30 * 24 * 60 * 60;
+
+/// Most connections opened to the Memcached server per cache
+const MAX_CONNECTIONS: u32 = 16;
+
+/// Memcached cache implementation
+///
+/// Stores entries serialized with a [`Codec`], [`JsonCodec`] by default, under
+/// a key prefix, and lets Memcached expire them natively after their TTL plus
+/// their stale-while-revalidate window, like [`RedisCache`](crate::RedisCache).
+/// Requires the "memcached" feature to be enabled.
+///
+/// Memcached keys are limited to 250 bytes without whitespace or control
+/// characters, prefix included. Reading other keys is a miss and writing them
+/// fails. [`Cache::set_if_version`] is atomic, using Memcached's `cas` and `add`.
+///
+/// Memcached can't list or count keys by prefix, and the server is usually
+/// shared with other applications. So [`Cache::clear`] does nothing, and
+/// [`Cache::len`] as well as [`Cache::len_valid`] always report zero, even
+/// though entries may be stored. Use [`MemcachedCache::flush_server`] to
+/// delete the entries of every application on the server instead.
+/// [`Cache::keys`] isn't supported.
+///
+/// Clones share a pool of up to 16 connections, so commands run concurrently.
+/// A connection that breaks, or whose operation is cancelled before its reply
+/// was read, e.g. by a timeout, is discarded instead of being reused.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "memcached")]
+/// use cachified::{cachified, CachifiedOptionsBuilder, MemcachedCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "memcached")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MemcachedCache<String> = MemcachedCache::new("localhost:11211").await?;
+///
+/// let value: String = cachified(
+///     CachifiedOptionsBuilder::new(cache, "greeting")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MemcachedCache<T, K = JsonCodec> {
+    pool: bb8::Pool<ConnectionManager>,
+    prefix: String,
+    codec: K,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> MemcachedCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Create a new MemcachedCache connected to the given address
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Address of the Memcached server (e.g., "localhost:11211")
+    pub async fn new(address: &str) -> Result<Self> {
+        Self::with_prefix(address, "cachified:".to_string()).await
+    }
+
+    /// Create a new MemcachedCache with a custom key prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Address of the Memcached server
+    /// * `prefix` - Custom prefix for all cache keys
+    pub async fn with_prefix(address: &str, prefix: String) -> Result<Self> {
+        // One connection is opened right away, so an unreachable server fails here
+        let pool = bb8::Pool::builder()
+            .max_size(MAX_CONNECTIONS)
+            .min_idle(1)
+            .test_on_check_out(false)
+            .retry_connection(false)
+            .build(ConnectionManager {
+                address: address.to_string(),
+            })
+            .await
+            .map_err(CachifiedError::cache_source)?;
+
+        Ok(Self {
+            pool,
+            prefix,
+            codec: JsonCodec,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<T, K> MemcachedCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Use a different codec for serializing entries
+    ///
+    /// Entries written with one codec generally can't be read with another,
+    /// so use a separate prefix when switching codecs.
+    pub fn with_codec<K2>(self, codec: K2) -> MemcachedCache<T, K2> {
+        MemcachedCache {
+            pool: self.pool,
+            prefix: self.prefix,
+            codec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get the raw stored payload for a key without deserializing it
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Some(bytes))` if the key exists, `Ok(None)` otherwise.
+    pub async fn get_raw(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let full_key = self.full_key(key)?;
+        let values = self.send(format!("get {full_key}\r\n").into_bytes()).await?.into_values()?;
+        Ok(find_value(values, &full_key).map(|value| value.data))
+    }
+
+    /// Delete every item on the Memcached server with `flush_all`
+    ///
+    /// This isn't limited to this cache's prefix, but removes the entries of
+    /// every application sharing the server, which is why [`Cache::clear`]
+    /// doesn't do it.
+    pub async fn flush_server(&self) -> Result<()> {
+        self.send(b"flush_all\r\n".to_vec()).await?.expect_status("OK")
+    }
+
+    /// Send a command on a pooled connection and read its reply
+    async fn send(&self, request: Vec<u8>) -> Result<Reply> {
+        let mut connection = self.pool.get().await.map_err(CachifiedError::cache_source)?;
+        let reply = connection.send(&request).await.map_err(CachifiedError::cache_source)?;
+
+        match reply {
+            Reply::Error(message) => Err(CachifiedError::cache(format!("Memcached error: {message}"))),
+            reply => Ok(reply),
+        }
+    }
+
+    /// Get the full key with prefix, checking that Memcached accepts it
+    fn full_key(&self, key: &str) -> Result<String> {
+        let full_key = format!("{}{}", self.prefix, key);
+        if full_key.len() > MAX_KEY_LENGTH || full_key.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(CachifiedError::cache(format!(
+                "Memcached keys must be at most {MAX_KEY_LENGTH} bytes without whitespace or control characters: {full_key:?}"
+            )));
+        }
+        Ok(full_key)
+    }
+}
+
+/// Get the expiry to store an entry in Memcached with, where zero means no expiry
+fn expiry(entry_expire_seconds: u64) -> u64 {
+    if entry_expire_seconds > MAX_RELATIVE_EXPIRY {
+        current_time().as_secs().saturating_add(entry_expire_seconds)
+    } else {
+        entry_expire_seconds
+    }
+}
+
+/// Find the value returned for `full_key`, ignoring values of any other key
+fn find_value(values: Vec<Value>, full_key: &str) -> Option<Value> {
+    values.into_iter().find(|value| value.key == full_key)
+}
+
+/// Build a storage command such as `set` or `add`, with the CAS unique value for `cas`
+fn storage_command(command: &str, full_key: &str, expiry: u64, cas: Option<u64>, data: &[u8]) -> Vec<u8> {
+    let mut request = match cas {
+        Some(cas) => format!("{command} {full_key} 0 {expiry} {} {cas}\r\n", data.len()),
+        None => format!("{command} {full_key} 0 {expiry} {}\r\n", data.len()),
+    }
+    .into_bytes();
+    request.extend_from_slice(data);
+    request.extend_from_slice(b"\r\n");
+    request
+}
+
+#[async_trait]
+impl<T, K> Cache<T> for MemcachedCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+    K: Codec<T> + Clone + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        self.try_get(key).await.ok().flatten()
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        match self.get_raw(key).await? {
+            Some(data) => Ok(Some(self.codec.decode(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        let full_keys: Vec<Option<String>> = keys.iter().map(|key| self.full_key(key).ok()).collect();
+        let valid_keys: Vec<&str> = full_keys.iter().flatten().map(String::as_str).collect();
+        if valid_keys.is_empty() {
+            return vec![None; keys.len()];
+        }
+
+        // A single `get` with all keys returns only the ones that exist
+        let request = format!("get {}\r\n", valid_keys.join(" ")).into_bytes();
+        let Ok(values) = self.send(request).await.and_then(Reply::into_values) else {
+            return vec![None; keys.len()];
+        };
+
+        full_keys
+            .iter()
+            .map(|full_key| {
+                let full_key = full_key.as_deref()?;
+                let value = values.iter().find(|value| value.key == full_key)?;
+                self.codec.decode(value.data.clone()).ok()
+            })
+            .collect()
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        let full_key = self.full_key(key)?;
+        let data = self.codec.encode(&entry)?;
+        let expiry = expiry(expire_seconds(&entry.metadata));
+
+        let request = storage_command("set", &full_key, expiry, None, &data);
+        self.send(request).await?.expect_status("STORED")
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let full_key = self.full_key(key)?;
+        let data = self.codec.encode(&entry)?;
+        let expiry = expiry(expire_seconds(&entry.metadata));
+
+        // `gets` returns the CAS unique value the write is conditioned on
+        let request = format!("gets {full_key}\r\n").into_bytes();
+        let current = find_value(self.send(request).await?.into_values()?, &full_key);
+        let request = match current {
+            Some(current) => {
+                let current_version = self.codec.decode(current.data)?.metadata.version;
+                if Some(current_version) != expected_version {
+                    return Ok(false);
+                }
+                let cas = current
+                    .cas
+                    .ok_or_else(|| CachifiedError::cache("Memcached returned no CAS value for gets"))?;
+                storage_command("cas", &full_key, expiry, Some(cas), &data)
+            }
+            None if expected_version.is_some() => return Ok(false),
+            // `add` only stores the entry if the key is still missing
+            None => storage_command("add", &full_key, expiry, None, &data),
+        };
+
+        match self.send(request).await? {
+            Reply::Status(status) if status == "STORED" => Ok(true),
+            Reply::Status(status) if matches!(status.as_str(), "EXISTS" | "NOT_FOUND" | "NOT_STORED") => Ok(false),
+            reply => Err(reply.unexpected()),
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let Ok(full_key) = self.full_key(key) else {
+            return;
+        };
+        let _ = self.send(format!("delete {full_key}\r\n").into_bytes()).await;
+    }
+
+    async fn clear(&self) {
+        // Memcached can't find keys by prefix, and flushing would hit every
+        // other application on the server, see `MemcachedCache::flush_server`
+    }
+
+    async fn len(&self) -> usize {
+        // Memcached can only count the items of the whole server
+        0
+    }
+
+    fn backend(&self) -> &'static str {
+        "memcached"
+    }
+}
+
+/// Opens connections to a Memcached server for the pool
+struct ConnectionManager {
+    address: String,
+}
+
+impl bb8::ManageConnection for ConnectionManager {
+    type Connection = Connection;
+    type Error = std::io::Error;
+
+    async fn connect(&self) -> std::io::Result<Connection> {
+        Ok(Connection {
+            stream: BufStream::new(TcpStream::connect(&self.address).await?),
+            busy: false,
+        })
+    }
+
+    async fn is_valid(&self, connection: &mut Connection) -> std::io::Result<()> {
+        match connection.send(b"version\r\n").await? {
+            Reply::Status(_) => Ok(()),
+            reply => Err(invalid_data(format!("Unexpected reply to version: {reply:?}"))),
+        }
+    }
+
+    fn has_broken(&self, connection: &mut Connection) -> bool {
+        connection.busy
+    }
+}
+
+/// A pooled connection to a Memcached server
+struct Connection {
+    stream: BufStream<TcpStream>,
+    /// Whether a command was sent whose reply wasn't read in full
+    ///
+    /// This is the case after I/O errors, or if the future sending the
+    /// command was dropped midway. The pool discards such connections instead
+    /// of letting the next command read the remains of another reply.
+    busy: bool,
+}
+
+impl Connection {
+    /// Send a command and read its reply
+    async fn send(&mut self, request: &[u8]) -> std::io::Result<Reply> {
+        self.busy = true;
+        self.stream.write_all(request).await?;
+        self.stream.flush().await?;
+        let reply = read_reply(&mut self.stream).await?;
+        self.busy = false;
+        Ok(reply)
+    }
+}
+
+/// A value returned by `get` or `gets`
+#[derive(Debug, PartialEq)]
+struct Value {
+    key: String,
+    /// CAS unique value, only returned by `gets`
+    cas: Option<u64>,
+    data: Vec<u8>,
+}
+
+/// Reply to a Memcached command
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// A single status line such as `STORED` or `DELETED`
+    Status(String),
+    /// The values found by `get` or `gets`
+    Values(Vec<Value>),
+    /// An `ERROR`, `CLIENT_ERROR` or `SERVER_ERROR` line
+    Error(String),
+}
+
+impl Reply {
+    fn into_values(self) -> Result<Vec<Value>> {
+        match self {
+            Reply::Values(values) => Ok(values),
+            reply => Err(reply.unexpected()),
+        }
+    }
+
+    fn expect_status(self, expected: &str) -> Result<()> {
+        match self {
+            Reply::Status(status) if status == expected => Ok(()),
+            reply => Err(reply.unexpected()),
+        }
+    }
+
+    fn unexpected(self) -> CachifiedError {
+        CachifiedError::cache(format!("Unexpected Memcached reply: {self:?}"))
+    }
+}
+
+/// Read one reply from the server
+async fn read_reply<R>(reader: &mut R) -> std::io::Result<Reply>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = read_line(reader).await?;
+    if line.starts_with("ERROR") || line.starts_with("CLIENT_ERROR") || line.starts_with("SERVER_ERROR") {
+        return Ok(Reply::Error(line));
+    }
+    if !line.starts_with("VALUE ") && line != "END" {
+        return Ok(Reply::Status(line));
+    }
+
+    let mut values = Vec::new();
+    while line != "END" {
+        let mut parts = line.split(' ');
+        match parts.next() {
+            // VALUE <key> <flags> <bytes> [<cas unique>]
+            Some("VALUE") => {
+                let key = parts.next().unwrap_or_default().to_string();
+                let length = parts.nth(1).and_then(|length| length.parse::<usize>().ok());
+                let Some(length) = length else {
+                    return Err(invalid_data(format!("Malformed VALUE line: {line}")));
+                };
+                let cas = parts.next().and_then(|cas| cas.parse().ok());
+
+                // The data block is followed by its own line terminator
+                let mut data = vec![0; length + 2];
+                reader.read_exact(&mut data).await?;
+                data.truncate(length);
+                values.push(Value { key, cas, data });
+            }
+            _ => return Err(invalid_data(format!("Unexpected line in reply: {line}"))),
+        }
+        line = read_line(reader).await?;
+    }
+
+    Ok(Reply::Values(values))
+}
+
+/// Read a line without its `\r\n` terminator
+async fn read_line<R>(reader: &mut R) -> std::io::Result<String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::CacheMetadata;
+    use std::time::Duration;
+
+    async fn parse(reply: &[u8]) -> std::io::Result<Reply> {
+        let mut reader = reply;
+        read_reply(&mut reader).await
+    }
+
+    #[tokio::test]
+    async fn test_read_reply() {
+        assert_eq!(parse(b"STORED\r\n").await.unwrap(), Reply::Status("STORED".to_string()));
+        assert_eq!(parse(b"END\r\n").await.unwrap(), Reply::Values(Vec::new()));
+        assert_eq!(
+            parse(b"SERVER_ERROR out of memory\r\n").await.unwrap(),
+            Reply::Error("SERVER_ERROR out of memory".to_string())
+        );
+
+        // Data may contain line terminators, only its length counts
+        let reply = parse(b"VALUE a 0 4 17\r\nx\r\ny\r\nVALUE b 0 1\r\nz\r\nEND\r\n").await.unwrap();
+        assert_eq!(
+            reply,
+            Reply::Values(vec![
+                Value { key: "a".to_string(), cas: Some(17), data: b"x\r\ny".to_vec() },
+                Value { key: "b".to_string(), cas: None, data: b"z".to_vec() },
+            ])
+        );
+
+        assert!(parse(b"VALUE a 0 4\r\nx").await.is_err());
+    }
+
+    /// Serve `get` commands with the key as value, replying late for the key `slow`
+    async fn serve_keys_as_values() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut socket = BufStream::new(socket);
+                    while let Ok(line) = read_line(&mut socket).await {
+                        let key = line.trim_start_matches("get ").to_string();
+                        if key.ends_with("slow") {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
+                        let reply = format!("VALUE {key} 0 {}\r\n{key}\r\nEND\r\n", key.len());
+                        if socket.write_all(reply.as_bytes()).await.is_err() || socket.flush().await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_command_doesnt_leak_reply() {
+        let address = serve_keys_as_values().await;
+        let cache: MemcachedCache<String> = MemcachedCache::new(&address).await.unwrap();
+
+        // Dropped after sending the command, before its reply arrives
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), cache.get_raw("slow")).await;
+        assert!(cancelled.is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The reply to the cancelled command must not be read as this one's
+        let value = cache.get_raw("fast").await.unwrap();
+        assert_eq!(value, Some(b"cachified:fast".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_commands_run_concurrently() {
+        let address = serve_keys_as_values().await;
+        let cache: MemcachedCache<String> = MemcachedCache::new(&address).await.unwrap();
+
+        // The fast command doesn't wait for the slow one's reply
+        let slow = tokio::spawn({
+            let cache = cache.clone();
+            async move { cache.get_raw("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let fast = tokio::time::timeout(Duration::from_millis(30), cache.get_raw("fast")).await;
+        assert_eq!(fast.unwrap().unwrap(), Some(b"cachified:fast".to_vec()));
+        assert_eq!(slow.await.unwrap().unwrap(), Some(b"cachified:slow".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_clear_and_len_leave_the_shared_server_alone() {
+        let address = serve_keys_as_values().await;
+        let cache: MemcachedCache<String> = MemcachedCache::new(&address).await.unwrap();
+
+        // The fake server would reply with a value to `flush_all`, failing the next command
+        cache.clear().await;
+        assert_eq!(cache.len().await, 0);
+        assert_eq!(cache.get_raw("key").await.unwrap(), Some(b"cachified:key".to_vec()));
+    }
+
+    #[test]
+    fn test_find_value_ignores_other_keys() {
+        let values = || {
+            vec![
+                Value { key: "other".to_string(), cas: None, data: b"x".to_vec() },
+                Value { key: "key".to_string(), cas: None, data: b"y".to_vec() },
+            ]
+        };
+        assert_eq!(find_value(values(), "key").unwrap().data, b"y");
+        assert!(find_value(values(), "missing").is_none());
+    }
+
+    #[test]
+    fn test_expiry() {
+        assert_eq!(expiry(0), 0);
+        assert_eq!(expiry(60), 60);
+
+        // Longer expiries have to be given as a UNIX timestamp
+        let long = MAX_RELATIVE_EXPIRY + 1;
+        assert!(expiry(long) >= current_time().as_secs() + long);
+    }
+
+    #[test]
+    fn test_expire_seconds_rounds_up() {
+        let expiring = |ttl: Option<Duration>| expire_seconds(&CacheMetadata::new(ttl));
+
+        assert_eq!(expiring(None), 0);
+        assert_eq!(expiring(Some(Duration::ZERO)), 1);
+        assert_eq!(expiring(Some(Duration::from_millis(300))), 1);
+        assert_eq!(expiring(Some(Duration::from_millis(1_500))), 2);
+        assert_eq!(expiring(Some(Duration::from_secs(60))), 60);
+    }
+
+    fn create_test_entry() -> CacheEntry<String> {
+        CacheEntry::with_metadata(
+            "test-value".to_string(),
+            CacheMetadata::with_time(Duration::from_secs(1000), Some(Duration::from_secs(300))),
+        )
+    }
+
+    async fn create_cache(prefix: &str) -> MemcachedCache<String> {
+        MemcachedCache::with_prefix("localhost:11211", prefix.to_string())
+            .await
+            .expect("Failed to connect to Memcached")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running Memcached instance"]
+    async fn test_memcached_cache_basic_operations() {
+        let cache = create_cache("cachified-basic:").await;
+        let entry = create_test_entry();
+
+        // Test set and get
+        cache.set("test-key", entry.clone()).await.unwrap();
+        let retrieved = cache.get("test-key").await;
+        assert!(retrieved.is_some());
+        assert_eq!(retrieved.unwrap().value, "test-value");
+
+        // Test remove
+        cache.remove("test-key").await;
+        assert!(cache.get("test-key").await.is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running Memcached instance"]
+    async fn test_memcached_cache_get_many() {
+        let cache = create_cache("cachified-many:").await;
+
+        cache.set("a", create_test_entry()).await.unwrap();
+        cache.set("c", create_test_entry()).await.unwrap();
+
+        let entries = cache.get_many(&["a", "b", "c", "not a valid key"]).await;
+        assert!(entries[0].is_some());
+        assert!(entries[1].is_none());
+        assert!(entries[2].is_some());
+        assert!(entries[3].is_none());
+        cache.remove("a").await;
+        cache.remove("c").await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running Memcached instance"]
+    async fn test_memcached_cache_set_if_version() {
+        let cache = create_cache("cachified-cas:").await;
+        cache.remove("key").await;
+
+        let entry = create_test_entry();
+        assert!(cache.set_if_version("key", entry.clone(), None).await.unwrap());
+        assert!(!cache.set_if_version("key", entry.clone(), None).await.unwrap());
+
+        let mut next = entry.clone();
+        next.metadata.version = 1;
+        assert!(!cache.set_if_version("key", next.clone(), Some(1)).await.unwrap());
+        assert!(cache.set_if_version("key", next, Some(0)).await.unwrap());
+        assert_eq!(cache.get("key").await.unwrap().metadata.version, 1);
+        cache.remove("key").await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires running Memcached instance"]
+    async fn test_memcached_cache_rejects_invalid_keys() {
+        let cache = create_cache("cachified-keys:").await;
+
+        assert!(cache.set("with space", create_test_entry()).await.is_err());
+        assert!(cache.set(&"k".repeat(MAX_KEY_LENGTH), create_test_entry()).await.is_err());
+        assert!(cache.try_get("with space").await.is_err());
+        assert!(cache.get("with space").await.is_none());
+    }
+}
@@ -0,0 +1,449 @@
+//! Redis Cluster backend.
+
+use super::{
+    expire_millis, redis_payload, touch_entry, touched, Cache, PrefixScan, GET_METADATA_SCRIPT, SET_IF_CHANGED_SCRIPT,
+    SET_IF_PAYLOAD_SCRIPT, TOUCH_SCRIPT,
+};
+use crate::codec::{Codec, JsonCodec};
+use crate::{CacheEntry, CacheMetadata, Result};
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::cluster_routing::{RoutingInfo, SingleNodeRoutingInfo};
+use redis::{AsyncCommands, IntoConnectionInfo};
+use std::time::Duration;
+
+/// Number of keys unlinked concurrently when clearing keys by prefix
+const UNLINK_CONCURRENCY: usize = 64;
+
+/// Redis Cluster based cache implementation
+///
+/// This is the cluster counterpart of [`RedisCache`](crate::RedisCache). Keys
+/// are routed to the node owning their slot and `MOVED`/`ASK` redirects are
+/// followed by the cluster connection, which also reconnects on its own.
+/// Requires the "redis-cluster" feature to be enabled.
+///
+/// Entries are serialized with a [`Codec`] and written exactly like by
+/// `RedisCache`, so both can read each other's entries. Listing keys and
+/// clearing the cache scan every primary of the cluster.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "redis-cluster")]
+/// use cachified::RedisClusterCache;
+///
+/// # #[cfg(feature = "redis-cluster")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: RedisClusterCache<String> = RedisClusterCache::new(vec![
+///     "redis://localhost:7000",
+///     "redis://localhost:7001",
+///     "redis://localhost:7002",
+/// ])
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisClusterCache<T, K = JsonCodec> {
+    connection: ClusterConnection,
+    prefix: String,
+    codec: K,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> RedisClusterCache<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Create a new RedisClusterCache from the URLs of some of the cluster's nodes
+    ///
+    /// The remaining nodes are discovered from the given ones.
+    pub async fn new<I: IntoConnectionInfo>(urls: Vec<I>) -> Result<Self> {
+        Self::with_prefix(urls, "cachified:".to_string()).await
+    }
+
+    /// Create a new RedisClusterCache with a custom key prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `urls` - URLs of some of the cluster's nodes
+    /// * `prefix` - Custom prefix for all cache keys
+    pub async fn with_prefix<I: IntoConnectionInfo>(urls: Vec<I>, prefix: String) -> Result<Self> {
+        let client = ClusterClient::new(urls)?;
+        Self::from_client(client, prefix).await
+    }
+
+    /// Create a new RedisClusterCache from an existing cluster client
+    ///
+    /// Use this when the client needs configuration beyond the node URLs,
+    /// such as credentials, TLS or reading from replicas.
+    pub async fn from_client(client: ClusterClient, prefix: String) -> Result<Self> {
+        let connection = client.get_async_connection().await?;
+
+        Ok(Self {
+            connection,
+            prefix,
+            codec: JsonCodec,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<T, K> RedisClusterCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Use a different codec for serializing entries
+    ///
+    /// Entries written with one codec generally can't be read with another,
+    /// so use a separate prefix when switching codecs.
+    pub fn with_codec<K2>(self, codec: K2) -> RedisClusterCache<T, K2> {
+        RedisClusterCache {
+            connection: self.connection,
+            prefix: self.prefix,
+            codec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get the raw payload encoded by the codec for a key without deserializing it
+    ///
+    /// See [`RedisCache::get_raw`](crate::RedisCache::get_raw).
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Some(bytes))` if the key exists, `Ok(None)` otherwise.
+    pub async fn get_raw(&self, key: &str) -> Result<Option<Vec<u8>>>
+    where
+        K: Codec<T>,
+    {
+        self.get_stored(key)
+            .await?
+            .map(|data| redis_payload::codec_payload::<T, _>(&self.codec, data))
+            .transpose()
+    }
+
+    /// Get the bytes stored for a key
+    async fn get_stored(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut conn = self.connection.clone();
+        Ok(conn.get(self.full_key(key)).await?)
+    }
+
+    /// Get the full key with prefix
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Get the addresses of all primaries of the cluster
+    async fn primaries(&self) -> Result<Vec<(String, u16)>> {
+        let mut conn = self.connection.clone();
+        let nodes = conn
+            .route_command(
+                redis::cmd("CLUSTER").arg("NODES"),
+                RoutingInfo::SingleNode(SingleNodeRoutingInfo::Random),
+            )
+            .await?;
+
+        Ok(parse_primaries(&redis::from_redis_value::<String>(&nodes)?))
+    }
+
+    /// Get all full keys starting with `full_prefix`, scanning every primary
+    async fn scan_full_keys(&self, full_prefix: &str) -> Result<Vec<String>> {
+        let mut conn = self.connection.clone();
+        let mut keys = Vec::new();
+
+        // Every key lives on a single primary, so the keys of different primaries never overlap
+        for (host, port) in self.primaries().await? {
+            let routing = RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress { host, port });
+            let mut scan = PrefixScan::new(full_prefix);
+
+            while let Some(command) = scan.next_command() {
+                let step = redis::from_redis_value(&conn.route_command(&command, routing.clone()).await?)?;
+                scan.advance(step);
+            }
+            keys.extend(scan.into_keys());
+        }
+
+        Ok(keys)
+    }
+
+    /// Unlink all keys starting with `full_prefix`
+    ///
+    /// Returns the number of keys that were unlinked. Stops at the first
+    /// batch in which unlinking a key failed and returns its error.
+    async fn unlink_prefix(&self, full_prefix: &str) -> Result<usize> {
+        let keys = self.scan_full_keys(full_prefix).await?;
+        let mut unlinked = 0;
+
+        // Keys live in different slots, so they are unlinked one by one, a batch at a time
+        for batch in keys.chunks(UNLINK_CONCURRENCY) {
+            let results = join_all(batch.iter().map(|key| {
+                let mut conn = self.connection.clone();
+                async move { conn.unlink::<&str, usize>(key.as_str()).await }
+            }))
+            .await;
+
+            let mut error = None;
+            for result in results {
+                match result {
+                    Ok(count) => unlinked += count,
+                    Err(e) => error = error.or(Some(e)),
+                }
+            }
+            if let Some(e) = error {
+                return Err(e.into());
+            }
+        }
+
+        Ok(unlinked)
+    }
+}
+
+/// Parse the addresses of healthy primaries from the output of `CLUSTER NODES`
+///
+/// Every line looks like `<id> <ip:port@cport[,hostname]> <flags> ...`.
+fn parse_primaries(nodes: &str) -> Vec<(String, u16)> {
+    nodes
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let address = fields.nth(1)?;
+            let flags = fields.next()?;
+            let is_healthy_primary = flags
+                .split(',')
+                .all(|flag| !matches!(flag, "fail" | "handshake" | "noaddr"))
+                && flags.split(',').any(|flag| flag == "master");
+            if !is_healthy_primary {
+                return None;
+            }
+
+            let address = address.split(['@', ',']).next()?;
+            let (host, port) = address.rsplit_once(':')?;
+            Some((host.to_string(), port.parse().ok()?))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl<T, K> Cache<T> for RedisClusterCache<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+    K: Codec<T> + Clone + 'static,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        self.try_get(key).await.ok().flatten()
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        match self.get_stored(key).await? {
+            Some(data) => Ok(Some(redis_payload::decode(&self.codec, data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        if keys.is_empty() {
+            return Vec::new();
+        }
+
+        // The cluster connection splits MGET by slot and reassembles the results
+        let full_keys: Vec<String> = keys.iter().map(|key| self.full_key(key)).collect();
+        let mut conn = self.connection.clone();
+        let payloads = redis::cmd("MGET")
+            .arg(&full_keys)
+            .query_async::<Vec<Option<Vec<u8>>>>(&mut conn)
+            .await
+            .unwrap_or_else(|_| vec![None; keys.len()]);
+
+        payloads
+            .into_iter()
+            .map(|payload| payload.and_then(|data| redis_payload::decode(&self.codec, data).ok()))
+            .collect()
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        let expire_millis = expire_millis(&entry.metadata);
+        let encoded = redis_payload::encode(&self.codec, entry)?;
+        let mut conn = self.connection.clone();
+
+        SET_IF_CHANGED_SCRIPT
+            .key(self.full_key(key))
+            .arg(encoded.data)
+            .arg(expire_millis)
+            .arg(encoded.prefix_length)
+            .invoke_async::<bool>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let full_key = self.full_key(key);
+        let expire_millis = expire_millis(&entry.metadata);
+        let data = redis_payload::encode(&self.codec, entry)?.data;
+
+        // WATCH isn't usable through the cluster connection, so the write is
+        // conditioned on the payload the version was read from instead
+        let current = self.get_stored(key).await?;
+        let current_version = current
+            .clone()
+            .map(|data| redis_payload::decode(&self.codec, data))
+            .transpose()?
+            .map(|current| current.metadata.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+
+        let mut conn = self.connection.clone();
+        let written = SET_IF_PAYLOAD_SCRIPT
+            .key(full_key)
+            .arg(if current.is_none() { 1 } else { 0 })
+            .arg(current.unwrap_or_default())
+            .arg(data)
+            .arg(expire_millis)
+            .invoke_async::<bool>(&mut conn)
+            .await?;
+
+        Ok(written)
+    }
+
+    async fn get_metadata(&self, key: &str) -> Option<CacheMetadata> {
+        // Only the metadata section is read, the value never leaves Redis
+        if let Some(header) = redis_payload::section_header::<T, _>(&self.codec) {
+            let mut conn = self.connection.clone();
+            let stored = GET_METADATA_SCRIPT
+                .key(self.full_key(key))
+                .arg(&header)
+                .invoke_async::<Option<Vec<u8>>>(&mut conn)
+                .await
+                .ok()?;
+            if let Some(stored) = stored {
+                return redis_payload::split_metadata(&stored).ok().map(|(metadata, _)| metadata);
+            }
+        }
+
+        self.get(key).await.map(|entry| entry.metadata)
+    }
+
+    async fn touch(&self, key: &str, ttl: Duration, now: Duration) -> Result<bool> {
+        let Some(header) = redis_payload::section_header::<T, _>(&self.codec) else {
+            return touch_entry(self, key, ttl, now).await;
+        };
+        let full_key = self.full_key(key);
+        let mut conn = self.connection.clone();
+
+        let stored = GET_METADATA_SCRIPT
+            .key(&full_key)
+            .arg(&header)
+            .invoke_async::<Option<Vec<u8>>>(&mut conn)
+            .await?;
+        let Some(stored) = stored else {
+            // Missing, or stored before entries were split into sections
+            return touch_entry(self, key, ttl, now).await;
+        };
+
+        // Only the metadata section is rewritten, conditioned on the one that was read
+        let (metadata, section) = redis_payload::split_metadata(&stored)?;
+        let metadata = touched(metadata, ttl, now);
+        let touched = TOUCH_SCRIPT
+            .key(&full_key)
+            .arg(&header)
+            .arg(section)
+            .arg(redis_payload::encode_metadata(&metadata)?)
+            .arg(expire_millis(&metadata))
+            .invoke_async::<Option<bool>>(&mut conn)
+            .await?;
+
+        Ok(touched.unwrap_or(false))
+    }
+
+    async fn remove(&self, key: &str) {
+        let mut conn = self.connection.clone();
+        let _ = conn.del::<String, ()>(self.full_key(key)).await;
+    }
+
+    async fn clear(&self) {
+        let _ = self.unlink_prefix(&self.prefix).await;
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        self.unlink_prefix(&self.full_key(prefix)).await
+    }
+
+    async fn len(&self) -> usize {
+        // Like `RedisCache`, a failed scan counts as empty, see `Cache::len`
+        self.scan_full_keys(&self.prefix).await.map_or(0, |keys| keys.len())
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        let mut conn = self.connection.clone();
+        conn.exists::<String, bool>(self.full_key(key)).await.unwrap_or(false)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let keys = self.scan_full_keys(&self.prefix).await?;
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        "redis-cluster"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_primaries() {
+        let nodes = "\
+07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,node-4 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002 master - 0 1426238316232 2 connected 5461-10922
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,node-1 myself,master - 0 0 1 connected 0-5460
+6ec23923021cf3ffec47632106199cb7f496ce01 127.0.0.1:30005@31005 master,fail - 1426238316232 0 5 disconnected
+292f8b365bb7edb5e285caf0b7e6ddc7265d2f4f ::1:30003@31003 master - 0 1426238318243 3 connected 10923-16383";
+
+        assert_eq!(
+            parse_primaries(nodes),
+            vec![
+                ("127.0.0.1".to_string(), 30002),
+                ("127.0.0.1".to_string(), 30001),
+                ("::1".to_string(), 30003),
+            ]
+        );
+    }
+
+    // Note: These tests require a running Redis Cluster
+    // They are ignored by default to avoid failing CI/CD
+
+    #[tokio::test]
+    #[ignore = "requires running Redis Cluster"]
+    async fn test_redis_cluster_cache_operations() {
+        let cache: RedisClusterCache<String> =
+            RedisClusterCache::with_prefix(vec!["redis://localhost:7000"], "cachified-cluster:".to_string())
+                .await
+                .expect("Failed to connect to Redis Cluster");
+        let keys = ["a", "b", "c", "d"];
+
+        for key in keys {
+            cache.set(key, CacheEntry::new(key.to_string(), None)).await.unwrap();
+        }
+
+        // Keys spread over several slots are read and listed across nodes
+        let entries = cache.get_many(&keys).await;
+        assert!(entries.iter().zip(keys).all(|(entry, key)| entry.as_ref().unwrap().value == key));
+        assert_eq!(cache.len().await, keys.len());
+
+        assert!(cache.set_if_version("a", CacheEntry::new("new".to_string(), None), Some(0)).await.unwrap());
+        assert!(!cache.set_if_version("a", CacheEntry::new("newer".to_string(), None), Some(5)).await.unwrap());
+
+        cache.clear().await;
+        assert!(cache.is_empty().await);
+    }
+}
@@ -0,0 +1,269 @@
+//! Reconnecting connection handling for `RedisCache`.
+
+use crate::jitter::ExponentialBackoff;
+use crate::{CachifiedError, Result};
+use redis::aio::MultiplexedConnection;
+use redis::RedisResult;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// How `RedisCache` re-establishes a dropped connection
+///
+/// The first reconnection attempt is made right away by the operation that
+/// found the connection broken. If it fails, the remaining attempts run in the
+/// background, spaced with exponential backoff and full jitter, so many clients
+/// losing their connection at once don't reconnect in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Maximum number of reconnection attempts before giving up
+    pub max_attempts: u32,
+    /// Upper bound of the delay before the first background attempt
+    pub initial_delay: Duration,
+    /// Upper bound of the delay before any background attempt
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// State of the connection of a `RedisCache`, e.g. for health checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisConnectionState {
+    /// The connection is believed to be healthy
+    Connected,
+    /// The connection was lost and is being re-established
+    Reconnecting,
+    /// Re-establishing the connection failed; the next operation tries again
+    Disconnected,
+}
+
+impl RedisConnectionState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            0 => RedisConnectionState::Connected,
+            1 => RedisConnectionState::Reconnecting,
+            _ => RedisConnectionState::Disconnected,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            RedisConnectionState::Connected => 0,
+            RedisConnectionState::Reconnecting => 1,
+            RedisConnectionState::Disconnected => 2,
+        }
+    }
+}
+
+/// A multiplexed connection that is rebuilt when it breaks
+///
+/// Shared by all clones of a `RedisCache`.
+pub(super) struct RedisConnection {
+    /// Client used to reconnect, missing if the connection was handed in directly
+    client: Option<redis::Client>,
+    connection: RwLock<MultiplexedConnection>,
+    /// Incremented whenever the connection is replaced
+    generation: AtomicU64,
+    state: AtomicU8,
+}
+
+impl RedisConnection {
+    /// Connect to Redis
+    pub(super) async fn connect(client: redis::Client) -> Result<Self> {
+        let connection = client.get_multiplexed_async_connection().await?;
+
+        Ok(Self::new(Some(client), connection))
+    }
+
+    /// Use an established connection, which can't be rebuilt when it breaks
+    pub(super) fn from_connection(connection: MultiplexedConnection) -> Self {
+        Self::new(None, connection)
+    }
+
+    fn new(client: Option<redis::Client>, connection: MultiplexedConnection) -> Self {
+        Self {
+            client,
+            connection: RwLock::new(connection),
+            generation: AtomicU64::new(0),
+            state: AtomicU8::new(RedisConnectionState::Connected.as_u8()),
+        }
+    }
+
+    /// Get the current connection state
+    pub(super) fn state(&self) -> RedisConnectionState {
+        RedisConnectionState::from_u8(self.state.load(Ordering::SeqCst))
+    }
+
+    /// Run an operation, reconnecting and retrying it once if the connection broke
+    ///
+    /// While the connection is being re-established, this fails immediately
+    /// instead of waiting for the connection to come back.
+    pub(super) async fn run<R, F, Fut>(self: &Arc<Self>, policy: &ReconnectPolicy, operation: F) -> Result<R>
+    where
+        F: Fn(MultiplexedConnection) -> Fut,
+        Fut: Future<Output = RedisResult<R>>,
+    {
+        let (connection, generation) = self.current()?;
+
+        match operation(connection).await {
+            Err(e) if is_connection_error(&e) => {
+                if !self.reconnect(policy, generation).await {
+                    return Err(e.into());
+                }
+                let (connection, _) = self.current()?;
+                Ok(operation(connection).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Get a handle to the current connection and its generation
+    fn current(&self) -> Result<(MultiplexedConnection, u64)> {
+        if self.state() == RedisConnectionState::Reconnecting {
+            return Err(CachifiedError::cache("Redis connection is being re-established"));
+        }
+
+        let connection = self
+            .connection
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        Ok((connection, self.generation.load(Ordering::SeqCst)))
+    }
+
+    /// Replace the connection that was found broken at `generation`
+    ///
+    /// Makes the first attempt right away. If it fails, the remaining attempts
+    /// run in the background, while operations fail fast, so this never waits
+    /// for the backoff. Returns `true` if a working connection is available
+    /// afterwards.
+    async fn reconnect(self: &Arc<Self>, policy: &ReconnectPolicy, generation: u64) -> bool {
+        let Some(client) = &self.client else {
+            self.set_state(RedisConnectionState::Disconnected);
+            return false;
+        };
+
+        // Only one caller reconnects at a time, everyone else fails fast
+        let Some(mut claim) = ReconnectClaim::claim(self) else {
+            return false;
+        };
+
+        // Someone else already replaced the broken connection
+        if self.generation.load(Ordering::SeqCst) != generation {
+            claim.outcome = claim.previous;
+            return claim.previous == RedisConnectionState::Connected;
+        }
+
+        if let Ok(connection) = client.get_multiplexed_async_connection().await {
+            claim.replace(connection);
+            return true;
+        }
+        if policy.max_attempts <= 1 {
+            return false;
+        }
+
+        let client = client.clone();
+        let backoff = ExponentialBackoff {
+            initial: policy.initial_delay,
+            max: policy.max_delay,
+        };
+        let max_attempts = policy.max_attempts;
+        tokio::spawn(async move {
+            // Dropped along with the task, e.g. on runtime shutdown, which leaves the state `Disconnected`
+            let mut claim = claim;
+            for attempt in 1..max_attempts {
+                tokio::time::sleep(backoff.delay(attempt - 1)).await;
+
+                if let Ok(connection) = client.get_multiplexed_async_connection().await {
+                    claim.replace(connection);
+                    return;
+                }
+            }
+        });
+        false
+    }
+
+    fn set_state(&self, state: RedisConnectionState) {
+        self.state.store(state.as_u8(), Ordering::SeqCst);
+    }
+}
+
+/// Claim of re-establishing the connection, held while the state is `Reconnecting`
+///
+/// When dropped, the state becomes `Connected` if the connection was replaced
+/// and `Disconnected` otherwise, also if the reconnecting caller was cancelled.
+struct ReconnectClaim {
+    connection: Arc<RedisConnection>,
+    /// State before the claim
+    previous: RedisConnectionState,
+    /// State set when the claim is dropped
+    outcome: RedisConnectionState,
+}
+
+impl ReconnectClaim {
+    /// Move the connection into the `Reconnecting` state, unless another claim holds it
+    fn claim(connection: &Arc<RedisConnection>) -> Option<Self> {
+        let reconnecting = RedisConnectionState::Reconnecting.as_u8();
+        let current = connection.state.load(Ordering::SeqCst);
+        if current == reconnecting {
+            return None;
+        }
+        connection
+            .state
+            .compare_exchange(current, reconnecting, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+
+        Some(Self {
+            connection: connection.clone(),
+            previous: RedisConnectionState::from_u8(current),
+            outcome: RedisConnectionState::Disconnected,
+        })
+    }
+
+    /// Replace the broken connection with a working one
+    fn replace(&mut self, connection: MultiplexedConnection) {
+        *self
+            .connection
+            .connection
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = connection;
+        self.connection.generation.fetch_add(1, Ordering::SeqCst);
+        self.outcome = RedisConnectionState::Connected;
+    }
+}
+
+impl Drop for ReconnectClaim {
+    fn drop(&mut self) {
+        self.connection.set_state(self.outcome);
+    }
+}
+
+/// Check whether an error means the connection itself is broken
+fn is_connection_error(error: &redis::RedisError) -> bool {
+    error.is_io_error() || error.is_connection_dropped() || error.is_connection_refusal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_state_round_trip() {
+        for state in [
+            RedisConnectionState::Connected,
+            RedisConnectionState::Reconnecting,
+            RedisConnectionState::Disconnected,
+        ] {
+            assert_eq!(RedisConnectionState::from_u8(state.as_u8()), state);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Layout of the entries stored by the Redis backends.
+//!
+//! With a deterministic codec, the value and the metadata of an entry are
+//! stored in separate sections:
+//!
+//! ```text
+//! [magic: 3 bytes][format version: 1 byte][value length: u32 big endian]
+//! [value encoded by the codec][metadata length: u32 big endian][metadata JSON]
+//! [unused bytes]
+//! ```
+//!
+//! The value is encoded together with placeholder metadata, so an unchanged
+//! value encodes to the same bytes however its metadata changed. Everything
+//! up to the metadata section is the comparison prefix: if it matches the
+//! stored one, only the metadata section is rewritten with `SETRANGE`. Redis
+//! strings can't shrink that way, so bytes after the metadata are ignored.
+//!
+//! Other codecs store the encoded entry as it is, and their entries are never
+//! parsed as sections. Entries of deterministic codecs that were stored before
+//! this layout existed are told apart by the first byte of the magic: it is
+//! `0xff`, which [`Codec::is_deterministic`] codecs never start their output
+//! with, so those entries can still be read.
+
+use crate::codec::Codec;
+use crate::{CacheEntry, CacheMetadata, CachifiedError, Result};
+use std::time::Duration;
+
+/// Marks entries stored in sections, starting with a byte deterministic codecs never start with
+const MAGIC: [u8; 3] = [0xff, b'c', b'f'];
+
+/// Version of the layout, changed whenever the sections change
+const FORMAT_VERSION: u8 = 1;
+
+/// Size of the header of entries stored in sections
+const HEADER_SIZE: usize = MAGIC.len() + 1;
+
+/// Size of the length headers of the sections
+const LENGTH_SIZE: usize = 4;
+
+/// Offset of the metadata section in an entry whose encoded value has the given length
+fn metadata_offset(value_length: usize) -> usize {
+    HEADER_SIZE + LENGTH_SIZE + value_length
+}
+
+/// An entry encoded for storing it in Redis
+pub(super) struct EncodedEntry {
+    /// The bytes to store
+    pub(super) data: Vec<u8>,
+    /// Length of the prefix identifying the value, or zero if the value can't be compared
+    pub(super) prefix_length: usize,
+}
+
+/// Encode an entry, in sections if the codec is deterministic
+pub(super) fn encode<T, K>(codec: &K, entry: CacheEntry<T>) -> Result<EncodedEntry>
+where
+    K: Codec<T>,
+{
+    if !codec.is_deterministic() {
+        return Ok(EncodedEntry {
+            data: codec.encode(&entry)?,
+            prefix_length: 0,
+        });
+    }
+
+    let CacheEntry { value, metadata } = entry;
+    let value = codec.encode(&CacheEntry::with_metadata(value, placeholder_metadata()))?;
+    let metadata = encode_metadata(&metadata)?;
+
+    let mut data = Vec::with_capacity(metadata_offset(value.len()) + metadata.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(FORMAT_VERSION);
+    data.extend_from_slice(&encode_length(value.len())?);
+    data.extend_from_slice(&value);
+    let prefix_length = data.len();
+    data.extend_from_slice(&metadata);
+
+    Ok(EncodedEntry { data, prefix_length })
+}
+
+/// Encode the metadata section, including its length header
+pub(super) fn encode_metadata(metadata: &CacheMetadata) -> Result<Vec<u8>> {
+    let metadata = serde_json::to_vec(metadata)?;
+    let mut section = Vec::with_capacity(LENGTH_SIZE + metadata.len());
+    section.extend_from_slice(&encode_length(metadata.len())?);
+    section.extend_from_slice(&metadata);
+    Ok(section)
+}
+
+/// Decode a stored entry, whether it is stored in sections or not
+pub(super) fn decode<T, K>(codec: &K, data: Vec<u8>) -> Result<CacheEntry<T>>
+where
+    K: Codec<T>,
+{
+    let Some((value, metadata)) = split(codec, &data)? else {
+        return codec.decode(data);
+    };
+
+    let metadata: CacheMetadata = serde_json::from_slice(metadata)?;
+    let mut entry = codec.decode(value.to_vec())?;
+    entry.metadata = metadata;
+    Ok(entry)
+}
+
+/// Get the bytes encoded by the codec from a stored entry
+///
+/// For entries stored in sections, this is the value with placeholder metadata.
+pub(super) fn codec_payload<T, K>(codec: &K, data: Vec<u8>) -> Result<Vec<u8>>
+where
+    K: Codec<T>,
+{
+    match split(codec, &data)? {
+        Some((value, _)) => Ok(value.to_vec()),
+        None => Ok(data),
+    }
+}
+
+/// Get the header of entries stored in sections, or `None` if the codec doesn't store them that way
+pub(super) fn section_header<T, K>(codec: &K) -> Option<[u8; HEADER_SIZE]>
+where
+    K: Codec<T>,
+{
+    codec
+        .is_deterministic()
+        .then_some([MAGIC[0], MAGIC[1], MAGIC[2], FORMAT_VERSION])
+}
+
+/// Decode a metadata section read from an entry stored in sections
+///
+/// `data` starts at the metadata section and may be followed by unused bytes.
+/// Returns the metadata along with the bytes of the section.
+pub(super) fn split_metadata(data: &[u8]) -> Result<(CacheMetadata, &[u8])> {
+    let (metadata, unused) = split_section(data)?;
+    let section = &data[..data.len() - unused.len()];
+    Ok((serde_json::from_slice(metadata)?, section))
+}
+
+/// Split an entry stored in sections into its encoded value and metadata
+fn split<'a, T, K>(codec: &K, data: &'a [u8]) -> Result<Option<(&'a [u8], &'a [u8])>>
+where
+    K: Codec<T>,
+{
+    let Some(rest) = strip_header(codec, data)? else {
+        return Ok(None);
+    };
+
+    let (value, rest) = split_section(rest)?;
+    let (metadata, _unused) = split_section(rest)?;
+    Ok(Some((value, metadata)))
+}
+
+/// Strip the header of an entry stored in sections, or return `None` for other entries
+///
+/// Only deterministic codecs store entries in sections, so the output of
+/// other codecs is never mistaken for one, whatever bytes it starts with.
+fn strip_header<'a, T, K>(codec: &K, data: &'a [u8]) -> Result<Option<&'a [u8]>>
+where
+    K: Codec<T>,
+{
+    if !codec.is_deterministic() {
+        return Ok(None);
+    }
+    let Some(rest) = data.strip_prefix(&MAGIC) else {
+        return Ok(None);
+    };
+
+    match rest.split_first() {
+        Some((&FORMAT_VERSION, rest)) => Ok(Some(rest)),
+        Some((version, _)) => Err(CachifiedError::other(format!(
+            "Redis entry has unsupported format version {version}"
+        ))),
+        None => Err(truncated()),
+    }
+}
+
+/// Split a length-prefixed section off the start of `data`
+fn split_section(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (length, rest) = split_length(data)?;
+    let section = rest.get(..length).ok_or_else(truncated)?;
+    Ok((section, &rest[length..]))
+}
+
+/// Split the length header of a section off the start of `data`
+fn split_length(data: &[u8]) -> Result<(usize, &[u8])> {
+    let header: [u8; LENGTH_SIZE] = data
+        .get(..LENGTH_SIZE)
+        .and_then(|header| header.try_into().ok())
+        .ok_or_else(truncated)?;
+    Ok((u32::from_be_bytes(header) as usize, &data[LENGTH_SIZE..]))
+}
+
+fn truncated() -> CachifiedError {
+    CachifiedError::other("Redis entry is truncated")
+}
+
+fn encode_length(length: usize) -> Result<[u8; LENGTH_SIZE]> {
+    u32::try_from(length)
+        .map(u32::to_be_bytes)
+        .map_err(|_| CachifiedError::other("Entry too large to store in Redis"))
+}
+
+/// Metadata the value is encoded with, so it encodes the same whatever its actual metadata
+fn placeholder_metadata() -> CacheMetadata {
+    CacheMetadata::with_time(Duration::ZERO, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{JsonCodec, RawBytesCodec};
+
+    fn entry(value: &str, created_time: u64) -> CacheEntry<String> {
+        CacheEntry::with_metadata(
+            value.to_string(),
+            CacheMetadata::with_time(Duration::from_secs(created_time), Some(Duration::from_secs(60)))
+                .with_version(created_time)
+                .with_swr(Some(Duration::from_secs(30))),
+        )
+    }
+
+    #[test]
+    fn test_round_trip_in_sections() {
+        let original = entry("value", 1_000);
+        let encoded = encode(&JsonCodec, original.clone()).unwrap();
+        assert!(encoded.data.starts_with(&MAGIC));
+
+        let decoded: CacheEntry<String> = decode(&JsonCodec, encoded.data).unwrap();
+        assert_eq!(decoded.value, original.value);
+        assert_eq!(decoded.metadata, original.metadata);
+    }
+
+    #[test]
+    fn test_prefix_identifies_the_value() {
+        let first = encode(&JsonCodec, entry("value", 1_000)).unwrap();
+        let refreshed = encode(&JsonCodec, entry("value", 2_000)).unwrap();
+        let changed = encode(&JsonCodec, entry("changed", 2_000)).unwrap();
+
+        // Refreshing an unchanged value only changes the metadata section
+        assert_eq!(first.prefix_length, refreshed.prefix_length);
+        assert_eq!(first.data[..first.prefix_length], refreshed.data[..refreshed.prefix_length]);
+        assert_ne!(first.data, refreshed.data);
+        assert_ne!(first.data[..first.prefix_length], changed.data[..changed.prefix_length]);
+    }
+
+    #[test]
+    fn test_ignores_bytes_after_the_metadata() {
+        // What remains in Redis after rewriting longer metadata with shorter one
+        let mut data = encode(&JsonCodec, entry("value", 1_000)).unwrap().data;
+        data.extend_from_slice(b"stale metadata");
+
+        let decoded: CacheEntry<String> = decode(&JsonCodec, data).unwrap();
+        assert_eq!(decoded.metadata, entry("value", 1_000).metadata);
+    }
+
+    #[test]
+    fn test_reads_entries_stored_by_the_codec() {
+        let original = entry("value", 1_000);
+        let data = JsonCodec.encode(&original).unwrap();
+
+        let decoded: CacheEntry<String> = decode(&JsonCodec, data.clone()).unwrap();
+        assert_eq!(decoded.metadata, original.metadata);
+        assert_eq!(codec_payload::<String, _>(&JsonCodec, data.clone()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_codec_payload_contains_the_value() {
+        let value: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&RawBytesCodec, CacheEntry::new(value.clone(), None)).unwrap();
+
+        let payload = codec_payload::<Vec<u8>, _>(&RawBytesCodec, encoded.data).unwrap();
+        assert!(payload.ends_with(&value));
+        let decoded: CacheEntry<Vec<u8>> = RawBytesCodec.decode(payload).unwrap();
+        assert_eq!(decoded.value, value);
+    }
+
+    /// Codec storing values verbatim, so its output may start with anything
+    struct VerbatimCodec;
+
+    impl Codec<Vec<u8>> for VerbatimCodec {
+        fn encode(&self, entry: &CacheEntry<Vec<u8>>) -> Result<Vec<u8>> {
+            Ok(entry.value.clone())
+        }
+
+        fn decode(&self, data: Vec<u8>) -> Result<CacheEntry<Vec<u8>>> {
+            Ok(CacheEntry::new(data, None))
+        }
+    }
+
+    #[test]
+    fn test_other_codecs_output_is_never_parsed_as_sections() {
+        // Output that looks exactly like an entry stored in sections
+        let data = encode(&JsonCodec, entry("value", 1_000)).unwrap().data;
+
+        let encoded = encode(&VerbatimCodec, CacheEntry::new(data.clone(), None)).unwrap();
+        assert_eq!(encoded.prefix_length, 0);
+        assert_eq!(decode(&VerbatimCodec, encoded.data).unwrap().value, data);
+        assert_eq!(codec_payload(&VerbatimCodec, data.clone()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_entries_stored_before_sections_never_start_with_the_magic() {
+        let raw = RawBytesCodec.encode(&CacheEntry::new(vec![0xff; 16], None)).unwrap();
+        assert_ne!(JsonCodec.encode(&entry("value", 1_000)).unwrap()[0], MAGIC[0]);
+        assert_ne!(raw[0], MAGIC[0]);
+
+        #[cfg(feature = "compression")]
+        {
+            let compressed = crate::codec::CompressedCodec::new(JsonCodec).encode(&entry("value", 1_000));
+            assert_ne!(compressed.unwrap()[0], MAGIC[0]);
+        }
+    }
+
+    #[test]
+    fn test_metadata_section_is_found_from_the_header() {
+        let original = entry("value", 1_000);
+        let data = encode(&JsonCodec, original.clone()).unwrap().data;
+
+        // Where the Lua scripts look for the header and the metadata section
+        let header = section_header::<String, _>(&JsonCodec).unwrap();
+        assert_eq!(data[..HEADER_SIZE], header);
+        let value_length = u32::from_be_bytes(data[HEADER_SIZE..HEADER_SIZE + LENGTH_SIZE].try_into().unwrap());
+        let offset = metadata_offset(value_length as usize);
+
+        let mut stored = data[offset..].to_vec();
+        stored.extend_from_slice(b"unused");
+        let (metadata, section) = split_metadata(&stored).unwrap();
+        assert_eq!(metadata, original.metadata);
+        assert_eq!(section, encode_metadata(&original.metadata).unwrap());
+        assert!(section_header::<Vec<u8>, _>(&VerbatimCodec).is_none());
+    }
+
+    #[test]
+    fn test_unknown_format_version_fails_to_decode() {
+        let mut data = encode(&JsonCodec, entry("value", 1_000)).unwrap().data;
+        data[MAGIC.len()] = FORMAT_VERSION + 1;
+
+        let error = decode::<String, _>(&JsonCodec, data).unwrap_err();
+        assert!(error.to_string().contains("format version"));
+    }
+
+    #[test]
+    fn test_truncated_entry_fails_to_decode() {
+        let data = encode(&JsonCodec, entry("value", 1_000)).unwrap().data;
+        let truncated = data[..data.len() - 1].to_vec();
+
+        assert!(decode::<String, _>(&JsonCodec, truncated).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Caches scoped to a key prefix.
+
+use super::Cache;
+use crate::{CacheEntry, Result};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// A view of a cache that namespaces all keys under a prefix
+///
+/// Created with [`Cache::scoped`]. Every key is prefixed before it reaches the
+/// inner cache, and [`Cache::keys`], [`Cache::len`] and [`Cache::clear`] only
+/// see entries within the scope, so a subsystem handed a scoped cache can't
+/// collide with or clear another subsystem's entries.
+///
+/// Enumerating the scope relies on [`Cache::keys`] of the inner cache. If the
+/// inner cache can't list its keys, `len` reports zero and `clear` does nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{Cache, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MokaCache<String> = MokaCache::new(1000);
+/// let users = cache.scoped("users:");
+///
+/// // Stored as "users:1" in the underlying cache
+/// users.put("1", "Marvin".to_string(), None).await?;
+/// assert!(cache.contains_key("users:1").await);
+///
+/// // Only clears the "users:" scope
+/// users.clear().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ScopedCache<C> {
+    inner: C,
+    prefix: String,
+}
+
+impl<C> ScopedCache<C> {
+    pub(super) fn new(inner: C, prefix: String) -> Self {
+        Self { inner, prefix }
+    }
+
+    /// Get the prefix of this scope
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Create a nested scope whose prefix is appended to this scope's prefix
+    pub fn scoped(&self, prefix: impl Into<String>) -> ScopedCache<C>
+    where
+        C: Clone,
+    {
+        ScopedCache::new(self.inner.clone(), format!("{}{}", self.prefix, prefix.into()))
+    }
+
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl<T, C> Cache<T> for ScopedCache<C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        self.inner.get(&self.full_key(key)).await
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        self.inner.try_get(&self.full_key(key)).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        let full_keys: Vec<String> = keys.iter().map(|key| self.full_key(key)).collect();
+        let full_keys: Vec<&str> = full_keys.iter().map(String::as_str).collect();
+        self.inner.get_many(&full_keys).await
+    }
+
+    async fn get_batch_if_fresh(&self, keys: &[&str], now: Duration) -> Vec<Option<CacheEntry<T>>> {
+        let full_keys: Vec<String> = keys.iter().map(|key| self.full_key(key)).collect();
+        let full_keys: Vec<&str> = full_keys.iter().map(String::as_str).collect();
+        self.inner.get_batch_if_fresh(&full_keys, now).await
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        self.inner.set(&self.full_key(key), entry).await
+    }
+
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, entry)| (self.full_key(&key), entry))
+            .collect();
+        self.inner.set_many(entries).await
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        self.inner.set_if_version(&self.full_key(key), entry, expected_version).await
+    }
+
+    async fn remove(&self, key: &str) {
+        self.inner.remove(&self.full_key(key)).await
+    }
+
+    async fn clear(&self) {
+        if let Ok(keys) = Cache::<T>::keys(self).await {
+            for key in keys {
+                self.inner.remove(&self.full_key(&key)).await;
+            }
+        }
+    }
+
+    async fn len(&self) -> usize {
+        Cache::<T>::keys(self).await.map_or(0, |keys| keys.len())
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        self.inner.contains_key(&self.full_key(key)).await
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        self.inner.remove_prefix(&self.full_key(prefix)).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .keys()
+            .await?
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::MokaCache;
+
+    crate::cache_conformance_tests!(
+        scoped_conformance,
+        MokaCache::<String>::new(100).scoped("scope:"),
+        "value".to_string()
+    );
+
+    #[tokio::test]
+    async fn test_scoped_cache_isolation() {
+        let cache: MokaCache<String> = MokaCache::new(100);
+        let users = cache.scoped("users:");
+        let posts = cache.scoped("posts:");
+
+        users.put("1", "user".to_string(), None).await.unwrap();
+        posts.put("1", "post".to_string(), None).await.unwrap();
+        cache.put("global", "global".to_string(), None).await.unwrap();
+
+        assert_eq!(users.get("1").await.unwrap().value, "user");
+        assert_eq!(cache.get("posts:1").await.unwrap().value, "post");
+        assert_eq!(users.keys().await.unwrap(), vec!["1".to_string()]);
+
+        users.clear().await;
+
+        assert!(users.is_empty().await);
+        assert_eq!(posts.len().await, 1);
+        assert!(cache.contains_key("global").await);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_cache_nesting() {
+        let cache: MokaCache<String> = MokaCache::new(100);
+        let comments = cache.scoped("posts:").scoped("comments:");
+
+        assert_eq!(comments.prefix(), "posts:comments:");
+
+        comments.put("1", "comment".to_string(), None).await.unwrap();
+        assert!(cache.contains_key("posts:comments:1").await);
+    }
+}
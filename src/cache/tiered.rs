@@ -0,0 +1,277 @@
+//! Two-tier caches with a fast cache in front of a shared one.
+
+use super::Cache;
+use crate::{CacheEntry, Result};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// A cache checking a fast first tier (L1) before a shared second tier (L2)
+///
+/// The typical setup is an in-process [`MokaCache`](crate::MokaCache) in front
+/// of a [`RedisCache`](crate::RedisCache) shared by all instances of a service.
+///
+/// Reads check L1 first. On an L1 miss, L2 is read and a hit is promoted into
+/// L1 with its original metadata, so it expires in L1 at the same time as in L2.
+/// Writes go to L2 first and then to L1, and removals and clears hit both tiers.
+///
+/// L2 is the source of truth: [`Cache::len`] and [`Cache::keys`] only consider
+/// L2, and [`Cache::set_if_version`] compares against the version stored in L2.
+/// Writes through other instances only reach their own L1, so the L1 of this
+/// instance may serve an outdated entry until it expires there. Keep the L1
+/// TTLs short or capacity small if that matters.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cache::TieredCache, Cache, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let local: MokaCache<String> = MokaCache::new(1000);
+/// let shared: MokaCache<String> = MokaCache::new(100_000);
+/// let cache = TieredCache::new(local, shared);
+///
+/// cache.put("user:1", "Marvin".to_string(), None).await?;
+/// assert!(cache.l1().contains_key("user:1").await);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TieredCache<L1, L2> {
+    l1: L1,
+    l2: L2,
+}
+
+impl<L1, L2> TieredCache<L1, L2> {
+    /// Create a new tiered cache with `l1` in front of `l2`
+    pub fn new(l1: L1, l2: L2) -> Self {
+        Self { l1, l2 }
+    }
+
+    /// Get the first tier
+    pub fn l1(&self) -> &L1 {
+        &self.l1
+    }
+
+    /// Get the second tier
+    pub fn l2(&self) -> &L2 {
+        &self.l2
+    }
+
+    /// Store an entry read from L2 in L1, keeping its metadata
+    async fn promote<T>(&self, key: &str, entry: &CacheEntry<T>)
+    where
+        T: Clone + Send + Sync + 'static,
+        L1: Cache<T>,
+    {
+        let _ = self.l1.set(key, entry.clone()).await;
+    }
+}
+
+#[async_trait]
+impl<T, L1, L2> Cache<T> for TieredCache<L1, L2>
+where
+    T: Clone + Send + Sync + 'static,
+    L1: Cache<T>,
+    L2: Cache<T>,
+{
+    async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+        if let Some(entry) = self.l1.get(key).await {
+            return Some(entry);
+        }
+
+        let entry = self.l2.get(key).await?;
+        self.promote(key, &entry).await;
+        Some(entry)
+    }
+
+    async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+        // L1 only saves a trip to L2, so its errors are treated as misses
+        if let Ok(Some(entry)) = self.l1.try_get(key).await {
+            return Ok(Some(entry));
+        }
+
+        let entry = self.l2.try_get(key).await?;
+        if let Some(entry) = &entry {
+            self.promote(key, entry).await;
+        }
+        Ok(entry)
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+        let mut entries = self.l1.get_many(keys).await;
+
+        let missing: Vec<usize> = (0..keys.len()).filter(|&index| entries[index].is_none()).collect();
+        if missing.is_empty() {
+            return entries;
+        }
+
+        let missing_keys: Vec<&str> = missing.iter().map(|&index| keys[index]).collect();
+        let mut promoted = Vec::new();
+        for (index, entry) in missing.into_iter().zip(self.l2.get_many(&missing_keys).await) {
+            if let Some(entry) = &entry {
+                promoted.push((keys[index].to_string(), entry.clone()));
+            }
+            entries[index] = entry;
+        }
+
+        if !promoted.is_empty() {
+            self.l1.set_many(promoted).await;
+        }
+        entries
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+        self.l2.set(key, entry.clone()).await?;
+        if self.l1.set(key, entry).await.is_err() {
+            // Don't keep serving the previous value from L1
+            self.l1.remove(key).await;
+        }
+        Ok(())
+    }
+
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<T>)>) -> Vec<Result<()>> {
+        let results = self.l2.set_many(entries.clone()).await;
+
+        let written: Vec<(String, CacheEntry<T>)> = entries
+            .into_iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(entry, _)| entry)
+            .collect();
+        for ((key, _), result) in written.iter().zip(self.l1.set_many(written.clone()).await) {
+            if result.is_err() {
+                self.l1.remove(key).await;
+            }
+        }
+
+        results
+    }
+
+    async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+        let written = self.l2.set_if_version(key, entry.clone(), expected_version).await?;
+        if !written || self.l1.set(key, entry).await.is_err() {
+            // L2 holds a newer entry than L1 may have
+            self.l1.remove(key).await;
+        }
+        Ok(written)
+    }
+
+    async fn remove(&self, key: &str) {
+        self.l2.remove(key).await;
+        self.l1.remove(key).await;
+    }
+
+    async fn clear(&self) {
+        self.l2.clear().await;
+        self.l1.clear().await;
+    }
+
+    async fn len(&self) -> usize {
+        self.l2.len().await
+    }
+
+    async fn len_valid(&self, now: Duration) -> usize {
+        self.l2.len_valid(now).await
+    }
+
+    async fn contains_key(&self, key: &str) -> bool {
+        self.l1.contains_key(key).await || self.l2.contains_key(key).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.l2.keys().await
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> Result<usize> {
+        let removed = self.l2.remove_prefix(prefix).await?;
+        let _ = self.l1.remove_prefix(prefix).await;
+        Ok(removed)
+    }
+
+    async fn clear_expired(&self, now: Duration) -> Result<usize> {
+        let removed = self.l2.clear_expired(now).await?;
+        let _ = self.l1.clear_expired(now).await;
+        Ok(removed)
+    }
+
+    fn backend(&self) -> &'static str {
+        "tiered"
+    }
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::{CacheMetadata, MokaCache};
+
+    crate::cache_conformance_tests!(
+        tiered_conformance,
+        TieredCache::new(MokaCache::<String>::new(100), MokaCache::<String>::new(100)),
+        "value".to_string()
+    );
+
+    #[tokio::test]
+    async fn test_tiered_cache_promotes_l2_hits() {
+        let l1: MokaCache<String> = MokaCache::new(100);
+        let l2: MokaCache<String> = MokaCache::new(100);
+        let cache = TieredCache::new(l1.clone(), l2.clone());
+
+        // Written by another instance, so only in L2
+        let entry = CacheEntry::builder("value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(30))
+            .build();
+        l2.set("key", entry.clone()).await.unwrap();
+        assert!(!l1.contains_key("key").await);
+
+        // An L1 miss and L2 hit repopulates L1 with the original metadata
+        let served = cache.get("key").await.unwrap();
+        assert_eq!(served.metadata, entry.metadata);
+        assert_eq!(l1.get("key").await.unwrap().metadata, entry.metadata);
+
+        // Later reads are served from L1
+        l2.remove("key").await;
+        assert_eq!(cache.get("key").await.unwrap().value, "value");
+
+        // get_many promotes the missing entries only
+        l2.put("other", "other".to_string(), None).await.unwrap();
+        let entries = cache.get_many(&["key", "other", "missing"]).await;
+        assert_eq!(entries[0].as_ref().unwrap().value, "value");
+        assert_eq!(entries[1].as_ref().unwrap().value, "other");
+        assert!(entries[2].is_none());
+        assert!(l1.contains_key("other").await);
+    }
+
+    #[tokio::test]
+    async fn test_tiered_cache_writes_and_removes_both_tiers() {
+        let l1: MokaCache<String> = MokaCache::new(100);
+        let l2: MokaCache<String> = MokaCache::new(100);
+        let cache = TieredCache::new(l1.clone(), l2.clone());
+
+        cache.put("key", "value".to_string(), None).await.unwrap();
+        assert_eq!(l1.get("key").await.unwrap().value, "value");
+        assert_eq!(l2.get("key").await.unwrap().value, "value");
+
+        // A rejected compare-and-set drops the possibly outdated L1 entry
+        let newer = CacheEntry::with_metadata("newer".to_string(), CacheMetadata::new(None).with_version(1));
+        l2.set("key", newer).await.unwrap();
+        let written = cache
+            .set_if_version("key", CacheEntry::new("lost".to_string(), None), Some(0))
+            .await
+            .unwrap();
+        assert!(!written);
+        assert!(!l1.contains_key("key").await);
+        assert_eq!(cache.get("key").await.unwrap().value, "newer");
+
+        cache.remove("key").await;
+        assert!(!l1.contains_key("key").await);
+        assert!(!l2.contains_key("key").await);
+
+        cache.put("key", "value".to_string(), None).await.unwrap();
+        cache.clear().await;
+        assert!(l1.is_empty().await);
+        assert!(l2.is_empty().await);
+    }
+}
@@ -0,0 +1,88 @@
+//! Time sources for cachified calls.
+//!
+//! Every cachified call reads the current time from a [`Clock`], which decides
+//! whether cached values are expired or stale and stamps the creation time of
+//! written entries. The default [`SystemClock`] reads the system time, while
+//! [`MockClock`] only moves when told to, so tests can step through expiry and
+//! stale-while-revalidate transitions without sleeping.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Trait for reading the current time
+pub trait Clock: Send + Sync {
+    /// Get the current time as duration since UNIX_EPOCH
+    fn now(&self) -> Duration;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// Clock reading the system time (default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Clock that only moves when advanced manually
+///
+/// Clones share the same time, so a test can keep a clone to advance the
+/// clock passed to its cachified calls.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::clock::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new(Duration::from_secs(1_000));
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now(), Duration::from_secs(1_060));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Create a clock showing `now`, as duration since UNIX_EPOCH
+    pub fn new(now: Duration) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Create a clock showing the current system time
+    pub fn starting_now() -> Self {
+        Self::new(SystemClock.now())
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    /// Set the clock to `now`, as duration since UNIX_EPOCH
+    pub fn set(&self, now: Duration) {
+        *self.lock() = now;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        *self.lock()
+    }
+}
@@ -0,0 +1,491 @@
+//! Codecs for serializing cache entries
+//!
+//! Codecs turn a `CacheEntry<T>` into bytes and back. They are used by
+//! backends that store entries outside of the process, such as `RedisCache`.
+
+use crate::{CacheEntry, CacheMetadata, CachifiedError, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "encryption")]
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+#[cfg(feature = "encryption")]
+use aes_gcm::{Aes256Gcm, Nonce};
+#[cfg(feature = "compression")]
+use std::io::{Read, Write};
+
+/// Trait for encoding and decoding cache entries to and from bytes.
+pub trait Codec<T>: Send + Sync {
+    /// Encode a cache entry into bytes
+    fn encode(&self, entry: &CacheEntry<T>) -> Result<Vec<u8>>;
+
+    /// Decode a cache entry from bytes
+    fn decode(&self, data: Vec<u8>) -> Result<CacheEntry<T>>;
+
+    /// Whether encoding the same entry twice yields the same bytes
+    ///
+    /// Backends use this to detect unchanged values by comparing their encoded
+    /// bytes, e.g. `RedisCache` then only rewrites the metadata of refreshed
+    /// entries whose value didn't change. A false mismatch merely costs a full
+    /// write. Defaults to `false`, which disables the comparison.
+    ///
+    /// `RedisCache` stores the entries of deterministic codecs behind a header
+    /// starting with the byte `0xff`, which tells them apart from entries
+    /// stored with the plain output of the codec by earlier versions. Codecs
+    /// returning `true` must therefore never start their output with `0xff`.
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+}
+
+/// Codec that stores entries as JSON (default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl<T> Codec<T> for JsonCodec
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode(&self, entry: &CacheEntry<T>) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(entry)?)
+    }
+
+    fn decode(&self, data: Vec<u8>) -> Result<CacheEntry<T>> {
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// Codec that stores byte values as raw bytes instead of JSON
+///
+/// JSON encodes byte values as arrays of numbers, which bloats them considerably.
+/// This codec stores the value bytes as-is, preceded by a small header containing
+/// the length of the JSON-encoded metadata and the metadata itself:
+///
+/// ```text
+/// [metadata length: u32 big endian][metadata JSON][value bytes]
+/// ```
+///
+/// It works with any value type that can be viewed as and built from bytes,
+/// such as `Vec<u8>` and `bytes::Bytes`. Decoding reuses the buffer read from
+/// the backend, so no additional copy of the value is made.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawBytesCodec;
+
+/// Size of the metadata length header used by `RawBytesCodec`
+const METADATA_LENGTH_SIZE: usize = 4;
+
+impl<T> Codec<T> for RawBytesCodec
+where
+    T: AsRef<[u8]> + From<Vec<u8>>,
+{
+    fn encode(&self, entry: &CacheEntry<T>) -> Result<Vec<u8>> {
+        let metadata = serde_json::to_vec(&entry.metadata)?;
+        let value = entry.value.as_ref();
+        let metadata_length = u32::try_from(metadata.len())
+            .map_err(|_| CachifiedError::other("Metadata too large to encode"))?;
+
+        let mut data = Vec::with_capacity(METADATA_LENGTH_SIZE + metadata.len() + value.len());
+        data.extend_from_slice(&metadata_length.to_be_bytes());
+        data.extend_from_slice(&metadata);
+        data.extend_from_slice(value);
+        Ok(data)
+    }
+
+    fn decode(&self, mut data: Vec<u8>) -> Result<CacheEntry<T>> {
+        let header: [u8; METADATA_LENGTH_SIZE] = data
+            .get(..METADATA_LENGTH_SIZE)
+            .and_then(|header| header.try_into().ok())
+            .ok_or_else(|| CachifiedError::other("Raw entry is missing its header"))?;
+        let metadata_end = METADATA_LENGTH_SIZE + u32::from_be_bytes(header) as usize;
+
+        let metadata: CacheMetadata = serde_json::from_slice(
+            data.get(METADATA_LENGTH_SIZE..metadata_end)
+                .ok_or_else(|| CachifiedError::other("Raw entry metadata is truncated"))?,
+        )?;
+
+        // Drop the header in place so the remaining buffer can become the value
+        data.drain(..metadata_end);
+
+        Ok(CacheEntry {
+            value: T::from(data),
+            metadata,
+        })
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// Codec that encrypts the entries encoded by another codec with AES-256-GCM
+///
+/// Meant for sensitive values in shared backends such as Redis, where the
+/// stored bytes shouldn't be readable by anyone with access to the backend.
+/// The entry is encoded with the inner codec, e.g. [`JsonCodec`], and the
+/// result is encrypted with a fresh random nonce:
+///
+/// ```text
+/// [format version: u8][nonce: 12 bytes][ciphertext and authentication tag]
+/// ```
+///
+/// Keys can be rotated by adding the old key with
+/// [`with_previous_key`](Self::with_previous_key): entries are always
+/// encrypted with the current key, and decryption falls back to the previous
+/// keys in the order they were added. Entries that can't be decrypted with
+/// any key fail to decode, so backends treat them like corrupted entries.
+/// As every entry gets a fresh nonce, backends can't detect unchanged values
+/// by their encoded bytes and always rewrite them.
+/// Requires the "encryption" feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(all(feature = "redis", feature = "encryption"))]
+/// use cachified::{codec::{EncryptedCodec, JsonCodec}, RedisCache};
+///
+/// # #[cfg(all(feature = "redis", feature = "encryption"))]
+/// # async fn example(current_key: [u8; 32], previous_key: [u8; 32]) -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = RedisCache::<String>::new("redis://localhost:6379")
+///     .await?
+///     .with_codec(EncryptedCodec::new(JsonCodec, current_key).with_previous_key(previous_key));
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+pub struct EncryptedCodec<K = JsonCodec> {
+    inner: K,
+    /// The current key, followed by previous keys that are only used to decrypt
+    ciphers: Vec<Aes256Gcm>,
+}
+
+/// Version of the format written by `EncryptedCodec`
+#[cfg(feature = "encryption")]
+const ENCRYPTED_FORMAT_VERSION: u8 = 1;
+
+/// Size of the nonce used by `EncryptedCodec`
+#[cfg(feature = "encryption")]
+const NONCE_SIZE: usize = 12;
+
+#[cfg(feature = "encryption")]
+impl<K> EncryptedCodec<K> {
+    /// Encrypt the entries encoded by `inner` with a 256-bit key
+    pub fn new(inner: K, key: [u8; 32]) -> Self {
+        Self {
+            inner,
+            ciphers: vec![Aes256Gcm::new(&key.into())],
+        }
+    }
+
+    /// Also decrypt entries that were encrypted with a previous key
+    ///
+    /// Previous keys are tried after the current key, in the order they were added.
+    pub fn with_previous_key(mut self, key: [u8; 32]) -> Self {
+        self.ciphers.push(Aes256Gcm::new(&key.into()));
+        self
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl<K> std::fmt::Debug for EncryptedCodec<K>
+where
+    K: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the keys
+        f.debug_struct("EncryptedCodec")
+            .field("inner", &self.inner)
+            .field("keys", &self.ciphers.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl<T, K> Codec<T> for EncryptedCodec<K>
+where
+    K: Codec<T>,
+{
+    fn encode(&self, entry: &CacheEntry<T>) -> Result<Vec<u8>> {
+        let plaintext = self.inner.encode(entry)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.ciphers[0]
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| CachifiedError::other("Encrypting cache entry failed"))?;
+
+        let mut data = Vec::with_capacity(1 + NONCE_SIZE + ciphertext.len());
+        data.push(ENCRYPTED_FORMAT_VERSION);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    fn decode(&self, data: Vec<u8>) -> Result<CacheEntry<T>> {
+        let (version, rest) = data
+            .split_first()
+            .ok_or_else(|| CachifiedError::other("Encrypted entry is empty"))?;
+        if *version != ENCRYPTED_FORMAT_VERSION {
+            return Err(CachifiedError::other(format!(
+                "Encrypted entry has unknown format version {version}"
+            )));
+        }
+        if rest.len() < NONCE_SIZE {
+            return Err(CachifiedError::other("Encrypted entry is truncated"));
+        }
+
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+        let nonce = Nonce::from_slice(nonce);
+        let plaintext = self
+            .ciphers
+            .iter()
+            .find_map(|cipher| cipher.decrypt(nonce, ciphertext).ok())
+            .ok_or_else(|| {
+                CachifiedError::other("Decrypting cache entry failed, it is corrupted or was encrypted with an unknown key")
+            })?;
+
+        self.inner.decode(plaintext)
+    }
+}
+
+/// Codec that gzip-compresses the entries encoded by another codec
+///
+/// Meant for large values in backends where memory is expensive, such as
+/// Redis. Only encoded entries of at least the threshold size (1 KiB by
+/// default) are compressed, as compressing tiny entries costs CPU time and
+/// can even make them larger. Every entry starts with a header byte telling
+/// whether it is compressed:
+///
+/// ```text
+/// [0][entry encoded by the inner codec]
+/// [1][gzip-compressed entry encoded by the inner codec]
+/// ```
+///
+/// Entries without a header, written before compression was enabled, are
+/// decoded with the inner codec as they are. This relies on the inner
+/// codec's output never starting with a `0` or `1` byte, which holds for
+/// [`JsonCodec`]. Requires the "compression" feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(all(feature = "redis", feature = "compression"))]
+/// use cachified::{codec::{CompressedCodec, JsonCodec}, RedisCache};
+///
+/// # #[cfg(all(feature = "redis", feature = "compression"))]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = RedisCache::<String>::new("redis://localhost:6379")
+///     .await?
+///     .with_codec(CompressedCodec::new(JsonCodec).with_threshold(4096));
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy)]
+pub struct CompressedCodec<K = JsonCodec> {
+    inner: K,
+    threshold: usize,
+}
+
+/// Header byte of entries stored uncompressed by `CompressedCodec`
+#[cfg(feature = "compression")]
+const UNCOMPRESSED_HEADER: u8 = 0;
+
+/// Header byte of entries stored gzip-compressed by `CompressedCodec`
+#[cfg(feature = "compression")]
+const GZIP_HEADER: u8 = 1;
+
+#[cfg(feature = "compression")]
+impl<K> CompressedCodec<K> {
+    /// Compress the entries encoded by `inner` that are at least 1 KiB large
+    pub fn new(inner: K) -> Self {
+        Self {
+            inner,
+            threshold: 1024,
+        }
+    }
+
+    /// Set the size in bytes from which encoded entries are compressed
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<T, K> Codec<T> for CompressedCodec<K>
+where
+    K: Codec<T>,
+{
+    fn encode(&self, entry: &CacheEntry<T>) -> Result<Vec<u8>> {
+        let encoded = self.inner.encode(entry)?;
+
+        if encoded.len() < self.threshold {
+            let mut data = Vec::with_capacity(1 + encoded.len());
+            data.push(UNCOMPRESSED_HEADER);
+            data.extend_from_slice(&encoded);
+            return Ok(data);
+        }
+
+        let mut encoder = flate2::write::GzEncoder::new(vec![GZIP_HEADER], flate2::Compression::default());
+        encoder
+            .write_all(&encoded)
+            .and_then(|()| encoder.finish())
+            .map_err(|e| CachifiedError::other(format!("Compressing cache entry failed: {e}")))
+    }
+
+    fn decode(&self, mut data: Vec<u8>) -> Result<CacheEntry<T>> {
+        match data.first() {
+            Some(&UNCOMPRESSED_HEADER) => {
+                data.remove(0);
+                self.inner.decode(data)
+            }
+            Some(&GZIP_HEADER) => {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(&data[1..])
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| CachifiedError::other(format!("Decompressing cache entry failed: {e}")))?;
+                self.inner.decode(decoded)
+            }
+            // Written before compression was enabled
+            _ => self.inner.decode(data),
+        }
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.inner.is_deterministic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_json_codec_round_trip() {
+        let entry = CacheEntry::new("value".to_string(), Some(Duration::from_secs(60)));
+
+        let data = JsonCodec.encode(&entry).unwrap();
+        let decoded: CacheEntry<String> = JsonCodec.decode(data).unwrap();
+
+        assert_eq!(decoded.value, entry.value);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[test]
+    fn test_json_codec_option_none() {
+        let entry: CacheEntry<Option<String>> = CacheEntry::new(None, Some(Duration::from_secs(60)));
+
+        let data = JsonCodec.encode(&entry).unwrap();
+        let decoded: CacheEntry<Option<String>> = JsonCodec.decode(data).unwrap();
+
+        assert_eq!(decoded.value, None);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[test]
+    fn test_raw_bytes_codec_round_trip() {
+        let value: Vec<u8> = (0..=255).collect();
+        let entry = CacheEntry::new(value.clone(), Some(Duration::from_secs(60)));
+
+        let data = RawBytesCodec.encode(&entry).unwrap();
+        // The value is stored verbatim at the end of the payload
+        assert!(data.ends_with(&value));
+
+        let decoded: CacheEntry<Vec<u8>> = RawBytesCodec.decode(data).unwrap();
+        assert_eq!(decoded.value, value);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[test]
+    fn test_raw_bytes_codec_bytes() {
+        let value = bytes::Bytes::from_static(b"\x00\x01binary\xffpayload");
+        let entry = CacheEntry::new(value.clone(), None);
+
+        let data = RawBytesCodec.encode(&entry).unwrap();
+        let decoded: CacheEntry<bytes::Bytes> = RawBytesCodec.decode(data).unwrap();
+
+        assert_eq!(decoded.value, value);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[test]
+    fn test_raw_bytes_codec_truncated() {
+        let result: Result<CacheEntry<Vec<u8>>> = RawBytesCodec.decode(vec![0, 0]);
+        assert!(result.is_err());
+
+        let result: Result<CacheEntry<Vec<u8>>> = RawBytesCodec.decode(vec![0, 0, 0, 10, b'{']);
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_codec_round_trip() {
+        let entry = CacheEntry::new("secret".to_string(), Some(Duration::from_secs(60)));
+        let codec = EncryptedCodec::new(JsonCodec, [7; 32]);
+
+        let data = codec.encode(&entry).unwrap();
+        assert!(!data.windows(b"secret".len()).any(|window| window == b"secret"));
+        // A fresh nonce makes every encryption differ
+        assert_ne!(data, codec.encode(&entry).unwrap());
+
+        let decoded: CacheEntry<String> = codec.decode(data).unwrap();
+        assert_eq!(decoded.value, entry.value);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_codec_key_rotation() {
+        let entry = CacheEntry::new("secret".to_string(), None);
+        let old = EncryptedCodec::new(JsonCodec, [1; 32]);
+        let rotated = EncryptedCodec::new(JsonCodec, [2; 32]).with_previous_key([1; 32]);
+        let unrelated = EncryptedCodec::new(JsonCodec, [3; 32]);
+
+        let data = old.encode(&entry).unwrap();
+        let decoded: CacheEntry<String> = rotated.decode(data.clone()).unwrap();
+        assert_eq!(decoded.value, "secret");
+        assert!(Codec::<String>::decode(&unrelated, data.clone()).is_err());
+
+        let mut tampered = data;
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(Codec::<String>::decode(&rotated, tampered).is_err());
+        assert!(Codec::<String>::decode(&rotated, vec![ENCRYPTED_FORMAT_VERSION, 0]).is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_codec_round_trip() {
+        let codec = CompressedCodec::new(JsonCodec);
+        let entry = CacheEntry::new("response ".repeat(1000), Some(Duration::from_secs(60)));
+
+        let data = codec.encode(&entry).unwrap();
+        assert_eq!(data[0], GZIP_HEADER);
+        assert!(data.len() < JsonCodec.encode(&entry).unwrap().len() / 10);
+
+        let decoded: CacheEntry<String> = codec.decode(data).unwrap();
+        assert_eq!(decoded.value, entry.value);
+        assert_eq!(decoded.metadata, entry.metadata);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_codec_keeps_small_entries_uncompressed() {
+        let codec = CompressedCodec::new(JsonCodec).with_threshold(4096);
+        let entry = CacheEntry::new("value".to_string(), Some(Duration::from_secs(60)));
+        let json = JsonCodec.encode(&entry).unwrap();
+
+        let data = codec.encode(&entry).unwrap();
+        assert_eq!(data[0], UNCOMPRESSED_HEADER);
+        assert_eq!(&data[1..], json.as_slice());
+        let decoded: CacheEntry<String> = codec.decode(data).unwrap();
+        assert_eq!(decoded.value, "value");
+
+        // Entries written without the codec still decode
+        let decoded: CacheEntry<String> = codec.decode(json).unwrap();
+        assert_eq!(decoded.value, "value");
+        assert!(Codec::<String>::decode(&codec, vec![GZIP_HEADER, 0, 1]).is_err());
+    }
+}
@@ -0,0 +1,420 @@
+//! Shared configuration for cachified calls
+//!
+//! This module provides the `CachifiedConfig` struct that holds state shared
+//! across multiple cachified calls, such as limits on concurrent refreshes
+//! and tracking of background refreshes.
+
+use crate::fresh_value::{FreshValueFuture, FreshValueOutcome};
+use crate::Result;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+/// Configuration shared across cachified calls
+///
+/// Unlike `CachifiedOptions`, which configures a single call, this struct holds
+/// state that is shared by every call it is attached to. Clones share the same
+/// state, so create it once and pass clones to `CachifiedOptionsBuilder::config`.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::CachifiedConfig;
+///
+/// // At most 10 fresh values are fetched at the same time across all keys
+/// let config = CachifiedConfig::new().max_concurrent_refreshes(10);
+/// ```
+#[derive(Clone, Default)]
+pub struct CachifiedConfig {
+    refresh_semaphore: Option<Arc<Semaphore>>,
+    background_semaphore: Option<Arc<Semaphore>>,
+    background_overflow: BackgroundRefreshOverflow,
+    refresh_tracker: RefreshTracker,
+    in_flight: Option<Arc<Mutex<InFlight>>>,
+    early_refreshes: Arc<Mutex<HashSet<String>>>,
+}
+
+/// What to do when a background refresh is due but the limit set with
+/// [`CachifiedConfig::max_background_refreshes`] is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackgroundRefreshOverflow {
+    /// Don't start the refresh and serve the stale value anyway
+    ///
+    /// A later read of the key triggers the refresh again.
+    #[default]
+    Skip,
+    /// Wait for a running refresh to finish before serving the stale value
+    ///
+    /// This applies backpressure to callers instead of dropping refreshes.
+    Wait,
+}
+
+impl CachifiedConfig {
+    /// Create a new configuration without any limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of concurrently running fresh value fetches
+    ///
+    /// This applies to both blocking fetches and background refreshes across
+    /// all keys. Fetches beyond the limit wait until a slot becomes available.
+    pub fn max_concurrent_refreshes(mut self, max: usize) -> Self {
+        self.refresh_semaphore = Some(Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// Limit the number of outstanding background refreshes
+    ///
+    /// Without a limit, every stale read spawns a new task, so a burst of
+    /// stale reads can spawn tasks without bound. Once `max` background
+    /// refreshes are outstanding, further ones are handled according to
+    /// [`CachifiedConfig::background_refresh_overflow`].
+    pub fn max_background_refreshes(mut self, max: usize) -> Self {
+        self.background_semaphore = Some(Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// Set what happens to background refreshes beyond the limit (default: [`BackgroundRefreshOverflow::Skip`])
+    pub fn background_refresh_overflow(mut self, overflow: BackgroundRefreshOverflow) -> Self {
+        self.background_overflow = overflow;
+        self
+    }
+
+    /// Coalesce concurrent fetches of the same key into a single fetch
+    ///
+    /// When several calls miss the same key at the same time, only the first
+    /// one calls `get_fresh_value`. The others wait for its result, which is
+    /// shared with all of them, including errors. Each call then handles the
+    /// result on its own, e.g. validates it or falls back to the cache. Only
+    /// the call that started the fetch writes the value to the cache.
+    ///
+    /// Fetches are coalesced by key across all calls using this configuration,
+    /// so calls on different caches sharing a configuration need distinct keys.
+    /// Background refreshes aren't coalesced.
+    pub fn single_flight(mut self, enabled: bool) -> Self {
+        self.in_flight = enabled.then(Arc::default);
+        self
+    }
+
+    /// Get the tracker of background refreshes started with this configuration
+    pub fn refresh_tracker(&self) -> &RefreshTracker {
+        &self.refresh_tracker
+    }
+
+    /// Wait for all background refreshes started with this configuration to finish
+    ///
+    /// This is a shortcut for [`RefreshTracker::drain`] and meant to be called
+    /// during shutdown. Returns `true` if all refreshes finished within the timeout.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.refresh_tracker.drain(timeout).await
+    }
+
+    /// Run a fresh value fetch, waiting for a free slot if the number of
+    /// concurrent refreshes is limited
+    ///
+    /// Urgent fetches don't wait for a slot and don't take one up.
+    pub(crate) async fn run_refresh<Fut: Future>(&self, priority: RefreshPriority, future: Fut) -> Fut::Output {
+        let _permit = match &self.refresh_semaphore {
+            Some(semaphore) if priority == RefreshPriority::Normal => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("refresh semaphore is never closed"),
+            ),
+            _ => None,
+        };
+
+        future.await
+    }
+
+    /// Run a blocking fresh value fetch, joining a running fetch of the same
+    /// key if single-flight is enabled
+    ///
+    /// Returns the result and whether this call started the fetch.
+    pub(crate) async fn fetch_fresh_value<T>(&self, key: &str, future: FreshValueFuture<T>) -> (Result<FreshValueOutcome<T>>, bool)
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let Some(in_flight) = &self.in_flight else {
+            return (self.run_refresh(RefreshPriority::Normal, future).await, true);
+        };
+
+        let (fetch, started) = {
+            let mut in_flight_guard = lock(in_flight);
+            // Fetches of another value type under the same key aren't joined
+            match in_flight_guard
+                .fetches
+                .get(key)
+                .and_then(|(_, fetch)| fetch.downcast_ref::<SharedFetch<T>>())
+            {
+                Some(fetch) => (fetch.clone(), false),
+                None => {
+                    in_flight_guard.next_id += 1;
+                    let id = in_flight_guard.next_id;
+                    let guard = FlightGuard {
+                        in_flight: in_flight.clone(),
+                        key: key.to_string(),
+                        id,
+                    };
+                    let config = self.clone();
+                    let fetch: SharedFetch<T> = async move {
+                        // Dropped when the fetch completes, panics or all callers give up
+                        let _guard = guard;
+                        config.run_refresh(RefreshPriority::Normal, future).await
+                    }
+                    .boxed()
+                    .shared();
+
+                    in_flight_guard
+                        .fetches
+                        .insert(key.to_string(), (id, Box::new(fetch.clone())));
+                    (fetch, true)
+                }
+            }
+        };
+
+        (fetch.await, started)
+    }
+
+    /// Claim the early refresh of a key
+    ///
+    /// Returns `None` if an early refresh of the key is already running. The
+    /// claim is released when dropped.
+    pub(crate) fn claim_early_refresh(&self, key: &str) -> Option<EarlyRefreshClaim> {
+        let mut early_refreshes = self.early_refreshes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        early_refreshes.insert(key.to_string()).then(|| EarlyRefreshClaim {
+            early_refreshes: self.early_refreshes.clone(),
+            key: key.to_string(),
+        })
+    }
+
+    /// Reserve a slot for a background refresh
+    ///
+    /// Returns `None` if the limit on background refreshes is reached and
+    /// overflowing refreshes are skipped. The slot is freed when dropped.
+    pub(crate) async fn reserve_background_refresh(&self) -> Option<BackgroundSlot> {
+        let Some(semaphore) = &self.background_semaphore else {
+            return Some(BackgroundSlot { _permit: None });
+        };
+
+        let permit = match self.background_overflow {
+            BackgroundRefreshOverflow::Skip => semaphore.clone().try_acquire_owned().ok()?,
+            BackgroundRefreshOverflow::Wait => semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("background semaphore is never closed"),
+        };
+
+        Some(BackgroundSlot { _permit: Some(permit) })
+    }
+}
+
+/// A fetch that several calls can wait for
+type SharedFetch<T> = Shared<BoxFuture<'static, Result<FreshValueOutcome<T>>>>;
+
+/// Fetches in flight by key, for single-flight
+#[derive(Default)]
+struct InFlight {
+    next_id: u64,
+    /// Fetches by key, with the id telling them apart from later fetches of the key
+    fetches: HashMap<String, (u64, Box<dyn Any + Send + Sync>)>,
+}
+
+fn lock(in_flight: &Mutex<InFlight>) -> std::sync::MutexGuard<'_, InFlight> {
+    in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Removes a fetch from the fetches in flight when dropped
+struct FlightGuard {
+    in_flight: Arc<Mutex<InFlight>>,
+    key: String,
+    id: u64,
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = lock(&self.in_flight);
+        if in_flight.fetches.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            in_flight.fetches.remove(&self.key);
+        }
+    }
+}
+
+/// Claim of a running early refresh, released when dropped
+pub(crate) struct EarlyRefreshClaim {
+    early_refreshes: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for EarlyRefreshClaim {
+    fn drop(&mut self) {
+        self.early_refreshes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&self.key);
+    }
+}
+
+/// Slot of an outstanding background refresh, freed when dropped
+pub(crate) struct BackgroundSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Priority of a fresh value fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RefreshPriority {
+    /// Subject to the limit on concurrent refreshes
+    Normal,
+    /// Bypasses the limit on concurrent refreshes
+    Urgent,
+}
+
+/// Tracks background refreshes so they can be awaited during shutdown
+///
+/// Every background refresh started by `cachified` is registered with the
+/// tracker of the call's `CachifiedConfig`. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct RefreshTracker {
+    inner: Arc<RefreshTrackerInner>,
+}
+
+#[derive(Default)]
+struct RefreshTrackerInner {
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+impl RefreshTracker {
+    /// Get the number of background refreshes that are still running
+    pub fn active(&self) -> usize {
+        self.inner.active.load(Ordering::SeqCst)
+    }
+
+    /// Wait for all running background refreshes to finish
+    ///
+    /// Returns `true` if no refreshes are running anymore, or `false` if the
+    /// timeout elapsed first. Refreshes started while draining are waited for too.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            // Register for notifications before checking the count so that a
+            // refresh finishing in between can't be missed
+            let idle = self.inner.idle.notified();
+            tokio::pin!(idle);
+            idle.as_mut().enable();
+
+            if self.active() == 0 {
+                return true;
+            }
+
+            if tokio::time::timeout_at(deadline, idle).await.is_err() {
+                return self.active() == 0;
+            }
+        }
+    }
+
+    /// Register a background refresh, which is tracked until the guard is dropped
+    pub(crate) fn track(&self) -> RefreshGuard {
+        self.inner.active.fetch_add(1, Ordering::SeqCst);
+        RefreshGuard {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Guard that marks a background refresh as finished when dropped
+pub(crate) struct RefreshGuard {
+    inner: Arc<RefreshTrackerInner>,
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        if self.inner.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_single_flight_cleans_up_after_panic() {
+        let config = CachifiedConfig::new().single_flight(true);
+
+        let panicking = config.clone();
+        let result = tokio::spawn(async move {
+            let fetch: FreshValueFuture<u32> = Box::pin(async { panic!("fetch panicked") });
+            panicking.fetch_fresh_value("key", fetch).await
+        })
+        .await;
+        assert!(result.is_err());
+
+        let fetch: FreshValueFuture<u32> = Box::pin(async { Ok(FreshValueOutcome::Value(1)) });
+        let (result, started) = config.fetch_fresh_value("key", fetch).await;
+        assert_eq!(result.unwrap(), FreshValueOutcome::Value(1));
+        assert!(started);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tracker_drain() {
+        let tracker = RefreshTracker::default();
+        assert!(tracker.drain(Duration::from_millis(10)).await);
+
+        let guard = tracker.track();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(guard);
+        });
+
+        assert_eq!(tracker.active(), 1);
+        assert!(tracker.drain(Duration::from_secs(1)).await);
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_limit_skips() {
+        let config = CachifiedConfig::new().max_background_refreshes(1);
+
+        let slot = config.reserve_background_refresh().await;
+        assert!(slot.is_some());
+        assert!(config.reserve_background_refresh().await.is_none());
+
+        drop(slot);
+        assert!(config.reserve_background_refresh().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_limit_waits() {
+        let config = CachifiedConfig::new()
+            .max_background_refreshes(1)
+            .background_refresh_overflow(BackgroundRefreshOverflow::Wait);
+
+        let slot = config.reserve_background_refresh().await;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(slot);
+        });
+
+        let waited = tokio::time::timeout(Duration::from_secs(1), config.reserve_background_refresh()).await;
+        assert!(matches!(waited, Ok(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tracker_drain_timeout() {
+        let tracker = RefreshTracker::default();
+        let _guard = tracker.track();
+
+        assert!(!tracker.drain(Duration::from_millis(20)).await);
+        assert_eq!(tracker.active(), 1);
+    }
+}
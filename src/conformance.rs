@@ -0,0 +1,270 @@
+//! Conformance test suite for cache implementations.
+//!
+//! This module provides the [`cache_conformance_tests!`](crate::cache_conformance_tests)
+//! macro, which generates a set of tests verifying that a `Cache<T>` implementation
+//! follows the contract documented on the [`Cache`](crate::Cache) trait.
+
+/// Generate a conformance test suite for a `Cache<T>` implementation.
+///
+/// The generated module contains `#[tokio::test]` functions that verify
+/// `get`, `get_many`, `get_metadata`, `set`, `set_many`, `set_if_version`, `touch`, `remove`, `remove_prefix`, `clear`, `len`, `is_empty`, `len_valid` and `contains_key`
+/// behave consistently with each other.
+///
+/// # Arguments
+///
+/// * `name` - Name of the generated test module
+/// * `cache` - Expression creating a new, empty and isolated cache. It is evaluated
+///   inside an async test function, so `.await` may be used.
+/// * `value` - Expression creating a value to store. `T` must implement
+///   `PartialEq` and `Debug`.
+///
+/// Attributes placed before the module name (e.g. `#[ignore]`) are applied to
+/// every generated test. The calling crate needs `tokio` with the `macros` and
+/// `rt` features enabled.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use cachified::{cache_conformance_tests, MokaCache};
+///
+/// cache_conformance_tests!(moka_conformance, MokaCache::<String>::new(100), "value".to_string());
+/// ```
+#[macro_export]
+macro_rules! cache_conformance_tests {
+    ($(#[$attr:meta])* $name:ident, $cache:expr, $value:expr $(,)?) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+            use $crate::{Cache, CacheEntry};
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn get_missing_key_returns_none() {
+                let cache = $cache;
+
+                assert!(cache.get("conformance:missing").await.is_none());
+                assert!(!cache.contains_key("conformance:missing").await);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn set_then_get_returns_entry() {
+                let cache = $cache;
+                let value = $value;
+
+                cache
+                    .set("conformance:a", CacheEntry::new(value.clone(), None))
+                    .await
+                    .unwrap();
+
+                let entry = cache.get("conformance:a").await.expect("entry should exist");
+                assert_eq!(entry.value, value);
+                assert!(cache.contains_key("conformance:a").await);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn get_many_aligns_with_keys() {
+                let cache = $cache;
+                let value = $value;
+
+                cache
+                    .set("conformance:a", CacheEntry::new(value.clone(), None))
+                    .await
+                    .unwrap();
+
+                let entries = cache
+                    .get_many(&["conformance:missing", "conformance:a"])
+                    .await;
+                assert_eq!(entries.len(), 2);
+                assert!(entries[0].is_none());
+                assert_eq!(entries[1].as_ref().expect("entry should exist").value, value);
+                assert!(cache.get_many(&[]).await.is_empty());
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn set_overwrites_existing_entry() {
+                let cache = $cache;
+                let value = $value;
+                let ttl = std::time::Duration::from_secs(60);
+
+                cache
+                    .set("conformance:a", CacheEntry::new(value.clone(), None))
+                    .await
+                    .unwrap();
+                cache
+                    .set("conformance:a", CacheEntry::new(value.clone(), Some(ttl)))
+                    .await
+                    .unwrap();
+
+                let entry = cache.get("conformance:a").await.expect("entry should exist");
+                assert_eq!(entry.metadata.ttl, Some(ttl));
+                assert_eq!(cache.len().await, 1);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn set_many_writes_all_entries() {
+                let cache = $cache;
+                let value = $value;
+
+                let results = cache
+                    .set_many(vec![
+                        ("conformance:a".to_string(), CacheEntry::new(value.clone(), None)),
+                        ("conformance:b".to_string(), CacheEntry::new(value.clone(), None)),
+                    ])
+                    .await;
+
+                assert_eq!(results.len(), 2);
+                assert!(results.iter().all(|result| result.is_ok()));
+                assert_eq!(cache.get("conformance:b").await.expect("entry should exist").value, value);
+                assert_eq!(cache.len().await, 2);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn set_if_version_compares_versions() {
+                let cache = $cache;
+                let entry = |version| {
+                    CacheEntry::with_metadata($value, $crate::CacheMetadata::new(None).with_version(version))
+                };
+
+                // `None` expects the key to be missing
+                assert!(cache.set_if_version("conformance:a", entry(0), None).await.unwrap());
+                assert!(!cache.set_if_version("conformance:a", entry(1), None).await.unwrap());
+
+                assert!(!cache.set_if_version("conformance:a", entry(1), Some(5)).await.unwrap());
+                assert!(cache.set_if_version("conformance:a", entry(1), Some(0)).await.unwrap());
+
+                let stored = cache.get("conformance:a").await.expect("entry should exist");
+                assert_eq!(stored.metadata.version, 1);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn get_metadata_and_touch() {
+                use $crate::clock::{Clock, SystemClock};
+
+                let cache = $cache;
+                let ttl = ::std::time::Duration::from_secs(60);
+
+                assert!(cache.get_metadata("conformance:missing").await.is_none());
+                assert!(!cache.touch("conformance:missing", ttl, SystemClock.now()).await.unwrap());
+
+                let entry = CacheEntry::new($value, Some(ttl));
+                cache.set("conformance:a", entry.clone()).await.unwrap();
+                let metadata = cache.get_metadata("conformance:a").await.expect("entry should exist");
+                assert_eq!(metadata.ttl, Some(ttl));
+
+                // The entry expires an hour from now, without changing its age
+                let extended = ::std::time::Duration::from_secs(3600);
+                let now = metadata.created_time + ::std::time::Duration::from_secs(30);
+                assert!(cache.touch("conformance:a", extended, now).await.unwrap());
+                let touched = cache.get("conformance:a").await.expect("entry should exist");
+                assert_eq!(touched.value, entry.value);
+                assert_eq!(touched.metadata.created_time, metadata.created_time);
+                assert_eq!(touched.metadata.ttl, Some(::std::time::Duration::from_secs(30) + extended));
+                assert_eq!(touched.metadata.version, metadata.version + 1);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn remove_deletes_entry() {
+                let cache = $cache;
+
+                cache
+                    .set("conformance:a", CacheEntry::new($value, None))
+                    .await
+                    .unwrap();
+                cache.remove("conformance:a").await;
+
+                assert!(cache.get("conformance:a").await.is_none());
+                assert!(!cache.contains_key("conformance:a").await);
+                assert!(cache.is_empty().await);
+
+                // Removing a missing key is a no-op
+                cache.remove("conformance:a").await;
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn remove_prefix_removes_only_matching_keys() {
+                let cache = $cache;
+
+                for key in ["conformance:user:1:profile", "conformance:user:1:posts", "conformance:user:12", "conformance:team:1"] {
+                    cache.set(key, CacheEntry::new($value, None)).await.unwrap();
+                }
+
+                assert_eq!(cache.remove_prefix("conformance:user:1:").await.unwrap(), 2);
+                assert!(cache.get("conformance:user:1:profile").await.is_none());
+                assert!(cache.get("conformance:user:1:posts").await.is_none());
+                assert!(cache.get("conformance:user:12").await.is_some());
+                assert!(cache.get("conformance:team:1").await.is_some());
+                assert_eq!(cache.len().await, 2);
+
+                assert_eq!(cache.remove_prefix("conformance:missing:").await.unwrap(), 0);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn clear_removes_all_entries() {
+                let cache = $cache;
+
+                for key in ["conformance:a", "conformance:b", "conformance:c"] {
+                    cache.set(key, CacheEntry::new($value, None)).await.unwrap();
+                }
+                cache.clear().await;
+
+                for key in ["conformance:a", "conformance:b", "conformance:c"] {
+                    assert!(cache.get(key).await.is_none());
+                }
+                assert_eq!(cache.len().await, 0);
+                assert!(cache.is_empty().await);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn len_and_is_empty_reflect_awaited_writes() {
+                let cache = $cache;
+
+                assert_eq!(cache.len().await, 0);
+                assert!(cache.is_empty().await);
+
+                for key in ["conformance:a", "conformance:b", "conformance:c"] {
+                    cache.set(key, CacheEntry::new($value, None)).await.unwrap();
+                }
+
+                assert_eq!(cache.len().await, 3);
+                assert!(!cache.is_empty().await);
+
+                cache.remove("conformance:b").await;
+                assert_eq!(cache.len().await, 2);
+            }
+
+            #[::tokio::test]
+            $(#[$attr])*
+            async fn len_valid_excludes_expired_entries() {
+                use $crate::clock::{Clock, SystemClock};
+                use ::std::time::Duration;
+
+                let cache = $cache;
+                let now = SystemClock.now();
+
+                cache
+                    .set("conformance:valid", CacheEntry::new($value, Some(Duration::from_secs(1000))))
+                    .await
+                    .unwrap();
+                let expired = CacheEntry::builder($value)
+                    .ttl(Duration::from_secs(60))
+                    .created_ago(Duration::from_secs(120))
+                    .build();
+                cache.set("conformance:expired", expired).await.unwrap();
+
+                assert_eq!(cache.len_valid(now).await, 1);
+                assert!(!cache.is_empty_valid(now).await);
+                assert!(cache.is_empty_valid(now + Duration::from_secs(2000)).await);
+            }
+        }
+    };
+}
@@ -0,0 +1,266 @@
+//! Shared defaults for cachified calls.
+//!
+//! Calls across an application often repeat the same cache, TTL and reporter.
+//! [`CachifiedDefaults`] holds them once and hands out pre-filled
+//! [`CachifiedOptionsBuilder`]s, whose methods still override any default.
+//! Calls that only vary in their key and fresh value function use
+//! [`CachifiedDefaults::get`], e.g. in repository types of web applications.
+
+use crate::clock::Clock;
+use crate::reporter::Reporter;
+use crate::{Cache, CacheKey, CachifiedConfig, CachifiedOptionsBuilder, Result};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings shared by many cachified calls
+///
+/// Create one per cache, e.g. at startup, and start every call with
+/// [`CachifiedDefaults::key`]. Clones share the reporter, clock and config.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::{cachified, CachifiedDefaults, HashMapCache};
+/// use std::time::Duration;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let defaults = CachifiedDefaults::new(HashMapCache::new())
+///     .ttl(Duration::from_secs(60))
+///     .stale_while_revalidate(Duration::from_secs(300));
+///
+/// let user: String = cachified(
+///     defaults
+///         .key("user:1")
+///         .get_fresh_value(|| async { Ok("Alice".to_string()) })
+/// ).await?;
+///
+/// // Defaults are overridden like any other builder option
+/// let config: String = cachified(
+///     defaults
+///         .key("config")
+///         .ttl(Duration::from_secs(3600))
+///         .get_fresh_value(|| async { Ok("{}".to_string()) })
+/// ).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CachifiedDefaults<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    cache: C,
+    ttl: Option<Duration>,
+    stale_while_revalidate: Option<Duration>,
+    fallback_to_cache: bool,
+    reporter: Option<Arc<dyn Reporter>>,
+    clock: Option<Arc<dyn Clock>>,
+    config: Option<CachifiedConfig>,
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, C> CachifiedDefaults<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    /// Create defaults for calls using `cache`, with every other option unset
+    pub fn new(cache: C) -> Self {
+        Self {
+            cache,
+            ttl: None,
+            stale_while_revalidate: None,
+            fallback_to_cache: false,
+            reporter: None,
+            clock: None,
+            config: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Set the default time-to-live
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the default stale-while-revalidate duration
+    pub fn stale_while_revalidate(mut self, duration: Duration) -> Self {
+        self.stale_while_revalidate = Some(duration);
+        self
+    }
+
+    /// Set whether calls fall back to cached values when fetching fails by default
+    pub fn fallback_to_cache(mut self, fallback: bool) -> Self {
+        self.fallback_to_cache = fallback;
+        self
+    }
+
+    /// Set the reporter notified about events of all calls
+    pub fn reporter<R>(mut self, reporter: R) -> Self
+    where
+        R: Reporter + 'static,
+    {
+        self.reporter = Some(Arc::new(reporter));
+        self
+    }
+
+    /// Set the clock all calls read the current time from
+    pub fn clock<K>(mut self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Set the configuration shared by all calls
+    pub fn config(mut self, config: CachifiedConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Get the cache all calls use
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Start a call for `key`, with the defaults already applied
+    pub fn key(&self, key: impl Into<CacheKey>) -> CachifiedOptionsBuilder<T, C> {
+        let mut builder =
+            CachifiedOptionsBuilder::new(self.cache.clone(), key).fallback_to_cache(self.fallback_to_cache);
+        if let Some(ttl) = self.ttl {
+            builder = builder.ttl(ttl);
+        }
+        if let Some(duration) = self.stale_while_revalidate {
+            builder = builder.stale_while_revalidate(duration);
+        }
+        if let Some(reporter) = &self.reporter {
+            builder = builder.reporter(reporter.clone());
+        }
+        if let Some(clock) = &self.clock {
+            builder = builder.clock(clock.clone());
+        }
+        if let Some(config) = &self.config {
+            builder = builder.config(config.clone());
+        }
+        builder
+    }
+
+    /// Get the value for `key` with the defaults, fetching it with `get_fresh_value` if needed
+    ///
+    /// This is a shortcut for calling `cachified` with
+    /// [`key`](Self::key) and `get_fresh_value`, for calls that don't
+    /// override any default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::{CachifiedDefaults, HashMapCache, Result};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone)]
+    /// struct UserRepository {
+    ///     cache: CachifiedDefaults<String, HashMapCache<String>>,
+    /// }
+    ///
+    /// impl UserRepository {
+    ///     async fn name(&self, id: u64) -> Result<String> {
+    ///         self.cache
+    ///             .get(format!("user:{id}"), move || async move { Ok(format!("User {id}")) })
+    ///             .await
+    ///     }
+    /// }
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let users = UserRepository {
+    ///     cache: CachifiedDefaults::new(HashMapCache::new()).ttl(Duration::from_secs(60)),
+    /// };
+    /// assert_eq!(users.name(1).await?, "User 1");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get<F, Fut>(&self, key: impl Into<CacheKey>, get_fresh_value: F) -> Result<T>
+    where
+        C: 'static,
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        crate::cachified(self.key(key).get_fresh_value(get_fresh_value)).await
+    }
+}
+
+impl<T, C> Clone for CachifiedDefaults<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            ttl: self.ttl,
+            stale_while_revalidate: self.stale_while_revalidate,
+            fallback_to_cache: self.fallback_to_cache,
+            reporter: self.reporter.clone(),
+            clock: self.clock.clone(),
+            config: self.config.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMapCache;
+
+    #[test]
+    fn test_defaults_apply_unless_overridden() {
+        let defaults: CachifiedDefaults<String, _> = CachifiedDefaults::new(HashMapCache::new())
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(30))
+            .fallback_to_cache(true);
+
+        let options = defaults
+            .key("default")
+            .get_fresh_value(|| async { Ok("value".to_string()) });
+        assert_eq!(options.key, "default");
+        assert_eq!(options.ttl, Some(Duration::from_secs(60)));
+        assert_eq!(options.stale_while_revalidate, Some(Duration::from_secs(30)));
+        assert!(options.fallback_to_cache);
+
+        let options = defaults
+            .key("overridden")
+            .ttl(Duration::from_secs(3600))
+            .fallback_to_cache(false)
+            .get_fresh_value(|| async { Ok("value".to_string()) });
+        assert_eq!(options.ttl, Some(Duration::from_secs(3600)));
+        assert_eq!(options.stale_while_revalidate, Some(Duration::from_secs(30)));
+        assert!(!options.fallback_to_cache);
+    }
+
+    #[tokio::test]
+    async fn test_defaults_share_cache() {
+        let defaults = CachifiedDefaults::new(HashMapCache::new()).ttl(Duration::from_secs(60));
+
+        let value: String = crate::cachified(
+            defaults
+                .key("shared")
+                .get_fresh_value(|| async { Ok("first".to_string()) }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(value, "first");
+
+        let entry = defaults.cache().get("shared").await.unwrap();
+        assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(60)));
+
+        // Only the key and the fresh value function vary between calls
+        let value = defaults
+            .get("shared", || async { Ok("second".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(value, "first");
+    }
+}
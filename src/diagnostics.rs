@@ -0,0 +1,117 @@
+//! Diagnostics for inspecting the contents of a cache.
+//!
+//! These helpers walk every entry of a cache and are meant for capacity
+//! planning and debugging, not for hot paths. Requires the "diagnostics"
+//! feature to be enabled.
+
+use crate::{current_time, Cache, Result};
+use std::time::Duration;
+
+/// Expiry information about a single cache entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryReport {
+    /// The cache key
+    pub key: String,
+    /// Time since the entry was created
+    pub age: Duration,
+    /// Time left until the entry expires, `None` if it never expires
+    ///
+    /// Entries that have already expired but are still stored report `Duration::ZERO`.
+    pub remaining: Option<Duration>,
+}
+
+/// Report the age and remaining TTL of every entry in a cache
+///
+/// This lists all keys with [`Cache::keys`] and reads each entry's metadata,
+/// so it is an O(n) operation. Keys removed while the report is built are
+/// skipped. Returns an error if the cache can't list its keys.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{diagnostics::cache_report, Cache, CacheEntry, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+/// cache.set("user-1", CacheEntry::new("value".to_string(), Some(Duration::from_secs(60)))).await?;
+///
+/// for entry in cache_report(&cache).await? {
+///     println!("{}: age {:?}, remaining {:?}", entry.key, entry.age, entry.remaining);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cache_report<T, C>(cache: &C) -> Result<Vec<EntryReport>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let now = current_time();
+    let mut report = Vec::new();
+
+    for key in cache.keys().await? {
+        if let Some(entry) = cache.get(&key).await {
+            report.push(EntryReport {
+                age: entry.metadata.age(now),
+                remaining: entry.metadata.remaining_ttl(now),
+                key,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::{CacheEntry, CacheMetadata, MokaCache};
+
+    #[tokio::test]
+    async fn test_cache_report() {
+        let cache: MokaCache<String> = MokaCache::new(100);
+        let now = current_time();
+
+        let created = now - Duration::from_secs(10);
+        cache
+            .set(
+                "live",
+                CacheEntry::with_metadata(
+                    "value".to_string(),
+                    CacheMetadata::with_time(created, Some(Duration::from_secs(60))),
+                ),
+            )
+            .await
+            .unwrap();
+        cache
+            .set(
+                "expired",
+                CacheEntry::with_metadata(
+                    "value".to_string(),
+                    CacheMetadata::with_time(created, Some(Duration::from_secs(5))),
+                ),
+            )
+            .await
+            .unwrap();
+        cache
+            .set("forever", CacheEntry::new("value".to_string(), None))
+            .await
+            .unwrap();
+
+        let mut report = cache_report(&cache).await.unwrap();
+        report.sort_by(|a, b| a.key.cmp(&b.key));
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].key, "expired");
+        assert_eq!(report[0].remaining, Some(Duration::ZERO));
+        assert_eq!(report[1].key, "forever");
+        assert_eq!(report[1].remaining, None);
+        assert_eq!(report[2].key, "live");
+        assert!(report[2].age >= Duration::from_secs(10));
+        let remaining = report[2].remaining.unwrap();
+        assert!(remaining <= Duration::from_secs(50) && remaining > Duration::from_secs(45));
+    }
+}
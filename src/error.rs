@@ -1,19 +1,54 @@
 //! Error types for cachified operations.
 
+use std::sync::Arc;
 use thiserror::Error;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "serde")]
 use serde_json;
 
 /// Result type alias for cachified operations.
 pub type Result<T> = std::result::Result<T, CachifiedError>;
 
+/// Underlying error wrapped by a [`CachifiedError`]
+///
+/// Shared so errors stay cloneable, e.g. to hand the same error to every
+/// caller waiting on a single-flight fetch.
+pub type ErrorSource = Arc<dyn std::error::Error + Send + Sync>;
+
 /// Errors that can occur during cachified operations.
-#[derive(Error, Debug)]
+///
+/// New variants may be added in future releases. To handle errors by category
+/// without matching every variant, use [`CachifiedError::kind`].
+///
+/// With the "serde" feature enabled, errors can be serialized, e.g. to send
+/// them across process boundaries.
+///
+/// Errors of the built-in cache backends, e.g. Redis errors, are kept as the
+/// source of a [`CachifiedError::CacheSource`], so they can be inspected with
+/// [`CachifiedError::downcast_source_ref`]. Other `From` conversions, e.g. of
+/// serde_json errors, only keep the message of the converted error. To keep
+/// it as the source instead, convert it explicitly with
+/// [`CachifiedError::cache_source`] or [`CachifiedError::fresh_value_source`].
+#[derive(Error, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub enum CachifiedError {
     /// Error when getting fresh value fails
     #[error("Failed to get fresh value: {0}")]
     FreshValueError(String),
+
+    /// Error when getting fresh value fails, keeping the underlying error as its source
+    ///
+    /// With the "serde" feature enabled, the source is serialized as its
+    /// message and only its message survives a round trip.
+    #[error("Failed to get fresh value: {0}")]
+    FreshValueSource(
+        #[source]
+        #[cfg_attr(feature = "serde", serde(with = "source_as_message"))]
+        ErrorSource,
+    ),
     
     /// Error when cache validation fails
     #[error("Cache validation failed: {0}")]
@@ -22,17 +57,209 @@ pub enum CachifiedError {
     /// Error when cache operations fail
     #[error("Cache operation failed: {0}")]
     CacheError(String),
+
+    /// Error when cache operations fail, keeping the underlying error as its source
+    #[error("Cache operation failed: {0}")]
+    CacheSource(
+        #[source]
+        #[cfg_attr(feature = "serde", serde(with = "source_as_message"))]
+        ErrorSource,
+    ),
     
+    /// Error when the call was cancelled or its deadline passed
+    #[error("Cachified call cancelled: {0}")]
+    Cancelled(String),
+
+    /// Error when getting a fresh value took longer than the configured timeout
+    #[error("Getting fresh value timed out: {0}")]
+    Timeout(String),
+
     /// Generic error for other failures
     #[error("Cachified error: {0}")]
     Other(String),
+
+    /// Error annotated with the stage of the cachified call in which it occurred
+    ///
+    /// Errors returned by `cachified` only carry their stage if it was asked
+    /// for with `CachifiedOptionsBuilder::error_stage`. Use
+    /// [`CachifiedError::stage`] to get it and [`CachifiedError::into_inner`]
+    /// to get the underlying error.
+    #[error("{stage} failed: {source}")]
+    Stage {
+        /// Stage in which the error occurred
+        stage: Stage,
+        /// The underlying error
+        source: Box<CachifiedError>,
+    },
+}
+
+/// Stage of a cachified call in which an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum Stage {
+    /// Resolving the key with the function set by `key_fn`
+    ResolveKey,
+    /// Reading the entry from the cache
+    ReadCache,
+    /// Validating the cached value
+    ValidateCachedValue,
+    /// Getting the fresh value
+    GetFreshValue,
+    /// Validating the fresh value
+    ValidateFreshValue,
+    /// Writing the entry to the cache, only reported by `cachified_set`
+    WriteCache,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            Stage::ResolveKey => "Resolving the cache key",
+            Stage::ReadCache => "Reading from the cache",
+            Stage::ValidateCachedValue => "Validating the cached value",
+            Stage::GetFreshValue => "Getting the fresh value",
+            Stage::ValidateFreshValue => "Validating the fresh value",
+            Stage::WriteCache => "Writing to the cache",
+        };
+        f.write_str(stage)
+    }
+}
+
+/// Coarse classification of [`CachifiedError`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Getting a fresh value failed
+    FreshValue,
+    /// A value failed validation
+    Validation,
+    /// A cache operation failed
+    Cache,
+    /// The call was cancelled or its deadline passed
+    Cancelled,
+    /// Getting a fresh value timed out
+    Timeout,
+    /// Any other failure
+    Other,
 }
 
 impl CachifiedError {
+    /// Get the kind of this error
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::{CachifiedError, ErrorKind};
+    ///
+    /// let error = CachifiedError::fresh_value("upstream unavailable");
+    /// match error.kind() {
+    ///     ErrorKind::FreshValue => println!("upstream failed"),
+    ///     _ => println!("something else failed"),
+    /// }
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CachifiedError::FreshValueError(_) | CachifiedError::FreshValueSource(_) => ErrorKind::FreshValue,
+            CachifiedError::ValidationError(_) => ErrorKind::Validation,
+            CachifiedError::CacheError(_) | CachifiedError::CacheSource(_) => ErrorKind::Cache,
+            CachifiedError::Cancelled(_) => ErrorKind::Cancelled,
+            CachifiedError::Timeout(_) => ErrorKind::Timeout,
+            CachifiedError::Other(_) => ErrorKind::Other,
+            CachifiedError::Stage { source, .. } => source.kind(),
+        }
+    }
+
+    /// Get the stage of the cachified call in which this error occurred, if known
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::{CachifiedError, Stage};
+    ///
+    /// let error = CachifiedError::fresh_value("upstream unavailable").with_stage(Stage::GetFreshValue);
+    /// assert_eq!(error.stage(), Some(Stage::GetFreshValue));
+    /// ```
+    pub fn stage(&self) -> Option<Stage> {
+        match self {
+            CachifiedError::Stage { stage, .. } => Some(*stage),
+            _ => None,
+        }
+    }
+
+    /// Annotate this error with the stage in which it occurred
+    ///
+    /// Errors that already carry a stage keep it, since the innermost stage
+    /// is the most precise.
+    pub fn with_stage(self, stage: Stage) -> Self {
+        match self {
+            CachifiedError::Stage { .. } => self,
+            source => CachifiedError::Stage {
+                stage,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Get the underlying error without its stage
+    pub fn inner(&self) -> &CachifiedError {
+        match self {
+            CachifiedError::Stage { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    /// Convert into the underlying error without its stage
+    pub fn into_inner(self) -> CachifiedError {
+        match self {
+            CachifiedError::Stage { source, .. } => source.into_inner(),
+            error => error,
+        }
+    }
+
+    /// Get the first error of the given type in the source chain of this error
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::{CachifiedError, Stage};
+    ///
+    /// let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such user");
+    /// let error = CachifiedError::fresh_value_source(io_error).with_stage(Stage::GetFreshValue);
+    ///
+    /// let io_error = error.downcast_source_ref::<std::io::Error>().unwrap();
+    /// assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+    /// ```
+    pub fn downcast_source_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        // Start at the wrapped error itself, as the `Arc` around it can't be downcast
+        let mut source: Option<&(dyn std::error::Error + 'static)> = match self.inner() {
+            CachifiedError::FreshValueSource(source) | CachifiedError::CacheSource(source) => Some(&**source),
+            _ => None,
+        };
+        while let Some(error) = source {
+            if let Some(error) = error.downcast_ref::<E>() {
+                return Some(error);
+            }
+            source = error.source();
+        }
+        None
+    }
+
     /// Create a new fresh value error
     pub fn fresh_value<S: Into<String>>(msg: S) -> Self {
         CachifiedError::FreshValueError(msg.into())
     }
+
+    /// Create a new fresh value error keeping the underlying error as its source
+    ///
+    /// Use this in `get_fresh_value` to keep the error chain, e.g. with
+    /// `.map_err(CachifiedError::fresh_value_source)?`.
+    pub fn fresh_value_source<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        CachifiedError::FreshValueSource(Arc::from(source.into()))
+    }
     
     /// Create a new validation error
     pub fn validation<S: Into<String>>(msg: S) -> Self {
@@ -44,6 +271,24 @@ impl CachifiedError {
         CachifiedError::CacheError(msg.into())
     }
     
+    /// Create a new cache error keeping the underlying error as its source
+    pub fn cache_source<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        CachifiedError::CacheSource(Arc::from(source.into()))
+    }
+
+    /// Create a new cancellation error
+    pub fn cancelled<S: Into<String>>(msg: S) -> Self {
+        CachifiedError::Cancelled(msg.into())
+    }
+    
+    /// Create a new timeout error
+    pub fn timeout<S: Into<String>>(msg: S) -> Self {
+        CachifiedError::Timeout(msg.into())
+    }
+    
     /// Create a new generic error
     pub fn other<S: Into<String>>(msg: S) -> Self {
         CachifiedError::Other(msg.into())
@@ -71,7 +316,7 @@ impl From<&str> for CachifiedError {
 #[cfg(feature = "redis")]
 impl From<redis::RedisError> for CachifiedError {
     fn from(err: redis::RedisError) -> Self {
-        CachifiedError::CacheError(format!("Redis error: {}", err))
+        CachifiedError::cache_source(err)
     }
 }
 
@@ -81,3 +326,87 @@ impl From<serde_json::Error> for CachifiedError {
         CachifiedError::Other(format!("Serialization error: {}", err))
     }
 }
+
+/// Serialize error sources as their message, since arbitrary errors can't be serialized
+#[cfg(feature = "serde")]
+mod source_as_message {
+    use super::ErrorSource;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::sync::Arc;
+
+    /// Error restored from the message of a serialized source
+    #[derive(Debug, thiserror::Error)]
+    #[error("{0}")]
+    struct Message(String);
+
+    pub(super) fn serialize<S: Serializer>(source: &ErrorSource, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(source)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ErrorSource, D::Error> {
+        Ok(Arc::new(Message(String::deserialize(deserializer)?)))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_serde_round_trip() {
+        let error = CachifiedError::fresh_value("upstream unavailable");
+
+        let json = serde_json::to_string(&error).unwrap();
+        let decoded: CachifiedError = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.kind(), ErrorKind::FreshValue);
+        assert_eq!(decoded.to_string(), error.to_string());
+    }
+
+    #[test]
+    fn test_error_stage() {
+        let error = CachifiedError::cache("connection refused")
+            .with_stage(Stage::ReadCache)
+            .with_stage(Stage::GetFreshValue);
+
+        assert_eq!(error.stage(), Some(Stage::ReadCache));
+        assert_eq!(error.kind(), ErrorKind::Cache);
+        assert_eq!(error.to_string(), "Reading from the cache failed: Cache operation failed: connection refused");
+        assert!(matches!(error.inner(), CachifiedError::CacheError(_)));
+        assert_eq!(CachifiedError::other("plain").stage(), None);
+
+        let json = serde_json::to_string(&error).unwrap();
+        let decoded: CachifiedError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.stage(), Some(Stage::ReadCache));
+        assert!(matches!(decoded.into_inner(), CachifiedError::CacheError(_)));
+    }
+
+    #[test]
+    fn test_error_source() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "connection refused");
+        let error = CachifiedError::cache_source(io_error).with_stage(Stage::ReadCache);
+
+        assert_eq!(error.kind(), ErrorKind::Cache);
+        assert_eq!(error.to_string(), "Reading from the cache failed: Cache operation failed: connection refused");
+        let source = error.downcast_source_ref::<std::io::Error>().unwrap();
+        assert_eq!(source.kind(), std::io::ErrorKind::ConnectionRefused);
+        assert!(CachifiedError::cache("connection refused").downcast_source_ref::<std::io::Error>().is_none());
+
+        // Only the message of the source survives serialization
+        let json = serde_json::to_string(&error).unwrap();
+        let decoded: CachifiedError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_string(), error.to_string());
+        assert!(decoded.downcast_source_ref::<std::io::Error>().is_none());
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_redis_error_is_kept_as_source() {
+        let error: CachifiedError = redis::RedisError::from((redis::ErrorKind::ResponseError, "busy")).into();
+
+        assert_eq!(error.kind(), ErrorKind::Cache);
+        let source = error.downcast_source_ref::<redis::RedisError>().unwrap();
+        assert_eq!(source.kind(), redis::ErrorKind::ResponseError);
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
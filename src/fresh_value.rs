@@ -0,0 +1,200 @@
+//! Fresh value sources for the cachified function.
+//!
+//! The `cachified` function accepts anything implementing [`GetFreshValue`].
+//! Plain closures returning `Result<T>` implement it directly, while wrappers
+//! such as [`OutcomeFn`] adapt closures with richer return types.
+
+use crate::{CachifiedError, Result};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Boxed future returned by [`GetFreshValue::call`].
+pub type FreshValueFuture<T> = Pin<Box<dyn Future<Output = Result<FreshValueOutcome<T>>> + Send>>;
+
+/// The outcome of fetching a fresh value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FreshValueOutcome<T> {
+    /// A new value that replaces the cached one
+    Value(T),
+    /// A new value with its own TTL, overriding the configured one if `Some`
+    ///
+    /// A TTL of zero means the value isn't cached.
+    ValueWithTtl(T, Option<Duration>),
+    /// The upstream value did not change; keep the cached value and refresh its TTL
+    Unchanged,
+}
+
+impl<T> FreshValueOutcome<T> {
+    /// Get the new value and its own TTL, or `None` if the value is unchanged
+    pub(crate) fn into_value(self) -> Option<(T, Option<Duration>)> {
+        match self {
+            FreshValueOutcome::Value(value) => Some((value, None)),
+            FreshValueOutcome::ValueWithTtl(value, ttl) => Some((value, ttl)),
+            FreshValueOutcome::Unchanged => None,
+        }
+    }
+}
+
+/// Why a fresh value is fetched.
+///
+/// Passed to closures given to `CachifiedOptionsBuilder::get_fresh_value_with_reason`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchReason {
+    /// Nothing was cached for the key
+    ColdMiss,
+    /// The cached value expired and can't be served stale
+    Expired,
+    /// The cached value failed validation or migration
+    ValidationFailed,
+    /// Fetching a fresh value was forced with `force_fresh` or `force_fresh_keys`
+    Forced,
+    /// The cached value is served while it is refreshed in the background
+    ///
+    /// This covers refreshes within the stale-while-revalidate window as well
+    /// as early refreshes and `always_revalidate`.
+    StaleRevalidation,
+}
+
+/// Trait for sources of fresh values.
+///
+/// This is implemented for closures returning `Result<T>` and for the wrapper
+/// types produced by the `get_fresh_value_*` builder methods.
+pub trait GetFreshValue<T>: Send + Sync {
+    /// Start fetching a fresh value.
+    fn call(&self) -> FreshValueFuture<T>;
+
+    /// Start fetching a fresh value for the given reason.
+    ///
+    /// `cachified` always calls this method. The default implementation
+    /// ignores the reason and delegates to [`GetFreshValue::call`].
+    fn call_with_reason(&self, reason: FetchReason) -> FreshValueFuture<T> {
+        let _ = reason;
+        self.call()
+    }
+}
+
+impl<T, F, Fut> GetFreshValue<T> for F
+where
+    T: Send + 'static,
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<T> {
+        let future = self();
+        Box::pin(async move { future.await.map(FreshValueOutcome::Value) })
+    }
+}
+
+/// Adapter for closures returning a [`FreshValueOutcome`].
+///
+/// Created by `CachifiedOptionsBuilder::get_fresh_value_outcome`.
+pub struct OutcomeFn<F>(pub F);
+
+impl<T, F, Fut> GetFreshValue<T> for OutcomeFn<F>
+where
+    T: Send + 'static,
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<FreshValueOutcome<T>>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<T> {
+        Box::pin((self.0)())
+    }
+}
+
+/// Adapter for closures receiving the reason a fresh value is fetched.
+///
+/// Created by `CachifiedOptionsBuilder::get_fresh_value_with_reason`. When
+/// called without a reason, the closure receives [`FetchReason::ColdMiss`].
+pub struct ReasonFn<F>(pub F);
+
+impl<T, F, Fut> GetFreshValue<T> for ReasonFn<F>
+where
+    T: Send + 'static,
+    F: Fn(FetchReason) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<T> {
+        self.call_with_reason(FetchReason::ColdMiss)
+    }
+
+    fn call_with_reason(&self, reason: FetchReason) -> FreshValueFuture<T> {
+        let future = (self.0)(reason);
+        Box::pin(async move { future.await.map(FreshValueOutcome::Value) })
+    }
+}
+
+/// Adapter for closures returning a value along with its TTL.
+///
+/// Created by `CachifiedOptionsBuilder::get_fresh_value_with_ttl`.
+pub struct TtlFn<F>(pub F);
+
+impl<T, F, Fut> GetFreshValue<T> for TtlFn<F>
+where
+    T: Send + 'static,
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(T, Option<Duration>)>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<T> {
+        let future = (self.0)();
+        Box::pin(async move { future.await.map(|(value, ttl)| FreshValueOutcome::ValueWithTtl(value, ttl)) })
+    }
+}
+
+/// Adapter for closures returning values that are shared through an `Arc`.
+///
+/// Created by `CachifiedOptionsBuilder::get_fresh_value_arc`. The returned value
+/// is converted into an `Arc<U>`, which makes it possible to cache values that
+/// aren't `Clone`, including trait objects returned as `Box<dyn Trait>`.
+pub struct ArcFn<F>(pub F);
+
+impl<U, V, F, Fut> GetFreshValue<Arc<U>> for ArcFn<F>
+where
+    U: ?Sized + Send + Sync + 'static,
+    V: Into<Arc<U>>,
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<V>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<Arc<U>> {
+        let future = (self.0)();
+        Box::pin(async move { future.await.map(|value| FreshValueOutcome::Value(value.into())) })
+    }
+}
+
+/// Adapter for closures that can only be called once.
+///
+/// Created by `CachifiedOptionsBuilder::get_fresh_value_once`. The closure is
+/// consumed by the first call, so later calls fail with an error instead of
+/// fetching a value.
+pub struct OnceFn<F>(Mutex<Option<F>>);
+
+impl<F> OnceFn<F> {
+    /// Wrap a closure to be called at most once
+    pub fn new(get_fresh_value: F) -> Self {
+        Self(Mutex::new(Some(get_fresh_value)))
+    }
+}
+
+impl<T, F, Fut> GetFreshValue<T> for OnceFn<F>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Fut + Send,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    fn call(&self) -> FreshValueFuture<T> {
+        // Taking the closure out can't leave it inconsistent, so a panic elsewhere doesn't matter
+        let get_fresh_value = self.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+        match get_fresh_value {
+            Some(get_fresh_value) => {
+                let future = get_fresh_value();
+                Box::pin(async move { future.await.map(FreshValueOutcome::Value) })
+            }
+            None => Box::pin(async {
+                Err(CachifiedError::other(
+                    "fresh value function given to get_fresh_value_once was already called",
+                ))
+            }),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Background cleanup of expired cache entries.
+//!
+//! Backends that don't expire entries on their own keep expired entries around
+//! until they are overwritten. A janitor periodically removes them, see
+//! [`spawn_janitor`] and [`Janitor`].
+
+use crate::cache::remove_expired;
+use crate::reporter::NoopReporter;
+use crate::{current_time, Cache, Reporter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Default number of keys checked per batch
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Default pause between batches of a single run
+const DEFAULT_BATCH_PAUSE: Duration = Duration::from_millis(10);
+
+/// Clock returning the current time as duration since UNIX_EPOCH
+type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// Spawn a janitor that removes expired entries from `cache` every `interval`
+///
+/// This is a shortcut for `Janitor::new(cache).interval(interval).spawn()`.
+/// Must be called from within a Tokio runtime.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{janitor::spawn_janitor, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() {
+/// let cache: MokaCache<String> = MokaCache::new(1000);
+/// let janitor = spawn_janitor(cache.clone(), Duration::from_secs(60));
+///
+/// // On shutdown
+/// janitor.abort();
+/// # }
+/// ```
+pub fn spawn_janitor<T, C>(cache: C, interval: Duration) -> JanitorHandle
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    Janitor::new(cache).interval(interval).spawn()
+}
+
+/// Builder for a background task that periodically removes expired entries
+///
+/// Every run lists the keys of the cache and checks them in batches of
+/// [`Janitor::batch_size`] keys, pausing for [`Janitor::batch_pause`] between
+/// batches so that cleaning up a large cache doesn't hog the backend. The
+/// number of removed entries and failures to list keys are sent to the reporter.
+pub struct Janitor<T, C> {
+    cache: C,
+    interval: Duration,
+    batch_size: usize,
+    batch_pause: Duration,
+    clock: Clock,
+    reporter: Arc<dyn Reporter>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, C> Janitor<T, C>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    /// Create a janitor for the given cache that runs every minute
+    pub fn new(cache: C) -> Self {
+        Self {
+            cache,
+            interval: Duration::from_secs(60),
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_pause: DEFAULT_BATCH_PAUSE,
+            clock: Arc::new(current_time),
+            reporter: Arc::new(NoopReporter),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set the time between runs
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the number of keys checked per batch (default: 1000)
+    ///
+    /// A batch size of zero is treated as one.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set the pause between batches of a single run (default: 10ms)
+    pub fn batch_pause(mut self, batch_pause: Duration) -> Self {
+        self.batch_pause = batch_pause;
+        self
+    }
+
+    /// Set the clock used to decide whether entries are expired
+    ///
+    /// The clock returns the current time as duration since UNIX_EPOCH and
+    /// defaults to the system time. This is mainly useful for tests.
+    pub fn clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> Duration + Send + Sync + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Set a reporter that is notified after every run
+    pub fn reporter<R: Reporter + 'static>(mut self, reporter: R) -> Self {
+        self.reporter = Arc::new(reporter);
+        self
+    }
+
+    /// Spawn the janitor onto the current Tokio runtime
+    ///
+    /// The first run happens after one interval.
+    pub fn spawn(self) -> JanitorHandle {
+        JanitorHandle {
+            task: tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(self.interval).await;
+                    self.run().await;
+                }
+            }),
+        }
+    }
+
+    /// Remove all expired entries once
+    async fn run(&self) {
+        let keys = match self.cache.keys().await {
+            Ok(keys) => keys,
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Janitor failed to list cache keys: {}", error);
+                self.reporter.on_janitor_error(&error);
+                return;
+            }
+        };
+
+        let mut removed = 0;
+        for (index, batch) in keys.chunks(self.batch_size).enumerate() {
+            if index > 0 {
+                tokio::time::sleep(self.batch_pause).await;
+            }
+
+            let batch: Vec<&str> = batch.iter().map(String::as_str).collect();
+            removed += remove_expired(&self.cache, &batch, (self.clock)()).await;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Janitor removed {} expired entries", removed);
+        self.reporter.on_expired_cleared(removed);
+    }
+}
+
+/// Handle to a running janitor
+///
+/// Dropping the handle doesn't stop the janitor, call [`JanitorHandle::abort`] instead.
+#[derive(Debug)]
+pub struct JanitorHandle {
+    task: JoinHandle<()>,
+}
+
+impl JanitorHandle {
+    /// Stop the janitor
+    ///
+    /// A run that is in progress is cancelled at its next await point.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Check whether the janitor has stopped
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::{CacheEntry, CacheMetadata, MokaCache};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct RemovedCounter(Arc<AtomicUsize>);
+
+    impl Reporter for RemovedCounter {
+        fn on_expired_cleared(&self, removed: usize) {
+            self.0.fetch_add(removed, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_janitor_removes_expired_entries() {
+        let cache: MokaCache<String> = MokaCache::new(100);
+        let expiring = CacheMetadata::with_time(Duration::from_secs(1000), Some(Duration::from_secs(60)));
+
+        for index in 0..5 {
+            cache
+                .set(&format!("expired-{index}"), CacheEntry::with_metadata("old".to_string(), expiring.clone()))
+                .await
+                .unwrap();
+        }
+        cache.set("forever", CacheEntry::new("value".to_string(), None)).await.unwrap();
+
+        let counter = RemovedCounter::default();
+        let janitor = Janitor::new(cache.clone())
+            .interval(Duration::from_millis(10))
+            .batch_size(2)
+            .batch_pause(Duration::ZERO)
+            .clock(|| Duration::from_secs(2000))
+            .reporter(counter.clone())
+            .spawn();
+
+        let cleared = tokio::time::timeout(Duration::from_secs(1), async {
+            while counter.0.load(Ordering::SeqCst) < 5 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+        janitor.abort();
+
+        assert!(cleared.is_ok());
+        assert_eq!(cache.keys().await.unwrap(), vec!["forever".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_janitor_abort() {
+        let cache: MokaCache<String> = MokaCache::new(100);
+        let janitor = spawn_janitor(cache, Duration::from_secs(60));
+
+        janitor.abort();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(janitor.is_finished());
+    }
+}
@@ -0,0 +1,162 @@
+//! Randomization helpers for spreading out retries and expirations.
+//!
+//! These don't need to be cryptographically secure, so a small splitmix64
+//! generator is used instead of pulling in a random number crate.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Increment of the splitmix64 generator
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Get a random number in `[0, 1)`
+pub(crate) fn random_unit() -> f64 {
+    // Mixing in the current time avoids identical sequences across processes
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos() as u64)
+        .unwrap_or(0);
+    unit_from(STATE.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed) ^ nanos)
+}
+
+/// Turn a splitmix64 state into a number in `[0, 1)`
+fn unit_from(mut z: u64) -> f64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    // Use the upper 53 bits, which is the precision of an f64
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Randomizes TTLs by up to a fraction in either direction
+///
+/// With a seed, the sequence of TTLs is deterministic, which keeps tests
+/// reproducible. Clones share the seeded sequence.
+#[derive(Debug, Clone)]
+pub(crate) struct TtlJitter {
+    fraction: f64,
+    seeded: Option<Arc<AtomicU64>>,
+}
+
+impl TtlJitter {
+    /// Jitter TTLs by up to `fraction` of their length, clamped to `[0, 1)`
+    pub(crate) fn new(fraction: f64, seed: Option<u64>) -> Self {
+        Self {
+            fraction: fraction.clamp(0.0, 0.99),
+            seeded: seed.map(|seed| Arc::new(AtomicU64::new(seed))),
+        }
+    }
+
+    /// Get `ttl` scaled by a random factor in `[1 - fraction, 1 + fraction)`
+    ///
+    /// The result is never shorter than a millisecond, so jitter never turns
+    /// a cacheable TTL into one that expires right away.
+    pub(crate) fn apply(&self, ttl: Duration) -> Duration {
+        let unit = match &self.seeded {
+            Some(state) => unit_from(state.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed)),
+            None => random_unit(),
+        };
+        let factor = 1.0 + self.fraction * (2.0 * unit - 1.0);
+        // `mul_f64` panics on overflow, huge TTLs stay as long as possible instead
+        scale(ttl, factor).max(Duration::from_millis(1))
+    }
+}
+
+/// Exponential backoff with full jitter
+#[cfg(any(feature = "redis", feature = "dynamodb"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExponentialBackoff {
+    /// Upper bound of the first delay
+    pub(crate) initial: Duration,
+    /// Upper bound of any delay
+    pub(crate) max: Duration,
+}
+
+#[cfg(any(feature = "redis", feature = "dynamodb"))]
+impl ExponentialBackoff {
+    /// Get the delay before the given attempt, starting at zero
+    ///
+    /// The delay is chosen uniformly between zero and `initial * 2^attempt`,
+    /// capped at `max`, so concurrent clients don't retry in lockstep.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max);
+        scale(ceiling, random_unit())
+    }
+}
+
+/// Multiply `duration` by `factor`, saturating at [`Duration::MAX`]
+fn scale(duration: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_unit_range() {
+        for _ in 0..1000 {
+            let value = random_unit();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn test_ttl_jitter_bounds_and_seeding() {
+        let ttl = Duration::from_secs(100);
+        let jitter = TtlJitter::new(0.1, None);
+        for _ in 0..1000 {
+            let jittered = jitter.apply(ttl);
+            assert!(jittered >= Duration::from_secs(90) && jittered <= Duration::from_secs(110));
+        }
+
+        // Seeded jitter repeats its sequence
+        let first: Vec<_> = (0..10).map({
+            let jitter = TtlJitter::new(0.5, Some(42));
+            move |_| jitter.apply(ttl)
+        }).collect();
+        let second: Vec<_> = (0..10).map({
+            let jitter = TtlJitter::new(0.5, Some(42));
+            move |_| jitter.apply(ttl)
+        }).collect();
+        assert_eq!(first, second);
+        assert!(first.iter().any(|jittered| *jittered != first[0]));
+
+        // Even full jitter never reaches zero
+        let jitter = TtlJitter::new(5.0, None);
+        for _ in 0..1000 {
+            assert!(jitter.apply(Duration::from_millis(2)) >= Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_ttl_jitter_saturates_huge_ttls() {
+        let jitter = TtlJitter::new(0.5, Some(7));
+        for _ in 0..100 {
+            assert!(jitter.apply(Duration::MAX) >= Duration::from_secs(u64::MAX / 4));
+        }
+    }
+
+    #[cfg(any(feature = "redis", feature = "dynamodb"))]
+    #[test]
+    fn test_exponential_backoff_bounds() {
+        let backoff = ExponentialBackoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+        };
+
+        for _ in 0..100 {
+            assert!(backoff.delay(0) <= Duration::from_millis(100));
+            assert!(backoff.delay(2) <= Duration::from_millis(400));
+            assert!(backoff.delay(10) <= Duration::from_secs(1));
+            assert!(backoff.delay(u32::MAX) <= Duration::from_secs(1));
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Cache key construction.
+//!
+//! [`CacheKey`] builds namespaced keys from segments, so keys follow one
+//! convention instead of being assembled with `format!` all over a codebase.
+//!
+//! Memoizing a function-like computation needs a key that identifies its
+//! input. [`stable_key`] serializes the input to a canonical JSON form, with
+//! object keys sorted at every level, and hashes it. The result is the same
+//! across runs, processes and machines, regardless of the iteration order of
+//! maps such as `HashMap` inside the input.
+
+#[cfg(feature = "serde")]
+use crate::Result;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write};
+
+/// Separator between the segments of a [`CacheKey`]
+pub const KEY_SEPARATOR: char = ':';
+
+/// A cache key built from segments
+///
+/// Segments are joined with [`KEY_SEPARATOR`]. Characters that would make
+/// keys ambiguous or that backends such as Memcached reject are
+/// percent-encoded within a segment: the separator, `%`, whitespace and
+/// control characters. Distinct segments therefore always render to distinct
+/// keys, e.g. the segments `a:b` and `c` never collide with `a` and `b:c`.
+///
+/// Plain strings convert into keys verbatim, without sanitization, so
+/// anything accepting `impl Into<CacheKey>` keeps accepting `&str` and `String`.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::CacheKey;
+///
+/// let key = CacheKey::new("user").segment(42).segment("profile");
+/// assert_eq!(key.as_str(), "user:42:profile");
+///
+/// // Segments can't inject separators
+/// let key = CacheKey::new("search").segment("rust: caching");
+/// assert_eq!(key.as_str(), "search:rust%3A%20caching");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Start a key with its namespace as the first segment
+    pub fn new(namespace: impl Display) -> Self {
+        let mut key = String::new();
+        push_segment(&mut key, namespace);
+        Self(key)
+    }
+
+    /// Append a segment to the key
+    pub fn segment(mut self, segment: impl Display) -> Self {
+        self.0.push(KEY_SEPARATOR);
+        push_segment(&mut self.0, segment);
+        self
+    }
+
+    /// Get the rendered key
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Convert into the rendered key
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+/// Append a segment to a key, percent-encoding the characters that aren't allowed in it
+fn push_segment(key: &mut String, segment: impl Display) {
+    for c in segment.to_string().chars() {
+        if c == KEY_SEPARATOR || c == '%' || c.is_whitespace() || c.is_control() {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                // Writing to a `String` can't fail
+                let _ = write!(key, "%{byte:02X}");
+            }
+        } else {
+            key.push(c);
+        }
+    }
+}
+
+impl Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for CacheKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<CacheKey> for String {
+    fn from(key: CacheKey) -> Self {
+        key.0
+    }
+}
+
+impl From<&str> for CacheKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
+}
+
+impl From<String> for CacheKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&String> for CacheKey {
+    fn from(key: &String) -> Self {
+        Self(key.clone())
+    }
+}
+
+impl From<Box<str>> for CacheKey {
+    fn from(key: Box<str>) -> Self {
+        Self(key.into())
+    }
+}
+
+impl From<Cow<'_, str>> for CacheKey {
+    fn from(key: Cow<'_, str>) -> Self {
+        Self(key.into_owned())
+    }
+}
+
+/// Offset basis of the 128-bit FNV-1a hash
+#[cfg(feature = "serde")]
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+
+/// Prime of the 128-bit FNV-1a hash
+#[cfg(feature = "serde")]
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Derive a stable cache key from a serializable input
+///
+/// The key consists of `prefix` followed by the 128-bit hash of the canonical
+/// JSON form of `input` as 32 hex digits. Inputs that serialize to the same
+/// JSON produce the same key, so e.g. a `HashMap` and a `BTreeMap` with the
+/// same entries are interchangeable.
+///
+/// # Errors
+///
+/// Returns an error if the input can't be serialized to JSON, e.g. because
+/// it contains a map with keys that aren't strings or numbers.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::key::stable_key;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Search {
+///     query: String,
+///     page: u32,
+/// }
+///
+/// let key = stable_key("search:", &Search { query: "rust".to_string(), page: 1 })?;
+/// assert!(key.starts_with("search:"));
+/// # Ok::<(), cachified::CachifiedError>(())
+/// ```
+#[cfg(feature = "serde")]
+pub fn stable_key<I>(prefix: &str, input: &I) -> Result<String>
+where
+    I: Serialize + ?Sized,
+{
+    let mut canonical = String::new();
+    write_canonical(&serde_json::to_value(input)?, &mut canonical);
+
+    let hash = canonical.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    Ok(format!("{prefix}{hash:032x}"))
+}
+
+/// Write a JSON value with the keys of all objects sorted
+///
+/// Objects are sorted explicitly since `serde_json` keeps insertion order
+/// when its `preserve_order` feature is enabled anywhere in the dependency graph.
+#[cfg(feature = "serde")]
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+
+            out.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_rendering() {
+        assert_eq!(CacheKey::new("user").as_str(), "user");
+        assert_eq!(CacheKey::new("user").segment(42).segment("profile").to_string(), "user:42:profile");
+        assert_eq!(CacheKey::new("user").segment("").segment('x').as_str(), "user::x");
+        assert_eq!(String::from(CacheKey::new("a").segment("b")), "a:b");
+    }
+
+    #[test]
+    fn test_cache_key_sanitization() {
+        assert_eq!(CacheKey::new("a:b").segment("c").as_str(), "a%3Ab:c");
+        assert_ne!(CacheKey::new("a:b").segment("c"), CacheKey::new("a").segment("b:c"));
+        assert_eq!(CacheKey::new("q").segment("50% off\n").as_str(), "q:50%25%20off%0A");
+        assert_eq!(CacheKey::new("q").segment("größe").as_str(), "q:größe");
+
+        // Plain strings are taken verbatim
+        assert_eq!(CacheKey::from("user:42 profile").as_str(), "user:42 profile");
+        assert_eq!(CacheKey::from(String::from("a%b")).into_string(), "a%b");
+    }
+
+    #[cfg(feature = "serde")]
+    use std::collections::{BTreeMap, HashMap};
+
+    #[cfg(feature = "serde")]
+    #[derive(Serialize)]
+    struct Search {
+        query: String,
+        filters: HashMap<String, u32>,
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stable_key_ignores_map_order() {
+        let filters: Vec<(String, u32)> = (0..32).map(|index| (format!("filter-{index}"), index)).collect();
+        let forward = Search {
+            query: "rust".to_string(),
+            filters: filters.iter().cloned().collect(),
+        };
+        let backward = Search {
+            query: "rust".to_string(),
+            filters: filters.iter().rev().cloned().collect(),
+        };
+        let sorted: BTreeMap<String, u32> = filters.into_iter().collect();
+
+        let key = stable_key("search:", &forward).unwrap();
+        assert_eq!(key, stable_key("search:", &backward).unwrap());
+        assert_eq!(
+            stable_key("", &sorted).unwrap(),
+            stable_key("", &forward.filters).unwrap()
+        );
+        assert_eq!(key.len(), "search:".len() + 32);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stable_key_distinguishes_inputs() {
+        assert_ne!(stable_key("", &("a", 1)).unwrap(), stable_key("", &("a", 2)).unwrap());
+        assert_ne!(stable_key("", &["ab", "c"]).unwrap(), stable_key("", &["a", "bc"]).unwrap());
+        assert_ne!(stable_key("", &Some(0)).unwrap(), stable_key("", &None::<u32>).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stable_key_is_stable_across_runs() {
+        // Changing this value invalidates keys that were already stored
+        assert_eq!(
+            stable_key("user:", &("marvin", 42)).unwrap(),
+            "user:44b761294ad5a1b1e1280ea3db2d76a0"
+        );
+    }
+}
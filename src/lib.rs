@@ -9,8 +9,18 @@
 //!
 //! - `moka` (default): Enable Moka in-memory cache backend
 //! - `redis`: Enable Redis distributed cache backend
+//! - `redis-cluster`: Enable the Redis Cluster cache backend
+//! - `memcached`: Enable the Memcached cache backend
+//! - `dynamodb`: Enable the Amazon DynamoDB cache backend
+//! - `fs`: Enable the file system cache backend
 //! - `serde` (default): Enable serialization support (required for Redis)
-//! - `tracing`: Enable tracing support
+//! - `tracing`: Enable a `cachified` span per call, recording its outcome, and the `TracingReporter`
+//! - `diagnostics`: Enable O(n) helpers for inspecting cache contents
+//! - `prometheus`: Enable a reporter exporting Prometheus metrics
+//! - `validator`: Enable validating cached values with the `validator` crate
+//! - `compression`: Enable compressing large entries with `CompressedCodec`
+//! - `encryption`: Enable encrypting entries with `EncryptedCodec`
+//! - `testing`: Enable caches that inject failures and `FileSystemCache::temporary`
 //!
 //! ## Quick Start
 //!
@@ -64,24 +74,76 @@
 //! }
 //! ```
 
+mod batch;
 pub mod cache;
+pub mod clock;
+#[cfg(feature = "serde")]
+pub mod codec;
+pub mod config;
+mod conformance;
+pub mod defaults;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
 pub mod error;
+pub mod fresh_value;
+pub mod janitor;
+pub mod key;
+mod jitter;
 pub mod options;
 pub mod metadata;
+pub mod registry;
+pub mod reporter;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod validation;
+pub mod window;
 
-pub use cache::Cache;
+pub use batch::{cachified_many, cachified_many_keyed};
+pub use cache::{Cache, HashMapCache, ScopedCache};
 #[cfg(feature = "moka")]
 pub use cache::MokaCache;
 #[cfg(feature = "redis")]
-pub use cache::RedisCache;
-pub use error::{CachifiedError, Result};
-pub use options::{CachifiedOptions, CachifiedOptionsBuilder};
-pub use metadata::{CacheMetadata, CacheEntry};
-pub use validation::CheckValue;
+pub use cache::{ReconnectPolicy, RedisCache, RedisConnectionState};
+#[cfg(feature = "redis-cluster")]
+pub use cache::RedisClusterCache;
+#[cfg(feature = "fs")]
+pub use cache::FileSystemCache;
+#[cfg(feature = "memcached")]
+pub use cache::MemcachedCache;
+#[cfg(feature = "dynamodb")]
+pub use cache::DynamoDbCache;
+pub use config::{BackgroundRefreshOverflow, CachifiedConfig, RefreshTracker};
+pub use defaults::CachifiedDefaults;
+pub use clock::Clock;
+use clock::SystemClock;
+use config::{EarlyRefreshClaim, RefreshPriority};
+use jitter::TtlJitter;
+pub use error::{CachifiedError, ErrorKind, Result, Stage};
+pub use fresh_value::{FetchReason, FreshValueOutcome, GetFreshValue};
+pub use key::CacheKey;
+use fresh_value::FreshValueFuture;
+pub use options::{CachifiedOptions, CachifiedOptionsBuilder, KeyDisplay, ReadErrorPolicy, RetryPolicy, SwrPolicy};
+use options::{AsyncValueCheck, NegativeTtl, TtlFromValue, ValueCheck, ValueMigration, WaitUntil};
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures_util::stream::{self, Stream};
+use futures_util::FutureExt;
 use std::future::Future;
+use tokio_util::sync::CancellationToken;
+use tokio::sync::oneshot;
+pub use metadata::{CacheInfo, CacheMetadata, CacheEntry, CacheEntryBuilder, CacheStatus};
+pub use reporter::Reporter;
+pub use reporter::stats::{CacheStats, CacheStatsSnapshot};
+#[cfg(feature = "tracing")]
+pub use reporter::TracingReporter;
+pub use validation::{AsyncCheckValue, CheckValue, MigrateValue, ValidationOutcome};
+
+use std::any::Any;
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// The main cachified function that provides caching functionality.
 ///
@@ -98,6 +160,13 @@ use std::future::Future;
 /// Returns the cached or fresh value, or an error if both cache retrieval and fresh value
 /// generation fail.
 ///
+/// # Sharing caches
+///
+/// The cache is moved into the options, so cheaply clonable handles such as
+/// `MokaCache`, `RedisCache` or an `Arc<dyn Cache<T>>` work best. Since background
+/// refreshes may outlive the call, the cache must be `'static`; a `&'static` reference,
+/// e.g. to a cache in a `static`, can be passed directly without cloning.
+///
 /// # Examples
 ///
 /// ```rust
@@ -117,133 +186,1298 @@ use std::future::Future;
 /// # Ok(())
 /// # }
 /// ```
-pub async fn cachified<T, F, Fut, C>(options: CachifiedOptions<T, F, C>) -> Result<T>
+pub async fn cachified<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<T>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    cachified_served(options).await.map(|served| served.value)
+}
+
+/// Like [`cachified`], but also returns information about how the value was obtained.
+///
+/// The [`CacheInfo`] tells whether the value came from the cache, how long ago
+/// it expired if it is stale, and whether background refreshes of the entry
+/// have been failing. This lets a UI warn that data may be outdated while
+/// still serving it.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_with_metadata, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let (value, info): (String, _) = cachified_with_metadata(
+///     CachifiedOptionsBuilder::new(cache, "my-key")
+///         .ttl(Duration::from_secs(60))
+///         .stale_while_revalidate(Duration::from_secs(300))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+///
+/// if info.consecutive_refresh_failures > 0 {
+///     println!("Data may be outdated: {:?}", info.last_refresh_error);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_with_metadata<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<(T, CacheInfo)>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    cachified_served(options).await.map(|served| (served.value, served.info))
+}
+
+/// Like [`cachified`], but also returns where the value came from.
+///
+/// The [`CacheStatus`] tells a cache hit apart from a fresh fetch, a stale or
+/// revalidated value and a cached value served because fetching failed, e.g.
+/// to set an `X-Cache` header.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_with_status, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let (value, status): (String, _) = cachified_with_status(
+///     CachifiedOptionsBuilder::new(cache, "my-key")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+///
+/// let x_cache = if status.is_cached() { "HIT" } else { "MISS" };
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_with_status<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<(T, CacheStatus)>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    cachified_served(options).await.map(|served| (served.value, served.status))
+}
+
+/// Like [`cachified`], but returns the whole cache entry that was served.
+///
+/// The entry carries the metadata of the served value, such as its creation
+/// time, TTL and version, e.g. to compute cache headers or store the entry
+/// elsewhere. For freshly fetched values, it is the metadata the value was
+/// written with. Values that weren't cached, such as fallback values or values
+/// whose TTL doesn't allow caching them, get a TTL of zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_entry, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let entry = cachified_entry(
+///     CachifiedOptionsBuilder::new(cache, "my-key")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+///
+/// println!("Cache-Control: max-age={}", entry.metadata.ttl.unwrap_or_default().as_secs());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_entry<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<CacheEntry<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    cachified_served(options)
+        .await
+        .map(|served| CacheEntry::with_metadata(served.value, served.metadata))
+}
+
+/// Like [`cachified`], but with a fresh value function returning the caller's own error type.
+///
+/// The fresh value function returns `Result<T, E>` and so does the call,
+/// which saves mapping domain errors into [`CachifiedError`] and back. Errors
+/// of the function are passed through as they were returned, including when
+/// a single-flight fetch hands them to several callers, which is why `E` must
+/// be `Clone`. Errors of the cache itself, such as failed validation or a
+/// read error with [`ReadErrorPolicy::Propagate`], are converted into `E`
+/// with its `From<CachifiedError>` implementation.
+///
+/// Like [`cachified_set`], this takes the options builder, along with the
+/// fresh value function.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_typed, CachifiedError, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// #[derive(Debug, Clone, thiserror::Error)]
+/// enum ApiError {
+///     #[error("user {0} not found")]
+///     NotFound(u32),
+///     #[error(transparent)]
+///     Cache(#[from] CachifiedError),
+/// }
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() {
+/// let cache = MokaCache::new(1000);
+///
+/// let result: Result<String, ApiError> = cachified_typed(
+///     CachifiedOptionsBuilder::new(cache, "user-7").ttl(Duration::from_secs(60)),
+///     || async { Err(ApiError::NotFound(7)) },
+/// ).await;
+/// assert!(matches!(result, Err(ApiError::NotFound(7))));
+/// # }
+/// ```
+pub async fn cachified_typed<T, E, F, Fut, C>(
+    options: CachifiedOptionsBuilder<T, C>,
+    get_fresh_value: F,
+) -> std::result::Result<T, E>
 where
     T: Clone + Send + Sync + 'static,
+    E: std::error::Error + From<CachifiedError> + Clone + Send + Sync + 'static,
     F: Fn() -> Fut + Send + Sync,
-    Fut: Future<Output = Result<T>> + Send + 'static,
+    Fut: Future<Output = std::result::Result<T, E>> + Send + 'static,
+    C: Cache<T> + Clone + 'static,
+{
+    let options = options.get_fresh_value(|| {
+        let fresh_value = get_fresh_value();
+        async move { fresh_value.await.map_err(CachifiedError::fresh_value_source) }
+    });
+
+    cachified(options).await.map_err(|error| match error.downcast_source_ref::<E>() {
+        Some(source) => source.clone(),
+        None => E::from(error),
+    })
+}
+
+/// Like [`cachified`] for a cached map, but returns only the value of one field.
+///
+/// The whole map is cached under the key and fetched as a whole on a miss, so
+/// reading several fields of the same map costs a single fetch.
+///
+/// # Returns
+///
+/// Returns the value of `field`, or `None` if the map doesn't contain it.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_map_entry, CachifiedOptionsBuilder, MokaCache};
+/// use std::collections::HashMap;
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MokaCache<HashMap<String, u32>> = MokaCache::new(1000);
+///
+/// let stock = cachified_map_entry(
+///     CachifiedOptionsBuilder::new(cache, "stock")
+///         .ttl(Duration::from_secs(60))
+///         .get_fresh_value(|| async {
+///             Ok(HashMap::from([("apples".to_string(), 3), ("pears".to_string(), 0)]))
+///         }),
+///     "apples",
+/// ).await?;
+/// assert_eq!(stock, Some(3));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_map_entry<K, V, Q, F, C>(
+    options: CachifiedOptions<HashMap<K, V>, F, C>,
+    field: &Q,
+) -> Result<Option<V>>
+where
+    K: Eq + Hash + Borrow<Q> + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    Q: Eq + Hash + ?Sized,
+    F: GetFreshValue<HashMap<K, V>>,
+    C: Cache<HashMap<K, V>> + Clone + 'static,
+{
+    let map = cachified_served(options).await?.value;
+    Ok(map.get(field).cloned())
+}
+
+/// Like [`cachified`], but yields the stale value first and the refreshed value afterwards.
+///
+/// When a cached value is served while a background refresh runs (stale-while-revalidate
+/// or `always_revalidate`), the stream yields the cached value immediately and then the
+/// result of the refresh once it completes. If the refresh fails, the error is yielded
+/// as the second item; the cached entry is left untouched in that case.
+///
+/// In all other cases, such as a fresh cache hit or a cold miss, the stream yields a
+/// single item just like [`cachified`] would return.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_stream, CachifiedOptionsBuilder, MokaCache};
+/// use futures_util::StreamExt;
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// let stream = cachified_stream(
+///     CachifiedOptionsBuilder::new(cache, "dashboard")
+///         .ttl(Duration::from_secs(60))
+///         .stale_while_revalidate(Duration::from_secs(300))
+///         .get_fresh_value(|| async { Ok("fresh".to_string()) })
+/// );
+/// futures_util::pin_mut!(stream);
+///
+/// while let Some(value) = stream.next().await {
+///     println!("Render: {}", value?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn cachified_stream<T, F, C>(options: CachifiedOptions<T, F, C>) -> impl Stream<Item = Result<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    enum State<T, F, C>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: GetFreshValue<T>,
+        C: Cache<T> + Clone + 'static,
+    {
+        Start(Box<CachifiedOptions<T, F, C>>),
+        Refreshing(oneshot::Receiver<Result<T>>),
+        Done,
+    }
+
+    let error_stage = options.error_stage;
+    stream::unfold(State::Start(Box::new(options)), move |state| async move {
+        match state {
+            State::Start(options) => match cachified_served(*options).await {
+                Ok(Served { value, refresh: Some(refresh), .. }) => Some((Ok(value), State::Refreshing(refresh))),
+                Ok(Served { value, refresh: None, .. }) => Some((Ok(value), State::Done)),
+                Err(e) => Some((Err(e), State::Done)),
+            },
+            // The sender is only dropped without a result if the refresh task panicked
+            State::Refreshing(refresh) => refresh
+                .await
+                .ok()
+                .map(|result| (result.map_err(|e| staged(e, error_stage)), State::Done)),
+            State::Done => None,
+        }
+    })
+}
+
+/// Write an already known value to the cache, as `cachified` would after fetching it.
+///
+/// This is a write-through for values that are at hand anyway, e.g. after a
+/// mutation, which saves invalidating the key and fetching the value again.
+/// The value is checked with the fresh value validator and stored with the
+/// same TTL handling (`ttl`, `ttl_from_value`, `min_cacheable_ttl`, `max_ttl`),
+/// versioning and compare-and-set as values fetched by `cachified`, so the
+/// resulting entry is indistinguishable from one `cachified` wrote.
+///
+/// The stored entry is read first to determine the next version. Options that
+/// only concern reading or fetching, such as `fallback_to_cache`, are ignored.
+///
+/// # Returns
+///
+/// Returns whether the value was written. It isn't if the TTL policy doesn't
+/// allow caching it or, with compare-and-set, if the entry changed concurrently.
+/// Unlike `cachified`, write failures are returned as errors.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified_set, CachifiedOptionsBuilder, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+///
+/// // The user was just renamed, so cache the new name right away
+/// let written = cachified_set(
+///     CachifiedOptionsBuilder::new(cache, "user-1-name").ttl(Duration::from_secs(60)),
+///     "Marvin".to_string(),
+/// ).await?;
+/// assert!(written);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cachified_set<T, C>(options: CachifiedOptionsBuilder<T, C>, value: T) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + Clone,
+{
+    let options = options.build(());
+    let write_policy = WritePolicy::from_options(&options);
+    let CachifiedOptions {
+        cache,
+        key,
+        key_fn,
+        read_error_policy,
+        check_fresh_value,
+        check_value_async,
+        reporter,
+        clock,
+        error_stage,
+        ..
+    } = options;
+
+    let written = async {
+        let key = match key_fn {
+            Some(key_fn) => key_fn().await.map_err(|e| e.with_stage(Stage::ResolveKey))?,
+            None => key,
+        };
+
+        if let Some(ref validator) = check_fresh_value {
+            validator
+                .check(&value)
+                .map_err(|e| e.with_stage(Stage::ValidateFreshValue))?;
+        }
+        check_async(&check_value_async, &value, Stage::ValidateFreshValue).await?;
+
+        let previous_version = read_entry(&cache, &key, read_error_policy, &*reporter)
+            .await?
+            .map(|entry| entry.metadata.version);
+
+        try_write_entry(&cache, &key, value, clock.now(), &write_policy, previous_version, &*reporter)
+            .await
+            .map_err(|e| e.with_stage(Stage::WriteCache))
+    };
+    written.await.map_err(|e| staged(e, error_stage))
+}
+
+/// A value served by `cachified_served`, along with the result of the
+/// background refresh it triggered, if any
+struct Served<T> {
+    value: T,
+    /// Metadata of the served entry, reconstructed for values that weren't cached
+    metadata: CacheMetadata,
+    info: CacheInfo,
+    status: CacheStatus,
+    refresh: Option<oneshot::Receiver<Result<T>>>,
+}
+
+impl<T: Clone> Served<T> {
+    /// Serve a freshly fetched value with the metadata it was written with,
+    /// or `None` if it wasn't cacheable
+    fn fresh(value: T, metadata: Option<CacheMetadata>, now: Duration) -> Self {
+        Self {
+            value,
+            metadata: metadata.unwrap_or_else(|| uncached_metadata(now)),
+            info: CacheInfo::fresh(),
+            status: CacheStatus::Miss,
+            refresh: None,
+        }
+    }
+
+    /// Serve a fallback value that was neither fetched nor cached
+    fn fallback(value: T, now: Duration) -> Self {
+        Self {
+            value,
+            metadata: uncached_metadata(now),
+            info: CacheInfo {
+                fallback: true,
+                ..CacheInfo::default()
+            },
+            status: CacheStatus::Miss,
+            refresh: None,
+        }
+    }
+
+    /// Serve the value of a cache entry at the given time
+    fn cached(entry: &CacheEntry<T>, now: Duration, status: CacheStatus) -> Self {
+        Self {
+            value: entry.value.clone(),
+            metadata: entry.metadata.clone(),
+            info: CacheInfo::cached(&entry.metadata, now),
+            status,
+            refresh: None,
+        }
+    }
+
+    /// Attach the background refresh triggered while serving the value, if one was started
+    fn with_refresh(mut self, refresh: Option<oneshot::Receiver<Result<T>>>) -> Self {
+        self.refresh = refresh;
+        self
+    }
+}
+
+/// Shared implementation of [`cachified`] and [`cachified_stream`]
+async fn cachified_served<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<Served<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
+    C: Cache<T> + Clone + 'static,
+{
+    let error_stage = options.error_stage;
+
+    #[cfg(feature = "tracing")]
+    let served = {
+        use tracing::Instrument;
+
+        // The key and whether the cache is skipped are recorded once they are resolved
+        let span = tracing::debug_span!(
+            "cachified",
+            cache.backend = options.cache.backend(),
+            cache.key = tracing::field::Empty,
+            cache.force_fresh = tracing::field::Empty,
+            cache.outcome = tracing::field::Empty,
+            cache.stage = tracing::field::Empty,
+        );
+        let served = serve(options).instrument(span.clone()).await;
+        span.record("cache.outcome", outcome(&served));
+        if let Err(Some(stage)) = served.as_ref().map_err(CachifiedError::stage) {
+            span.record("cache.stage", tracing::field::display(stage));
+        }
+        served
+    };
+    #[cfg(not(feature = "tracing"))]
+    let served = serve(options).await;
+
+    served.map_err(|e| staged(e, error_stage))
+}
+
+/// Keep the stage of an error only if it was asked for with `error_stage`
+fn staged(error: CachifiedError, error_stage: bool) -> CachifiedError {
+    if error_stage { error } else { error.into_inner() }
+}
+
+/// Name of the outcome recorded on the `cachified` span
+///
+/// Fallback values count as errors, as they are only served if fetching failed.
+#[cfg(feature = "tracing")]
+fn outcome<T>(served: &Result<Served<T>>) -> &'static str {
+    match served {
+        Ok(served) if served.info.cached && served.info.is_stale() => "stale",
+        Ok(served) if served.info.cached => "hit",
+        Ok(served) if !served.info.fallback => "miss",
+        _ => "error",
+    }
+}
+
+/// Serve a value from the cache or a fresh fetch
+async fn serve<T, F, C>(options: CachifiedOptions<T, F, C>) -> Result<Served<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    F: GetFreshValue<T>,
     C: Cache<T> + Clone + 'static,
 {
+    let write_policy = WritePolicy::from_options(&options);
     let CachifiedOptions {
         cache,
         key,
-        ttl,
+        key_fn,
+        // Only used through the write policy
+        ttl: _,
+        no_expiry: _,
+        min_cacheable_ttl: _,
+        max_ttl: _,
+        ttl_jitter: _,
+        ttl_jitter_seed: _,
+        ttl_from_value: _,
+        negative_ttl: _,
+        compare_and_set: _,
         stale_while_revalidate,
+        swr_policy,
+        early_refresh,
+        clock_skew_tolerance,
+        always_revalidate,
         force_fresh,
+        force_fresh_keys,
         fallback_to_cache,
-        check_value,
+        stale_if_error,
+        fallback_value,
+        deadline,
+        cancellation_token,
+        fresh_value_timeout,
+        retry,
+        retry_if,
+        read_error_policy,
+        key_display,
+        error_stage: _,
+        migrate_value,
+        check_cached_value,
+        check_fresh_value,
+        check_value_async,
         get_fresh_value,
+        reporter,
+        wait_until,
+        config,
+        clock,
     } = options;
 
-    let now = current_time();
+    // The key has to be known before anything else happens
+    let key = match key_fn {
+        Some(key_fn) => key_fn().await.map_err(|e| e.with_stage(Stage::ResolveKey))?,
+        None => key,
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("cache.key", key_display.display(&key));
+    #[cfg(not(feature = "tracing"))]
+    let _ = key_display;
+
+    let refresh_context = || RefreshContext {
+        cache: cache.clone(),
+        key: key.clone(),
+        write_policy: write_policy.clone(),
+        config: config.clone(),
+        reporter: reporter.clone(),
+        clock: clock.clone(),
+        wait_until: wait_until.clone(),
+        early_refresh_claim: None,
+    };
+    let fresh_value_future =
+        |reason| with_timeout(get_fresh_value.call_with_reason(reason), fresh_value_timeout);
+    let now = clock.now();
+    // Expiry is judged against a clock moved back by the tolerated skew of writers
+    let expiry_now = now.saturating_sub(clock_skew_tolerance.unwrap_or_default());
+    let mut cached = None;
+    // Passed to the fresh value function, updated as the cached value is ruled out
+    let mut reason = FetchReason::ColdMiss;
 
-    // If force_fresh is true, skip cache lookup and get fresh value
+    // If force_fresh is true or names the key, skip cache lookup and get fresh value
+    let force_fresh = force_fresh || force_fresh_keys.is_some_and(|keys| keys.contains(&key));
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("cache.force_fresh", force_fresh);
     if !force_fresh {
         // Try to get value from cache
-        if let Some(entry) = cache.get(&key).await {
+        cached = read_entry(&cache, &key, read_error_policy, &*reporter).await?;
+
+        // Values failing to migrate are refetched, but their version is still needed
+        let mut servable = true;
+        if let Some(entry) = &mut cached {
+            servable = migrate_entry(&cache, &key, &migrate_value, entry, &*reporter).await.is_ok();
+            if !servable {
+                reason = FetchReason::ValidationFailed;
+            }
+        }
+
+        if let Some(entry) = cached.as_ref().filter(|_| servable) {
+            if always_revalidate && passes_check(&check_cached_value, &check_value_async, &entry.value).await? {
+                // Serve whatever is cached and always refresh in the background
+                #[cfg(feature = "tracing")]
+                tracing::debug!("cache hit, revalidating in background");
+                reporter.on_cache_hit(&key);
+                let status = if is_expired(&entry.metadata, expiry_now) {
+                    reporter.on_stale_hit(&key);
+                    CacheStatus::Stale
+                } else {
+                    CacheStatus::RevalidatedBackground
+                };
+                let refresh = spawn_refresh(
+                    refresh_context(),
+                    entry.clone(),
+                    RefreshPriority::Normal,
+                    fresh_value_future(FetchReason::StaleRevalidation),
+                )
+                .await;
+                return Ok(Served::cached(entry, now, status).with_refresh(refresh));
+            }
+
             // Check if value is still valid (not expired)
-            if !is_expired(&entry.metadata, now) {
+            if !is_expired(&entry.metadata, expiry_now) {
                 // Validate the cached value if validator is provided
-                if let Some(ref validator) = check_value {
-                    if validator.check(&entry.value).is_ok() {
-                        return Ok(entry.value);
+                if passes_check(&check_cached_value, &check_value_async, &entry.value).await? {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("cache hit");
+                    reporter.on_cache_hit(&key);
+
+                    // Refresh values about to expire ahead of time, once per key
+                    if let Some(threshold) = early_refresh
+                        && let Some(expires_at) = entry.metadata.expires_at()
+                        && expiry_now + threshold >= expires_at
+                        && let Some(claim) = config.claim_early_refresh(&key)
+                    {
+                        let context = RefreshContext {
+                            early_refresh_claim: Some(claim),
+                            ..refresh_context()
+                        };
+                        let refresh = spawn_refresh(
+                            context,
+                            entry.clone(),
+                            RefreshPriority::Normal,
+                            fresh_value_future(FetchReason::StaleRevalidation),
+                        )
+                        .await;
+                        let served = Served::cached(entry, now, CacheStatus::RevalidatedBackground);
+                        return Ok(served.with_refresh(refresh));
                     }
-                    // If validation fails, continue to get fresh value
-                } else {
-                    return Ok(entry.value);
+                    return Ok(Served::cached(entry, now, CacheStatus::Hit));
                 }
-            } else if let Some(swr_duration) = stale_while_revalidate {
-                // Check if we're in the stale-while-revalidate window
-                let stale_until = entry.metadata.created_time + 
-                    entry.metadata.ttl.unwrap_or(Duration::ZERO) + swr_duration;
+                // If validation fails, continue to get fresh value
+                reason = FetchReason::ValidationFailed;
+            } else if let Some(swr_duration) = stale_while_revalidate.or(entry.metadata.swr) {
+                // Check if we're in the stale-while-revalidate window, which
+                // the entry remembers if this call doesn't configure one
+                let expired_at = entry.metadata.created_time + 
+                    entry.metadata.ttl.unwrap_or(Duration::ZERO);
+                let stale_until = expired_at + swr_duration;
                 
-                if now < stale_until {
-                    // Serve stale value and trigger background refresh
-                    let cache_clone = cache.clone();
-                    let key_clone = key.clone();
-                    let fresh_value_future = get_fresh_value();
-                    
-                    // Start background refresh
-                    tokio::spawn(async move {
-                        if let Ok(fresh_value) = fresh_value_future.await {
-                            let metadata = CacheMetadata {
-                                created_time: current_time(),
-                                ttl,
-                            };
-                            let entry = CacheEntry {
-                                value: fresh_value,
-                                metadata,
-                            };
-                            let _ = cache_clone.set(&key_clone, entry).await;
-                        }
-                    });
+                if expiry_now < stale_until {
+                    // Serve stale value and trigger background refresh,
+                    // prioritized by how deep into the window we are
+                    let priority = swr_policy.priority(expiry_now - expired_at, swr_duration);
+                    let refresh = spawn_refresh(
+                        refresh_context(),
+                        entry.clone(),
+                        priority,
+                        fresh_value_future(FetchReason::StaleRevalidation),
+                    )
+                    .await;
                     
                     // Return stale value immediately
-                    if let Some(ref validator) = check_value {
-                        if validator.check(&entry.value).is_ok() {
-                            return Ok(entry.value);
-                        }
-                    } else {
-                        return Ok(entry.value);
+                    if passes_check(&check_cached_value, &check_value_async, &entry.value).await? {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(?priority, "serving stale value while revalidating");
+                        reporter.on_cache_hit(&key);
+                        reporter.on_stale_hit(&key);
+                        return Ok(Served::cached(entry, now, CacheStatus::Stale).with_refresh(refresh));
                     }
+                    reason = FetchReason::ValidationFailed;
+                } else {
+                    reason = FetchReason::Expired;
                 }
+            } else {
+                reason = FetchReason::Expired;
             }
         }
+    } else {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("forcing fresh value, skipping cache");
+        reason = FetchReason::Forced;
+        if write_policy.compare_and_set {
+            // The version of the stored entry is needed to detect concurrent writes
+            cached = read_entry(&cache, &key, read_error_policy, &*reporter).await?;
+        }
     }
 
     // Get fresh value
-    match get_fresh_value().await {
-        Ok(fresh_value) => {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?reason, "cache miss, getting fresh value");
+    reporter.on_cache_miss(&key);
+    reporter.on_get_fresh_value_start(&key);
+    let fetch_started = Instant::now();
+    let fresh_value = async {
+        let mut attempt = 1;
+        loop {
+            let (result, started_fetch) = config.fetch_fresh_value(&key, fresh_value_future(reason)).await;
+            if let (Err(e), Some(retry)) = (&result, &retry)
+                && attempt < retry.max_attempts()
+                && retry_if.as_ref().map_or_else(|| RetryPolicy::retries_by_default(e), |retry_if| retry_if(e))
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(error = %e, attempt, "getting fresh value failed, retrying");
+                tokio::time::sleep(retry.delay(attempt)).await;
+                attempt += 1;
+                continue;
+            }
+            return result.map(|outcome| (outcome, started_fetch));
+        }
+    };
+    let fetched = until_cancelled(deadline, cancellation_token, fresh_value).await;
+    let elapsed = fetch_started.elapsed();
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?elapsed, success = fetched.is_ok(), "getting fresh value finished");
+    if fetched.is_ok() {
+        reporter.on_get_fresh_value_success(&key, elapsed);
+    }
+    match fetched.map(|(outcome, started_fetch)| (outcome.into_value(), started_fetch)) {
+        Ok((Some((fresh_value, ttl)), started_fetch)) => {
             // Validate fresh value if validator is provided
-            if let Some(ref validator) = check_value {
-                validator.check(&fresh_value)?;
+            if let Some(ref validator) = check_fresh_value {
+                validator
+                    .check(&fresh_value)
+                    .map_err(|e| e.with_stage(Stage::ValidateFreshValue))?;
             }
+            check_async(&check_value_async, &fresh_value, Stage::ValidateFreshValue).await?;
 
-            // Cache the fresh value if TTL is positive
-            if let Some(ttl_duration) = ttl {
-                if ttl_duration > Duration::ZERO {
-                    let metadata = CacheMetadata {
-                        created_time: now,
-                        ttl,
-                    };
-                    let entry = CacheEntry {
-                        value: fresh_value.clone(),
-                        metadata,
-                    };
-                    
-                    if cache.set(&key, entry).await.is_err() {
-                        // If cache write fails, we still return the fresh value
-                        // This is consistent with the original cachified behavior
-                    }
-                }
+            let previous_version = cached.map(|entry| entry.metadata.version);
+            let write_policy = write_policy.with_fetched_ttl(ttl);
+            // With single-flight, the value is only written by the call that fetched it
+            let metadata = if started_fetch {
+                write_entry(&cache, &key, fresh_value.clone(), now, &write_policy, previous_version, &*reporter).await
+            } else {
+                write_policy.metadata(&fresh_value, now, previous_version)
+            };
+
+            Ok(Served::fresh(fresh_value, metadata, now))
+        }
+        Ok((None, started_fetch)) => {
+            // Keep the existing value but refresh its creation time and TTL
+            let entry = match cached {
+                Some(entry) => Some(entry),
+                None => read_entry(&cache, &key, read_error_policy, &*reporter).await?,
+            };
+            let Some(mut entry) = entry else {
+                return Err(CachifiedError::fresh_value(
+                    "Fresh value reported as unchanged but no cached value exists",
+                )
+                .with_stage(Stage::GetFreshValue));
+            };
+
+            migrate_entry(&cache, &key, &migrate_value, &mut entry, &*reporter)
+                .await
+                .map_err(|e| e.with_stage(Stage::ValidateCachedValue))?;
+
+            if let Some(ref validator) = check_cached_value {
+                validator
+                    .check(&entry.value)
+                    .map_err(|e| e.with_stage(Stage::ValidateCachedValue))?;
             }
+            check_async(&check_value_async, &entry.value, Stage::ValidateCachedValue).await?;
 
-            Ok(fresh_value)
+            let previous_version = Some(entry.metadata.version);
+            let metadata = if started_fetch {
+                write_entry(&cache, &key, entry.value.clone(), now, &write_policy, previous_version, &*reporter).await
+            } else {
+                write_policy.metadata(&entry.value, now, previous_version)
+            };
+
+            Ok(Served::fresh(entry.value, metadata, now))
         }
         Err(e) => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %e, "getting fresh value failed");
+            reporter.on_get_fresh_value_error(&key, &e);
+
             // If getting fresh value fails and fallback_to_cache is enabled,
-            // try to return cached value even if it's expired
-            if fallback_to_cache {
-                if let Some(entry) = cache.get(&key).await {
-                    if let Some(ref validator) = check_value {
-                        if validator.check(&entry.value).is_ok() {
-                            return Ok(entry.value);
-                        }
-                    } else {
-                        return Ok(entry.value);
-                    }
+            // try to return cached value even if it's expired. With
+            // stale_if_error, only values young enough are served.
+            if (fallback_to_cache || stale_if_error.is_some())
+                && let Some(mut entry) = cache.get(&key).await
+                && migrate_entry(&cache, &key, &migrate_value, &mut entry, &*reporter).await.is_ok()
+                && (fallback_to_cache
+                    || stale_if_error.is_some_and(|max_age| entry.metadata.age(now) <= max_age))
+                // The fetch already failed, so a fatal validation error doesn't replace it
+                && passes_check(&check_cached_value, &check_value_async, &entry.value).await.unwrap_or(false)
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("serving cached value after getting fresh value failed");
+                if is_expired(&entry.metadata, expiry_now) {
+                    reporter.on_stale_hit(&key);
                 }
+                return Ok(Served::cached(&entry, now, CacheStatus::FallbackStale));
+            }
+
+            // Serve the default as a last resort, without caching it
+            if let Some(fallback_value) = fallback_value {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("serving fallback value");
+                reporter.on_fallback_value(&key);
+                return Ok(Served::fallback(fallback_value(), now));
+            }
+            Err(e.with_stage(Stage::GetFreshValue))
+        }
+    }
+}
+
+/// Everything a background refresh needs besides the value being refreshed
+struct RefreshContext<T, C> {
+    cache: C,
+    key: String,
+    write_policy: WritePolicy<T>,
+    config: CachifiedConfig,
+    reporter: Arc<dyn Reporter>,
+    clock: Arc<dyn Clock>,
+    /// Hook running the refresh, spawned on Tokio if unset
+    wait_until: Option<WaitUntil>,
+    /// Claim released once an early refresh finishes
+    early_refresh_claim: Option<EarlyRefreshClaim>,
+}
+
+/// Refresh a cache entry in the background
+///
+/// Failures, including panics of the fetch, are recorded on the stale entry,
+/// which stays in the cache. The returned receiver resolves to the refreshed
+/// value, or to the error if the refresh failed.
+/// Returns `None` if the refresh was skipped because too many are outstanding.
+async fn spawn_refresh<T, C>(
+    context: RefreshContext<T, C>,
+    stale_entry: CacheEntry<T>,
+    priority: RefreshPriority,
+    fresh_value_future: FreshValueFuture<T>,
+) -> Option<oneshot::Receiver<Result<T>>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + 'static,
+{
+    let RefreshContext {
+        cache,
+        key,
+        write_policy,
+        config,
+        reporter,
+        clock,
+        wait_until,
+        early_refresh_claim,
+    } = context;
+
+    let Some(slot) = config.reserve_background_refresh().await else {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("too many background refreshes, skipping refresh");
+        return None;
+    };
+
+    // Track the refresh from the moment it is scheduled so draining can't miss it
+    let guard = config.refresh_tracker().track();
+    let (sender, receiver) = oneshot::channel();
+
+    let refresh = async move {
+        let _slot = slot;
+        let _guard = guard;
+        let _early_refresh_claim = early_refresh_claim;
+        let CacheEntry { value: stale_value, metadata } = stale_entry;
+        let previous_version = Some(metadata.version);
+
+        reporter.on_get_fresh_value_start(&key);
+        let fetch_started = Instant::now();
+        // A panicking fetch fails the refresh instead of silently killing the task
+        let fetched = config
+            .run_refresh(priority, AssertUnwindSafe(fresh_value_future).catch_unwind())
+            .await
+            .unwrap_or_else(|panic| Err(panicked(&*panic)));
+        if fetched.is_ok() {
+            reporter.on_get_fresh_value_success(&key, fetch_started.elapsed());
+        }
+        let result = match fetched.map(FreshValueOutcome::into_value) {
+            Ok(Some((fresh_value, ttl))) => {
+                let write_policy = write_policy.with_fetched_ttl(ttl);
+                let now = clock.now();
+                write_entry(&cache, &key, fresh_value.clone(), now, &write_policy, previous_version, &*reporter).await;
+                Ok(fresh_value)
+            }
+            Ok(None) => {
+                let now = clock.now();
+                write_entry(&cache, &key, stale_value.clone(), now, &write_policy, previous_version, &*reporter).await;
+                Ok(stale_value)
             }
-            Err(e)
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "background refresh failed");
+                reporter.on_get_fresh_value_error(&key, &e);
+                record_refresh_failure(&cache, &key, &e).await;
+                Err(e.with_stage(Stage::GetFreshValue))
+            }
+        };
+
+        // Nobody may be interested in the result
+        let _ = sender.send(result);
+    };
+
+    // Keep background refreshes attributed to the call that started them
+    #[cfg(feature = "tracing")]
+    let refresh = tracing::Instrument::in_current_span(refresh);
+    match wait_until {
+        Some(wait_until) => wait_until(Box::pin(refresh)),
+        None => {
+            tokio::spawn(refresh);
+        }
+    }
+
+    Some(receiver)
+}
+
+/// Upgrade a cached entry with the migration, if one is set
+///
+/// A migrated value replaces the one of the entry and is written back to the
+/// cache with the same metadata, unless the entry was replaced since it was
+/// read. Returns the error of a failed migration, in which case the entry
+/// must not be served.
+async fn migrate_entry<T, C>(
+    cache: &C,
+    key: &str,
+    migrate_value: &Option<ValueMigration<T>>,
+    entry: &mut CacheEntry<T>,
+    reporter: &dyn Reporter,
+) -> Result<()>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let Some(migrate_value) = migrate_value else {
+        return Ok(());
+    };
+    let Some(migrated) = migrate_value.migrate(&entry.value)? else {
+        return Ok(());
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!("migrated cached value");
+    entry.value = migrated;
+    let expected_version = entry.metadata.version;
+    let mut metadata = entry.metadata.clone();
+    metadata.version += 1;
+    // Failing to store the migrated value only means migrating it again next time
+    let stored = CacheEntry::with_metadata(entry.value.clone(), metadata.clone());
+    if let Ok(true) = cache.set_if_version(key, stored, Some(expected_version)).await {
+        reporter.on_write(key, &metadata);
+        entry.metadata = metadata;
+    }
+    Ok(())
+}
+
+/// Record a failed background refresh in the metadata of the cached entry
+///
+/// The entry is only updated if it wasn't replaced since it was read, so a
+/// concurrently written fresh value isn't overwritten. Failures are ignored.
+async fn record_refresh_failure<T, C>(cache: &C, key: &str, error: &CachifiedError)
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let Some(mut entry) = cache.get(key).await else {
+        return;
+    };
+
+    let expected_version = entry.metadata.version;
+    entry.metadata.version += 1;
+    entry.metadata.refresh_failures += 1;
+    entry.metadata.last_refresh_error = Some(error.to_string());
+    let _ = cache.set_if_version(key, entry, Some(expected_version)).await;
+}
+
+/// Run a fresh value fetch until it completes or the call is cancelled
+///
+/// The fetch is dropped when the deadline passes or the token is cancelled.
+async fn until_cancelled<O>(
+    deadline: Option<tokio::time::Instant>,
+    cancellation_token: Option<CancellationToken>,
+    fresh_value: impl Future<Output = Result<O>>,
+) -> Result<O> {
+    if deadline.is_none() && cancellation_token.is_none() {
+        return fresh_value.await;
+    }
+
+    let deadline_passed = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+    let token_cancelled = async {
+        match &cancellation_token {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        result = fresh_value => result,
+        _ = deadline_passed => Err(CachifiedError::cancelled("Deadline passed while getting fresh value")),
+        _ = token_cancelled => Err(CachifiedError::cancelled("Cancelled while getting fresh value")),
+    }
+}
+
+/// Fail a fresh value fetch with a timeout error once the timeout passes
+fn with_timeout<T: Send + 'static>(
+    fresh_value_future: FreshValueFuture<T>,
+    timeout: Option<Duration>,
+) -> FreshValueFuture<T> {
+    let Some(timeout) = timeout else {
+        return fresh_value_future;
+    };
+
+    Box::pin(async move {
+        tokio::time::timeout(timeout, fresh_value_future)
+            .await
+            .unwrap_or_else(|_| Err(CachifiedError::timeout(format!("No fresh value after {timeout:?}"))))
+    })
+}
+
+/// Turn the payload of a panicking fresh value fetch into a fresh value error
+fn panicked(payload: &(dyn Any + Send)) -> CachifiedError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    CachifiedError::fresh_value(format!("Getting fresh value panicked: {message}"))
+}
+
+/// Read an entry from the cache, handling read errors according to the policy
+///
+/// Errors treated as a miss are reported, as they would go unnoticed otherwise.
+async fn read_entry<T, C>(
+    cache: &C,
+    key: &str,
+    policy: ReadErrorPolicy,
+    reporter: &dyn Reporter,
+) -> Result<Option<CacheEntry<T>>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    match cache.try_get(key).await {
+        Ok(entry) => Ok(entry),
+        Err(e) if policy == ReadErrorPolicy::TreatAsMiss => {
+            reporter.on_cache_read_error(key, &e);
+            Ok(None)
+        }
+        Err(e) => Err(e.with_stage(Stage::ReadCache)),
+    }
+}
+
+/// Check a cached value against the optional validators
+///
+/// The async validator only runs if the value passes the synchronous one.
+/// Returns whether the value can be served, or the error of a fatal validation failure.
+async fn passes_check<T: Sync>(
+    check_value: &Option<ValueCheck<T>>,
+    check_value_async: &Option<AsyncValueCheck<T>>,
+    value: &T,
+) -> Result<bool> {
+    match check_value.as_ref().map(|validator| validator.validate(value)) {
+        None | Some(ValidationOutcome::Valid) => {
+            Ok(check_async(check_value_async, value, Stage::ValidateCachedValue).await.is_ok())
+        }
+        Some(ValidationOutcome::InvalidRefetch) => Ok(false),
+        Some(ValidationOutcome::InvalidFatal(e)) => Err(e.with_stage(Stage::ValidateCachedValue)),
+    }
+}
+
+/// Check a value against the optional async validator
+async fn check_async<T: Sync>(
+    check_value_async: &Option<AsyncValueCheck<T>>,
+    value: &T,
+    stage: Stage,
+) -> Result<()> {
+    match check_value_async {
+        Some(validator) => validator.check(value).await.map_err(|e| e.with_stage(stage)),
+        None => Ok(()),
+    }
+}
+
+/// Settings that determine how fresh values are written to the cache
+#[derive(Clone)]
+struct WritePolicy<T> {
+    ttl: Option<Duration>,
+    /// Write values without expiry if no TTL applies to them
+    no_expiry: bool,
+    min_cacheable_ttl: Option<Duration>,
+    max_ttl: Option<Duration>,
+    ttl_jitter: Option<TtlJitter>,
+    ttl_from_value: Option<TtlFromValue<T>>,
+    negative_ttl: Option<NegativeTtl<T>>,
+    /// Stale-while-revalidate window stored with the entry
+    stale_while_revalidate: Option<Duration>,
+    /// Only write if the stored entry still has the version that was read
+    compare_and_set: bool,
+}
+
+impl<T: Clone> WritePolicy<T> {
+    /// Get the write policy configured by the TTL and write options of `options`
+    fn from_options<F, C>(options: &CachifiedOptions<T, F, C>) -> Self
+    where
+        T: Send + Sync + 'static,
+        C: Cache<T> + Clone,
+    {
+        WritePolicy {
+            ttl: options.ttl,
+            no_expiry: options.no_expiry,
+            min_cacheable_ttl: options.min_cacheable_ttl,
+            max_ttl: options.max_ttl,
+            ttl_jitter: options.ttl_jitter.map(|fraction| TtlJitter::new(fraction, options.ttl_jitter_seed)),
+            ttl_from_value: options.ttl_from_value.clone(),
+            negative_ttl: options.negative_ttl.clone(),
+            stale_while_revalidate: options.stale_while_revalidate,
+            compare_and_set: options.compare_and_set,
         }
     }
+
+    /// Get the TTL to store for a value, `Some(None)` to store it without
+    /// expiry, or `None` if it shouldn't be cached
+    ///
+    /// The TTL of negative results takes precedence over the TTL derived from
+    /// the value, which takes precedence over the static TTL.
+    /// Values without any TTL are only cached with `no_expiry`, capped at `max_ttl`.
+    /// Otherwise, values are only cached with a positive TTL of at least `min_cacheable_ttl`.
+    /// The TTL is capped at `max_ttl`, after jittering it.
+    fn effective_ttl(&self, value: &T) -> Option<Option<Duration>> {
+        let ttl = self
+            .negative_ttl
+            .as_ref()
+            .filter(|negative| (negative.is_negative)(value))
+            .map(|negative| negative.ttl)
+            .or_else(|| {
+                self.ttl_from_value
+                    .as_ref()
+                    .and_then(|ttl_from_value| ttl_from_value(value))
+            })
+            .or(self.ttl);
+        let Some(ttl) = ttl else {
+            return self.no_expiry.then_some(self.max_ttl);
+        };
+        let ttl = Some(ttl).filter(|ttl| *ttl > Duration::ZERO)?;
+
+        if let Some(min) = self.min_cacheable_ttl
+            && ttl < min
+        {
+            return None;
+        }
+
+        let ttl = match &self.ttl_jitter {
+            Some(jitter) => jitter.apply(ttl),
+            None => ttl,
+        };
+
+        Some(Some(match self.max_ttl {
+            Some(max) => ttl.min(max),
+            None => ttl,
+        }))
+    }
+
+    /// Get this policy with the TTL a fresh value was fetched with, if any
+    ///
+    /// The fetched TTL takes precedence over the static TTL, the TTL derived
+    /// from the value and the TTL of negative results. The `min_cacheable_ttl`
+    /// and `max_ttl` clamps still apply.
+    fn with_fetched_ttl(&self, ttl: Option<Duration>) -> Cow<'_, Self> {
+        match ttl {
+            Some(ttl) => Cow::Owned(WritePolicy {
+                ttl: Some(ttl),
+                ttl_from_value: None,
+                negative_ttl: None,
+                ..self.clone()
+            }),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// Get the metadata to write a value with, or `None` if it shouldn't be cached
+    ///
+    /// The entry gets the version following `previous_version`, the version of
+    /// the entry that was read before fetching the value, if any.
+    fn metadata(&self, value: &T, created_time: Duration, previous_version: Option<u64>) -> Option<CacheMetadata> {
+        let ttl = self.effective_ttl(value)?;
+        let version = previous_version.map_or(0, |version| version + 1);
+        Some(
+            CacheMetadata::with_time(created_time, ttl)
+                .with_version(version)
+                .with_swr(self.stale_while_revalidate),
+        )
+    }
+}
+
+/// Write a value to the cache if the write policy allows it
+///
+/// Write failures are ignored, the value is still returned to the caller.
+/// This is consistent with the original cachified behavior.
+///
+/// Returns the metadata of the written entry, or `None` if the value isn't cacheable.
+async fn write_entry<T, C>(
+    cache: &C,
+    key: &str,
+    value: T,
+    created_time: Duration,
+    write_policy: &WritePolicy<T>,
+    previous_version: Option<u64>,
+    reporter: &dyn Reporter,
+) -> Option<CacheMetadata>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let Some(metadata) = write_policy.metadata(&value, created_time, previous_version) else {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("no TTL allows caching the value, not writing it");
+        return None;
+    };
+    let entry = CacheEntry::with_metadata(value, metadata.clone());
+    let _ = store_entry(cache, key, entry, write_policy, previous_version, reporter).await;
+    Some(metadata)
+}
+
+/// Write a value to the cache if the write policy allows it, returning whether it was written
+///
+/// With compare-and-set enabled, the write is skipped if the stored entry
+/// changed in the meantime.
+async fn try_write_entry<T, C>(
+    cache: &C,
+    key: &str,
+    value: T,
+    created_time: Duration,
+    write_policy: &WritePolicy<T>,
+    previous_version: Option<u64>,
+    reporter: &dyn Reporter,
+) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let Some(metadata) = write_policy.metadata(&value, created_time, previous_version) else {
+        return Ok(false);
+    };
+
+    let entry = CacheEntry::with_metadata(value, metadata);
+    store_entry(cache, key, entry, write_policy, previous_version, reporter).await
+}
+
+/// Store an entry, with compare-and-set if the write policy asks for it
+///
+/// The reporter is notified if the entry was written.
+async fn store_entry<T, C>(
+    cache: &C,
+    key: &str,
+    entry: CacheEntry<T>,
+    write_policy: &WritePolicy<T>,
+    previous_version: Option<u64>,
+    reporter: &dyn Reporter,
+) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let metadata = entry.metadata.clone();
+    let written = if write_policy.compare_and_set {
+        cache.set_if_version(key, entry, previous_version).await?
+    } else {
+        cache.set(key, entry).await.map(|()| true)?
+    };
+
+    if written {
+        reporter.on_write(key, &metadata);
+    }
+    Ok(written)
+}
+
+/// Metadata of a value that was served without being cached
+///
+/// The TTL is zero, so the value counts as expired right away.
+fn uncached_metadata(now: Duration) -> CacheMetadata {
+    CacheMetadata::with_time(now, Some(Duration::ZERO))
 }
 
 /// Get current time as Duration since UNIX_EPOCH
 fn current_time() -> Duration {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::ZERO)
+    SystemClock.now()
 }
 
 /// Check if a cache entry is expired
@@ -255,6 +1489,40 @@ fn is_expired(metadata: &CacheMetadata, now: Duration) -> bool {
     }
 }
 
+/// Extend the life of a cache entry without fetching its value again.
+///
+/// The entry expires `ttl` after the current time of `clock` afterwards. This
+/// is a shortcut for [`Cache::touch`], e.g. to keep a session alive on activity.
+///
+/// # Returns
+///
+/// Returns `Ok(true)` if the entry was extended and `Ok(false)` if it doesn't exist.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{clock::SystemClock, extend_ttl, Cache, MokaCache};
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MokaCache<String> = MokaCache::new(1000);
+/// cache.put("session-1", "user-1".to_string(), Some(Duration::from_secs(60))).await?;
+///
+/// assert!(extend_ttl(&cache, "session-1", Duration::from_secs(3600), &SystemClock).await?);
+/// assert!(!extend_ttl(&cache, "session-2", Duration::from_secs(3600), &SystemClock).await?);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn extend_ttl<T, C>(cache: &C, key: &str, ttl: Duration, clock: &dyn Clock) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T> + ?Sized,
+{
+    cache.touch(key, ttl, clock.now()).await
+}
+
 /// Soft purge options for controlling soft purging behavior
 pub struct SoftPurgeOptions {
     /// The cache key to soft purge
@@ -262,6 +1530,8 @@ pub struct SoftPurgeOptions {
     /// How long the stale data should remain available after purging
     /// If not specified, defaults to 5 minutes (300 seconds)
     pub stale_while_revalidate: Option<Duration>,
+    /// Clock the purge time is read from
+    pub clock: Arc<dyn Clock>,
 }
 
 impl SoftPurgeOptions {
@@ -270,6 +1540,7 @@ impl SoftPurgeOptions {
         Self {
             key: key.into(),
             stale_while_revalidate: None,
+            clock: Arc::new(SystemClock),
         }
     }
     
@@ -278,6 +1549,15 @@ impl SoftPurgeOptions {
         self.stale_while_revalidate = Some(duration);
         self
     }
+
+    /// Set the clock the purge time is read from (default: [`SystemClock`])
+    pub fn clock<K>(mut self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
 }
 
 /// Soft purge a cache entry.
@@ -330,26 +1610,205 @@ where
 {
     let SoftPurgeOptions {
         key,
-        stale_while_revalidate: _,
+        stale_while_revalidate,
+        clock,
     } = options;
 
-    // Try to get the existing cache entry
-    if let Some(mut entry) = cache.get(&key).await {
-        let now = current_time();
-        
-        // Set TTL to 0 to mark as expired
-        entry.metadata.ttl = Some(Duration::ZERO);
-        
-        // If the entry was already expired, we need to update created_time
-        // to now so that the stale-while-revalidate period starts from now
-        if entry.metadata.is_expired(now) {
-            entry.metadata.created_time = now;
-        }
-        
-        // Store the modified entry back to cache
-        cache.set(&key, entry).await?;
-    }
     // If the entry doesn't exist, soft purging succeeds without doing anything
+    soft_purge_key(cache, &key, clock.now(), stale_while_revalidate).await?;
     
     Ok(())
 }
+
+/// Number of keys `soft_purge_prefix` reads and writes per batch
+const SOFT_PURGE_BATCH_SIZE: usize = 100;
+
+/// How long soft purged entries stay available as stale data by default
+const DEFAULT_SOFT_PURGE_SWR: Duration = Duration::from_secs(300);
+
+/// Result of soft purging multiple cache entries
+#[derive(Debug, Default)]
+pub struct SoftPurgeReport {
+    /// Number of entries that were soft purged
+    pub purged: usize,
+    /// Keys that could not be soft purged, together with the error that occurred
+    pub failed: Vec<(String, CachifiedError)>,
+}
+
+/// Soft purge all cache entries whose key starts with a prefix.
+///
+/// This works like [`soft_purge`], but treats the `key` of the options as a key
+/// prefix and soft purges every matching entry. Matching keys are found with
+/// [`Cache::keys`], so the cache must support listing its keys. They are
+/// purged in batches with [`soft_purge_many`], so backends batching
+/// [`Cache::get_many`] and [`Cache::set_many`] need few round trips.
+///
+/// Failing to purge a single entry doesn't abort the operation. Instead, the
+/// returned [`SoftPurgeReport`] lists how many entries were purged and which
+/// ones failed.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{soft_purge_prefix, SoftPurgeOptions, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MokaCache<String> = MokaCache::new(1000);
+///
+/// // Soft purge all entries of tenant 42
+/// let report = soft_purge_prefix(&cache, SoftPurgeOptions::new("tenant-42:")).await?;
+/// println!("Purged {} entries", report.purged);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn soft_purge_prefix<T, C>(cache: &C, options: SoftPurgeOptions) -> Result<SoftPurgeReport>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    let SoftPurgeOptions {
+        key: prefix,
+        stale_while_revalidate,
+        clock,
+    } = options;
+
+    let keys: Vec<String> = cache
+        .keys()
+        .await?
+        .into_iter()
+        .filter(|key| key.starts_with(&prefix))
+        .collect();
+    let mut report = SoftPurgeReport::default();
+
+    for batch in keys.chunks(SOFT_PURGE_BATCH_SIZE) {
+        let options = batch.iter().map(|key| SoftPurgeOptions {
+            key: key.clone(),
+            stale_while_revalidate,
+            clock: clock.clone(),
+        });
+        for (key, outcome) in soft_purge_many(cache, options).await {
+            match outcome {
+                SoftPurgeOutcome::Purged => report.purged += 1,
+                // The entry disappeared between listing and purging
+                SoftPurgeOutcome::Missing => {}
+                SoftPurgeOutcome::Failed(e) => report.failed.push((key, e)),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Outcome of soft purging one of several cache entries
+#[derive(Debug)]
+pub enum SoftPurgeOutcome {
+    /// The entry was soft purged
+    Purged,
+    /// There was no entry to soft purge
+    Missing,
+    /// Storing the soft purged entry failed
+    Failed(CachifiedError),
+}
+
+/// Soft purge several cache entries in one call.
+///
+/// This works like calling [`soft_purge`] for each of the options, but reads
+/// all entries with [`Cache::get_many`] and writes them back with
+/// [`Cache::set_many`], which backends such as Redis batch into a single round
+/// trip each. This suits invalidating a known set of keys after a write, e.g.
+/// both a list and the item that changed.
+///
+/// # Returns
+///
+/// Returns the outcome for every key, in the order of `options`.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{soft_purge_many, SoftPurgeOptions, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() {
+/// let cache: MokaCache<String> = MokaCache::new(1000);
+///
+/// // After updating item 42, purge both the item and the list containing it
+/// let outcomes = soft_purge_many(&cache, [
+///     SoftPurgeOptions::new("items"),
+///     SoftPurgeOptions::new("item:42"),
+/// ]).await;
+///
+/// for (key, outcome) in outcomes {
+///     println!("{key}: {outcome:?}");
+/// }
+/// # }
+/// ```
+pub async fn soft_purge_many<T, C, I>(cache: &C, options: I) -> Vec<(String, SoftPurgeOutcome)>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+    I: IntoIterator<Item = SoftPurgeOptions>,
+{
+    let options: Vec<SoftPurgeOptions> = options.into_iter().collect();
+    let key_refs: Vec<&str> = options.iter().map(|options| options.key.as_str()).collect();
+    let entries = cache.get_many(&key_refs).await;
+
+    let purged: Vec<(String, CacheEntry<T>)> = options
+        .iter()
+        .zip(entries.iter())
+        .filter_map(|(options, entry)| {
+            let entry = soft_purged(entry.clone()?, options.clock.now(), options.stale_while_revalidate);
+            Some((options.key.clone(), entry))
+        })
+        .collect();
+    let mut results = cache.set_many(purged).await.into_iter();
+
+    options
+        .into_iter()
+        .zip(entries)
+        .map(|(SoftPurgeOptions { key, .. }, entry)| {
+            let outcome = match entry.and_then(|_| results.next()) {
+                None => SoftPurgeOutcome::Missing,
+                Some(Ok(())) => SoftPurgeOutcome::Purged,
+                Some(Err(e)) => SoftPurgeOutcome::Failed(e),
+            };
+            (key, outcome)
+        })
+        .collect()
+}
+
+/// Soft purge a single cache entry, returning whether it existed
+async fn soft_purge_key<T, C>(cache: &C, key: &str, now: Duration, swr: Option<Duration>) -> Result<bool>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<T>,
+{
+    // Try to get the existing cache entry
+    let Some(entry) = cache.get(key).await else {
+        return Ok(false);
+    };
+
+    // Store the modified entry back to cache
+    cache.set(key, soft_purged(entry, now, swr)).await?;
+
+    Ok(true)
+}
+
+/// Mark a cache entry as expired while keeping it available as stale data
+fn soft_purged<T>(mut entry: CacheEntry<T>, now: Duration, swr: Option<Duration>) -> CacheEntry<T> {
+    // Set TTL to 0 to mark as expired
+    entry.metadata.ttl = Some(Duration::ZERO);
+
+    // Backends evict at the end of the stale window, so it has to be stored
+    entry.metadata.swr = Some(swr.unwrap_or(DEFAULT_SOFT_PURGE_SWR));
+    
+    // If the entry was already expired, we need to update created_time
+    // to now so that the stale-while-revalidate period starts from now
+    if entry.metadata.is_expired(now) {
+        entry.metadata.created_time = now;
+    }
+
+    entry
+}
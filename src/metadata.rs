@@ -1,11 +1,35 @@
 //! Cache metadata and entry structures.
+//!
+//! The expiry math (`is_expired`, `age`, `expires_at`, `remaining_ttl`) takes
+//! the current time as a `Duration` since UNIX_EPOCH and never reads the clock.
+//! Only the constructors that create entries "now" and the `SystemTime`
+//! conversions use `std::time::SystemTime`. Use [`CacheMetadata::with_time`]
+//! and [`CacheEntry::with_time`] on targets where the clock comes from
+//! elsewhere, e.g. wasm or embedded runtimes.
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// Metadata associated with a cache entry.
+///
+/// The `version`, `refresh_failures`, `last_refresh_error` and `swr` fields
+/// were added after `created_time` and `ttl`, which is a breaking change for
+/// code building the metadata with a struct literal. More fields may follow,
+/// so create metadata with [`CacheMetadata::new`] or [`CacheMetadata::with_time`]
+/// and the `with_*` methods, or whole entries with [`CacheEntry::builder`].
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::CacheMetadata;
+/// use std::time::Duration;
+///
+/// let metadata = CacheMetadata::with_time(Duration::from_secs(1_000), Some(Duration::from_secs(60)))
+///     .with_swr(Some(Duration::from_secs(300)));
+/// assert_eq!(metadata.version, 0);
+/// ```
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CacheMetadata {
@@ -13,17 +37,33 @@ pub struct CacheMetadata {
     pub created_time: Duration,
     /// Time-to-live for the cache entry
     pub ttl: Option<Duration>,
+    /// Version of the cache entry, incremented by every write of `cachified`
+    ///
+    /// Used by [`Cache::set_if_version`](crate::Cache::set_if_version) to detect
+    /// entries that were overwritten concurrently. Entries stored before this
+    /// field existed read as version 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub version: u64,
+    /// Number of background refreshes of this entry that failed in a row
+    ///
+    /// Reset whenever a fresh value is written.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub refresh_failures: u32,
+    /// Error message of the last failed background refresh, if any
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub last_refresh_error: Option<String>,
+    /// How long after expiring the entry may still be served stale while revalidating
+    ///
+    /// Written by `cachified` from its `stale_while_revalidate` option, so
+    /// backends with their own expiry can keep the entry for that long.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub swr: Option<Duration>,
 }
 
 impl CacheMetadata {
     /// Create new cache metadata with current time
     pub fn new(ttl: Option<Duration>) -> Self {
-        Self {
-            created_time: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::ZERO),
-            ttl,
-        }
+        Self::from_system_time(SystemTime::now(), ttl)
     }
     
     /// Create cache metadata with specific creation time
@@ -31,8 +71,36 @@ impl CacheMetadata {
         Self {
             created_time,
             ttl,
+            version: 0,
+            refresh_failures: 0,
+            last_refresh_error: None,
+            swr: None,
         }
     }
+
+    /// Set the version of this cache entry
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the stale-while-revalidate window of this cache entry
+    pub fn with_swr(mut self, swr: Option<Duration>) -> Self {
+        self.swr = swr;
+        self
+    }
+
+    /// Create cache metadata with a specific creation time given as `SystemTime`
+    ///
+    /// Times before the UNIX epoch are clamped to the epoch.
+    pub fn from_system_time(created_at: SystemTime, ttl: Option<Duration>) -> Self {
+        Self::with_time(since_epoch(created_at), ttl)
+    }
+
+    /// Get the creation time of this cache entry as `SystemTime`
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + self.created_time
+    }
     
     /// Check if this cache entry is expired at the given time
     pub fn is_expired(&self, now: Duration) -> bool {
@@ -42,6 +110,11 @@ impl CacheMetadata {
             false // No TTL means never expires
         }
     }
+
+    /// Check if this cache entry is expired at the given `SystemTime`
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.is_expired(since_epoch(now))
+    }
     
     /// Get the expiration time for this cache entry
     pub fn expires_at(&self) -> Option<Duration> {
@@ -52,6 +125,29 @@ impl CacheMetadata {
     pub fn age(&self, now: Duration) -> Duration {
         now.saturating_sub(self.created_time)
     }
+
+    /// Get the time this cache entry has left until it expires at the given time
+    ///
+    /// Returns `None` if the entry never expires and `Duration::ZERO` if it
+    /// has already expired.
+    pub fn remaining_ttl(&self, now: Duration) -> Option<Duration> {
+        self.expires_at().map(|expires_at| expires_at.saturating_sub(now))
+    }
+
+    /// Get the time until which this cache entry may be served stale
+    ///
+    /// This is the expiration time plus the stale-while-revalidate window, or
+    /// just the expiration time without a window. Returns `None` if the entry
+    /// never expires.
+    pub fn stale_until(&self) -> Option<Duration> {
+        self.expires_at()
+            .map(|expires_at| expires_at + self.swr.unwrap_or_default())
+    }
+
+    /// Check if this cache entry is expired but still within its stale-while-revalidate window at the given time
+    pub fn is_stale_servable(&self, now: Duration) -> bool {
+        self.is_expired(now) && self.stale_until().is_some_and(|stale_until| now < stale_until)
+    }
 }
 
 /// A cache entry containing both the value and its metadata.
@@ -73,6 +169,39 @@ impl<T> CacheEntry<T> {
         }
     }
     
+    /// Start building a cache entry for the given value
+    ///
+    /// The entry never expires and is created when [`CacheEntryBuilder::build`]
+    /// is called unless configured otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::CacheEntry;
+    /// use std::time::Duration;
+    ///
+    /// // An entry that expired a minute ago
+    /// let entry = CacheEntry::builder("value".to_string())
+    ///     .ttl(Duration::from_secs(60))
+    ///     .created_ago(Duration::from_secs(120))
+    ///     .build();
+    /// assert!(entry.is_expired_at(std::time::SystemTime::now()));
+    /// ```
+    pub fn builder(value: T) -> CacheEntryBuilder<T> {
+        CacheEntryBuilder {
+            value,
+            ttl: None,
+            created_time: None,
+        }
+    }
+
+    /// Create a new cache entry with the given value, creation time and TTL
+    ///
+    /// Unlike [`CacheEntry::new`], this doesn't read the system clock.
+    pub fn with_time(value: T, created_time: Duration, ttl: Option<Duration>) -> Self {
+        Self::with_metadata(value, CacheMetadata::with_time(created_time, ttl))
+    }
+
     /// Create a new cache entry with specific metadata
     pub fn with_metadata(value: T, metadata: CacheMetadata) -> Self {
         Self {
@@ -85,6 +214,11 @@ impl<T> CacheEntry<T> {
     pub fn is_expired(&self, now: Duration) -> bool {
         self.metadata.is_expired(now)
     }
+
+    /// Check if this cache entry is expired at the given `SystemTime`
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.metadata.is_expired_at(now)
+    }
     
     /// Get the expiration time for this cache entry
     pub fn expires_at(&self) -> Option<Duration> {
@@ -97,6 +231,131 @@ impl<T> CacheEntry<T> {
     }
 }
 
+/// Where a value returned by `cachified_with_status` came from
+///
+/// Maps directly onto headers like `X-Cache: HIT` or `X-Cache: MISS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Served from the cache without refreshing it
+    Hit,
+    /// Fetched fresh, including fallback values served because fetching failed
+    Miss,
+    /// Served from the cache after expiring, while it is refreshed in the background
+    Stale,
+    /// Served from the cache before expiring, while it is refreshed in the background
+    ///
+    /// Happens with `always_revalidate` and early refreshes.
+    RevalidatedBackground,
+    /// Served from the cache because fetching a fresh value failed
+    ///
+    /// Happens with `fallback_to_cache` and `stale_if_error`.
+    FallbackStale,
+}
+
+impl CacheStatus {
+    /// Check whether the value was served from the cache
+    pub fn is_cached(&self) -> bool {
+        !matches!(self, CacheStatus::Miss)
+    }
+}
+
+/// Information about how a value returned by `cachified_with_metadata` was obtained
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheInfo {
+    /// Whether the value was served from the cache instead of being fetched
+    pub cached: bool,
+    /// Whether the value is the configured fallback value because fetching failed
+    pub fallback: bool,
+    /// How long ago the served value expired, `None` if it isn't stale
+    pub stale_since: Option<Duration>,
+    /// Number of background refreshes of the entry that failed in a row
+    pub consecutive_refresh_failures: u32,
+    /// Error message of the last failed background refresh, if any
+    pub last_refresh_error: Option<String>,
+}
+
+impl CacheInfo {
+    /// Information about a freshly fetched value
+    pub(crate) fn fresh() -> Self {
+        Self::default()
+    }
+
+    /// Information about a value served from a cache entry at the given time
+    pub(crate) fn cached(metadata: &CacheMetadata, now: Duration) -> Self {
+        Self {
+            cached: true,
+            fallback: false,
+            stale_since: metadata
+                .expires_at()
+                .filter(|expires_at| now >= *expires_at)
+                .map(|expires_at| now - expires_at),
+            consecutive_refresh_failures: metadata.refresh_failures,
+            last_refresh_error: metadata.last_refresh_error.clone(),
+        }
+    }
+
+    /// Check whether the served value is stale
+    pub fn is_stale(&self) -> bool {
+        self.stale_since.is_some()
+    }
+}
+
+/// Builder for a `CacheEntry`, created by [`CacheEntry::builder`]
+#[derive(Debug, Clone)]
+pub struct CacheEntryBuilder<T> {
+    value: T,
+    ttl: Option<Duration>,
+    created_time: Option<Duration>,
+}
+
+impl<T> CacheEntryBuilder<T> {
+    /// Set the time-to-live of the entry
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Create the entry now (default)
+    pub fn created_now(mut self) -> Self {
+        self.created_time = None;
+        self
+    }
+
+    /// Set the creation time as `SystemTime`
+    ///
+    /// Times before the UNIX epoch are clamped to the epoch.
+    pub fn created_at(mut self, created_at: SystemTime) -> Self {
+        self.created_time = Some(since_epoch(created_at));
+        self
+    }
+
+    /// Set the creation time as duration since UNIX_EPOCH
+    pub fn created_time(mut self, created_time: Duration) -> Self {
+        self.created_time = Some(created_time);
+        self
+    }
+
+    /// Set the creation time to the given duration before now
+    pub fn created_ago(self, age: Duration) -> Self {
+        let created_at = SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH);
+        self.created_at(created_at)
+    }
+
+    /// Build the cache entry
+    pub fn build(self) -> CacheEntry<T> {
+        let created_time = self
+            .created_time
+            .unwrap_or_else(|| since_epoch(SystemTime::now()));
+
+        CacheEntry::with_metadata(self.value, CacheMetadata::with_time(created_time, self.ttl))
+    }
+}
+
+/// Convert a `SystemTime` to a `Duration` since UNIX_EPOCH
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +379,41 @@ mod tests {
         assert!(metadata.is_expired(expiry));
     }
     
+    #[test]
+    fn test_cache_metadata_remaining_ttl() {
+        let metadata = CacheMetadata::with_time(Duration::from_secs(100), Some(Duration::from_secs(60)));
+
+        assert_eq!(metadata.remaining_ttl(Duration::from_secs(130)), Some(Duration::from_secs(30)));
+        assert_eq!(metadata.remaining_ttl(Duration::from_secs(200)), Some(Duration::ZERO));
+        assert_eq!(CacheMetadata::new(None).remaining_ttl(Duration::from_secs(200)), None);
+    }
+
+    #[test]
+    fn test_cache_metadata_stale_window() {
+        let metadata = CacheMetadata::with_time(Duration::from_secs(100), Some(Duration::from_secs(60)))
+            .with_swr(Some(Duration::from_secs(30)));
+
+        assert_eq!(metadata.stale_until(), Some(Duration::from_secs(190)));
+        assert!(!metadata.is_stale_servable(Duration::from_secs(159)));
+        assert!(metadata.is_stale_servable(Duration::from_secs(160)));
+        assert!(!metadata.is_stale_servable(Duration::from_secs(190)));
+
+        // Without a window, entries are never servable stale
+        let metadata = metadata.with_swr(None);
+        assert_eq!(metadata.stale_until(), Some(Duration::from_secs(160)));
+        assert!(!metadata.is_stale_servable(Duration::from_secs(160)));
+        assert_eq!(CacheMetadata::new(None).stale_until(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_cache_metadata_without_swr_deserializes() {
+        let metadata: CacheMetadata =
+            serde_json::from_str(r#"{"created_time":{"secs":100,"nanos":0},"ttl":null,"version":3}"#).unwrap();
+        assert_eq!(metadata.swr, None);
+        assert_eq!(metadata.version, 3);
+    }
+
     #[test]
     fn test_cache_metadata_no_ttl() {
         let metadata = CacheMetadata::new(None);
@@ -142,4 +436,65 @@ mod tests {
         let now = entry.metadata.created_time + Duration::from_secs(10);
         assert_eq!(entry.age(now), Duration::from_secs(10));
     }
+
+    #[test]
+    fn test_cache_info() {
+        let mut metadata = CacheMetadata::with_time(Duration::from_secs(100), Some(Duration::from_secs(60)));
+
+        let info = CacheInfo::cached(&metadata, Duration::from_secs(130));
+        assert!(info.cached);
+        assert!(!info.is_stale());
+
+        metadata.refresh_failures = 2;
+        metadata.last_refresh_error = Some("upstream down".to_string());
+        let info = CacheInfo::cached(&metadata, Duration::from_secs(200));
+        assert_eq!(info.stale_since, Some(Duration::from_secs(40)));
+        assert_eq!(info.consecutive_refresh_failures, 2);
+        assert_eq!(info.last_refresh_error.as_deref(), Some("upstream down"));
+
+        assert!(!CacheInfo::fresh().cached);
+    }
+
+    #[test]
+    fn test_cache_entry_with_time() {
+        let created_time = Duration::from_secs(1_000);
+        let entry = CacheEntry::with_time("value", created_time, Some(Duration::from_secs(60)));
+
+        assert_eq!(entry.metadata.created_time, created_time);
+        assert_eq!(entry.expires_at(), Some(Duration::from_secs(1_060)));
+        assert_eq!(entry.age(Duration::from_secs(1_030)), Duration::from_secs(30));
+        assert!(!entry.is_expired(Duration::from_secs(1_059)));
+        assert!(entry.is_expired(Duration::from_secs(1_060)));
+    }
+
+    #[test]
+    fn test_cache_entry_builder() {
+        let ttl = Duration::from_secs(30);
+
+        let entry = CacheEntry::builder(1).ttl(ttl).created_time(Duration::from_secs(100)).build();
+        assert_eq!(entry.metadata, CacheMetadata::with_time(Duration::from_secs(100), Some(ttl)));
+
+        let entry = CacheEntry::builder(1).build();
+        assert_eq!(entry.metadata.ttl, None);
+        assert!(entry.age(since_epoch(SystemTime::now())) < Duration::from_secs(1));
+
+        let entry = CacheEntry::builder(1).ttl(ttl).created_ago(Duration::from_secs(60)).build();
+        assert!(entry.is_expired_at(SystemTime::now()));
+        assert!(!CacheEntry::new(1, Some(ttl)).is_expired_at(SystemTime::now()));
+    }
+
+    #[test]
+    fn test_cache_metadata_system_time() {
+        let created_at = SystemTime::now();
+        let ttl = Duration::from_secs(60);
+        let metadata = CacheMetadata::from_system_time(created_at, Some(ttl));
+
+        assert_eq!(metadata.created_at(), created_at);
+        assert!(!metadata.is_expired_at(created_at));
+        assert!(metadata.is_expired_at(created_at + ttl));
+
+        // Round trips through the Duration representation
+        let same = CacheMetadata::with_time(metadata.created_time, Some(ttl));
+        assert_eq!(same, metadata);
+    }
 }
@@ -3,9 +3,206 @@
 //! This module provides the `CachifiedOptions` struct that configures
 //! how the cachified function behaves.
 
-use crate::{Cache, CheckValue, Result};
+use crate::config::RefreshPriority;
+use crate::validation::{AsyncCheckValue, MigrateValue};
+use crate::{Cache, CacheKey, CachifiedConfig, CachifiedError, CheckValue, ErrorKind, Result};
+use crate::fresh_value::{ArcFn, FetchReason, FreshValueOutcome, OnceFn, OutcomeFn, ReasonFn, TtlFn};
+use crate::clock::{Clock, SystemClock};
+use crate::reporter::stats::{CacheStats, StatsReporter};
+use crate::reporter::{NoopReporter, Reporter};
+use futures_util::future::BoxFuture;
 use std::time::Duration;
 use std::future::Future;
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Function deriving the TTL of a cache entry from its value
+pub type TtlFromValue<T> = Arc<dyn Fn(&T) -> Option<Duration> + Send + Sync>;
+
+/// TTL of negative results, along with the check telling them apart
+#[derive(Clone)]
+pub struct NegativeTtl<T> {
+    /// TTL of entries holding a negative result
+    pub ttl: Duration,
+    /// Function deciding whether a value is a negative result
+    pub is_negative: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+}
+
+/// Function producing a last-resort value when fetching a fresh value fails
+pub type FallbackValue<T> = Arc<dyn Fn() -> T + Send + Sync>;
+
+/// Validator shared between the cached and the fresh value checks
+pub type ValueCheck<T> = Arc<dyn CheckValue<T> + Send + Sync>;
+
+/// Migration upgrading cached values before they are validated
+pub type ValueMigration<T> = Arc<dyn MigrateValue<T> + Send + Sync>;
+
+/// Validator doing I/O, applied to both cached and fresh values
+pub type AsyncValueCheck<T> = Arc<dyn AsyncCheckValue<T>>;
+
+/// Predicate deciding whether a failed fresh value fetch is retried
+pub type RetryIf = Arc<dyn Fn(&CachifiedError) -> bool + Send + Sync>;
+
+/// Hook receiving the background refreshes of a cachified call to run them
+pub type WaitUntil = Arc<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>;
+
+/// Function resolving the cache key at the start of a cachified call
+pub type KeyFn = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = Result<String>> + Send>> + Send + Sync>;
+
+/// How `cachified` handles errors while reading from the cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadErrorPolicy {
+    /// Treat read errors like a cache miss and get a fresh value (default)
+    ///
+    /// The errors are reported with [`Reporter::on_cache_read_error`](crate::Reporter::on_cache_read_error).
+    #[default]
+    TreatAsMiss,
+    /// Return read errors to the caller without getting a fresh value
+    Propagate,
+}
+
+/// How cache keys appear in tracing spans and events
+///
+/// Cache keys often contain sensitive identifiers such as user IDs or emails,
+/// so they are hashed by default. Hashed keys still allow correlating events
+/// for the same key. Only affects output of the "tracing" feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyDisplay {
+    /// Record the raw key
+    Full,
+    /// Record a hash of the key (default)
+    #[default]
+    Hashed,
+    /// Don't record the key at all
+    Redacted,
+}
+
+impl KeyDisplay {
+    /// Format a key according to this policy
+    pub fn display(&self, key: &str) -> String {
+        match self {
+            KeyDisplay::Full => key.to_string(),
+            KeyDisplay::Hashed => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            }
+            KeyDisplay::Redacted => "<redacted>".to_string(),
+        }
+    }
+}
+
+/// How background refreshes of stale values are prioritized
+///
+/// Stale values served early in the stale-while-revalidate window are
+/// refreshed normally, subject to `CachifiedConfig::max_concurrent_refreshes`.
+/// Once a configurable fraction of the window has passed, the refresh becomes
+/// urgent and starts immediately, bypassing the limit on concurrent refreshes.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::SwrPolicy;
+///
+/// // Refresh urgently in the second half of the stale window
+/// let policy = SwrPolicy::urgent_after(0.5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwrPolicy {
+    urgent_after: f64,
+}
+
+impl SwrPolicy {
+    /// Refresh urgently once `fraction` of the stale window has passed
+    ///
+    /// The fraction is clamped to `0.0..=1.0`, where `0.0` makes every stale
+    /// refresh urgent and `1.0` never does.
+    pub fn urgent_after(fraction: f64) -> Self {
+        Self {
+            urgent_after: fraction.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Get the priority of a refresh `elapsed` into a stale window of length `window`
+    pub(crate) fn priority(&self, elapsed: Duration, window: Duration) -> RefreshPriority {
+        if self.urgent_after < 1.0 && elapsed >= window.mul_f64(self.urgent_after) {
+            RefreshPriority::Urgent
+        } else {
+            RefreshPriority::Normal
+        }
+    }
+}
+
+impl Default for SwrPolicy {
+    /// Never refresh urgently
+    fn default() -> Self {
+        Self::urgent_after(1.0)
+    }
+}
+
+/// How failed fresh value fetches are retried
+///
+/// Only blocking fetches are retried. A failed background refresh leaves the
+/// stale value in place, so the next stale read starts another refresh anyway.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::RetryPolicy;
+/// use std::time::Duration;
+///
+/// // Up to 4 attempts, waiting 100ms, 200ms and 400ms in between
+/// let policy = RetryPolicy::new(4, Duration::from_millis(100)).exponential();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    exponential: bool,
+}
+
+impl RetryPolicy {
+    /// Make up to `max_attempts` attempts in total, waiting `backoff` between them
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            exponential: false,
+        }
+    }
+
+    /// Double the backoff after every failed attempt
+    pub fn exponential(mut self) -> Self {
+        self.exponential = true;
+        self
+    }
+
+    /// Get the number of attempts in total, including the first one
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Get the time to wait after the given failed attempt, starting at one
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        if self.exponential {
+            self.backoff
+                .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        } else {
+            self.backoff
+        }
+    }
+
+    /// Check whether an error is retried if no predicate was set with `retry_if`
+    ///
+    /// Only fetch failures are retried, not e.g. timeouts or cancellations.
+    pub(crate) fn retries_by_default(error: &CachifiedError) -> bool {
+        error.kind() == ErrorKind::FreshValue
+    }
+}
 
 /// Configuration options for the cachified function
 ///
@@ -23,23 +220,116 @@ where
     /// The cache key to use for storing/retrieving the value
     pub key: String,
 
+    /// Optional function resolving the cache key, replacing `key`
+    pub key_fn: Option<KeyFn>,
+
     /// Time-to-live for cached values
     pub ttl: Option<Duration>,
 
+    /// Whether values without a TTL are cached without expiry instead of not at all
+    pub no_expiry: bool,
+
+    /// Minimum TTL a value needs to be written to the cache
+    pub min_cacheable_ttl: Option<Duration>,
+
+    /// Maximum TTL of written values, longer TTLs are capped
+    pub max_ttl: Option<Duration>,
+
+    /// Fraction by which TTLs of written values are randomized in either direction
+    pub ttl_jitter: Option<f64>,
+
+    /// Seed making the TTL jitter deterministic
+    pub ttl_jitter_seed: Option<u64>,
+
+    /// Optional function deriving the TTL from a fresh value, overriding `ttl`
+    pub ttl_from_value: Option<TtlFromValue<T>>,
+
+    /// Optional TTL of negative results, overriding `ttl` and `ttl_from_value`
+    pub negative_ttl: Option<NegativeTtl<T>>,
+
+    /// Whether writes are skipped if the entry was changed concurrently
+    pub compare_and_set: bool,
+
     /// Stale-while-revalidate duration
     pub stale_while_revalidate: Option<Duration>,
 
+    /// How background refreshes of stale values are prioritized
+    pub swr_policy: SwrPolicy,
+
+    /// How long before expiry a cache hit starts a background refresh
+    pub early_refresh: Option<Duration>,
+
+    /// How far the clock of the process that wrote an entry may be behind this one
+    pub clock_skew_tolerance: Option<Duration>,
+
+    /// Whether to always serve cached values, even expired ones, and refresh them in the background
+    pub always_revalidate: bool,
+
     /// Whether to force fetching a fresh value, bypassing the cache
     pub force_fresh: bool,
 
+    /// Keys to force fetching a fresh value for, in addition to `force_fresh`
+    pub force_fresh_keys: Option<HashSet<String>>,
+
     /// Whether to fall back to cached values when fresh value fetching fails
     pub fallback_to_cache: bool,
 
-    /// Optional validator for cached values
-    pub check_value: Option<Box<dyn CheckValue<T> + Send + Sync>>,
+    /// Maximum age of cached values served when fresh value fetching fails
+    pub stale_if_error: Option<Duration>,
+
+    /// Optional value served when fetching fails and no cached value can be used
+    pub fallback_value: Option<FallbackValue<T>>,
+
+    /// Point in time after which a blocking fresh value fetch is aborted
+    pub deadline: Option<Instant>,
+
+    /// Token that aborts a blocking fresh value fetch when cancelled
+    pub cancellation_token: Option<CancellationToken>,
+
+    /// Maximum time a single fresh value fetch may take
+    pub fresh_value_timeout: Option<Duration>,
+
+    /// How failed blocking fresh value fetches are retried
+    pub retry: Option<RetryPolicy>,
+
+    /// Optional predicate deciding which fetch errors are retried
+    pub retry_if: Option<RetryIf>,
+
+    /// How errors while reading from the cache are handled
+    pub read_error_policy: ReadErrorPolicy,
+
+    /// How the key appears in tracing output
+    pub key_display: KeyDisplay,
+
+    /// Whether returned errors are wrapped in [`CachifiedError::Stage`]
+    pub error_stage: bool,
+
+    /// Optional migration upgrading cached values written in an older form
+    pub migrate_value: Option<ValueMigration<T>>,
+
+    /// Optional validator deciding whether a cached value is still usable
+    pub check_cached_value: Option<ValueCheck<T>>,
+
+    /// Optional validator deciding whether a fresh value is correct
+    pub check_fresh_value: Option<ValueCheck<T>>,
+
+    /// Optional validator doing I/O, run after the other validators
+    pub check_value_async: Option<AsyncValueCheck<T>>,
 
     /// Function to get a fresh value when cache miss or validation failure occurs
     pub get_fresh_value: F,
+
+    /// Reporter notified about events of this call
+    pub reporter: Arc<dyn Reporter>,
+
+    /// Hook running background refreshes instead of `tokio::spawn`
+    pub wait_until: Option<WaitUntil>,
+
+    /// Configuration shared with other cachified calls
+    pub config: CachifiedConfig,
+
+    /// Clock the call reads the current time from
+    pub clock: Arc<dyn Clock>,
 }
 
 /// Builder for `CachifiedOptions` to make construction more ergonomic
@@ -50,11 +340,43 @@ where
 {
     cache: C,
     key: String,
+    key_fn: Option<KeyFn>,
     ttl: Option<Duration>,
+    no_expiry: bool,
+    min_cacheable_ttl: Option<Duration>,
+    max_ttl: Option<Duration>,
+    ttl_jitter: Option<f64>,
+    ttl_jitter_seed: Option<u64>,
+    ttl_from_value: Option<TtlFromValue<T>>,
+    negative_ttl: Option<NegativeTtl<T>>,
+    compare_and_set: bool,
     stale_while_revalidate: Option<Duration>,
+    swr_policy: SwrPolicy,
+    early_refresh: Option<Duration>,
+    clock_skew_tolerance: Option<Duration>,
+    always_revalidate: bool,
     force_fresh: bool,
+    force_fresh_keys: Option<HashSet<String>>,
     fallback_to_cache: bool,
-    check_value: Option<Box<dyn CheckValue<T> + Send + Sync>>,
+    stale_if_error: Option<Duration>,
+    fallback_value: Option<FallbackValue<T>>,
+    deadline: Option<Instant>,
+    cancellation_token: Option<CancellationToken>,
+    fresh_value_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    retry_if: Option<RetryIf>,
+    read_error_policy: ReadErrorPolicy,
+    key_display: KeyDisplay,
+    error_stage: bool,
+    migrate_value: Option<ValueMigration<T>>,
+    check_cached_value: Option<ValueCheck<T>>,
+    check_fresh_value: Option<ValueCheck<T>>,
+    check_value_async: Option<AsyncValueCheck<T>>,
+    reporter: Arc<dyn Reporter>,
+    stats: Option<Arc<CacheStats>>,
+    wait_until: Option<WaitUntil>,
+    config: CachifiedConfig,
+    clock: Arc<dyn Clock>,
 }
 
 impl<T, C> CachifiedOptionsBuilder<T, C>
@@ -63,48 +385,560 @@ where
     C: Cache<T> + Clone,
 {
     /// Create a new builder with required parameters
-    pub fn new(cache: C, key: impl Into<String>) -> Self {
+    ///
+    /// The key is either a plain string, used verbatim, or a [`CacheKey`].
+    pub fn new(cache: C, key: impl Into<CacheKey>) -> Self {
         Self {
             cache,
-            key: key.into(),
+            key: key.into().into_string(),
+            key_fn: None,
             ttl: None,
+            no_expiry: false,
+            min_cacheable_ttl: None,
+            max_ttl: None,
+            ttl_jitter: None,
+            ttl_jitter_seed: None,
+            ttl_from_value: None,
+            negative_ttl: None,
+            compare_and_set: false,
             stale_while_revalidate: None,
+            swr_policy: SwrPolicy::default(),
+            early_refresh: None,
+            clock_skew_tolerance: None,
+            always_revalidate: false,
             force_fresh: false,
+            force_fresh_keys: None,
             fallback_to_cache: false,
-            check_value: None,
+            stale_if_error: None,
+            fallback_value: None,
+            deadline: None,
+            cancellation_token: None,
+            fresh_value_timeout: None,
+            retry: None,
+            retry_if: None,
+            read_error_policy: ReadErrorPolicy::default(),
+            key_display: KeyDisplay::default(),
+            error_stage: false,
+            migrate_value: None,
+            check_cached_value: None,
+            check_fresh_value: None,
+            check_value_async: None,
+            reporter: Arc::new(NoopReporter),
+            stats: None,
+            wait_until: None,
+            config: CachifiedConfig::default(),
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Resolve the cache key at the start of the call instead of up front
+    ///
+    /// The function is awaited before the cache is read and its result replaces
+    /// the key passed to [`new`](Self::new). If it fails, `cachified` returns
+    /// the error without touching the cache or fetching a fresh value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cachified, CachifiedOptionsBuilder, MokaCache};
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # async fn resolve_tenant() -> cachified::Result<String> { Ok("acme".to_string()) }
+    /// # #[cfg(feature = "moka")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = MokaCache::new(1000);
+    ///
+    /// let settings: String = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "settings")
+    ///         .key_fn(|| async { Ok(format!("settings:{}", resolve_tenant().await?)) })
+    ///         .get_fresh_value(|| async { Ok("tenant settings".to_string()) })
+    /// ).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn key_fn<K, Fut>(mut self, key_fn: K) -> Self
+    where
+        K: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        self.key_fn = Some(Box::new(move || Box::pin(key_fn())));
+        self
+    }
+
+    /// Derive the cache key from a serializable input
+    ///
+    /// The key passed to [`new`](Self::new) becomes the prefix of a key computed
+    /// with [`stable_key`](crate::key::stable_key), so distinct inputs never
+    /// share a key and equal inputs always do. If the input can't be serialized,
+    /// `cachified` returns the error without touching the cache.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cachified, CachifiedOptionsBuilder, MokaCache};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Search {
+    ///     query: String,
+    ///     page: u32,
+    /// }
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = MokaCache::new(1000);
+    /// let search = Search { query: "rust".to_string(), page: 1 };
+    ///
+    /// let results: Vec<String> = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "search:")
+    ///         .key_from_input(&search)
+    ///         .get_fresh_value(|| async { Ok(vec!["cachified".to_string()]) })
+    /// ).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn key_from_input<I>(mut self, input: &I) -> Self
+    where
+        I: serde::Serialize + ?Sized,
+    {
+        let key = crate::key::stable_key(&self.key, input);
+        self.key_fn = Some(Box::new(move || Box::pin(async move { key })));
+        self
+    }
+
     /// Set the time-to-live for cached values
+    ///
+    /// Without a TTL, whether set here, returned by the fresh value function
+    /// or derived from the value, fresh values are returned without being
+    /// cached. Use [`no_expiry`](Self::no_expiry) to cache them forever instead.
     pub fn ttl(mut self, ttl: Duration) -> Self {
         self.ttl = Some(ttl);
         self
     }
 
+    /// Cache values without expiry, replacing any TTL set with [`ttl`](Self::ttl)
+    ///
+    /// Entries are written without a TTL and stay valid until they are
+    /// removed, evicted or soft purged. TTLs derived from the value, returned by
+    /// the fresh value function or set for negative results still take
+    /// precedence, and [`max_ttl`](Self::max_ttl) still caps the lifetime.
+    pub fn no_expiry(mut self) -> Self {
+        self.ttl = None;
+        self.no_expiry = true;
+        self
+    }
+
+    /// Set the minimum TTL a value needs to be written to the cache
+    ///
+    /// If the effective TTL is below this floor, the fresh value is returned
+    /// without being cached.
+    pub fn min_cacheable_ttl(mut self, min: Duration) -> Self {
+        self.min_cacheable_ttl = Some(min);
+        self
+    }
+
+    /// Set the maximum TTL of written values
+    ///
+    /// Longer TTLs are capped to this value when the entry is written.
+    pub fn max_ttl(mut self, max: Duration) -> Self {
+        self.max_ttl = Some(max);
+        self
+    }
+
+    /// Randomize the TTL of written entries by up to `fraction` in either direction
+    ///
+    /// With a fraction of `0.1`, every entry is written with a TTL between 90%
+    /// and 110% of its TTL, so keys warmed together don't all expire at the
+    /// same moment. The fraction is clamped below `1.0` and jittered TTLs are
+    /// at least a millisecond long. Jittered TTLs are still capped at
+    /// `max_ttl`. The stale-while-revalidate window isn't jittered
+    /// itself, it starts whenever the jittered TTL expires.
+    pub fn ttl_jitter(mut self, fraction: f64) -> Self {
+        self.ttl_jitter = Some(fraction);
+        self
+    }
+
+    /// Seed the TTL jitter, making the randomized TTLs deterministic for tests
+    pub fn ttl_jitter_seed(mut self, seed: u64) -> Self {
+        self.ttl_jitter_seed = Some(seed);
+        self
+    }
+
+    /// Derive the TTL of written entries from the fresh value
+    ///
+    /// The function is evaluated after a successful fresh fetch, e.g. to cache a
+    /// value until an expiry timestamp it contains. If it returns `Some`, the
+    /// returned TTL overrides the one set with [`ttl`](Self::ttl). If it returns
+    /// `None`, the configured TTL is used. The `min_cacheable_ttl` and `max_ttl`
+    /// clamps are applied to the result either way.
+    pub fn ttl_from_value<V>(mut self, ttl_from_value: V) -> Self
+    where
+        V: Fn(&T) -> Option<Duration> + Send + Sync + 'static,
+    {
+        self.ttl_from_value = Some(Arc::new(ttl_from_value));
+        self
+    }
+
+    /// Cache negative results, such as "not found", with their own TTL
+    ///
+    /// Values for which `is_negative` returns `true` are written with `ttl`
+    /// instead of the TTL set with [`ttl`](Self::ttl) or derived with
+    /// [`ttl_from_value`](Self::ttl_from_value). Cached negative results are
+    /// served like any other value until they expire, so known-missing keys
+    /// don't hit the upstream on every call. Validators see negative results
+    /// too, so they must accept them or the result is refetched or rejected.
+    /// For `Option` values, [`negative_ttl`](Self::negative_ttl) is a shortcut
+    /// treating `None` as the negative result.
+    pub fn negative_ttl_if<P>(mut self, ttl: Duration, is_negative: P) -> Self
+    where
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.negative_ttl = Some(NegativeTtl {
+            ttl,
+            is_negative: Arc::new(is_negative),
+        });
+        self
+    }
+
+    /// Only write fresh values if the cached entry wasn't changed meanwhile
+    ///
+    /// When enabled, writes use [`Cache::set_if_version`] with the version of
+    /// the entry that was read before fetching the fresh value. If another
+    /// task or process wrote the key in the meantime, the newer entry is kept
+    /// and the fetched value is only returned to the caller. This matters most
+    /// for background refreshes of caches shared between processes.
+    pub fn compare_and_set(mut self, compare_and_set: bool) -> Self {
+        self.compare_and_set = compare_and_set;
+        self
+    }
+
     /// Set the stale-while-revalidate duration
+    ///
+    /// The duration is stored with written entries, so later calls without
+    /// their own stale-while-revalidate duration use the one of the entry.
     pub fn stale_while_revalidate(mut self, duration: Duration) -> Self {
         self.stale_while_revalidate = Some(duration);
         self
     }
 
+    /// Set how background refreshes of stale values are prioritized
+    pub fn swr_policy(mut self, policy: SwrPolicy) -> Self {
+        self.swr_policy = policy;
+        self
+    }
+
+    /// Refresh cached values in the background shortly before they expire
+    ///
+    /// A cache hit within the last `threshold` of its TTL serves the still
+    /// fresh value immediately and starts a background refresh, so callers
+    /// after expiry find a fresh value instead of a stale one. Only one early
+    /// refresh per key runs at a time across all calls sharing a
+    /// [`CachifiedConfig`]; hits while it runs don't start another one.
+    pub fn early_refresh(mut self, threshold: Duration) -> Self {
+        self.early_refresh = Some(threshold);
+        self
+    }
+
+    /// Tolerate clocks of writing processes that are behind this one
+    ///
+    /// The creation time of an entry comes from the clock of the process that
+    /// wrote it. If that clock is behind the clock of this process, e.g. with
+    /// a Redis cache shared between machines, entries look older than they are
+    /// and expire early. With a tolerance, entries are considered fresh until
+    /// `created_time + ttl + tolerance`, and the stale-while-revalidate window
+    /// is shifted by the same amount. Clocks ahead of this one make entries
+    /// expire late instead, which a tolerance can't correct.
+    pub fn clock_skew_tolerance(mut self, tolerance: Duration) -> Self {
+        self.clock_skew_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Set whether to always serve cached values and refresh them in the background
+    ///
+    /// When enabled, any cached value is returned immediately, regardless of its
+    /// TTL or the stale-while-revalidate window, and a background refresh is
+    /// started on every call. Only a cache miss (or a value failing validation)
+    /// waits for a fresh value.
+    pub fn always_revalidate(mut self, always: bool) -> Self {
+        self.always_revalidate = always;
+        self
+    }
+
     /// Set whether to force fetching fresh values
     pub fn force_fresh(mut self, force: bool) -> Self {
         self.force_fresh = force;
         self
     }
 
+    /// Force fetching fresh values only if the key of the call is in `keys`
+    ///
+    /// This suits options built by a shared helper from a request-scoped set
+    /// of keys to bust, e.g. parsed from a debug header. The key is checked
+    /// once it is resolved, so this works with [`key_fn`](Self::key_fn) too.
+    /// [`force_fresh(true)`](Self::force_fresh) forces every key regardless.
+    pub fn force_fresh_keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.force_fresh_keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
     /// Set whether to fall back to cache on fresh value failure
     pub fn fallback_to_cache(mut self, fallback: bool) -> Self {
         self.fallback_to_cache = fallback;
         self
     }
 
-    /// Set a validator for cached values
+    /// Serve cached values up to `max_age` old when fetching a fresh value fails
+    ///
+    /// Unlike [`fallback_to_cache`](Self::fallback_to_cache), which serves
+    /// cached values of any age, values whose age (measured from their
+    /// creation time) exceeds `max_age` are not served and the error is
+    /// returned instead. Cached values still have to pass validation.
+    pub fn stale_if_error(mut self, max_age: Duration) -> Self {
+        self.stale_if_error = Some(max_age);
+        self
+    }
+
+    /// Serve a default value when fetching a fresh value fails
+    ///
+    /// This is the last resort after [`fallback_to_cache`](Self::fallback_to_cache)
+    /// and [`stale_if_error`](Self::stale_if_error): it is only used if the
+    /// fetch fails and no usable cached value exists. The value is returned to
+    /// the caller but never cached, and the reporter is notified with
+    /// [`Reporter::on_fallback_value`].
+    pub fn fallback_value(self, value: T) -> Self {
+        self.fallback_value_fn(move || value.clone())
+    }
+
+    /// Like [`fallback_value`](Self::fallback_value), but computes the value
+    /// only when it is needed
+    pub fn fallback_value_fn<V>(mut self, fallback: V) -> Self
+    where
+        V: Fn() -> T + Send + Sync + 'static,
+    {
+        self.fallback_value = Some(Arc::new(fallback));
+        self
+    }
+
+    /// Abort a blocking fresh value fetch once the deadline passes
+    ///
+    /// This ties the call to a request budget. When the deadline passes while
+    /// waiting for a fresh value, the fetch is dropped and the call behaves as
+    /// if the fetch failed with a [`CachifiedError::Cancelled`] error: the cached
+    /// value is served if `fallback_to_cache` is enabled, otherwise the error is
+    /// returned. Background refreshes are not affected.
+    pub fn deadline(mut self, deadline: impl Into<Instant>) -> Self {
+        self.deadline = Some(deadline.into());
+        self
+    }
+
+    /// Abort a blocking fresh value fetch when the token is cancelled
+    ///
+    /// Behaves like [`deadline`](Self::deadline), but is triggered by an
+    /// externally managed cancellation source.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Give up on a fresh value fetch that takes longer than `timeout`
+    ///
+    /// Unlike [`deadline`](Self::deadline), the timeout is measured from the
+    /// start of each fetch and also applies to background refreshes, so a hung
+    /// upstream can't hold on to a refresh slot forever. A fetch that times out
+    /// fails with a [`CachifiedError::Timeout`] error, which is handled like any
+    /// other fetch failure: the cached value is served if `fallback_to_cache` or
+    /// `stale_if_error` allow it, otherwise the error is returned.
+    pub fn fresh_value_timeout(mut self, timeout: Duration) -> Self {
+        self.fresh_value_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry failed fresh value fetches up to `max_attempts` attempts in total
+    ///
+    /// A blocking fetch that fails with an [`ErrorKind::FreshValue`] error
+    /// is started again after waiting `backoff`, until it succeeds or the
+    /// attempts are used up. Only the error of the last attempt is handled,
+    /// e.g. by falling back to the cache. Use [`retry_policy`](Self::retry_policy)
+    /// for an exponential backoff and [`retry_if`](Self::retry_if) to retry
+    /// other errors. Validation errors of fetched values are never retried.
+    pub fn retry(self, max_attempts: u32, backoff: Duration) -> Self {
+        self.retry_policy(RetryPolicy::new(max_attempts, backoff))
+    }
+
+    /// Set how failed fresh value fetches are retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Decide which fetch errors are retried, replacing the default of only
+    /// retrying fresh value errors
+    ///
+    /// Has no effect without [`retry`](Self::retry) or
+    /// [`retry_policy`](Self::retry_policy).
+    pub fn retry_if<P>(mut self, predicate: P) -> Self
+    where
+        P: Fn(&CachifiedError) -> bool + Send + Sync + 'static,
+    {
+        self.retry_if = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Set how errors while reading from the cache are handled
+    ///
+    /// Only caches that implement [`Cache::try_get`] can report read errors.
+    pub fn read_error_policy(mut self, policy: ReadErrorPolicy) -> Self {
+        self.read_error_policy = policy;
+        self
+    }
+
+    /// Set how the key appears in tracing spans and events
+    pub fn key_display(mut self, key_display: KeyDisplay) -> Self {
+        self.key_display = key_display;
+        self
+    }
+
+    /// Report the stage in which errors occurred
+    ///
+    /// When enabled, returned errors are wrapped in [`CachifiedError::Stage`],
+    /// so [`CachifiedError::stage`] tells e.g. a failed cache read apart from a
+    /// failed fetch. Use [`CachifiedError::inner`] to match on the underlying
+    /// error then. Disabled by default, so errors can be matched directly; the
+    /// stage is recorded on the `cachified` tracing span either way.
+    pub fn error_stage(mut self, error_stage: bool) -> Self {
+        self.error_stage = error_stage;
+        self
+    }
+
+    /// Upgrade cached values written in an older form instead of refetching them
+    ///
+    /// Every cached value is passed to the migration before the validators
+    /// see it. A migrated value is served and written back to the cache,
+    /// keeping the TTL of the entry, so only values that fail to migrate are
+    /// refetched. Fresh values are never migrated.
+    pub fn migrate_value<M>(mut self, migration: M) -> Self
+    where
+        M: MigrateValue<T> + Send + Sync + 'static,
+    {
+        self.migrate_value = Some(Arc::new(migration));
+        self
+    }
+
+    /// Set a validator for both cached and fresh values
+    ///
+    /// This is a shortcut for passing the same validator to
+    /// [`check_cached_value`](Self::check_cached_value) and
+    /// [`check_fresh_value`](Self::check_fresh_value).
     pub fn check_value<V>(mut self, validator: V) -> Self
     where
         V: CheckValue<T> + Send + Sync + 'static,
     {
-        self.check_value = Some(Box::new(validator));
+        let validator: ValueCheck<T> = Arc::new(validator);
+        self.check_cached_value = Some(validator.clone());
+        self.check_fresh_value = Some(validator);
+        self
+    }
+
+    /// Set a validator for cached values
+    ///
+    /// Cached values failing the check are treated as missing and a fresh
+    /// value is fetched instead. This also applies to stale values and to
+    /// values served when falling back to the cache.
+    pub fn check_cached_value<V>(mut self, validator: V) -> Self
+    where
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        self.check_cached_value = Some(Arc::new(validator));
+        self
+    }
+
+    /// Set a validator for fresh values
+    ///
+    /// Fresh values failing the check are not cached and the call returns the
+    /// validation error.
+    pub fn check_fresh_value<V>(mut self, validator: V) -> Self
+    where
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        self.check_fresh_value = Some(Arc::new(validator));
+        self
+    }
+
+    /// Set a validator doing I/O for both cached and fresh values
+    ///
+    /// It runs after the expiry check and the synchronous validators, so it
+    /// is only awaited for values that would otherwise be served: fresh,
+    /// stale within the stale-while-revalidate window, or served when falling
+    /// back to the cache. Cached values failing it are treated as missing and
+    /// a fresh value is fetched instead. Fresh values failing it are not
+    /// cached and the call returns the validation error.
+    pub fn check_value_async<V>(mut self, validator: V) -> Self
+    where
+        V: AsyncCheckValue<T> + 'static,
+    {
+        self.check_value_async = Some(Arc::new(validator));
+        self
+    }
+
+    /// Set a reporter that is notified about cache hits, misses and failures
+    ///
+    /// To share one reporter between calls, pass a clone of an `Arc` holding it.
+    pub fn reporter<R>(mut self, reporter: R) -> Self
+    where
+        R: Reporter + 'static,
+    {
+        self.reporter = Arc::new(reporter);
+        self
+    }
+
+    /// Count hits, misses, stale values, fetch errors and writes of this call
+    ///
+    /// The counters are updated in addition to notifying the reporter. Pass
+    /// clones of the same `Arc` to aggregate the counters of several calls.
+    pub fn stats(mut self, stats: Arc<CacheStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Hand background refreshes to `wait_until` instead of spawning them with `tokio::spawn`
+    ///
+    /// The hook receives every refresh started by this call, e.g. when serving
+    /// a stale value, and is responsible for driving it to completion. Use it
+    /// to register refreshes with a runtime that may otherwise stop before
+    /// they finish, or to collect them in tests and await them deterministically.
+    /// Refreshes handed to the hook still count towards
+    /// [`CachifiedConfig::drain`] until they complete.
+    pub fn wait_until<W>(mut self, wait_until: W) -> Self
+    where
+        W: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+    {
+        self.wait_until = Some(Arc::new(wait_until));
+        self
+    }
+
+    /// Set the configuration shared with other cachified calls
+    pub fn config(mut self, config: CachifiedConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the clock the call reads the current time from (default: [`SystemClock`])
+    ///
+    /// The clock decides whether cached values are expired or stale and
+    /// stamps the creation time of written entries. Pass a
+    /// [`MockClock`](crate::clock::MockClock) to test expiry without sleeping.
+    pub fn clock<K>(mut self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
         self
     }
 
@@ -114,19 +948,271 @@ where
         F: Fn() -> Fut + Send + Sync,
         Fut: Future<Output = Result<T>> + Send,
     {
+        self.build(get_fresh_value)
+    }
+
+    /// Build the final `CachifiedOptions` with a fresh value function that is
+    /// called at most once
+    ///
+    /// The function takes ownership of what it captures, e.g. a connection
+    /// handle or the sender of a channel that isn't `Clone`. A call fetches at
+    /// most once, whether in the foreground or in a background refresh
+    /// triggered by stale-while-revalidate, early refreshes or
+    /// `always_revalidate`. Retries set with [`retry`](Self::retry) are
+    /// ignored, so the error of the only fetch is returned. Any other further
+    /// fetch the call would make, such as one after a stale value failed
+    /// validation, fails with an [`ErrorKind::Other`] error instead, which the
+    /// fallback options handle like any other failed fetch.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use cachified::{cachified, CachifiedOptionsBuilder, HashMapCache};
+    /// use std::time::Duration;
+    /// use tokio::sync::oneshot;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = HashMapCache::new();
+    /// let (sender, receiver) = oneshot::channel();
+    /// sender.send("report".to_string()).unwrap();
+    ///
+    /// let report: String = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "report")
+    ///         .ttl(Duration::from_secs(60))
+    ///         .get_fresh_value_once(move || async move {
+    ///             receiver.await.map_err(cachified::CachifiedError::fresh_value_source)
+    ///         })
+    /// ).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_fresh_value_once<F, Fut>(self, get_fresh_value: F) -> CachifiedOptions<T, OnceFn<F>, C>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        // Retrying would only call the consumed closure again
+        let options = Self { retry: None, ..self };
+        options.build(OnceFn::new(get_fresh_value))
+    }
+
+    /// Build the final `CachifiedOptions` with a fresh value function that
+    /// returns the TTL of the value along with it
+    ///
+    /// This suits values that carry their own expiry, such as HTTP responses
+    /// with `Cache-Control: max-age` or tokens with an `expires_in`. A returned
+    /// TTL overrides the ones set with [`ttl`](Self::ttl) and
+    /// [`ttl_from_value`](Self::ttl_from_value), while `None` falls back to them.
+    /// A TTL of `Duration::ZERO` means the value isn't cached. The
+    /// `min_cacheable_ttl` and `max_ttl` clamps apply either way, and the
+    /// stale-while-revalidate window starts once the returned TTL has elapsed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cachified, CachifiedOptionsBuilder, MokaCache};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = MokaCache::new(1000);
+    ///
+    /// let token: String = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "access-token")
+    ///         .ttl(Duration::from_secs(60))
+    ///         .get_fresh_value_with_ttl(|| async {
+    ///             // e.g. from an OAuth token response with `expires_in: 3600`
+    ///             Ok(("token".to_string(), Some(Duration::from_secs(3600))))
+    ///         })
+    /// ).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_fresh_value_with_ttl<F, Fut>(self, get_fresh_value: F) -> CachifiedOptions<T, TtlFn<F>, C>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(T, Option<Duration>)>> + Send,
+    {
+        self.build(TtlFn(get_fresh_value))
+    }
+
+    /// Build the final `CachifiedOptions` with a fresh value function that
+    /// receives the reason it is called
+    ///
+    /// The [`FetchReason`] tells a cold miss apart from an expired or invalid
+    /// cached value, a forced fetch and a background refresh, e.g. to log them
+    /// differently or to skip expensive checks during background refreshes.
+    pub fn get_fresh_value_with_reason<F, Fut>(self, get_fresh_value: F) -> CachifiedOptions<T, ReasonFn<F>, C>
+    where
+        F: Fn(FetchReason) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        self.build(ReasonFn(get_fresh_value))
+    }
+
+    /// Build the final `CachifiedOptions` with a fresh value function that can
+    /// report that the upstream value is unchanged
+    ///
+    /// When the function returns [`FreshValueOutcome::Unchanged`], the existing
+    /// cached value is kept and its creation time and TTL are refreshed. This is
+    /// useful for ETag/Last-Modified style upstreams. If nothing is cached when
+    /// `Unchanged` is returned, `cachified` fails with a fresh value error.
+    pub fn get_fresh_value_outcome<F, Fut>(
+        self,
+        get_fresh_value: F,
+    ) -> CachifiedOptions<T, OutcomeFn<F>, C>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<FreshValueOutcome<T>>> + Send,
+    {
+        self.build(OutcomeFn(get_fresh_value))
+    }
+
+    pub(crate) fn build<F>(self, get_fresh_value: F) -> CachifiedOptions<T, F, C> {
         CachifiedOptions {
             cache: self.cache,
             key: self.key,
+            key_fn: self.key_fn,
             ttl: self.ttl,
+            no_expiry: self.no_expiry,
+            min_cacheable_ttl: self.min_cacheable_ttl,
+            max_ttl: self.max_ttl,
+            ttl_jitter: self.ttl_jitter,
+            ttl_jitter_seed: self.ttl_jitter_seed,
+            ttl_from_value: self.ttl_from_value,
+            negative_ttl: self.negative_ttl,
+            compare_and_set: self.compare_and_set,
             stale_while_revalidate: self.stale_while_revalidate,
+            swr_policy: self.swr_policy,
+            early_refresh: self.early_refresh,
+            clock_skew_tolerance: self.clock_skew_tolerance,
+            always_revalidate: self.always_revalidate,
             force_fresh: self.force_fresh,
+            force_fresh_keys: self.force_fresh_keys,
             fallback_to_cache: self.fallback_to_cache,
-            check_value: self.check_value,
+            stale_if_error: self.stale_if_error,
+            fallback_value: self.fallback_value,
+            deadline: self.deadline,
+            cancellation_token: self.cancellation_token,
+            fresh_value_timeout: self.fresh_value_timeout,
+            retry: self.retry,
+            retry_if: self.retry_if,
+            read_error_policy: self.read_error_policy,
+            key_display: self.key_display,
+            error_stage: self.error_stage,
+            migrate_value: self.migrate_value,
+            check_cached_value: self.check_cached_value,
+            check_fresh_value: self.check_fresh_value,
+            check_value_async: self.check_value_async,
             get_fresh_value,
+            reporter: match self.stats {
+                Some(stats) => Arc::new(StatsReporter::new(self.reporter, stats)),
+                None => self.reporter,
+            },
+            wait_until: self.wait_until,
+            config: self.config,
+            clock: self.clock,
         }
     }
 }
 
+impl<U, C> CachifiedOptionsBuilder<Option<U>, C>
+where
+    U: Clone + Send + Sync + 'static,
+    C: Cache<Option<U>> + Clone,
+{
+    /// Cache `None` values, i.e. "not found" results, with a shorter TTL
+    ///
+    /// A fresh value function returning `Ok(None)` for a missing entity has
+    /// the `None` cached with `ttl`, while `Some` values keep the regular TTL.
+    /// See [`negative_ttl_if`](Self::negative_ttl_if) for details, e.g. on
+    /// how this interacts with validators such as `NonNullValidator`, which
+    /// reject every negative result.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cachified, CachifiedOptionsBuilder, MokaCache};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # async fn find_user(_id: u32) -> cachified::Result<Option<String>> { Ok(None) }
+    /// # #[cfg(feature = "moka")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = MokaCache::new(1000);
+    ///
+    /// let user: Option<String> = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "user-42")
+    ///         .ttl(Duration::from_secs(3600))
+    ///         // Users that don't exist yet may be created soon
+    ///         .negative_ttl(Duration::from_secs(30))
+    ///         .get_fresh_value(|| find_user(42))
+    /// ).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn negative_ttl(self, ttl: Duration) -> Self {
+        self.negative_ttl_if(ttl, Option::is_none)
+    }
+}
+
+impl<U, C> CachifiedOptionsBuilder<Arc<U>, C>
+where
+    U: ?Sized + Send + Sync + 'static,
+    C: Cache<Arc<U>> + Clone,
+{
+    /// Build the final `CachifiedOptions` with a fresh value function whose
+    /// values are shared through an `Arc`
+    ///
+    /// Cached values need to be `Clone`. Wrapping them in an `Arc` satisfies this
+    /// for any value, so values that can't be cloned, such as trait objects, can
+    /// be cached in memory. The function may return the value itself or a `Box`
+    /// of it, which is converted into an `Arc<U>`. Backends that serialize values,
+    /// such as Redis, don't support this.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "moka")]
+    /// use cachified::{cachified, CachifiedOptionsBuilder, MokaCache};
+    /// use std::sync::Arc;
+    ///
+    /// trait Plugin: Send + Sync {
+    ///     fn name(&self) -> &str;
+    /// }
+    ///
+    /// struct Markdown;
+    ///
+    /// impl Plugin for Markdown {
+    ///     fn name(&self) -> &str {
+    ///         "markdown"
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(feature = "moka")]
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache: MokaCache<Arc<dyn Plugin>> = MokaCache::new(100);
+    ///
+    /// let plugin = cachified(
+    ///     CachifiedOptionsBuilder::new(cache, "plugin")
+    ///         .get_fresh_value_arc(|| async { Ok(Box::new(Markdown) as Box<dyn Plugin>) })
+    /// ).await?;
+    /// assert_eq!(plugin.name(), "markdown");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_fresh_value_arc<F, Fut, V>(self, get_fresh_value: F) -> CachifiedOptions<Arc<U>, ArcFn<F>, C>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<V>> + Send,
+        V: Into<Arc<U>>,
+    {
+        self.build(ArcFn(get_fresh_value))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,7 +1235,56 @@ mod tests {
         assert_eq!(options.stale_while_revalidate, Some(Duration::from_secs(60)));
         assert!(!options.force_fresh);
         assert!(options.fallback_to_cache);
-        assert!(options.check_value.is_some());
+        assert!(options.check_cached_value.is_some());
+        assert!(options.check_fresh_value.is_some());
+    }
+
+    #[test]
+    fn test_key_display() {
+        let key = "user:contact@nurmarv.in";
+
+        assert_eq!(KeyDisplay::Full.display(key), key);
+        assert_eq!(KeyDisplay::Redacted.display(key), "<redacted>");
+
+        let hashed = KeyDisplay::Hashed.display(key);
+        assert_eq!(hashed.len(), 16);
+        assert!(!hashed.contains("nurmarv"));
+        // Hashes are stable so events for the same key can be correlated
+        assert_eq!(hashed, KeyDisplay::Hashed.display(key));
+        assert_ne!(hashed, KeyDisplay::Hashed.display("user:someone-else"));
+    }
+
+    #[test]
+    fn test_swr_policy_priority() {
+        let window = Duration::from_secs(100);
+        let policy = SwrPolicy::urgent_after(0.5);
+
+        assert_eq!(policy.priority(Duration::from_secs(10), window), RefreshPriority::Normal);
+        assert_eq!(policy.priority(Duration::from_secs(50), window), RefreshPriority::Urgent);
+        assert_eq!(policy.priority(Duration::from_secs(90), window), RefreshPriority::Urgent);
+
+        let default = SwrPolicy::default();
+        assert_eq!(default.priority(Duration::from_secs(99), window), RefreshPriority::Normal);
+        assert_eq!(default.priority(window, window), RefreshPriority::Normal);
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let backoff = Duration::from_millis(100);
+
+        let fixed = RetryPolicy::new(3, backoff);
+        assert_eq!(fixed.delay(1), backoff);
+        assert_eq!(fixed.delay(2), backoff);
+
+        let exponential = fixed.exponential();
+        assert_eq!(exponential.delay(1), Duration::from_millis(100));
+        assert_eq!(exponential.delay(2), Duration::from_millis(200));
+        assert_eq!(exponential.delay(3), Duration::from_millis(400));
+        // Huge attempt counts saturate instead of overflowing
+        assert!(exponential.delay(u32::MAX) > Duration::from_secs(3600));
+
+        assert!(RetryPolicy::retries_by_default(&CachifiedError::fresh_value("down")));
+        assert!(!RetryPolicy::retries_by_default(&CachifiedError::timeout("slow")));
     }
 
     #[tokio::test]
@@ -160,10 +1295,29 @@ mod tests {
             .get_fresh_value(|| async { Ok("test".to_string()) });
 
         assert_eq!(options.key, "test-key");
+        assert!(options.key_fn.is_none());
         assert_eq!(options.ttl, None);
+        assert!(!options.no_expiry);
+        assert_eq!(options.ttl_jitter, None);
         assert_eq!(options.stale_while_revalidate, None);
+        assert_eq!(options.early_refresh, None);
+        assert_eq!(options.clock_skew_tolerance, None);
+        assert!(!options.always_revalidate);
         assert!(!options.force_fresh);
         assert!(!options.fallback_to_cache);
-        assert!(options.check_value.is_none());
+        assert_eq!(options.stale_if_error, None);
+        assert_eq!(options.fresh_value_timeout, None);
+        assert_eq!(options.retry, None);
+        assert!(options.retry_if.is_none());
+        assert_eq!(options.read_error_policy, ReadErrorPolicy::TreatAsMiss);
+        assert_eq!(options.key_display, KeyDisplay::Hashed);
+        assert!(!options.error_stage);
+        assert!(options.migrate_value.is_none());
+        assert!(options.check_cached_value.is_none());
+        assert!(options.check_fresh_value.is_none());
+        assert!(options.check_value_async.is_none());
+        assert!(options.negative_ttl.is_none());
+        assert!(options.force_fresh_keys.is_none());
+        assert!(options.wait_until.is_none());
     }
 }
@@ -0,0 +1,193 @@
+//! Typed registry of logical caches.
+//!
+//! Storing several value types under one namespace makes it easy to read a
+//! key with the wrong type, which shows up as silent deserialization misses.
+//! A [`CacheRegistry`] fixes the value type and key prefix of every logical
+//! cache once, in a [`CacheKind`], and hands out caches that only accept that
+//! type.
+//!
+//! ```rust,compile_fail
+//! # use cachified::registry::{CacheKind, CacheRegistry};
+//! # use cachified::Cache;
+//! # struct Users;
+//! # impl CacheKind for Users {
+//! #     type Value = String;
+//! #     const PREFIX: &'static str = "users:";
+//! # }
+//! # async fn example(registry: CacheRegistry) {
+//! let users = registry.cache::<Users>().unwrap();
+//! // Users store `String` values, so storing a number doesn't compile
+//! users.put("user-1", 42u64, None).await.unwrap();
+//! # }
+//! ```
+
+use crate::{Cache, CachifiedError, Result, ScopedCache};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Declaration of a logical cache: its value type and key prefix
+///
+/// Implement this on a marker type for every logical cache.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::registry::CacheKind;
+///
+/// struct Users;
+///
+/// impl CacheKind for Users {
+///     type Value = String;
+///     const PREFIX: &'static str = "users:";
+/// }
+/// ```
+pub trait CacheKind: 'static {
+    /// Type of the values stored in this cache
+    type Value: Clone + Send + Sync + 'static;
+
+    /// Prefix of all keys of this cache
+    const PREFIX: &'static str;
+}
+
+/// A cache handed out by [`CacheRegistry::cache`], storing values of `K::Value` under `K::PREFIX`
+pub type TypedCache<K> = ScopedCache<Arc<dyn Cache<<K as CacheKind>::Value>>>;
+
+/// Registry of logical caches, each with a fixed value type and key prefix
+///
+/// Caches are registered once per [`CacheKind`] and looked up by the kind, so
+/// the value type of a prefix can't be mixed up. Prefixes of registered kinds
+/// may not overlap, which rules out reading one kind's keys through another.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{registry::{CacheKind, CacheRegistry}, Cache, MokaCache};
+///
+/// struct Users;
+///
+/// impl CacheKind for Users {
+///     type Value = String;
+///     const PREFIX: &'static str = "users:";
+/// }
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut registry = CacheRegistry::new();
+/// registry.register::<Users, _>(MokaCache::new(1000))?;
+///
+/// let users = registry.cache::<Users>().unwrap();
+/// users.put("user-1", "Marvin".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct CacheRegistry {
+    caches: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    prefixes: Vec<&'static str>,
+}
+
+impl CacheRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the backend of a logical cache
+    ///
+    /// Returns an error if the kind is already registered or its prefix
+    /// overlaps with the prefix of another registered kind.
+    pub fn register<K, C>(&mut self, cache: C) -> Result<()>
+    where
+        K: CacheKind,
+        C: Cache<K::Value> + 'static,
+    {
+        if self.caches.contains_key(&TypeId::of::<K>()) {
+            return Err(CachifiedError::other(format!(
+                "Cache kind with prefix {:?} is already registered",
+                K::PREFIX
+            )));
+        }
+
+        if let Some(prefix) = self
+            .prefixes
+            .iter()
+            .find(|prefix| prefix.starts_with(K::PREFIX) || K::PREFIX.starts_with(*prefix))
+        {
+            return Err(CachifiedError::other(format!(
+                "Cache prefix {:?} overlaps with registered prefix {:?}",
+                K::PREFIX,
+                prefix
+            )));
+        }
+
+        let cache: Arc<dyn Cache<K::Value>> = Arc::new(cache);
+        let typed: TypedCache<K> = cache.scoped(K::PREFIX);
+        self.caches.insert(TypeId::of::<K>(), Box::new(typed));
+        self.prefixes.push(K::PREFIX);
+        Ok(())
+    }
+
+    /// Get the cache of a kind, or `None` if it isn't registered
+    pub fn cache<K: CacheKind>(&self) -> Option<TypedCache<K>> {
+        self.caches
+            .get(&TypeId::of::<K>())
+            .and_then(|cache| cache.downcast_ref::<TypedCache<K>>())
+            .cloned()
+    }
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::MokaCache;
+
+    struct Users;
+
+    impl CacheKind for Users {
+        type Value = String;
+        const PREFIX: &'static str = "users:";
+    }
+
+    struct Sessions;
+
+    impl CacheKind for Sessions {
+        type Value = u64;
+        const PREFIX: &'static str = "sessions:";
+    }
+
+    struct Admins;
+
+    impl CacheKind for Admins {
+        type Value = String;
+        const PREFIX: &'static str = "users:admins:";
+    }
+
+    #[tokio::test]
+    async fn test_registry_typed_caches() {
+        let mut registry = CacheRegistry::new();
+        registry.register::<Users, _>(MokaCache::new(100)).unwrap();
+        registry.register::<Sessions, _>(MokaCache::new(100)).unwrap();
+
+        let users = registry.cache::<Users>().unwrap();
+        let sessions = registry.cache::<Sessions>().unwrap();
+        users.put("1", "Marvin".to_string(), None).await.unwrap();
+        sessions.put("1", 42, None).await.unwrap();
+
+        assert_eq!(users.get("1").await.unwrap().value, "Marvin");
+        assert_eq!(sessions.get("1").await.unwrap().value, 42);
+        assert_eq!(users.prefix(), "users:");
+    }
+
+    #[test]
+    fn test_registry_rejects_duplicates_and_overlaps() {
+        let mut registry = CacheRegistry::new();
+        registry.register::<Users, _>(MokaCache::new(100)).unwrap();
+
+        assert!(registry.register::<Users, _>(MokaCache::new(100)).is_err());
+        assert!(registry.register::<Admins, _>(MokaCache::new(100)).is_err());
+        assert!(registry.cache::<Admins>().is_none());
+        assert!(registry.cache::<Sessions>().is_none());
+    }
+}
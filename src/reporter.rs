@@ -0,0 +1,181 @@
+//! Hooks for observing cachified calls.
+//!
+//! A [`Reporter`] is notified about cache hits, misses, fresh value fetches
+//! and cache writes of every call it is attached to with
+//! `CachifiedOptionsBuilder::reporter`, and about the runs of a
+//! [`Janitor`](crate::janitor::Janitor) it is attached to.
+//! This is the place to hook up metrics and logging. For plain counters
+//! without a metrics library, attach [`CacheStats`](stats::CacheStats) with
+//! `CachifiedOptionsBuilder::stats`.
+
+use crate::{CacheMetadata, CachifiedError};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod stats;
+
+/// Trait for receiving events from cachified calls
+///
+/// All methods default to doing nothing, so implementations only need to
+/// override the events they are interested in. Methods are called inline,
+/// so they should return quickly.
+pub trait Reporter: Send + Sync {
+    /// Called when a cached value is served, including stale values served
+    /// while refreshing in the background
+    fn on_cache_hit(&self, _key: &str) {}
+
+    /// Called when an expired cached value is served, either while refreshing
+    /// it in the background or because fetching a fresh value failed
+    ///
+    /// In the first case, [`Reporter::on_cache_hit`] is called as well.
+    fn on_stale_hit(&self, _key: &str) {}
+
+    /// Called when no usable cached value exists and a fresh value is fetched
+    fn on_cache_miss(&self, _key: &str) {}
+
+    /// Called when reading the cached entry failed, e.g. because it couldn't
+    /// be decoded, and the error is treated as a miss
+    ///
+    /// See `ReadErrorPolicy::TreatAsMiss`. Errors that are returned to the
+    /// caller instead aren't reported.
+    fn on_cache_read_error(&self, _key: &str, _error: &CachifiedError) {}
+
+    /// Called before fetching a fresh value, both for blocking fetches and
+    /// background refreshes
+    ///
+    /// Calls joining a fetch that is already in flight for the same key
+    /// (see `CachifiedConfig::single_flight`) report their own fetch events.
+    fn on_get_fresh_value_start(&self, _key: &str) {}
+
+    /// Called when fetching a fresh value succeeded, with how long it took
+    fn on_get_fresh_value_success(&self, _key: &str, _duration: Duration) {}
+
+    /// Called when fetching a fresh value fails, both for blocking fetches and
+    /// background refreshes
+    fn on_get_fresh_value_error(&self, _key: &str, _error: &CachifiedError) {}
+
+    /// Called when the fallback value is served because fetching failed and
+    /// no usable cached value exists
+    fn on_fallback_value(&self, _key: &str) {}
+
+    /// Called after an entry was written to the cache
+    ///
+    /// Writes skipped by the write policy or rejected by a compare-and-set
+    /// aren't reported.
+    fn on_write(&self, _key: &str, _metadata: &CacheMetadata) {}
+
+    /// Called after a janitor run with the number of removed expired entries
+    fn on_expired_cleared(&self, _removed: usize) {}
+
+    /// Called when a janitor run fails because the cache can't list its keys
+    fn on_janitor_error(&self, _error: &CachifiedError) {}
+}
+
+impl<R: Reporter + ?Sized> Reporter for Arc<R> {
+    fn on_cache_hit(&self, key: &str) {
+        (**self).on_cache_hit(key)
+    }
+
+    fn on_stale_hit(&self, key: &str) {
+        (**self).on_stale_hit(key)
+    }
+
+    fn on_cache_miss(&self, key: &str) {
+        (**self).on_cache_miss(key)
+    }
+
+    fn on_cache_read_error(&self, key: &str, error: &CachifiedError) {
+        (**self).on_cache_read_error(key, error)
+    }
+
+    fn on_get_fresh_value_start(&self, key: &str) {
+        (**self).on_get_fresh_value_start(key)
+    }
+
+    fn on_get_fresh_value_success(&self, key: &str, duration: Duration) {
+        (**self).on_get_fresh_value_success(key, duration)
+    }
+
+    fn on_get_fresh_value_error(&self, key: &str, error: &CachifiedError) {
+        (**self).on_get_fresh_value_error(key, error)
+    }
+
+    fn on_fallback_value(&self, key: &str) {
+        (**self).on_fallback_value(key)
+    }
+
+    fn on_write(&self, key: &str, metadata: &CacheMetadata) {
+        (**self).on_write(key, metadata)
+    }
+
+    fn on_expired_cleared(&self, removed: usize) {
+        (**self).on_expired_cleared(removed)
+    }
+
+    fn on_janitor_error(&self, error: &CachifiedError) {
+        (**self).on_janitor_error(error)
+    }
+}
+
+/// Reporter that ignores all events (default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopReporter;
+
+impl Reporter for NoopReporter {}
+
+/// Reporter logging all events with `tracing`
+///
+/// Failures are logged at warn level, everything else at debug level.
+/// Requires the "tracing" feature to be enabled.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingReporter;
+
+#[cfg(feature = "tracing")]
+impl Reporter for TracingReporter {
+    fn on_cache_hit(&self, key: &str) {
+        tracing::debug!(key, "Cache hit");
+    }
+
+    fn on_stale_hit(&self, key: &str) {
+        tracing::debug!(key, "Serving stale value");
+    }
+
+    fn on_cache_miss(&self, key: &str) {
+        tracing::debug!(key, "Cache miss");
+    }
+
+    fn on_cache_read_error(&self, key: &str, error: &CachifiedError) {
+        tracing::warn!(key, %error, "Failed to read cache entry, treating it as a miss");
+    }
+
+    fn on_get_fresh_value_start(&self, key: &str) {
+        tracing::debug!(key, "Getting fresh value");
+    }
+
+    fn on_get_fresh_value_success(&self, key: &str, duration: Duration) {
+        tracing::debug!(key, ?duration, "Got fresh value");
+    }
+
+    fn on_get_fresh_value_error(&self, key: &str, error: &CachifiedError) {
+        tracing::warn!(key, %error, "Failed to get fresh value");
+    }
+
+    fn on_fallback_value(&self, key: &str) {
+        tracing::warn!(key, "Serving fallback value");
+    }
+
+    fn on_write(&self, key: &str, metadata: &CacheMetadata) {
+        tracing::debug!(key, ttl = ?metadata.ttl, "Wrote cache entry");
+    }
+
+    fn on_expired_cleared(&self, removed: usize) {
+        tracing::debug!(removed, "Cleared expired entries");
+    }
+
+    fn on_janitor_error(&self, error: &CachifiedError) {
+        tracing::warn!(%error, "Janitor failed to list keys");
+    }
+}
@@ -0,0 +1,132 @@
+//! Prometheus metrics for cachified calls.
+//!
+//! Requires the "prometheus" feature to be enabled. The following counters
+//! are registered, each labeled with `cache`:
+//!
+//! - `cachified_hits_total`: cached values served
+//! - `cachified_misses_total`: fresh values fetched because nothing usable was cached
+//! - `cachified_refresh_failures_total`: failed fresh value fetches
+//!
+//! The `cache` label should name a logical cache, such as `"users"` or
+//! `"sessions"`. Never use the cache key as the label value: every distinct
+//! label value creates a new time series, so labeling by key makes the number
+//! of series grow without bound.
+
+use super::Reporter;
+use crate::CachifiedError;
+use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+
+/// Name of the label identifying the cache
+const CACHE_LABEL: &str = "cache";
+
+/// The counter vectors shared by all [`PrometheusReporter`]s
+///
+/// Register the metrics once per registry, then create a reporter for every
+/// logical cache with [`PrometheusMetrics::reporter`].
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::reporter::prometheus::PrometheusMetrics;
+///
+/// let metrics = PrometheusMetrics::register(prometheus::default_registry())?;
+/// let reporter = metrics.reporter("users");
+/// # Ok::<(), prometheus::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    hits: IntCounterVec,
+    misses: IntCounterVec,
+    refresh_failures: IntCounterVec,
+}
+
+impl PrometheusMetrics {
+    /// Create the counters and register them with the given registry
+    ///
+    /// Fails if the counters are already registered with the registry.
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let metrics = Self {
+            hits: counter_vec("cachified_hits_total", "Number of cached values served")?,
+            misses: counter_vec(
+                "cachified_misses_total",
+                "Number of fresh values fetched because no usable value was cached",
+            )?,
+            refresh_failures: counter_vec(
+                "cachified_refresh_failures_total",
+                "Number of failed fresh value fetches",
+            )?,
+        };
+
+        registry.register(Box::new(metrics.hits.clone()))?;
+        registry.register(Box::new(metrics.misses.clone()))?;
+        registry.register(Box::new(metrics.refresh_failures.clone()))?;
+
+        Ok(metrics)
+    }
+
+    /// Create a reporter that counts events under the given cache name
+    pub fn reporter(&self, cache_name: &str) -> PrometheusReporter {
+        PrometheusReporter {
+            hits: self.hits.with_label_values(&[cache_name]),
+            misses: self.misses.with_label_values(&[cache_name]),
+            refresh_failures: self.refresh_failures.with_label_values(&[cache_name]),
+        }
+    }
+}
+
+fn counter_vec(name: &str, help: &str) -> prometheus::Result<IntCounterVec> {
+    IntCounterVec::new(Opts::new(name, help), &[CACHE_LABEL])
+}
+
+/// Reporter incrementing Prometheus counters for a single logical cache
+///
+/// Created by [`PrometheusMetrics::reporter`].
+#[derive(Debug, Clone)]
+pub struct PrometheusReporter {
+    hits: IntCounter,
+    misses: IntCounter,
+    refresh_failures: IntCounter,
+}
+
+impl Reporter for PrometheusReporter {
+    fn on_cache_hit(&self, _key: &str) {
+        self.hits.inc();
+    }
+
+    fn on_cache_miss(&self, _key: &str) {
+        self.misses.inc();
+    }
+
+    fn on_get_fresh_value_error(&self, _key: &str, _error: &CachifiedError) {
+        self.refresh_failures.inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_reporter() {
+        let registry = Registry::new();
+        let metrics = PrometheusMetrics::register(&registry).unwrap();
+        let users = metrics.reporter("users");
+        let sessions = metrics.reporter("sessions");
+
+        users.on_cache_hit("user-1");
+        users.on_cache_hit("user-2");
+        users.on_cache_miss("user-3");
+        sessions.on_get_fresh_value_error("session-1", &CachifiedError::fresh_value("down"));
+
+        assert_eq!(metrics.hits.with_label_values(&["users"]).get(), 2);
+        assert_eq!(metrics.misses.with_label_values(&["users"]).get(), 1);
+        assert_eq!(metrics.refresh_failures.with_label_values(&["sessions"]).get(), 1);
+        assert_eq!(metrics.hits.with_label_values(&["sessions"]).get(), 0);
+
+        let names: Vec<_> = registry.gather().iter().map(|family| family.name().to_string()).collect();
+        assert!(names.contains(&"cachified_hits_total".to_string()));
+
+        // Registering the same metrics twice fails
+        assert!(PrometheusMetrics::register(&registry).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Plain counters of cachified calls.
+//!
+//! [`CacheStats`] counts the outcomes of every call it is attached to with
+//! `CachifiedOptionsBuilder::stats`, which is enough to export the
+//! effectiveness of a cache without implementing a [`Reporter`].
+
+use super::Reporter;
+use crate::{CacheMetadata, CachifiedError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Atomic counters of cachified calls
+///
+/// Share one instance through an `Arc` between all calls that should be
+/// counted together and read the counters with [`CacheStats::snapshot`].
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{cachified, CacheStats, CachifiedOptionsBuilder, MokaCache};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = MokaCache::new(1000);
+/// let stats = Arc::new(CacheStats::new());
+///
+/// let value: String = cachified(
+///     CachifiedOptionsBuilder::new(cache, "my-key")
+///         .ttl(Duration::from_secs(60))
+///         .stats(stats.clone())
+///         .get_fresh_value(|| async { Ok("Hello, World!".to_string()) })
+/// ).await?;
+///
+/// println!("Hit rate: {:?}", stats.snapshot().hit_rate());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stale_served: AtomicU64,
+    fresh_errors: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl CacheStats {
+    /// Create counters starting at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the current values of all counters
+    ///
+    /// The counters are read one after another, so a snapshot taken while
+    /// calls are running may be slightly inconsistent.
+    pub fn snapshot(&self) -> CacheStatsSnapshot {
+        CacheStatsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            stale_served: self.stale_served.load(Ordering::Relaxed),
+            fresh_errors: self.fresh_errors.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the share of calls served from the cache so far
+    ///
+    /// This is a shortcut for [`CacheStatsSnapshot::hit_rate`].
+    pub fn hit_rate(&self) -> Option<f64> {
+        self.snapshot().hit_rate()
+    }
+}
+
+/// Values of [`CacheStats`] counters at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStatsSnapshot {
+    /// Cached values served, including stale values served while revalidating
+    pub hits: u64,
+    /// Fresh values fetched because no usable value was cached
+    pub misses: u64,
+    /// Expired values served, while revalidating or after a failed fetch
+    pub stale_served: u64,
+    /// Failed fresh value fetches, including background refreshes
+    pub fresh_errors: u64,
+    /// Entries written to the cache
+    pub writes: u64,
+}
+
+impl CacheStatsSnapshot {
+    /// Get the share of hits among hits and misses, or `None` before the first call
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+/// Reporter counting events in [`CacheStats`] before forwarding them
+pub(crate) struct StatsReporter {
+    reporter: Arc<dyn Reporter>,
+    stats: Arc<CacheStats>,
+}
+
+impl StatsReporter {
+    pub(crate) fn new(reporter: Arc<dyn Reporter>, stats: Arc<CacheStats>) -> Self {
+        Self { reporter, stats }
+    }
+}
+
+impl Reporter for StatsReporter {
+    fn on_cache_hit(&self, key: &str) {
+        self.stats.hits.fetch_add(1, Ordering::Relaxed);
+        self.reporter.on_cache_hit(key)
+    }
+
+    fn on_stale_hit(&self, key: &str) {
+        self.stats.stale_served.fetch_add(1, Ordering::Relaxed);
+        self.reporter.on_stale_hit(key)
+    }
+
+    fn on_cache_miss(&self, key: &str) {
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        self.reporter.on_cache_miss(key)
+    }
+
+    fn on_cache_read_error(&self, key: &str, error: &CachifiedError) {
+        self.reporter.on_cache_read_error(key, error)
+    }
+
+    fn on_get_fresh_value_start(&self, key: &str) {
+        self.reporter.on_get_fresh_value_start(key)
+    }
+
+    fn on_get_fresh_value_success(&self, key: &str, duration: Duration) {
+        self.reporter.on_get_fresh_value_success(key, duration)
+    }
+
+    fn on_get_fresh_value_error(&self, key: &str, error: &CachifiedError) {
+        self.stats.fresh_errors.fetch_add(1, Ordering::Relaxed);
+        self.reporter.on_get_fresh_value_error(key, error)
+    }
+
+    fn on_fallback_value(&self, key: &str) {
+        self.reporter.on_fallback_value(key)
+    }
+
+    fn on_write(&self, key: &str, metadata: &CacheMetadata) {
+        self.stats.writes.fetch_add(1, Ordering::Relaxed);
+        self.reporter.on_write(key, metadata)
+    }
+
+    fn on_expired_cleared(&self, removed: usize) {
+        self.reporter.on_expired_cleared(removed)
+    }
+
+    fn on_janitor_error(&self, error: &CachifiedError) {
+        self.reporter.on_janitor_error(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_hit_rate() {
+        assert_eq!(CacheStats::new().hit_rate(), None);
+
+        let snapshot = CacheStatsSnapshot {
+            hits: 3,
+            misses: 1,
+            ..CacheStatsSnapshot::default()
+        };
+        assert_eq!(snapshot.hit_rate(), Some(0.75));
+    }
+}
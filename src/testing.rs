@@ -0,0 +1,370 @@
+//! Caches that inject failures, for testing error handling.
+//!
+//! In-memory caches like `MokaCache` never fail, which makes paths such as
+//! `fallback_to_cache`, [`ReadErrorPolicy`](crate::ReadErrorPolicy) and
+//! failing writes hard to exercise. The wrappers in this module delegate to
+//! another cache and fail operations on demand ([`FailingCache`]) or
+//! intermittently ([`FlakyCache`]). Requires the "testing" feature.
+//!
+//! Failed reads behave like reads of a broken backend: [`Cache::try_get`]
+//! returns an error, while [`Cache::get`] and [`Cache::get_many`] report
+//! misses. Failed writes return an error and failed removals do nothing.
+
+use crate::{Cache, CacheEntry, CachifiedError, Result};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A cache whose reads and writes can be made to fail on demand
+///
+/// Clones share the failure switches, so a test can keep a clone to flip
+/// them while the cache is in use.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{testing::FailingCache, Cache, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() {
+/// let cache = FailingCache::new(MokaCache::<String>::new(100));
+/// cache.put("key", "value".to_string(), None).await.unwrap();
+///
+/// cache.fail_reads(true);
+/// assert!(cache.try_get("key").await.is_err());
+///
+/// cache.fail_reads(false);
+/// assert!(cache.get("key").await.is_some());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FailingCache<C> {
+    inner: C,
+    switches: Arc<FailureSwitches>,
+}
+
+#[derive(Default)]
+struct FailureSwitches {
+    reads: AtomicBool,
+    writes: AtomicBool,
+}
+
+impl<C> FailingCache<C> {
+    /// Wrap a cache, initially without failing any operation
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            switches: Arc::default(),
+        }
+    }
+
+    /// Set whether reads fail
+    pub fn fail_reads(&self, fail: bool) {
+        self.switches.reads.store(fail, Ordering::SeqCst);
+    }
+
+    /// Set whether writes and removals fail
+    pub fn fail_writes(&self, fail: bool) {
+        self.switches.writes.store(fail, Ordering::SeqCst);
+    }
+
+    /// Set whether all operations fail
+    pub fn fail_all(&self, fail: bool) {
+        self.fail_reads(fail);
+        self.fail_writes(fail);
+    }
+
+    /// Get the wrapped cache
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn fails_read(&self) -> bool {
+        self.switches.reads.load(Ordering::SeqCst)
+    }
+
+    fn fails_write(&self) -> bool {
+        self.switches.writes.load(Ordering::SeqCst)
+    }
+}
+
+/// When a [`FlakyCache`] fails an operation
+///
+/// Operations are numbered from zero in the order they are issued, counting
+/// reads and writes alike across all clones of the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlakyPolicy {
+    /// Fail every `n`-th operation, i.e. operations `n - 1`, `2n - 1`, ...
+    ///
+    /// Zero never fails.
+    EveryNth(usize),
+    /// Fail the operations with the given numbers
+    Schedule(Vec<usize>),
+    /// Fail every operation with a probability between `0.0` and `1.0`
+    ///
+    /// Failures are drawn from a generator seeded with `seed`, so a run with
+    /// the same seed and the same order of operations fails the same way.
+    Probability {
+        /// Probability of an operation failing
+        probability: f64,
+        /// Seed of the random number generator
+        seed: u64,
+    },
+}
+
+/// A cache that fails operations intermittently according to a [`FlakyPolicy`]
+///
+/// Clones share the operation counter and random number generator.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{testing::{FlakyCache, FlakyPolicy}, Cache, MokaCache};
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() {
+/// // Every third operation fails
+/// let cache = FlakyCache::new(MokaCache::<String>::new(100), FlakyPolicy::EveryNth(3));
+///
+/// assert!(cache.put("key", "value".to_string(), None).await.is_ok());
+/// assert!(cache.try_get("key").await.is_ok());
+/// assert!(cache.try_get("key").await.is_err());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FlakyCache<C> {
+    inner: C,
+    policy: Arc<FlakyPolicy>,
+    operations: Arc<AtomicUsize>,
+    rng: Arc<Mutex<u64>>,
+}
+
+impl<C> FlakyCache<C> {
+    /// Wrap a cache, failing operations according to `policy`
+    pub fn new(inner: C, policy: FlakyPolicy) -> Self {
+        // xorshift gets stuck at zero, so a zero seed is replaced
+        let seed = match policy {
+            FlakyPolicy::Probability { seed, .. } if seed != 0 => seed,
+            _ => 0x9e3779b97f4a7c15,
+        };
+
+        Self {
+            inner,
+            policy: Arc::new(policy),
+            operations: Arc::default(),
+            rng: Arc::new(Mutex::new(seed)),
+        }
+    }
+
+    /// Get the number of operations issued so far
+    pub fn operations(&self) -> usize {
+        self.operations.load(Ordering::SeqCst)
+    }
+
+    /// Get the wrapped cache
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Count an operation and decide whether it fails
+    fn fails(&self) -> bool {
+        let operation = self.operations.fetch_add(1, Ordering::SeqCst);
+
+        match &*self.policy {
+            FlakyPolicy::EveryNth(0) => false,
+            FlakyPolicy::EveryNth(n) => (operation + 1).is_multiple_of(*n),
+            FlakyPolicy::Schedule(operations) => operations.contains(&operation),
+            FlakyPolicy::Probability { probability, .. } => {
+                let mut state = self.rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+
+                // The upper 53 bits give a uniformly distributed f64 in [0, 1)
+                ((*state >> 11) as f64 / (1u64 << 53) as f64) < *probability
+            }
+        }
+    }
+
+    fn fails_read(&self) -> bool {
+        self.fails()
+    }
+
+    fn fails_write(&self) -> bool {
+        self.fails()
+    }
+}
+
+/// Implement `Cache<T>` for a wrapper with `fails_read` and `fails_write` methods
+macro_rules! failure_injecting_cache_impl {
+    ($($wrapper:ident),*) => {$(
+        #[async_trait]
+        impl<T, C> Cache<T> for $wrapper<C>
+        where
+            T: Clone + Send + Sync + 'static,
+            C: Cache<T>,
+        {
+            async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
+                self.try_get(key).await.ok().flatten()
+            }
+
+            async fn try_get(&self, key: &str) -> Result<Option<CacheEntry<T>>> {
+                if self.fails_read() {
+                    return Err(injected("read"));
+                }
+                self.inner.try_get(key).await
+            }
+
+            async fn get_many(&self, keys: &[&str]) -> Vec<Option<CacheEntry<T>>> {
+                if self.fails_read() {
+                    return vec![None; keys.len()];
+                }
+                self.inner.get_many(keys).await
+            }
+
+            async fn set(&self, key: &str, entry: CacheEntry<T>) -> Result<()> {
+                if self.fails_write() {
+                    return Err(injected("write"));
+                }
+                self.inner.set(key, entry).await
+            }
+
+            async fn set_if_version(&self, key: &str, entry: CacheEntry<T>, expected_version: Option<u64>) -> Result<bool> {
+                if self.fails_write() {
+                    return Err(injected("write"));
+                }
+                self.inner.set_if_version(key, entry, expected_version).await
+            }
+
+            async fn remove(&self, key: &str) {
+                if !self.fails_write() {
+                    self.inner.remove(key).await
+                }
+            }
+
+            async fn clear(&self) {
+                if !self.fails_write() {
+                    self.inner.clear().await
+                }
+            }
+
+            async fn len(&self) -> usize {
+                self.inner.len().await
+            }
+
+            async fn contains_key(&self, key: &str) -> bool {
+                !self.fails_read() && self.inner.contains_key(key).await
+            }
+
+            async fn keys(&self) -> Result<Vec<String>> {
+                if self.fails_read() {
+                    return Err(injected("read"));
+                }
+                self.inner.keys().await
+            }
+
+            fn backend(&self) -> &'static str {
+                self.inner.backend()
+            }
+        }
+    )*};
+}
+
+failure_injecting_cache_impl!(FailingCache, FlakyCache);
+
+fn injected(operation: &str) -> CachifiedError {
+    CachifiedError::cache(format!("Injected {operation} failure"))
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::{cachified, CachifiedOptionsBuilder, MokaCache};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_len_valid_falls_back_to_len_when_keys_fail() {
+        let cache = FailingCache::new(MokaCache::<String>::new(100));
+        cache.put("fresh", "value".to_string(), None).await.unwrap();
+        cache.put("expired", "value".to_string(), Some(Duration::from_millis(1))).await.unwrap();
+        let later = crate::current_time() + Duration::from_secs(1);
+        assert_eq!(cache.len_valid(later).await, 1);
+
+        // Expired entries are counted when the keys can't be listed
+        cache.fail_reads(true);
+        assert_eq!(cache.len_valid(later).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failing_cache_switches() {
+        let cache = FailingCache::new(MokaCache::<String>::new(100));
+        cache.put("key", "value".to_string(), None).await.unwrap();
+
+        cache.fail_writes(true);
+        assert!(cache.put("key", "other".to_string(), None).await.is_err());
+        cache.remove("key").await;
+        assert_eq!(cache.get("key").await.unwrap().value, "value");
+
+        // Clones share the switches
+        cache.clone().fail_all(true);
+        assert!(cache.try_get("key").await.is_err());
+        assert!(cache.get("key").await.is_none());
+        assert!(!cache.contains_key("key").await);
+        assert!(cache.inner().contains_key("key").await);
+    }
+
+    #[tokio::test]
+    async fn test_failing_cache_exercises_fallback_to_cache() {
+        let cache = FailingCache::new(MokaCache::<String>::new(100));
+        let stale = CacheEntry::new("stale".to_string(), Some(Duration::ZERO));
+        cache.set("key", stale).await.unwrap();
+
+        // The failed write of the fresh value doesn't fail the call
+        cache.fail_writes(true);
+        let value: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "key")
+                .ttl(Duration::from_secs(60))
+                .get_fresh_value(|| async { Ok("fresh".to_string()) }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(value, "fresh");
+        assert_eq!(cache.inner().get("key").await.unwrap().value, "stale");
+    }
+
+    #[tokio::test]
+    async fn test_flaky_cache_policies() {
+        let every_third = FlakyCache::new(MokaCache::<String>::new(100), FlakyPolicy::EveryNth(3));
+        let mut failures = Vec::new();
+        for _ in 0..6 {
+            failures.push(every_third.try_get("key").await.is_err());
+        }
+        assert_eq!(failures, [false, false, true, false, false, true]);
+
+        let scheduled = FlakyCache::new(MokaCache::<String>::new(100), FlakyPolicy::Schedule(vec![1]));
+        assert!(scheduled.put("key", "value".to_string(), None).await.is_ok());
+        assert!(scheduled.try_get("key").await.is_err());
+        assert!(scheduled.try_get("key").await.is_ok());
+        assert_eq!(scheduled.operations(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_flaky_cache_probability_is_reproducible() {
+        let policy = FlakyPolicy::Probability { probability: 0.3, seed: 42 };
+        let first = FlakyCache::new(MokaCache::<String>::new(100), policy.clone());
+        let second = FlakyCache::new(MokaCache::<String>::new(100), policy);
+
+        let mut first_failures = Vec::new();
+        let mut second_failures = Vec::new();
+        for _ in 0..1000 {
+            first_failures.push(first.try_get("key").await.is_err());
+            second_failures.push(second.try_get("key").await.is_err());
+        }
+
+        assert_eq!(first_failures, second_failures);
+        let failed = first_failures.iter().filter(|failed| **failed).count();
+        assert!((200..400).contains(&failed), "{failed} of 1000 operations failed");
+    }
+}
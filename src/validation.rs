@@ -1,6 +1,7 @@
 //! Value validation for cached entries.
 
 use crate::{CachifiedError, Result};
+use async_trait::async_trait;
 
 /// Trait for validating cache values.
 /// 
@@ -12,6 +13,259 @@ pub trait CheckValue<T> {
     /// 
     /// Returns `Ok(())` if the value is valid, or `Err(CachifiedError)` if invalid.
     fn check(&self, value: &T) -> Result<()>;
+
+    /// Validate the given value and decide how an invalid cached value is handled.
+    ///
+    /// The default implementation delegates to [`CheckValue::check`] and asks for
+    /// a refetch when it fails. Override it, or wrap the validator in [`Fatal`],
+    /// to fail the call immediately instead.
+    fn validate(&self, value: &T) -> ValidationOutcome {
+        match self.check(value) {
+            Ok(()) => ValidationOutcome::Valid,
+            Err(_) => ValidationOutcome::InvalidRefetch,
+        }
+    }
+}
+
+/// Trait for validating cache values with I/O.
+///
+/// Use this for checks that have to ask another system, e.g. whether a cached
+/// auth token has been revoked. Set it with
+/// `CachifiedOptionsBuilder::check_value_async`. Like with [`CheckValue`], a
+/// cached value failing the check is treated as missing and a fresh value is
+/// fetched, and a fresh value failing it is not cached and fails the call.
+#[async_trait]
+pub trait AsyncCheckValue<T: Sync>: Send + Sync {
+    /// Validate the given value.
+    ///
+    /// Returns `Ok(())` if the value is valid, or `Err(CachifiedError)` if invalid.
+    async fn check(&self, value: &T) -> Result<()>;
+}
+
+/// Trait for upgrading cached values written in an older form.
+///
+/// Set it with `CachifiedOptionsBuilder::migrate_value`. Cached values are
+/// migrated before they are validated or served, and a migrated value is
+/// written back to the cache with its original TTL. This way a deploy that
+/// only changes the shape of values doesn't have to refetch all of them.
+///
+/// Implemented for closures taking `&T`.
+pub trait MigrateValue<T> {
+    /// Upgrade the given value.
+    ///
+    /// Returns `Ok(Some(value))` with the migrated value, `Ok(None)` if the value
+    /// is already in its current form, or `Err(CachifiedError)` if it can't be
+    /// migrated, in which case a fresh value is fetched instead.
+    fn migrate(&self, value: &T) -> Result<Option<T>>;
+}
+
+impl<T, F> MigrateValue<T> for F
+where
+    F: Fn(&T) -> Result<Option<T>>,
+{
+    fn migrate(&self, value: &T) -> Result<Option<T>> {
+        self(value)
+    }
+}
+
+/// The outcome of validating a cached value.
+#[derive(Debug)]
+pub enum ValidationOutcome {
+    /// The value is valid and can be served
+    Valid,
+    /// The value is invalid; fetch a fresh value instead
+    InvalidRefetch,
+    /// The value is invalid and a fresh value won't be any better; fail the call
+    /// with the given error without fetching a fresh value
+    InvalidFatal(CachifiedError),
+}
+
+/// A validator whose failures on cached values are fatal.
+///
+/// Cached values failing the wrapped validator make `cachified` return the
+/// validation error instead of fetching a fresh value. Use this when a
+/// failure means the upstream data itself is broken.
+pub struct Fatal<V>(pub V);
+
+impl<T, V> CheckValue<T> for Fatal<V>
+where
+    V: CheckValue<T>,
+{
+    fn check(&self, value: &T) -> Result<()> {
+        self.0.check(value)
+    }
+
+    fn validate(&self, value: &T) -> ValidationOutcome {
+        match self.0.check(value) {
+            Ok(()) => ValidationOutcome::Valid,
+            Err(e) => ValidationOutcome::InvalidFatal(e),
+        }
+    }
+}
+
+/// Boxed validator stored by the combinators
+type BoxedCheck<T> = Box<dyn CheckValue<T> + Send + Sync>;
+
+/// A validator that passes if all of its validators pass.
+///
+/// Validators run in order and the first failure is returned, naming its
+/// position, e.g. `Validator 2 of 3 failed: String is empty`. Fatal failures
+/// (see [`Fatal`]) stay fatal. Create one with [`CheckValueExt::and`].
+pub struct AndValidator<T> {
+    validators: Vec<BoxedCheck<T>>,
+}
+
+impl<T> AndValidator<T> {
+    /// Add another validator that has to pass
+    pub fn and<V>(mut self, validator: V) -> Self
+    where
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    fn failure(&self, index: usize, error: &CachifiedError) -> CachifiedError {
+        CachifiedError::validation(format!(
+            "Validator {} of {} failed: {}",
+            index + 1,
+            self.validators.len(),
+            failure_message(error)
+        ))
+    }
+}
+
+impl<T> CheckValue<T> for AndValidator<T> {
+    fn check(&self, value: &T) -> Result<()> {
+        for (index, validator) in self.validators.iter().enumerate() {
+            validator.check(value).map_err(|e| self.failure(index, &e))?;
+        }
+        Ok(())
+    }
+
+    fn validate(&self, value: &T) -> ValidationOutcome {
+        for (index, validator) in self.validators.iter().enumerate() {
+            match validator.validate(value) {
+                ValidationOutcome::Valid => {}
+                ValidationOutcome::InvalidRefetch => return ValidationOutcome::InvalidRefetch,
+                ValidationOutcome::InvalidFatal(e) => {
+                    return ValidationOutcome::InvalidFatal(self.failure(index, &e));
+                }
+            }
+        }
+        ValidationOutcome::Valid
+    }
+}
+
+/// A validator that passes if any of its validators passes.
+///
+/// If all validators fail, the failure lists all of their messages, e.g.
+/// `No validator passed: String is empty; Unknown schema`. The failure is
+/// fatal if any of the validators failed fatally (see [`Fatal`]). Create one
+/// with [`CheckValueExt::or`].
+pub struct OrValidator<T> {
+    validators: Vec<BoxedCheck<T>>,
+}
+
+impl<T> OrValidator<T> {
+    /// Add another validator that may pass instead
+    pub fn or<V>(mut self, validator: V) -> Self
+    where
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        self.validators.push(Box::new(validator));
+        self
+    }
+}
+
+impl<T> CheckValue<T> for OrValidator<T> {
+    fn check(&self, value: &T) -> Result<()> {
+        let mut failures = Vec::with_capacity(self.validators.len());
+        for validator in &self.validators {
+            match validator.check(value) {
+                Ok(()) => return Ok(()),
+                Err(e) => failures.push(failure_message(&e)),
+            }
+        }
+        Err(CachifiedError::validation(format!("No validator passed: {}", failures.join("; "))))
+    }
+
+    fn validate(&self, value: &T) -> ValidationOutcome {
+        let mut fatal = false;
+        for validator in &self.validators {
+            match validator.validate(value) {
+                ValidationOutcome::Valid => return ValidationOutcome::Valid,
+                ValidationOutcome::InvalidRefetch => {}
+                ValidationOutcome::InvalidFatal(_) => fatal = true,
+            }
+        }
+        if !fatal {
+            return ValidationOutcome::InvalidRefetch;
+        }
+
+        // Report the messages of all validators, like `check`
+        match self.check(value) {
+            Err(e) => ValidationOutcome::InvalidFatal(e),
+            Ok(()) => ValidationOutcome::InvalidRefetch,
+        }
+    }
+}
+
+/// Extension methods combining validators.
+///
+/// Implemented for all validators, so `NonEmptyStringValidator.and(other)` works
+/// for any pair of validators of the same value type. Chaining more
+/// validators of the same kind extends the combinator instead of nesting it.
+///
+/// # Examples
+///
+/// ```rust
+/// use cachified::validation::{validator, CheckValue, CheckValueExt, NonEmptyStringValidator};
+/// use cachified::CachifiedError;
+///
+/// let check = NonEmptyStringValidator.and(validator(|value: &String| {
+///     if value.starts_with("v2:") {
+///         Ok(())
+///     } else {
+///         Err(CachifiedError::validation("Unknown schema"))
+///     }
+/// }));
+///
+/// assert!(check.check(&"v2:data".to_string()).is_ok());
+/// assert!(check.check(&"v1:data".to_string()).is_err());
+/// ```
+pub trait CheckValueExt<T>: CheckValue<T> + Sized {
+    /// Combine with another validator that has to pass as well
+    fn and<V>(self, other: V) -> AndValidator<T>
+    where
+        Self: Send + Sync + 'static,
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        AndValidator {
+            validators: vec![Box::new(self), Box::new(other)],
+        }
+    }
+
+    /// Combine with another validator that may pass instead
+    fn or<V>(self, other: V) -> OrValidator<T>
+    where
+        Self: Send + Sync + 'static,
+        V: CheckValue<T> + Send + Sync + 'static,
+    {
+        OrValidator {
+            validators: vec![Box::new(self), Box::new(other)],
+        }
+    }
+}
+
+impl<T, V: CheckValue<T>> CheckValueExt<T> for V {}
+
+/// The message of a validation error, without the error's prefix
+fn failure_message(error: &CachifiedError) -> String {
+    match error {
+        CachifiedError::ValidationError(message) => message.clone(),
+        error => error.to_string(),
+    }
 }
 
 /// A function-based validator that can be used with closures.
@@ -79,6 +333,66 @@ impl CheckValue<&str> for NonEmptyStringValidator {
     }
 }
 
+/// A validator that runs the `validator` crate's [`Validate`](validator::Validate) derive.
+///
+/// Requires the "validator" feature to be enabled. Failures are reported as a
+/// validation error listing every failing field with its message, or its
+/// validation code if it has no message, e.g.
+/// `Value failed validation: address.city: length; name: Name must not be empty`.
+#[cfg(feature = "validator")]
+pub struct DeriveValidator;
+
+#[cfg(feature = "validator")]
+impl<T> CheckValue<T> for DeriveValidator
+where
+    T: validator::Validate,
+{
+    fn check(&self, value: &T) -> Result<()> {
+        value.validate().map_err(|errors| {
+            let mut failures = Vec::new();
+            collect_validation_failures(&errors, "", &mut failures);
+            failures.sort();
+
+            CachifiedError::validation(format!("Value failed validation: {}", failures.join("; ")))
+        })
+    }
+}
+
+/// Flatten nested `ValidationErrors` into `path: message` strings
+#[cfg(feature = "validator")]
+fn collect_validation_failures(
+    errors: &validator::ValidationErrors,
+    prefix: &str,
+    failures: &mut Vec<String>,
+) {
+    use validator::ValidationErrorsKind;
+
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                for error in field_errors {
+                    let message = error.message.as_ref().unwrap_or(&error.code);
+                    failures.push(format!("{}: {}", path, message));
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                collect_validation_failures(nested, &path, failures);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_validation_failures(nested, &format!("{}[{}]", path, index), failures);
+                }
+            }
+        }
+    }
+}
+
 /// Helper function to create a function validator from a closure.
 /// 
 /// # Examples
@@ -119,6 +433,93 @@ mod tests {
         assert!(validator.check(&-1).is_err());
     }
 
+    #[test]
+    fn test_validation_outcome() {
+        let validator = validator(|x: &i32| {
+            if *x > 0 {
+                Ok(())
+            } else {
+                Err(CachifiedError::validation("Must be positive"))
+            }
+        });
+
+        assert!(matches!(validator.validate(&5), ValidationOutcome::Valid));
+        assert!(matches!(validator.validate(&-1), ValidationOutcome::InvalidRefetch));
+
+        let fatal = Fatal(validator);
+        assert!(matches!(fatal.validate(&5), ValidationOutcome::Valid));
+        assert!(matches!(fatal.validate(&-1), ValidationOutcome::InvalidFatal(_)));
+        assert!(fatal.check(&-1).is_err());
+    }
+
+    fn v2_schema(value: &str) -> Result<()> {
+        if value.starts_with("v2:") {
+            Ok(())
+        } else {
+            Err(CachifiedError::validation("Unknown schema"))
+        }
+    }
+
+    #[test]
+    fn test_and_validator() {
+        let all = NonEmptyStringValidator
+            .and(validator(|value: &String| v2_schema(value)))
+            .and(validator(|value: &String| {
+                if value.len() < 10 {
+                    Ok(())
+                } else {
+                    Err(CachifiedError::validation("Too long"))
+                }
+            }));
+
+        assert!(all.check(&"v2:data".to_string()).is_ok());
+
+        // The first failure is reported with its position
+        let error = all.check(&String::new()).unwrap_err();
+        assert_eq!(error.to_string(), "Cache validation failed: Validator 1 of 3 failed: String is empty");
+        let error = all.check(&"v1:data".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Cache validation failed: Validator 2 of 3 failed: Unknown schema");
+        assert!(matches!(all.validate(&"v2:too long".to_string()), ValidationOutcome::InvalidRefetch));
+
+        // Fatal failures stay fatal
+        let fatal = NonEmptyStringValidator.and(Fatal(validator(|value: &String| v2_schema(value))));
+        assert!(matches!(fatal.validate(&"v1:data".to_string()), ValidationOutcome::InvalidFatal(_)));
+        assert!(matches!(fatal.validate(&String::new()), ValidationOutcome::InvalidRefetch));
+    }
+
+    #[test]
+    fn test_or_validator() {
+        let any = validator(|value: &String| v2_schema(value)).or(validator(|value: &String| {
+            if value.starts_with("v1:") {
+                Ok(())
+            } else {
+                Err(CachifiedError::validation("Not legacy"))
+            }
+        }));
+
+        assert!(any.check(&"v1:data".to_string()).is_ok());
+        assert!(any.check(&"v2:data".to_string()).is_ok());
+
+        let error = any.check(&"v3:data".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Cache validation failed: No validator passed: Unknown schema; Not legacy");
+
+        // Combinators nest
+        let either = NonEmptyStringValidator.and(validator(|value: &String| v2_schema(value))).or(NoValidator);
+        assert!(either.check(&String::new()).is_ok());
+
+        // Fatal failures stay fatal, unless another validator passes
+        let fatal = Fatal(validator(|value: &String| v2_schema(value))).or(NonEmptyStringValidator);
+        assert!(matches!(fatal.validate(&"v1:data".to_string()), ValidationOutcome::Valid));
+        match fatal.validate(&String::new()) {
+            ValidationOutcome::InvalidFatal(error) => assert_eq!(
+                error.to_string(),
+                "Cache validation failed: No validator passed: Unknown schema; String is empty"
+            ),
+            outcome => panic!("expected a fatal failure, got {outcome:?}"),
+        }
+        assert!(matches!(any.validate(&"v3:data".to_string()), ValidationOutcome::InvalidRefetch));
+    }
+
     #[test]
     fn test_non_null_validator() {
         let validator = NonNullValidator;
@@ -137,6 +538,49 @@ mod tests {
         assert!(validator.check(&"").is_err());
     }
 
+    #[cfg(feature = "validator")]
+    #[test]
+    fn test_derive_validator() {
+        use validator::Validate;
+
+        #[derive(Validate)]
+        struct Address {
+            #[validate(length(min = 1))]
+            city: String,
+        }
+
+        #[derive(Validate)]
+        struct User {
+            #[validate(length(min = 1, message = "Name must not be empty"))]
+            name: String,
+            #[validate(email)]
+            email: String,
+            #[validate(nested)]
+            address: Address,
+        }
+
+        let validator = DeriveValidator;
+        let valid = User {
+            name: "Marvin".to_string(),
+            email: "contact@nurmarv.in".to_string(),
+            address: Address {
+                city: "Berlin".to_string(),
+            },
+        };
+        assert!(validator.check(&valid).is_ok());
+
+        let invalid = User {
+            name: String::new(),
+            email: "nope".to_string(),
+            address: Address { city: String::new() },
+        };
+        let error = validator.check(&invalid).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cache validation failed: Value failed validation: address.city: length; email: email; name: Name must not be empty"
+        );
+    }
+
     #[test]
     fn test_no_validator() {
         let validator = NoValidator;
@@ -0,0 +1,156 @@
+//! Rolling windows of recent items cached under a key.
+//!
+//! These helpers keep the most recent items of a feed, e.g. the last events
+//! of an event source, in a capped `VecDeque` so late subscribers can be
+//! served the recent history. Appends are read-modify-write operations built
+//! on [`Cache::update`].
+
+use crate::{current_time, Cache, CacheEntry, Result};
+use futures_util::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Append an item to the window cached under `key`, keeping the last `capacity` items
+///
+/// Older items are dropped once the window holds more than `capacity` items.
+/// Every append resets the TTL of the window, so it expires `ttl` after the
+/// last append. An expired window is started over.
+///
+/// # Returns
+///
+/// Returns the window after appending the item.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[cfg(feature = "moka")]
+/// use cachified::{window::append_to_window, Cache, MokaCache};
+/// use std::collections::VecDeque;
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "moka")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache: MokaCache<VecDeque<String>> = MokaCache::new(1000);
+///
+/// for event in ["joined", "posted", "left"] {
+///     append_to_window(&cache, "room-1", event.to_string(), 2, Some(Duration::from_secs(60))).await?;
+/// }
+///
+/// let window = cache.get("room-1").await.unwrap().value;
+/// assert_eq!(window, ["posted", "left"]);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn append_to_window<T, C>(
+    cache: &C,
+    key: &str,
+    item: T,
+    capacity: usize,
+    ttl: Option<Duration>,
+) -> Result<VecDeque<T>>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<VecDeque<T>>,
+{
+    let entry = cache
+        .update(key, |current| {
+            let now = current_time();
+            let mut window = current
+                .filter(|entry| !entry.is_expired(now))
+                .map(|entry| entry.value)
+                .unwrap_or_default();
+
+            window.push_back(item.clone());
+            while window.len() > capacity {
+                window.pop_front();
+            }
+
+            CacheEntry::new(window, ttl)
+        })
+        .await?;
+
+    Ok(entry.value)
+}
+
+/// Append every item of a stream to the window cached under `key`
+///
+/// This consumes the stream, calling [`append_to_window`] for each item.
+/// Stops at the first error of the stream or of the cache and returns it.
+pub async fn cache_stream_window<T, C, S>(
+    cache: &C,
+    key: &str,
+    stream: S,
+    capacity: usize,
+    ttl: Option<Duration>,
+) -> Result<()>
+where
+    T: Clone + Send + Sync + 'static,
+    C: Cache<VecDeque<T>>,
+    S: Stream<Item = Result<T>>,
+{
+    let mut stream = std::pin::pin!(stream);
+
+    while let Some(item) = stream.next().await {
+        append_to_window(cache, key, item?, capacity, ttl).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "moka"))]
+mod tests {
+    use super::*;
+    use crate::{CachifiedError, MokaCache};
+    use futures_util::stream;
+
+    #[tokio::test]
+    async fn test_append_to_window_keeps_last_items() {
+        let cache: MokaCache<VecDeque<u32>> = MokaCache::new(100);
+
+        for event in 0..5 {
+            append_to_window(&cache, "feed", event, 3, Some(Duration::from_secs(60)))
+                .await
+                .unwrap();
+        }
+
+        let entry = cache.get("feed").await.unwrap();
+        assert_eq!(entry.value, [2, 3, 4]);
+        assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(60)));
+        assert_eq!(entry.metadata.version, 4);
+    }
+
+    #[tokio::test]
+    async fn test_append_to_window_honors_ttl() {
+        let cache: MokaCache<VecDeque<u32>> = MokaCache::new(100);
+        let ttl = Some(Duration::from_millis(200));
+
+        append_to_window(&cache, "feed", 1, 3, ttl).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        // Appending resets the TTL
+        append_to_window(&cache, "feed", 2, 3, ttl).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(cache.get_value("feed", current_time()).await, Some(VecDeque::from([1, 2])));
+
+        // An expired window starts over
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(cache.get_value("feed", current_time()).await, None);
+        let window = append_to_window(&cache, "feed", 3, 3, ttl).await.unwrap();
+        assert_eq!(window, [3]);
+    }
+
+    #[tokio::test]
+    async fn test_cache_stream_window_stops_at_error() {
+        let cache: MokaCache<VecDeque<u32>> = MokaCache::new(100);
+        let events = stream::iter(vec![
+            Ok(1),
+            Ok(2),
+            Err(CachifiedError::other("source closed")),
+            Ok(3),
+        ]);
+
+        let result = cache_stream_window(&cache, "feed", events, 10, None).await;
+
+        assert!(result.is_err());
+        assert_eq!(cache.get("feed").await.unwrap().value, [1, 2]);
+    }
+}
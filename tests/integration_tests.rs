@@ -1,7 +1,11 @@
-use cachified::{cachified, CachifiedOptionsBuilder, MokaCache, Cache, CachifiedError, validation::NonEmptyStringValidator};
+use cachified::{clock::{Clock, MockClock}, cachified, cachified_entry, cachified_typed, cachified_many, cachified_many_keyed, cachified_map_entry, cachified_set, cachified_stream, cachified_with_metadata, cachified_with_status, extend_ttl, CachifiedConfig, CachifiedOptionsBuilder, MokaCache, Cache, CacheEntry, CacheStats, CacheStatus, CachifiedError, CacheMetadata, ErrorKind, Stage, FetchReason, FreshValueOutcome, SwrPolicy, ReadErrorPolicy, Reporter, validation::{AsyncCheckValue, Fatal, FunctionValidator, NonEmptyStringValidator}};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use std::collections::HashSet;
 use std::time::Duration;
 use tokio::time::sleep;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[tokio::test]
 async fn test_basic_caching() {
@@ -127,6 +131,10 @@ async fn test_stale_while_revalidate() {
     ).await.unwrap();
 
     assert_eq!(fresh_value, "fresh-value");
+
+    // The refreshed entry remembers the stale window it was written with
+    let entry = cache.get("swr-test").await.unwrap();
+    assert_eq!(entry.metadata.swr, Some(Duration::from_secs(60)));
 }
 
 #[tokio::test]
@@ -155,6 +163,182 @@ async fn test_force_fresh() {
     assert_eq!(fresh_value, "forced-value");
 }
 
+#[tokio::test]
+async fn test_migrate_value_upgrades_cached_value() {
+    let cache = MokaCache::new(100);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let old = CacheEntry::new("v1:data".to_string(), Some(Duration::from_secs(60)));
+    let expires_at = old.expires_at();
+    cache.set("migrate", old).await.unwrap();
+    cache.set("unmigratable", CacheEntry::new("v0:data".to_string(), Some(Duration::from_secs(60)))).await.unwrap();
+
+    let get = |key: &'static str| {
+        let calls = calls.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .migrate_value(|value: &String| match value.strip_prefix("v1:") {
+                    Some(data) => Ok(Some(format!("v2:{data}"))),
+                    None if value.starts_with("v2:") => Ok(None),
+                    None => Err(CachifiedError::validation("Unknown schema")),
+                })
+                .get_fresh_value(move || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    async { Ok("v2:fresh".to_string()) }
+                }),
+        )
+    };
+
+    // The old value is upgraded on read and written back with its TTL
+    assert_eq!(get("migrate").await.unwrap(), "v2:data");
+    let stored = cache.get("migrate").await.unwrap();
+    assert_eq!(stored.value, "v2:data");
+    assert_eq!(stored.expires_at(), expires_at);
+    assert_eq!(get("migrate").await.unwrap(), "v2:data");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // Values that can't be migrated are refetched
+    assert_eq!(get("unmigratable").await.unwrap(), "v2:fresh");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+/// Run a call and return the reason its fresh value function was called with
+async fn fetch_reason(options: CachifiedOptionsBuilder<String, MokaCache<String>>) -> FetchReason {
+    let config = CachifiedConfig::new();
+    let reasons = Arc::new(Mutex::new(Vec::new()));
+    let recorded = reasons.clone();
+    let _: String = cachified(
+        options
+            .ttl(Duration::from_secs(60))
+            .config(config.clone())
+            .get_fresh_value_with_reason(move |reason| {
+                recorded.lock().unwrap().push(reason);
+                async { Ok("fresh-value".to_string()) }
+            }),
+    ).await.unwrap();
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    let reasons = reasons.lock().unwrap();
+    assert_eq!(reasons.len(), 1);
+    reasons[0]
+}
+
+#[tokio::test]
+async fn test_fetch_reason_per_branch() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let entry = |value: &str| {
+        CacheEntry::with_metadata(value.to_string(), CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60))))
+    };
+    cache.set("reason-expired", entry("cached-value")).await.unwrap();
+    cache.set("reason-stale", entry("cached-value")).await.unwrap();
+    cache.set("reason-forced", entry("cached-value")).await.unwrap();
+    clock.advance(Duration::from_secs(70));
+    // Still fresh, but failing validation
+    cache.set("reason-invalid", entry("")).await.unwrap();
+
+    let options = |key: &'static str| CachifiedOptionsBuilder::new(cache.clone(), key).clock(clock.clone());
+    assert_eq!(fetch_reason(options("reason-missing")).await, FetchReason::ColdMiss);
+    assert_eq!(fetch_reason(options("reason-expired")).await, FetchReason::Expired);
+    assert_eq!(
+        fetch_reason(options("reason-invalid").check_value(NonEmptyStringValidator)).await,
+        FetchReason::ValidationFailed,
+    );
+    assert_eq!(fetch_reason(options("reason-forced").force_fresh(true)).await, FetchReason::Forced);
+    assert_eq!(
+        fetch_reason(options("reason-stale").stale_while_revalidate(Duration::from_secs(30))).await,
+        FetchReason::StaleRevalidation,
+    );
+}
+
+async fn cache_status(options: CachifiedOptionsBuilder<String, MokaCache<String>>, fetch_fails: bool) -> CacheStatus {
+    let config = CachifiedConfig::new();
+    let (_, status) = cachified_with_status(
+        options
+            .ttl(Duration::from_secs(60))
+            .config(config.clone())
+            .get_fresh_value(move || async move {
+                if fetch_fails {
+                    Err(CachifiedError::fresh_value("upstream down"))
+                } else {
+                    Ok("fresh-value".to_string())
+                }
+            }),
+    ).await.unwrap();
+    assert!(config.drain(Duration::from_secs(1)).await);
+    status
+}
+
+#[tokio::test]
+async fn test_cache_status_per_branch() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let entry = || {
+        CacheEntry::with_metadata("cached-value".to_string(), CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60))))
+    };
+    for key in ["status-expired", "status-stale", "status-fallback", "status-stale-revalidate"] {
+        cache.set(key, entry()).await.unwrap();
+    }
+    clock.advance(Duration::from_secs(50));
+    for key in ["status-hit", "status-forced", "status-revalidate", "status-early"] {
+        cache.set(key, entry()).await.unwrap();
+    }
+    clock.advance(Duration::from_secs(20));
+
+    let options = |key: &'static str| CachifiedOptionsBuilder::new(cache.clone(), key).clock(clock.clone());
+    assert_eq!(cache_status(options("status-missing"), false).await, CacheStatus::Miss);
+    assert_eq!(cache_status(options("status-expired"), false).await, CacheStatus::Miss);
+    assert_eq!(cache_status(options("status-forced").force_fresh(true), false).await, CacheStatus::Miss);
+    assert_eq!(cache_status(options("status-hit"), false).await, CacheStatus::Hit);
+    assert_eq!(
+        cache_status(options("status-stale").stale_while_revalidate(Duration::from_secs(30)), false).await,
+        CacheStatus::Stale,
+    );
+    assert_eq!(
+        cache_status(options("status-stale-revalidate").always_revalidate(true), false).await,
+        CacheStatus::Stale,
+    );
+    assert_eq!(
+        cache_status(options("status-revalidate").always_revalidate(true), false).await,
+        CacheStatus::RevalidatedBackground,
+    );
+    assert_eq!(
+        cache_status(options("status-early").early_refresh(Duration::from_secs(45)), false).await,
+        CacheStatus::RevalidatedBackground,
+    );
+    assert_eq!(
+        cache_status(options("status-fallback").fallback_to_cache(true), true).await,
+        CacheStatus::FallbackStale,
+    );
+    assert_eq!(
+        cache_status(options("status-default").fallback_value("default".to_string()), true).await,
+        CacheStatus::Miss,
+    );
+}
+
+#[tokio::test]
+async fn test_force_fresh_keys() {
+    let cache = MokaCache::new(100);
+    for key in ["force-keys-in", "force-keys-out"] {
+        cache.set(key, CacheEntry::new("cached-value".to_string(), Some(Duration::from_secs(60)))).await.unwrap();
+    }
+
+    // Built by a shared helper, so every call gets the same set of keys
+    let forced: HashSet<String> = HashSet::from(["force-keys-in".to_string()]);
+    let get = |key: &'static str| {
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .force_fresh_keys(forced.clone())
+                .get_fresh_value(|| async { Ok("forced-value".to_string()) }),
+        )
+    };
+
+    assert_eq!(get("force-keys-in").await.unwrap(), "forced-value");
+    assert_eq!(cache.get("force-keys-in").await.unwrap().value, "forced-value");
+    assert_eq!(get("force-keys-out").await.unwrap(), "cached-value");
+}
+
 #[tokio::test]
 async fn test_validation() {
     let cache = MokaCache::new(100);
@@ -162,12 +346,12 @@ async fn test_validation() {
     // First, put invalid data in cache manually
     cache.set("validation-test", cachified::CacheEntry {
         value: "".to_string(), // Empty string - will fail validation
-        metadata: cachified::CacheMetadata {
-            created_time: std::time::SystemTime::now()
+        metadata: cachified::CacheMetadata::with_time(
+            std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap(),
-            ttl: Some(Duration::from_secs(300)),
-        }
+            Some(Duration::from_secs(300)),
+        )
     }).await.unwrap();
 
     // Try to get it with validation - should fetch fresh value
@@ -183,6 +367,71 @@ async fn test_validation() {
     assert_eq!(valid_value, "valid-fresh-value");
 }
 
+#[tokio::test]
+async fn test_fatal_validation_skips_fetch() {
+    let cache = MokaCache::new(100);
+    let call_count = Arc::new(AtomicUsize::new(0));
+    cache.put("fatal-test", "".to_string(), Some(Duration::from_secs(300))).await.unwrap();
+
+    let fetch_count = call_count.clone();
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "fatal-test")
+            .check_value(Fatal(NonEmptyStringValidator))
+            .get_fresh_value(move || {
+                let fetch_count = fetch_count.clone();
+                async move {
+                    fetch_count.fetch_add(1, Ordering::SeqCst);
+                    Ok("fresh".to_string())
+                }
+            })
+    ).await;
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Validation);
+    assert_eq!(call_count.load(Ordering::SeqCst), 0);
+}
+
+fn known_schema(value: &str) -> cachified::Result<()> {
+    if value.starts_with("v1:") || value.starts_with("v2:") {
+        Ok(())
+    } else {
+        Err(CachifiedError::validation("Unknown schema"))
+    }
+}
+
+fn latest_schema(value: &str) -> cachified::Result<()> {
+    if value.starts_with("v2:") {
+        Ok(())
+    } else {
+        Err(CachifiedError::validation("Outdated schema"))
+    }
+}
+
+#[tokio::test]
+async fn test_separate_cached_and_fresh_validators() {
+    let cache = MokaCache::new(100);
+    cache.put("schema-test", "v1:cached".to_string(), Some(Duration::from_secs(300))).await.unwrap();
+
+    // The cached value only has to pass the lenient check
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "schema-test")
+            .check_cached_value(FunctionValidator::new(|value: &String| known_schema(value)))
+            .check_fresh_value(FunctionValidator::new(|value: &String| latest_schema(value)))
+            .get_fresh_value(|| async { Ok("v2:fresh".to_string()) })
+    ).await.unwrap();
+    assert_eq!(value, "v1:cached");
+
+    // Fresh values have to pass the strict check
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "schema-test")
+            .force_fresh(true)
+            .check_cached_value(FunctionValidator::new(|value: &String| known_schema(value)))
+            .check_fresh_value(FunctionValidator::new(|value: &String| latest_schema(value)))
+            .get_fresh_value(|| async { Ok("v1:fresh".to_string()) })
+    ).await;
+    assert!(result.is_err());
+    assert_eq!(cache.get_value("schema-test", Duration::ZERO).await, Some("v1:cached".to_string()));
+}
+
 #[tokio::test]
 async fn test_fallback_to_cache() {
     let cache = MokaCache::new(100);
@@ -226,12 +475,68 @@ async fn test_error_handling() {
     ).await;
 
     assert!(result.is_err());
-    match result.unwrap_err() {
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::FreshValue);
+    match error {
         CachifiedError::FreshValueError(msg) => assert_eq!(msg, "Test error"),
         _ => panic!("Wrong error type"),
     }
 }
 
+#[tokio::test]
+async fn test_error_source_is_preserved() {
+    let cache = MokaCache::new(100);
+
+    let result: Result<String, CachifiedError> = cachified(
+        CachifiedOptionsBuilder::new(cache, "error-source-test")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                let response: Result<String, std::io::Error> =
+                    Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "upstream timed out"));
+                response.map_err(CachifiedError::fresh_value_source)
+            })
+    ).await;
+
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::FreshValue);
+    let source = error.downcast_source_ref::<std::io::Error>().unwrap();
+    assert_eq!(source.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+enum DomainError {
+    #[error("not found")]
+    NotFound,
+    #[error(transparent)]
+    Cache(#[from] CachifiedError),
+}
+
+#[tokio::test]
+async fn test_typed_error_round_trip() {
+    let cache = MokaCache::new(100);
+
+    let result: Result<String, DomainError> = cachified_typed(
+        CachifiedOptionsBuilder::new(cache.clone(), "typed-error").ttl(Duration::from_secs(60)),
+        || async { Err(DomainError::NotFound) },
+    ).await;
+    assert!(matches!(result, Err(DomainError::NotFound)));
+
+    // Errors of cachified itself are converted into the caller's type
+    let result: Result<String, DomainError> = cachified_typed(
+        CachifiedOptionsBuilder::new(cache.clone(), "typed-error")
+            .ttl(Duration::from_secs(60))
+            .check_fresh_value(NonEmptyStringValidator),
+        || async { Ok(String::new()) },
+    ).await;
+    assert!(matches!(result, Err(DomainError::Cache(ref e)) if e.kind() == ErrorKind::Validation));
+
+    let value: Result<String, DomainError> = cachified_typed(
+        CachifiedOptionsBuilder::new(cache, "typed-error").ttl(Duration::from_secs(60)),
+        || async { Ok("value".to_string()) },
+    ).await;
+    assert_eq!(value.unwrap(), "value");
+}
+
 #[tokio::test]
 async fn test_different_key_isolation() {
     let cache = MokaCache::new(100);
@@ -267,3 +572,2018 @@ async fn test_different_key_isolation() {
 
     assert_eq!(value1_again, "value1"); // Should still be cached
 }
+
+#[tokio::test]
+async fn test_fresh_value_unchanged_keeps_cached_value() {
+    let cache = MokaCache::new(100);
+
+    // Populate cache with a short TTL
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "unchanged-test")
+            .ttl(Duration::from_millis(50))
+            .get_fresh_value(|| async {
+                Ok("original-value".to_string())
+            })
+    ).await.unwrap();
+
+    let original = cache.get("unchanged-test").await.unwrap();
+
+    // Wait for expiration
+    sleep(Duration::from_millis(100)).await;
+
+    // Upstream reports nothing changed, the cached value should be kept
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "unchanged-test")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value_outcome(|| async {
+                Ok(FreshValueOutcome::Unchanged)
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "original-value");
+
+    // The entry's creation time and TTL should have been refreshed
+    let refreshed = cache.get("unchanged-test").await.unwrap();
+    assert_eq!(refreshed.value, "original-value");
+    assert_eq!(refreshed.metadata.ttl, Some(Duration::from_secs(60)));
+    assert!(refreshed.metadata.created_time > original.metadata.created_time);
+}
+
+#[tokio::test]
+async fn test_fresh_value_unchanged_without_cached_value() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+
+    let result: Result<String, CachifiedError> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "unchanged-missing")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value_outcome(|| async {
+                Ok(FreshValueOutcome::Unchanged)
+            })
+    ).await;
+
+    assert!(matches!(result, Err(CachifiedError::FreshValueError(_))));
+}
+
+#[tokio::test]
+async fn test_max_concurrent_refreshes() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new().max_concurrent_refreshes(2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    // Cold cache, many different keys at once
+    let mut handles = Vec::new();
+    for i in 0..10 {
+        let cache = cache.clone();
+        let config = config.clone();
+        let running = running.clone();
+        let max_running = max_running.clone();
+
+        handles.push(tokio::spawn(async move {
+            let value: String = cachified(
+                CachifiedOptionsBuilder::new(cache, format!("limit-{}", i))
+                    .ttl(Duration::from_secs(60))
+                    .config(config)
+                    .get_fresh_value(move || {
+                        let running = running.clone();
+                        let max_running = max_running.clone();
+                        async move {
+                            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_running.fetch_max(now_running, Ordering::SeqCst);
+                            sleep(Duration::from_millis(20)).await;
+                            running.fetch_sub(1, Ordering::SeqCst);
+                            Ok(format!("value-{}", i))
+                        }
+                    })
+            ).await.unwrap();
+            assert_eq!(value, format!("value-{}", i));
+        }));
+    }
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+}
+
+/// A cache whose reads always fail
+#[derive(Clone)]
+struct UnreadableCache;
+
+#[async_trait::async_trait]
+impl Cache<String> for UnreadableCache {
+    async fn get(&self, _key: &str) -> Option<CacheEntry<String>> {
+        None
+    }
+
+    async fn try_get(&self, _key: &str) -> Result<Option<CacheEntry<String>>, CachifiedError> {
+        Err(CachifiedError::cache("connection refused"))
+    }
+
+    async fn set(&self, _key: &str, _entry: CacheEntry<String>) -> Result<(), CachifiedError> {
+        Ok(())
+    }
+
+    async fn remove(&self, _key: &str) {}
+
+    async fn clear(&self) {}
+
+    async fn len(&self) -> usize {
+        0
+    }
+}
+
+#[tokio::test]
+async fn test_read_error_treated_as_miss() {
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(UnreadableCache, "read-error")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                Ok("fresh-value".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "fresh-value");
+}
+
+/// A reporter recording the read errors treated as misses
+#[derive(Default)]
+struct ReadErrorReporter {
+    errors: Mutex<Vec<(String, String)>>,
+}
+
+impl Reporter for ReadErrorReporter {
+    fn on_cache_read_error(&self, key: &str, error: &CachifiedError) {
+        self.errors.lock().unwrap().push((key.to_string(), error.to_string()));
+    }
+}
+
+#[tokio::test]
+async fn test_read_error_treated_as_miss_is_reported() {
+    let reporter = Arc::new(ReadErrorReporter::default());
+    let stats = Arc::new(CacheStats::new());
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(UnreadableCache, "read-error")
+            .ttl(Duration::from_secs(60))
+            .reporter(reporter.clone())
+            .stats(stats.clone())
+            .get_fresh_value(|| async { Ok("fresh-value".to_string()) })
+    ).await.unwrap();
+
+    assert_eq!(value, "fresh-value");
+    let errors = reporter.errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "read-error");
+    assert!(errors[0].1.contains("connection refused"));
+    assert_eq!(stats.snapshot().misses, 1);
+}
+
+#[tokio::test]
+async fn test_read_error_propagated() {
+    let call_count = Arc::new(Mutex::new(0));
+    let call_count_clone = call_count.clone();
+
+    let result: Result<String, CachifiedError> = cachified(
+        CachifiedOptionsBuilder::new(UnreadableCache, "read-error")
+            .ttl(Duration::from_secs(60))
+            .read_error_policy(ReadErrorPolicy::Propagate)
+            .get_fresh_value(move || {
+                let call_count = call_count_clone.clone();
+                async move {
+                    *call_count.lock().unwrap() += 1;
+                    Ok("fresh-value".to_string())
+                }
+            })
+    ).await;
+
+    assert!(matches!(result, Err(CachifiedError::CacheError(_))));
+    assert_eq!(*call_count.lock().unwrap(), 0); // No fresh fetch on read errors
+}
+
+#[tokio::test]
+async fn test_error_stage_is_opt_in() {
+    let result: Result<String, CachifiedError> = cachified(
+        CachifiedOptionsBuilder::new(UnreadableCache, "read-error")
+            .ttl(Duration::from_secs(60))
+            .read_error_policy(ReadErrorPolicy::Propagate)
+            .error_stage(true)
+            .get_fresh_value(|| async { Ok("fresh-value".to_string()) })
+    ).await;
+
+    let error = result.unwrap_err();
+    assert_eq!(error.stage(), Some(Stage::ReadCache));
+    assert_eq!(error.kind(), ErrorKind::Cache);
+    assert!(matches!(error.into_inner(), CachifiedError::CacheError(_)));
+}
+
+#[tokio::test]
+async fn test_always_revalidate() {
+    let cache = MokaCache::new(100);
+
+    // Populate cache with a long TTL
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "always-revalidate")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                Ok("cached-value".to_string())
+            })
+    ).await.unwrap();
+
+    // Even though the value is fresh, it is served and refreshed in the background
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "always-revalidate")
+            .ttl(Duration::from_secs(60))
+            .always_revalidate(true)
+            .get_fresh_value(|| async {
+                Ok("refreshed-value".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "cached-value");
+
+    // Wait for background refresh to complete
+    sleep(Duration::from_millis(50)).await;
+
+    let entry = cache.get("always-revalidate").await.unwrap();
+    assert_eq!(entry.value, "refreshed-value");
+}
+
+#[tokio::test]
+async fn test_always_revalidate_cold_cache() {
+    let cache = MokaCache::new(100);
+
+    // Nothing cached, so the fresh value is awaited
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "always-revalidate-cold")
+            .ttl(Duration::from_secs(60))
+            .always_revalidate(true)
+            .get_fresh_value(|| async {
+                Ok("fresh-value".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_min_cacheable_ttl() {
+    let cache = MokaCache::new(100);
+
+    // TTL below the floor, the value is returned but not cached
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "min-ttl")
+            .ttl(Duration::from_secs(1))
+            .min_cacheable_ttl(Duration::from_secs(5))
+            .get_fresh_value(|| async {
+                Ok("short-lived".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "short-lived");
+    assert!(cache.get("min-ttl").await.is_none());
+}
+
+#[tokio::test]
+async fn test_max_ttl() {
+    let cache = MokaCache::new(100);
+
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "max-ttl")
+            .ttl(Duration::from_secs(365 * 24 * 60 * 60))
+            .max_ttl(Duration::from_secs(3600))
+            .get_fresh_value(|| async {
+                Ok("long-lived".to_string())
+            })
+    ).await.unwrap();
+
+    let entry = cache.get("max-ttl").await.unwrap();
+    assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(3600)));
+}
+
+#[tokio::test]
+async fn test_ttl_from_value() {
+    let cache = MokaCache::new(100);
+
+    // A forecast that is valid for 10 minutes
+    let _: (String, u64) = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "forecast")
+            .ttl(Duration::from_secs(60))
+            .ttl_from_value(|(_, valid_for_secs): &(String, u64)| {
+                Some(Duration::from_secs(*valid_for_secs))
+            })
+            .get_fresh_value(|| async {
+                Ok(("sunny".to_string(), 600))
+            })
+    ).await.unwrap();
+
+    let entry = cache.get("forecast").await.unwrap();
+    assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(600)));
+
+    // Falls back to the configured TTL when no TTL is derived
+    let _: (String, u64) = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "forecast-unknown")
+            .ttl(Duration::from_secs(60))
+            .ttl_from_value(|_: &(String, u64)| None)
+            .get_fresh_value(|| async {
+                Ok(("cloudy".to_string(), 0))
+            })
+    ).await.unwrap();
+
+    let entry = cache.get("forecast-unknown").await.unwrap();
+    assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(60)));
+}
+
+#[tokio::test]
+async fn test_ttl_jitter_spreads_expiry() {
+    let cache = MokaCache::new(100);
+
+    for index in 0..20 {
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), format!("jitter-{index}"))
+                .ttl(Duration::from_secs(100))
+                .ttl_jitter(0.1)
+                .ttl_jitter_seed(index)
+                .get_fresh_value(|| async { Ok("value".to_string()) })
+        ).await.unwrap();
+    }
+
+    let mut ttls = Vec::new();
+    for index in 0..20 {
+        let ttl = cache.get(&format!("jitter-{index}")).await.unwrap().metadata.ttl.unwrap();
+        assert!(ttl >= Duration::from_secs(90) && ttl <= Duration::from_secs(110));
+        ttls.push(ttl);
+    }
+    ttls.sort();
+    ttls.dedup();
+    assert!(ttls.len() > 1);
+
+    // The same seed gives the same TTL, and max_ttl still caps it
+    let ttl_with_seed = |seed| {
+        let cache = cache.clone();
+        async move {
+            let _: String = cachified(
+                CachifiedOptionsBuilder::new(cache.clone(), "jitter-seeded")
+                    .force_fresh(true)
+                    .ttl(Duration::from_secs(100))
+                    .max_ttl(Duration::from_secs(100))
+                    .ttl_jitter(0.5)
+                    .ttl_jitter_seed(seed)
+                    .get_fresh_value(|| async { Ok("value".to_string()) })
+            ).await.unwrap();
+            cache.get("jitter-seeded").await.unwrap().metadata.ttl.unwrap()
+        }
+    };
+    assert_eq!(ttl_with_seed(7).await, ttl_with_seed(7).await);
+    assert!(ttl_with_seed(7).await <= Duration::from_secs(100));
+}
+
+#[tokio::test]
+async fn test_drain_background_refreshes() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new();
+
+    // Populate cache
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "drain-test")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                Ok("cached-value".to_string())
+            })
+    ).await.unwrap();
+
+    // Trigger a slow background refresh
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "drain-test")
+            .ttl(Duration::from_secs(60))
+            .always_revalidate(true)
+            .config(config.clone())
+            .get_fresh_value(|| async {
+                sleep(Duration::from_millis(50)).await;
+                Ok("refreshed-value".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(config.refresh_tracker().active(), 1);
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    // The refresh completed before draining returned
+    let entry = cache.get("drain-test").await.unwrap();
+    assert_eq!(entry.value, "refreshed-value");
+}
+
+#[tokio::test]
+async fn test_early_refresh_before_expiry() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let get = |key: &'static str| {
+        let calls = calls.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .early_refresh(Duration::from_secs(10))
+                .config(config.clone())
+                .get_fresh_value(move || {
+                    let calls = calls.clone();
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(50)).await;
+                        Ok("refreshed-value".to_string())
+                    }
+                }),
+        )
+    };
+    let entry = |age: u64| {
+        CacheEntry::builder("cached-value".to_string())
+            .ttl(Duration::from_secs(60))
+            .created_ago(Duration::from_secs(age))
+            .build()
+    };
+
+    // Before the threshold, hits don't refresh
+    cache.set("early-refresh-young", entry(30)).await.unwrap();
+    let value: String = get("early-refresh-young").await.unwrap();
+    assert_eq!(value, "cached-value");
+    assert_eq!(config.refresh_tracker().active(), 0);
+
+    // Within the threshold, the fresh value is served and refreshed once in the background
+    cache.set("early-refresh-old", entry(55)).await.unwrap();
+    for _ in 0..3 {
+        let value: String = get("early-refresh-old").await.unwrap();
+        assert_eq!(value, "cached-value");
+    }
+    assert_eq!(config.refresh_tracker().active(), 1);
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.get("early-refresh-old").await.unwrap().value, "refreshed-value");
+    assert_eq!(cache.get("early-refresh-young").await.unwrap().value, "cached-value");
+}
+
+#[tokio::test]
+async fn test_mock_clock_drives_expiry_and_swr() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let config = CachifiedConfig::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let get = || {
+        let calls = calls.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "mock-clock")
+                .ttl(Duration::from_secs(60))
+                .stale_while_revalidate(Duration::from_secs(30))
+                .clock(clock.clone())
+                .config(config.clone())
+                .get_fresh_value(move || {
+                    let count = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { Ok(format!("value-{count}")) }
+                }),
+        )
+    };
+
+    assert_eq!(get().await.unwrap(), "value-1");
+    let created = cache.get("mock-clock").await.unwrap().metadata.created_time;
+    assert_eq!(created, clock.now());
+
+    // Still fresh just before expiry
+    clock.advance(Duration::from_secs(59));
+    assert_eq!(get().await.unwrap(), "value-1");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Stale within the window: served while refreshing in the background
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(get().await.unwrap(), "value-1");
+    assert!(config.drain(Duration::from_secs(1)).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    let refreshed = cache.get("mock-clock").await.unwrap();
+    assert_eq!(refreshed.value, "value-2");
+    assert_eq!(refreshed.metadata.created_time, clock.now());
+
+    // Past the window, a fresh value is fetched right away
+    clock.advance(Duration::from_secs(91));
+    assert_eq!(get().await.unwrap(), "value-3");
+}
+
+#[tokio::test]
+async fn test_stored_swr_window_applies_without_option() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let config = CachifiedConfig::new();
+
+    let metadata = CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60)))
+        .with_swr(Some(Duration::from_secs(30)));
+    cache.set("stored-swr", CacheEntry::with_metadata("stale-value".to_string(), metadata)).await.unwrap();
+    clock.advance(Duration::from_secs(70));
+
+    // The caller doesn't configure a window, so the one of the entry is used
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "stored-swr")
+            .ttl(Duration::from_secs(60))
+            .clock(clock.clone())
+            .config(config.clone())
+            .get_fresh_value(|| async { Ok("fresh-value".to_string()) }),
+    ).await.unwrap();
+    assert_eq!(value, "stale-value");
+    assert!(config.drain(Duration::from_secs(1)).await);
+    assert_eq!(cache.get("stored-swr").await.unwrap().value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_wait_until_receives_background_refresh() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let pending: Arc<Mutex<Vec<BoxFuture<'static, ()>>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let metadata = CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60)));
+    cache.set("wait-until", CacheEntry::with_metadata("stale-value".to_string(), metadata)).await.unwrap();
+    clock.advance(Duration::from_secs(70));
+
+    let collected = pending.clone();
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "wait-until")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(30))
+            .clock(clock.clone())
+            .wait_until(move |refresh| collected.lock().unwrap().push(refresh))
+            .get_fresh_value(|| async { Ok("fresh-value".to_string()) }),
+    ).await.unwrap();
+    assert_eq!(value, "stale-value");
+
+    // Nothing runs the refresh until the collected future is awaited
+    let refreshes: Vec<_> = pending.lock().unwrap().drain(..).collect();
+    assert_eq!(refreshes.len(), 1);
+    assert_eq!(cache.get("wait-until").await.unwrap().value, "stale-value");
+
+    futures_util::future::join_all(refreshes).await;
+    assert_eq!(cache.get("wait-until").await.unwrap().value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_panicking_background_refresh_is_reported() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let config = CachifiedConfig::new();
+    let stats = Arc::new(CacheStats::new());
+
+    let metadata = CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60)));
+    cache.set("panicking-refresh", CacheEntry::with_metadata("stale-value".to_string(), metadata)).await.unwrap();
+    clock.advance(Duration::from_secs(70));
+
+    let options = || {
+        CachifiedOptionsBuilder::new(cache.clone(), "panicking-refresh")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(30))
+            .clock(clock.clone())
+            .config(config.clone())
+            .stats(stats.clone())
+    };
+
+    let value: String = cachified(
+        options().get_fresh_value(|| async { panic!("upstream exploded") }),
+    ).await.unwrap();
+    assert_eq!(value, "stale-value");
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    // The panic counts as a failed fetch and the stale entry is kept
+    assert_eq!(stats.snapshot().fresh_errors, 1);
+    let entry = cache.get("panicking-refresh").await.unwrap();
+    assert_eq!(entry.value, "stale-value");
+    assert_eq!(entry.metadata.refresh_failures, 1);
+    assert!(entry.metadata.last_refresh_error.unwrap().contains("upstream exploded"));
+
+    // Later calls still serve the stale value and refresh it
+    let value: String = cachified(
+        options().get_fresh_value(|| async { Ok("fresh-value".to_string()) }),
+    ).await.unwrap();
+    assert_eq!(value, "stale-value");
+    assert!(config.drain(Duration::from_secs(1)).await);
+    assert_eq!(cache.get("panicking-refresh").await.unwrap().value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_max_background_refreshes_skips_overflow() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new().max_background_refreshes(1);
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    for key in ["bg-limit-a", "bg-limit-b"] {
+        cache.set(key, CacheEntry::new("cached-value".to_string(), None)).await.unwrap();
+    }
+
+    for key in ["bg-limit-a", "bg-limit-b"] {
+        let calls = calls.clone();
+        let value: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .always_revalidate(true)
+                .config(config.clone())
+                .get_fresh_value(move || {
+                    let calls = calls.clone();
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(50)).await;
+                        Ok("refreshed-value".to_string())
+                    }
+                })
+        ).await.unwrap();
+
+        // Stale values are served whether or not a refresh was started
+        assert_eq!(value, "cached-value");
+    }
+
+    assert_eq!(config.refresh_tracker().active(), 1);
+    assert!(config.drain(Duration::from_secs(1)).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.get("bg-limit-a").await.unwrap().value, "refreshed-value");
+    assert_eq!(cache.get("bg-limit-b").await.unwrap().value, "cached-value");
+}
+
+#[tokio::test]
+async fn test_stream_yields_stale_then_fresh() {
+    let cache = MokaCache::new(100);
+    cache
+        .set("stream-swr", CacheEntry::new("stale-value".to_string(), Some(Duration::ZERO)))
+        .await
+        .unwrap();
+
+    let values: Vec<String> = cachified_stream(
+        CachifiedOptionsBuilder::new(cache.clone(), "stream-swr")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                sleep(Duration::from_millis(20)).await;
+                Ok("fresh-value".to_string())
+            })
+    )
+    .map(|value| value.unwrap())
+    .collect()
+    .await;
+
+    assert_eq!(values, vec!["stale-value", "fresh-value"]);
+    assert_eq!(cache.get("stream-swr").await.unwrap().value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_stream_single_value_on_miss() {
+    let cache = MokaCache::new(100);
+
+    let values: Vec<String> = cachified_stream(
+        CachifiedOptionsBuilder::new(cache.clone(), "stream-miss")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(60))
+            .get_fresh_value(|| async { Ok("fresh-value".to_string()) })
+    )
+    .map(|value| value.unwrap())
+    .collect()
+    .await;
+
+    assert_eq!(values, vec!["fresh-value"]);
+}
+
+#[tokio::test]
+async fn test_stream_yields_refresh_error() {
+    let cache = MokaCache::new(100);
+    cache
+        .set("stream-error", CacheEntry::new("stale-value".to_string(), Some(Duration::ZERO)))
+        .await
+        .unwrap();
+
+    let results: Vec<Result<String, CachifiedError>> = cachified_stream(
+        CachifiedOptionsBuilder::new(cache.clone(), "stream-error")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                Err::<String, _>(CachifiedError::fresh_value("upstream down"))
+            })
+    )
+    .collect()
+    .await;
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap(), "stale-value");
+    assert_eq!(results[1].as_ref().unwrap_err().kind(), ErrorKind::FreshValue);
+    // The stale entry is kept
+    assert_eq!(cache.get("stream-error").await.unwrap().value, "stale-value");
+}
+
+#[derive(Default)]
+struct CountingReporter {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    fetch_starts: AtomicUsize,
+    fetch_successes: AtomicUsize,
+    errors: AtomicUsize,
+    fallbacks: AtomicUsize,
+    writes: AtomicUsize,
+}
+
+impl Reporter for CountingReporter {
+    fn on_cache_hit(&self, _key: &str) {
+        self.hits.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_cache_miss(&self, _key: &str) {
+        self.misses.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_get_fresh_value_start(&self, _key: &str) {
+        self.fetch_starts.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_get_fresh_value_success(&self, _key: &str, _duration: Duration) {
+        self.fetch_successes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_get_fresh_value_error(&self, _key: &str, _error: &CachifiedError) {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_fallback_value(&self, _key: &str) {
+        self.fallbacks.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_write(&self, _key: &str, metadata: &CacheMetadata) {
+        assert_eq!(metadata.ttl, Some(Duration::from_secs(60)));
+        self.writes.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn test_reporter_events() {
+    let cache = MokaCache::new(100);
+    let reporter = Arc::new(CountingReporter::default());
+
+    // Miss, then hit
+    for _ in 0..2 {
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "reporter-test")
+                .ttl(Duration::from_secs(60))
+                .reporter(reporter.clone())
+                .get_fresh_value(|| async { Ok("value".to_string()) })
+        ).await.unwrap();
+    }
+
+    // Miss with a failing fetch
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "reporter-error")
+            .reporter(reporter.clone())
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("down")) })
+    ).await;
+    assert!(result.is_err());
+
+    assert_eq!(reporter.hits.load(Ordering::SeqCst), 1);
+    assert_eq!(reporter.misses.load(Ordering::SeqCst), 2);
+    assert_eq!(reporter.fetch_starts.load(Ordering::SeqCst), 2);
+    assert_eq!(reporter.fetch_successes.load(Ordering::SeqCst), 1);
+    assert_eq!(reporter.errors.load(Ordering::SeqCst), 1);
+    assert_eq!(reporter.writes.load(Ordering::SeqCst), 1);
+
+    // Writes through cachified_set are reported too
+    cachified_set(
+        CachifiedOptionsBuilder::new(cache.clone(), "reporter-test")
+            .ttl(Duration::from_secs(60))
+            .reporter(reporter.clone()),
+        "updated".to_string(),
+    ).await.unwrap();
+    assert_eq!(reporter.writes.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_key_fn() {
+    let cache = MokaCache::new(100);
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "placeholder")
+            .ttl(Duration::from_secs(60))
+            .key_fn(|| async {
+                sleep(Duration::from_millis(5)).await; // Simulate resolving a tenant
+                Ok("tenant:acme".to_string())
+            })
+            .get_fresh_value(|| async { Ok("value".to_string()) })
+    ).await.unwrap();
+
+    assert_eq!(value, "value");
+    assert!(cache.get("tenant:acme").await.is_some());
+    assert!(cache.get("placeholder").await.is_none());
+}
+
+#[tokio::test]
+async fn test_key_fn_error_short_circuits() {
+    let cache = MokaCache::new(100);
+    let call_count = Arc::new(AtomicUsize::new(0));
+
+    let call_count_clone = call_count.clone();
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "placeholder")
+            .key_fn(|| async { Err(CachifiedError::other("tenant not found")) })
+            .get_fresh_value(move || {
+                let call_count = call_count_clone.clone();
+                async move {
+                    call_count.fetch_add(1, Ordering::SeqCst);
+                    Ok("value".to_string())
+                }
+            })
+    ).await;
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(call_count.load(Ordering::SeqCst), 0);
+    assert!(cache.is_empty().await);
+}
+
+#[tokio::test]
+async fn test_key_from_input() {
+    let cache = MokaCache::new(100);
+    let call_count = Arc::new(AtomicUsize::new(0));
+
+    for (query, page) in [("rust", 1), ("rust", 1), ("rust", 2)] {
+        let call_count = call_count.clone();
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "search:")
+                .ttl(Duration::from_secs(60))
+                .key_from_input(&(query, page))
+                .get_fresh_value(move || {
+                    let call_count = call_count.clone();
+                    async move {
+                        call_count.fetch_add(1, Ordering::SeqCst);
+                        Ok(format!("{query} page {page}"))
+                    }
+                })
+        ).await.unwrap();
+    }
+
+    // Equal inputs share an entry, different inputs don't
+    assert_eq!(call_count.load(Ordering::SeqCst), 2);
+    let key = cachified::key::stable_key("search:", &("rust", 2)).unwrap();
+    assert_eq!(cache.get(&key).await.unwrap().value, "rust page 2");
+}
+
+/// Cache an entry that is `depth` (0.0 to 1.0) into its stale-while-revalidate window
+async fn set_stale_entry(cache: &MokaCache<String>, key: &str, ttl: Duration, swr: Duration, depth: f64) {
+    let entry = CacheEntry::builder("stale".to_string())
+        .ttl(ttl)
+        .created_ago(ttl + swr.mul_f64(depth))
+        .build();
+    cache.set(key, entry).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_swr_policy_urgent_refresh_bypasses_limit() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new().max_concurrent_refreshes(1);
+    let ttl = Duration::from_secs(10);
+    let swr = Duration::from_secs(100);
+
+    set_stale_entry(&cache, "shallow", ttl, swr, 0.1).await;
+    set_stale_entry(&cache, "deep", ttl, swr, 0.8).await;
+
+    // Occupy the only refresh slot
+    let blocking_cache = cache.clone();
+    let blocking_config = config.clone();
+    let blocker = tokio::spawn(async move {
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(blocking_cache, "blocker")
+                .config(blocking_config)
+                .get_fresh_value(|| async {
+                    sleep(Duration::from_millis(300)).await;
+                    Ok("blocker".to_string())
+                })
+        ).await.unwrap();
+    });
+    sleep(Duration::from_millis(20)).await;
+
+    for key in ["shallow", "deep"] {
+        let value: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .stale_while_revalidate(swr)
+                .swr_policy(SwrPolicy::urgent_after(0.5))
+                .config(config.clone())
+                .get_fresh_value(|| async { Ok("fresh".to_string()) })
+        ).await.unwrap();
+        assert_eq!(value, "stale");
+    }
+
+    sleep(Duration::from_millis(50)).await;
+
+    // Only the refresh deep into the window ran while the slot was taken
+    assert_eq!(cache.get("deep").await.unwrap().value, "fresh");
+    assert_eq!(cache.get("shallow").await.unwrap().value, "stale");
+
+    blocker.await.unwrap();
+    assert!(config.drain(Duration::from_secs(1)).await);
+    assert_eq!(cache.get("shallow").await.unwrap().value, "fresh");
+}
+
+#[tokio::test]
+async fn test_cachified_many_fetches_only_misses() {
+    let cache = MokaCache::new(100);
+    for key in ["a", "c", "e"] {
+        cache.put(key, format!("cached-{key}"), Some(Duration::from_secs(60))).await.unwrap();
+    }
+    cache.put("d", "expired-d".to_string(), Some(Duration::ZERO)).await.unwrap();
+
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    let requested_clone = requested.clone();
+    let values: Vec<String> = cachified_many(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)),
+        &["a", "b", "c", "d", "e"],
+        |missing| async move {
+            requested_clone.lock().unwrap().push(missing.clone());
+            Ok(missing.iter().map(|key| format!("fresh-{key}")).collect())
+        },
+    ).await.unwrap();
+
+    assert_eq!(values, vec!["cached-a", "fresh-b", "cached-c", "fresh-d", "cached-e"]);
+    // A single batch call for exactly the misses
+    assert_eq!(*requested.lock().unwrap(), vec![vec!["b".to_string(), "d".to_string()]]);
+    assert_eq!(cache.get("b").await.unwrap().value, "fresh-b");
+}
+
+#[tokio::test]
+async fn test_cachified_many_all_cached() {
+    let cache = MokaCache::new(100);
+    cache.put("a", "cached-a".to_string(), None).await.unwrap();
+
+    let values: Vec<String> = cachified_many(
+        CachifiedOptionsBuilder::new(&cache, "batch"),
+        &["a"],
+        |_| async { panic!("no fresh values should be fetched") },
+    ).await.unwrap();
+
+    assert_eq!(values, vec!["cached-a"]);
+}
+
+#[tokio::test]
+async fn test_cachified_many_wrong_value_count() {
+    let cache = MokaCache::new(100);
+
+    let result: Result<Vec<String>, _> = cachified_many(
+        CachifiedOptionsBuilder::new(&cache, "batch"),
+        &["a", "b"],
+        |_| async { Ok(vec!["only-one".to_string()]) },
+    ).await;
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::FreshValue);
+}
+
+#[tokio::test]
+async fn test_cachified_many_applies_write_and_validation_options() {
+    let cache = MokaCache::new(100);
+    cache.put("a", "".to_string(), Some(Duration::from_secs(60))).await.unwrap();
+    let options = || {
+        CachifiedOptionsBuilder::new(&cache, "batch")
+            .ttl(Duration::from_secs(60))
+            .max_ttl(Duration::from_secs(10))
+            .check_value(NonEmptyStringValidator)
+    };
+
+    // The invalid cached value is refetched, and the TTL is capped
+    let values: Vec<String> = cachified_many(options(), &["a", "b"], |missing| async move {
+        Ok(missing.iter().map(|key| format!("fresh-{key}")).collect())
+    }).await.unwrap();
+    assert_eq!(values, vec!["fresh-a", "fresh-b"]);
+    assert_eq!(cache.get("a").await.unwrap().metadata.ttl, Some(Duration::from_secs(10)));
+
+    // Invalid fresh values fail the call and aren't cached
+    let result: Result<Vec<Option<String>>, _> = cachified_many_keyed(options(), &["c"], |missing| async move {
+        Ok(missing.into_iter().map(|key| (key, String::new())).collect())
+    }).await;
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Validation);
+    assert!(cache.get("c").await.is_none());
+}
+
+#[tokio::test]
+async fn test_cachified_many_keyed_handles_omitted_keys() {
+    let cache = MokaCache::new(100);
+    cache.put("a", "cached-a".to_string(), Some(Duration::from_secs(60))).await.unwrap();
+
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    let requested_clone = requested.clone();
+    let values: Vec<Option<String>> = cachified_many_keyed(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)),
+        &["a", "b", "missing", "c"],
+        |missing| async move {
+            requested_clone.lock().unwrap().push(missing.clone());
+            Ok(missing
+                .into_iter()
+                .filter(|key| key != "missing")
+                .map(|key| {
+                    let value = format!("fresh-{key}");
+                    (key, value)
+                })
+                .collect())
+        },
+    ).await.unwrap();
+
+    assert_eq!(values, vec![
+        Some("cached-a".to_string()),
+        Some("fresh-b".to_string()),
+        None,
+        Some("fresh-c".to_string()),
+    ]);
+    assert_eq!(*requested.lock().unwrap(), vec![vec!["b".to_string(), "missing".to_string(), "c".to_string()]]);
+    assert_eq!(cache.get("c").await.unwrap().value, "fresh-c");
+    // Omitted keys aren't cached
+    assert!(cache.get("missing").await.is_none());
+}
+
+/// A cache recording how its entries were written
+#[derive(Clone)]
+struct WriteRecordingCache {
+    inner: MokaCache<String>,
+    single_writes: Arc<Mutex<usize>>,
+    batch_writes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl WriteRecordingCache {
+    fn new() -> Self {
+        Self {
+            inner: MokaCache::new(100),
+            single_writes: Arc::default(),
+            batch_writes: Arc::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache<String> for WriteRecordingCache {
+    async fn get(&self, key: &str) -> Option<CacheEntry<String>> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, entry: CacheEntry<String>) -> Result<(), CachifiedError> {
+        *self.single_writes.lock().unwrap() += 1;
+        self.inner.set(key, entry).await
+    }
+
+    async fn set_many(&self, entries: Vec<(String, CacheEntry<String>)>) -> Vec<Result<(), CachifiedError>> {
+        self.batch_writes.lock().unwrap().push(entries.len());
+        self.inner.set_many(entries).await
+    }
+
+    async fn remove(&self, key: &str) {
+        self.inner.remove(key).await
+    }
+
+    async fn clear(&self) {
+        self.inner.clear().await
+    }
+
+    async fn len(&self) -> usize {
+        self.inner.len().await
+    }
+}
+
+#[tokio::test]
+async fn test_cachified_many_writes_misses_in_one_batch() {
+    let cache = WriteRecordingCache::new();
+    cache.inner.set("a", CacheEntry::new("cached-a".to_string(), Some(Duration::from_secs(60)))).await.unwrap();
+
+    let values: Vec<String> = cachified_many(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)),
+        &["a", "b", "c", "d"],
+        |missing| async move { Ok(missing.iter().map(|key| format!("fresh-{key}")).collect()) },
+    ).await.unwrap();
+    assert_eq!(values, vec!["cached-a", "fresh-b", "fresh-c", "fresh-d"]);
+
+    let values: Vec<Option<String>> = cachified_many_keyed(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)),
+        &["e", "f", "missing"],
+        |missing| async move {
+            Ok(missing
+                .into_iter()
+                .filter(|key| key != "missing")
+                .map(|key| (key.clone(), format!("fresh-{key}")))
+                .collect())
+        },
+    ).await.unwrap();
+    assert_eq!(values, vec![Some("fresh-e".to_string()), Some("fresh-f".to_string()), None]);
+
+    assert_eq!(*cache.single_writes.lock().unwrap(), 0);
+    assert_eq!(*cache.batch_writes.lock().unwrap(), vec![3, 2]);
+    assert_eq!(cache.get("d").await.unwrap().value, "fresh-d");
+}
+
+#[tokio::test]
+async fn test_cachified_many_uses_injected_clock() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::new(Duration::from_secs(1_000));
+
+    // Long expired by the system clock, but fresh by the mock clock
+    cache
+        .set("a", CacheEntry::with_time("cached-a".to_string(), clock.now(), Some(Duration::from_secs(60))))
+        .await
+        .unwrap();
+
+    let values: Vec<String> = cachified_many(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)).clock(clock.clone()),
+        &["a", "b"],
+        |missing| async move { Ok(missing.iter().map(|key| format!("fresh-{key}")).collect()) },
+    ).await.unwrap();
+
+    assert_eq!(values, vec!["cached-a", "fresh-b"]);
+    assert_eq!(cache.get("b").await.unwrap().metadata.created_time, clock.now());
+
+    clock.advance(Duration::from_secs(60));
+    let values: Vec<Option<String>> = cachified_many_keyed(
+        CachifiedOptionsBuilder::new(&cache, "batch").ttl(Duration::from_secs(60)).clock(clock.clone()),
+        &["a"],
+        |missing| async move { Ok(missing.into_iter().map(|key| (key, "refetched".to_string())).collect()) },
+    ).await.unwrap();
+
+    assert_eq!(values, vec![Some("refetched".to_string())]);
+}
+
+#[tokio::test]
+async fn test_static_cache_reference() {
+    static CACHE: std::sync::LazyLock<MokaCache<String>> = std::sync::LazyLock::new(|| MokaCache::new(100));
+
+    // A `&'static` cache can be passed without cloning
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(&*CACHE, "static-ref")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async { Ok("value".to_string()) })
+    ).await.unwrap();
+
+    assert_eq!(value, "value");
+    assert!(CACHE.contains_key("static-ref").await);
+}
+
+#[tokio::test]
+async fn test_deadline_cancels_fresh_value() {
+    let cache = MokaCache::new(100);
+
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "deadline-test")
+            .deadline(tokio::time::Instant::now() + Duration::from_millis(20))
+            .get_fresh_value(|| async {
+                sleep(Duration::from_secs(5)).await;
+                Ok("too-late".to_string())
+            })
+    ).await;
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+    assert!(cache.get("deadline-test").await.is_none());
+}
+
+#[tokio::test]
+async fn test_cancellation_token_falls_back_to_cache() {
+    let cache = MokaCache::new(100);
+    cache.put("cancel-test", "cached-value".to_string(), Some(Duration::ZERO)).await.unwrap();
+
+    let token = tokio_util::sync::CancellationToken::new();
+    let trigger = token.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(20)).await;
+        trigger.cancel();
+    });
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "cancel-test")
+            .fallback_to_cache(true)
+            .cancellation_token(token)
+            .get_fresh_value(|| async {
+                sleep(Duration::from_secs(5)).await;
+                Ok("too-late".to_string())
+            })
+    ).await.unwrap();
+
+    assert_eq!(value, "cached-value");
+}
+
+trait Plugin: Send + Sync {
+    fn name(&self) -> String;
+}
+
+struct NamedPlugin(String);
+
+impl Plugin for NamedPlugin {
+    fn name(&self) -> String {
+        self.0.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_get_fresh_value_arc_trait_object() {
+    let cache: MokaCache<Arc<dyn Plugin>> = MokaCache::new(100);
+    let call_count = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..2 {
+        let call_count = call_count.clone();
+        let plugin = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "plugin")
+                .ttl(Duration::from_secs(60))
+                .get_fresh_value_arc(move || {
+                    let call_count = call_count.clone();
+                    async move {
+                        call_count.fetch_add(1, Ordering::SeqCst);
+                        Ok(Box::new(NamedPlugin("markdown".to_string())) as Box<dyn Plugin>)
+                    }
+                })
+        ).await.unwrap();
+
+        assert_eq!(plugin.name(), "markdown");
+    }
+
+    assert_eq!(call_count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_compare_and_set_keeps_concurrent_write() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new();
+    let ttl = Duration::from_secs(10);
+    let stale = CacheEntry::builder("stale".to_string())
+        .ttl(ttl)
+        .created_ago(ttl + Duration::from_secs(1))
+        .build();
+    cache.set("cas-test", stale).await.unwrap();
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "cas-test")
+            .ttl(ttl)
+            .stale_while_revalidate(Duration::from_secs(60))
+            .compare_and_set(true)
+            .config(config.clone())
+            .get_fresh_value(|| async {
+                sleep(Duration::from_millis(50)).await;
+                Ok("refreshed".to_string())
+            })
+    ).await.unwrap();
+    assert_eq!(value, "stale");
+
+    // Another writer stores a newer version while the refresh is running
+    let newer = CacheEntry::with_metadata("newer".to_string(), CacheMetadata::new(Some(ttl)).with_version(1));
+    cache.set("cas-test", newer).await.unwrap();
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    let entry = cache.get("cas-test").await.unwrap();
+    assert_eq!(entry.value, "newer");
+}
+
+#[tokio::test]
+async fn test_writes_increment_version() {
+    let cache = MokaCache::new(100);
+
+    for expected_version in 0..3 {
+        let _: String = cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "version-test")
+                .ttl(Duration::from_secs(60))
+                .force_fresh(true)
+                .compare_and_set(true)
+                .get_fresh_value(|| async { Ok("value".to_string()) })
+        ).await.unwrap();
+
+        let entry = cache.get("version-test").await.unwrap();
+        assert_eq!(entry.metadata.version, expected_version);
+    }
+}
+
+#[tokio::test]
+async fn test_cachified_with_metadata_reports_refresh_failures() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new();
+    let ttl = Duration::from_secs(10);
+    let stale = CacheEntry::builder("stale".to_string())
+        .ttl(ttl)
+        .created_ago(ttl + Duration::from_secs(5))
+        .build();
+    cache.set("info-test", stale).await.unwrap();
+
+    let options = |fail: bool| {
+        CachifiedOptionsBuilder::new(cache.clone(), "info-test")
+            .ttl(ttl)
+            .stale_while_revalidate(Duration::from_secs(60))
+            .config(config.clone())
+            .get_fresh_value(move || async move {
+                if fail {
+                    Err(CachifiedError::fresh_value("upstream down"))
+                } else {
+                    Ok("fresh".to_string())
+                }
+            })
+    };
+
+    for expected_failures in 0..2 {
+        let (value, info) = cachified_with_metadata(options(true)).await.unwrap();
+        assert_eq!(value, "stale");
+        assert!(info.cached);
+        assert!(info.stale_since.unwrap() >= Duration::from_secs(5));
+        assert_eq!(info.consecutive_refresh_failures, expected_failures);
+        assert!(config.drain(Duration::from_secs(1)).await);
+    }
+
+    let (_, info) = cachified_with_metadata(options(false)).await.unwrap();
+    assert_eq!(info.consecutive_refresh_failures, 2);
+    assert!(info.last_refresh_error.unwrap().contains("upstream down"));
+    assert!(config.drain(Duration::from_secs(1)).await);
+
+    // A successful refresh resets the failure state
+    let (value, info) = cachified_with_metadata(options(false)).await.unwrap();
+    assert_eq!(value, "fresh");
+    assert!(!info.is_stale());
+    assert_eq!(info.consecutive_refresh_failures, 0);
+    assert_eq!(info.last_refresh_error, None);
+}
+
+#[tokio::test]
+async fn test_fallback_value() {
+    let cache = MokaCache::new(100);
+    let reporter = Arc::new(CountingReporter::default());
+
+    let (value, info) = cachified_with_metadata(
+        CachifiedOptionsBuilder::new(cache.clone(), "fallback-value-test")
+            .ttl(Duration::from_secs(60))
+            .fallback_value("default".to_string())
+            .reporter(reporter.clone())
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await.unwrap();
+
+    assert_eq!(value, "default");
+    assert!(info.fallback);
+    assert!(!info.cached);
+    assert_eq!(reporter.errors.load(Ordering::SeqCst), 1);
+    assert_eq!(reporter.fallbacks.load(Ordering::SeqCst), 1);
+    // The fallback value is never cached
+    assert!(cache.get("fallback-value-test").await.is_none());
+}
+
+#[tokio::test]
+async fn test_fallback_to_cache_takes_precedence_over_fallback_value() {
+    let cache = MokaCache::new(100);
+    let expired = CacheEntry::builder("cached".to_string())
+        .ttl(Duration::from_secs(1))
+        .created_ago(Duration::from_secs(10))
+        .build();
+    cache.set("fallback-order-test", expired).await.unwrap();
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "fallback-order-test")
+            .fallback_to_cache(true)
+            .fallback_value_fn(|| "default".to_string())
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await.unwrap();
+
+    assert_eq!(value, "cached");
+}
+
+#[tokio::test]
+async fn test_stale_if_error_serves_values_within_window() {
+    let cache = MokaCache::new(100);
+    let expired = CacheEntry::builder("cached".to_string())
+        .ttl(Duration::from_secs(1))
+        .created_ago(Duration::from_secs(10))
+        .build();
+    cache.set("stale-if-error", expired).await.unwrap();
+
+    let options = || {
+        CachifiedOptionsBuilder::new(cache.clone(), "stale-if-error")
+            .ttl(Duration::from_secs(1))
+            .stale_if_error(Duration::from_secs(60))
+    };
+
+    let value: String = cachified(
+        options().get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await.unwrap();
+    assert_eq!(value, "cached");
+
+    // Stale values still have to pass validation
+    let result: Result<String, _> = cachified(
+        options()
+            .check_value(FunctionValidator::new(|_: &String| Err(CachifiedError::validation("Outdated"))))
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await;
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::FreshValue);
+}
+
+#[tokio::test]
+async fn test_stale_if_error_rejects_values_too_old() {
+    let cache = MokaCache::new(100);
+    let expired = CacheEntry::builder("cached".to_string())
+        .ttl(Duration::from_secs(1))
+        .created_ago(Duration::from_secs(120))
+        .build();
+    cache.set("stale-if-error-old", expired).await.unwrap();
+
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "stale-if-error-old")
+            .ttl(Duration::from_secs(1))
+            .stale_if_error(Duration::from_secs(60))
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await;
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::FreshValue);
+
+    // fallback_to_cache serves values of any age
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "stale-if-error-old")
+            .ttl(Duration::from_secs(1))
+            .stale_if_error(Duration::from_secs(60))
+            .fallback_to_cache(true)
+            .get_fresh_value(|| async { Err(CachifiedError::fresh_value("upstream down")) })
+    ).await.unwrap();
+    assert_eq!(value, "cached");
+}
+
+/// Async validator rejecting revoked tokens, like a revocation list lookup
+struct NotRevoked {
+    revoked: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl AsyncCheckValue<String> for NotRevoked {
+    async fn check(&self, value: &String) -> cachified::Result<()> {
+        sleep(Duration::from_millis(1)).await;
+        if self.revoked.lock().unwrap().contains(value) {
+            Err(CachifiedError::validation("Token revoked"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_async_validator_refetches_invalid_cached_values() {
+    let cache = MokaCache::new(100);
+    let revoked = Arc::new(Mutex::new(Vec::new()));
+    let call_count = Arc::new(AtomicUsize::new(0));
+
+    let get_token = || {
+        let call_count = call_count.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "token")
+                .ttl(Duration::from_secs(60))
+                .check_value_async(NotRevoked { revoked: revoked.clone() })
+                .error_stage(true)
+                .get_fresh_value(move || {
+                    let count = call_count.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { Ok(format!("token-{count}")) }
+                }),
+        )
+    };
+
+    assert_eq!(get_token().await.unwrap(), "token-1");
+    assert_eq!(get_token().await.unwrap(), "token-1");
+
+    // A revoked cached token is refetched like one failing a sync validator
+    revoked.lock().unwrap().push("token-1".to_string());
+    assert_eq!(get_token().await.unwrap(), "token-2");
+    assert_eq!(cache.get("token").await.unwrap().value, "token-2");
+
+    // Fresh values failing the check aren't cached
+    revoked.lock().unwrap().push("token-3".to_string());
+    cache.remove("token").await;
+    let error = get_token().await.unwrap_err();
+    assert_eq!(error.stage(), Some(Stage::ValidateFreshValue));
+    assert!(cache.get("token").await.is_none());
+}
+
+#[tokio::test]
+async fn test_cachified_set_writes_through() {
+    let cache = MokaCache::new(100);
+    let options = || {
+        CachifiedOptionsBuilder::new(cache.clone(), "write-through")
+            .ttl(Duration::from_secs(60))
+            .max_ttl(Duration::from_secs(30))
+            .check_value(NonEmptyStringValidator)
+            .error_stage(true)
+    };
+
+    let _: String = cachified(options().get_fresh_value(|| async { Ok("fetched".to_string()) }))
+        .await
+        .unwrap();
+    assert!(cachified_set(options(), "written".to_string()).await.unwrap());
+
+    // The entry is versioned and clamped like one written by cachified
+    let entry = cache.get("write-through").await.unwrap();
+    assert_eq!(entry.value, "written");
+    assert_eq!(entry.metadata.version, 1);
+    assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(30)));
+
+    // The value is served without fetching
+    let value: String = cachified(options().get_fresh_value(|| async { panic!("should not fetch") }))
+        .await
+        .unwrap();
+    assert_eq!(value, "written");
+
+    // Invalid values are rejected and not written
+    let error = cachified_set(options(), String::new()).await.unwrap_err();
+    assert_eq!(error.stage(), Some(Stage::ValidateFreshValue));
+    assert_eq!(cache.get("write-through").await.unwrap().value, "written");
+
+    // Values the TTL policy doesn't cache are skipped
+    let uncached = CachifiedOptionsBuilder::new(cache.clone(), "write-through-uncached");
+    assert!(!cachified_set(uncached, "value".to_string()).await.unwrap());
+    assert!(cache.get("write-through-uncached").await.is_none());
+}
+
+#[tokio::test]
+async fn test_clock_skew_tolerance_delays_expiry() {
+    let cache = MokaCache::new(100);
+    let ttl = Duration::from_secs(60);
+    // Written by a process whose clock is 5 seconds behind, so it looks expired
+    let entry = CacheEntry::builder("cached".to_string())
+        .ttl(ttl)
+        .created_ago(ttl + Duration::from_secs(5))
+        .build();
+    cache.set("skew-test", entry).await.unwrap();
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "skew-test")
+            .ttl(ttl)
+            .clock_skew_tolerance(Duration::from_secs(10))
+            .get_fresh_value(|| async { Ok("fresh".to_string()) })
+    ).await.unwrap();
+    assert_eq!(value, "cached");
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "skew-test")
+            .ttl(ttl)
+            .get_fresh_value(|| async { Ok("fresh".to_string()) })
+    ).await.unwrap();
+    assert_eq!(value, "fresh");
+}
+
+#[tokio::test]
+async fn test_cachified_map_entry_fetches_map_once() {
+    let cache = MokaCache::new(100);
+    let fetches = Arc::new(AtomicUsize::new(0));
+
+    let mut values = Vec::new();
+    for field in ["a", "b", "missing"] {
+        let fetches = fetches.clone();
+        values.push(
+            cachified_map_entry(
+                CachifiedOptionsBuilder::new(cache.clone(), "map-test")
+                    .ttl(Duration::from_secs(60))
+                    .get_fresh_value(move || {
+                        let fetches = fetches.clone();
+                        async move {
+                            fetches.fetch_add(1, Ordering::SeqCst);
+                            Ok(std::collections::HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]))
+                        }
+                    }),
+                field,
+            )
+            .await
+            .unwrap(),
+        );
+    }
+
+    assert_eq!(values, [Some(1), Some(2), None]);
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_cachified_entry_returns_served_metadata() {
+    let cache = MokaCache::new(100);
+    let options = || {
+        CachifiedOptionsBuilder::new(cache.clone(), "entry-test")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async { Ok("value".to_string()) })
+    };
+
+    // A fetched value comes with the metadata it was written with
+    let fetched = cachified_entry(options()).await.unwrap();
+    let stored = cache.get("entry-test").await.unwrap();
+    assert_eq!(fetched.value, "value");
+    assert_eq!(fetched.metadata, stored.metadata);
+
+    // A cached value comes with its stored metadata
+    let cached = cachified_entry(options()).await.unwrap();
+    assert_eq!(cached.metadata, stored.metadata);
+
+    // Values that aren't cached expire right away
+    let uncached = cachified_entry(
+        CachifiedOptionsBuilder::new(cache.clone(), "entry-test-uncached")
+            .get_fresh_value(|| async { Ok("value".to_string()) })
+    ).await.unwrap();
+    assert_eq!(uncached.metadata.ttl, Some(Duration::ZERO));
+    assert!(cache.get("entry-test-uncached").await.is_none());
+}
+
+#[tokio::test]
+async fn test_single_flight_fetches_once() {
+    let cache = MokaCache::new(100);
+    let config = CachifiedConfig::new().single_flight(true);
+    let fetches = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = (0..100)
+        .map(|_| {
+            let cache = cache.clone();
+            let config = config.clone();
+            let fetches = fetches.clone();
+            tokio::spawn(async move {
+                cachified(
+                    CachifiedOptionsBuilder::new(cache, "single-flight")
+                        .ttl(Duration::from_secs(60))
+                        .config(config)
+                        .get_fresh_value(move || {
+                            let fetches = fetches.clone();
+                            async move {
+                                fetches.fetch_add(1, Ordering::SeqCst);
+                                sleep(Duration::from_millis(50)).await;
+                                Ok("value".to_string())
+                            }
+                        })
+                ).await
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        assert_eq!(task.await.unwrap().unwrap(), "value");
+    }
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.get("single-flight").await.unwrap().metadata.version, 0);
+}
+
+#[tokio::test]
+async fn test_single_flight_shares_errors() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+    let config = CachifiedConfig::new().single_flight(true);
+    let fetches = Arc::new(AtomicUsize::new(0));
+
+    let call = || {
+        let fetches = fetches.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), "single-flight-error")
+                .ttl(Duration::from_secs(60))
+                .config(config.clone())
+                .get_fresh_value(move || {
+                    let fetches = fetches.clone();
+                    async move {
+                        fetches.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(50)).await;
+                        Err::<String, _>(CachifiedError::fresh_value("upstream down"))
+                    }
+                })
+        )
+    };
+
+    let (first, second) = tokio::join!(call(), call());
+    assert_eq!(first.unwrap_err().to_string(), second.unwrap_err().to_string());
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+    // The failed fetch isn't joined by later calls
+    assert!(call().await.is_err());
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_get_fresh_value_with_ttl() {
+    let cache = MokaCache::new(100);
+    let call = |key: &'static str, ttl: Option<Duration>| {
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .stale_while_revalidate(Duration::from_secs(60))
+                .get_fresh_value_with_ttl(move || async move { Ok((key.to_string(), ttl)) })
+        )
+    };
+
+    // The returned TTL overrides the builder TTL, None falls back to it
+    call("with-ttl", Some(Duration::from_millis(50))).await.unwrap();
+    call("without-ttl", None).await.unwrap();
+    assert_eq!(cache.get("with-ttl").await.unwrap().metadata.ttl, Some(Duration::from_millis(50)));
+    assert_eq!(cache.get("without-ttl").await.unwrap().metadata.ttl, Some(Duration::from_secs(60)));
+
+    // A zero TTL isn't cached
+    call("zero-ttl", Some(Duration::ZERO)).await.unwrap();
+    assert!(cache.get("zero-ttl").await.is_none());
+
+    // Once the returned TTL elapsed, the value is served stale while revalidating
+    sleep(Duration::from_millis(80)).await;
+    let stale: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "with-ttl")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(60))
+            .get_fresh_value_with_ttl(|| async { Ok(("refreshed".to_string(), Some(Duration::from_secs(5)))) })
+    ).await.unwrap();
+    assert_eq!(stale, "with-ttl");
+
+    sleep(Duration::from_millis(50)).await;
+    let entry = cache.get("with-ttl").await.unwrap();
+    assert_eq!(entry.value, "refreshed");
+    assert_eq!(entry.metadata.ttl, Some(Duration::from_secs(5)));
+}
+
+#[tokio::test]
+async fn test_fresh_value_timeout() {
+    let cache = MokaCache::new(100);
+    let slow = || async {
+        sleep(Duration::from_secs(10)).await;
+        Ok("slow".to_string())
+    };
+
+    // A cold cache has nothing to fall back to
+    let started = std::time::Instant::now();
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "timeout-test")
+            .ttl(Duration::from_millis(50))
+            .fresh_value_timeout(Duration::from_millis(50))
+            .error_stage(true)
+            .get_fresh_value(slow)
+    ).await;
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Timeout);
+    assert_eq!(error.stage(), Some(Stage::GetFreshValue));
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // An expired value is served if falling back to the cache is allowed
+    let expired = CacheEntry::builder("cached".to_string())
+        .ttl(Duration::from_millis(50))
+        .created_ago(Duration::from_secs(1))
+        .build();
+    cache.set("timeout-test", expired).await.unwrap();
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "timeout-test")
+            .ttl(Duration::from_millis(50))
+            .fresh_value_timeout(Duration::from_millis(50))
+            .fallback_to_cache(true)
+            .get_fresh_value(slow)
+    ).await.unwrap();
+    assert_eq!(value, "cached");
+}
+
+/// Fresh value function failing `failures` times before succeeding
+fn flaky(calls: Arc<AtomicUsize>, failures: usize) -> impl Fn() -> futures_util::future::BoxFuture<'static, cachified::Result<String>> + Send + Sync {
+    move || {
+        let calls = calls.clone();
+        Box::pin(async move {
+            if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                Err(CachifiedError::fresh_value("transient failure"))
+            } else {
+                Ok("fresh".to_string())
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_retry_transient_failures() {
+    let cache = MokaCache::new(100);
+
+    // Fails twice, then succeeds on the third attempt
+    let calls = Arc::new(AtomicUsize::new(0));
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "retry-test")
+            .ttl(Duration::from_secs(60))
+            .retry(3, Duration::from_millis(5))
+            .get_fresh_value(flaky(calls.clone(), 2))
+    ).await.unwrap();
+    assert_eq!(value, "fresh");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.get("retry-test").await.unwrap().value, "fresh");
+
+    // Gives up once the attempts are used up
+    let calls = Arc::new(AtomicUsize::new(0));
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "retry-exhausted")
+            .retry_policy(cachified::RetryPolicy::new(2, Duration::from_millis(5)).exponential())
+            .get_fresh_value(flaky(calls.clone(), 2))
+    ).await;
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::FreshValue);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // The predicate decides which errors are retried
+    let calls = Arc::new(AtomicUsize::new(0));
+    let result: Result<String, _> = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "retry-if")
+            .retry(3, Duration::from_millis(5))
+            .retry_if(|error| error.kind() == ErrorKind::Timeout)
+            .get_fresh_value(flaky(calls.clone(), 2))
+    ).await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_negative_ttl() {
+    let cache: MokaCache<Option<String>> = MokaCache::new(100);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let find = |key: &'static str, value: Option<&'static str>| {
+        let calls = calls.clone();
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .negative_ttl(Duration::from_millis(50))
+                .get_fresh_value(move || {
+                    let calls = calls.clone();
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        Ok(value.map(str::to_string))
+                    }
+                })
+        )
+    };
+
+    // Missing entities are cached with the negative TTL, found ones with the regular TTL
+    assert_eq!(find("missing", None).await.unwrap(), None);
+    assert_eq!(find("found", Some("user")).await.unwrap(), Some("user".to_string()));
+    assert_eq!(cache.get("missing").await.unwrap().metadata.ttl, Some(Duration::from_millis(50)));
+    assert_eq!(cache.get("found").await.unwrap().metadata.ttl, Some(Duration::from_secs(60)));
+
+    // The negative result short-circuits fetches until it expires
+    assert_eq!(find("missing", Some("created")).await.unwrap(), None);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    sleep(Duration::from_millis(80)).await;
+    assert_eq!(find("missing", Some("created")).await.unwrap(), Some("created".to_string()));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_cache_stats_count_outcomes() {
+    let cache = MokaCache::new(100);
+    let stats = Arc::new(cachified::CacheStats::new());
+    let call = |key: &'static str, fail: bool| {
+        cachified(
+            CachifiedOptionsBuilder::new(cache.clone(), key)
+                .ttl(Duration::from_secs(60))
+                .stale_while_revalidate(Duration::from_secs(60))
+                .fallback_to_cache(true)
+                .stats(stats.clone())
+                .get_fresh_value(move || async move {
+                    if fail {
+                        Err(CachifiedError::fresh_value("down"))
+                    } else {
+                        Ok("fresh".to_string())
+                    }
+                })
+        )
+    };
+
+    // Miss and write, then a hit
+    call("key", false).await.unwrap();
+    call("key", false).await.unwrap();
+
+    // Stale hit, refreshed and written in the background
+    set_stale_entry(&cache, "stale", Duration::from_secs(10), Duration::from_secs(60), 0.5).await;
+    assert_eq!(call("stale", false).await.unwrap(), "stale");
+    sleep(Duration::from_millis(50)).await;
+
+    // Failed fetch falling back to a value that is too old to be served as stale-while-revalidate
+    set_stale_entry(&cache, "old", Duration::from_secs(10), Duration::from_secs(600), 0.5).await;
+    assert_eq!(call("old", true).await.unwrap(), "stale");
+
+    let snapshot = stats.snapshot();
+    assert_eq!(
+        snapshot,
+        cachified::CacheStatsSnapshot {
+            hits: 2,
+            misses: 2,
+            stale_served: 2,
+            fresh_errors: 1,
+            writes: 2,
+        }
+    );
+    assert_eq!(snapshot.hit_rate(), Some(0.5));
+}
+
+/// Handle that can't be cloned, like a connection or the end of a channel
+struct Connection {
+    value: String,
+}
+
+impl Connection {
+    async fn query(self) -> cachified::Result<String> {
+        Ok(self.value)
+    }
+}
+
+#[tokio::test]
+async fn test_get_fresh_value_once_takes_ownership() {
+    let cache = MokaCache::new(100);
+    let connection = Connection { value: "from-connection".to_string() };
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "once")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value_once(move || connection.query()),
+    ).await.unwrap();
+    assert_eq!(value, "from-connection");
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache, "once")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value_once(|| async { Ok("other".to_string()) }),
+    ).await.unwrap();
+    assert_eq!(value, "from-connection");
+}
+
+#[tokio::test]
+async fn test_get_fresh_value_once_refreshes_in_background() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let pending: Arc<Mutex<Vec<BoxFuture<'static, ()>>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let metadata = CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60)));
+    cache.set("once-swr", CacheEntry::with_metadata("stale-value".to_string(), metadata)).await.unwrap();
+    clock.advance(Duration::from_secs(70));
+
+    let connection = Connection { value: "fresh-value".to_string() };
+    let collected = pending.clone();
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "once-swr")
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(30))
+            .clock(clock.clone())
+            .wait_until(move |refresh| collected.lock().unwrap().push(refresh))
+            .get_fresh_value_once(move || connection.query()),
+    ).await.unwrap();
+    assert_eq!(value, "stale-value");
+
+    // The background refresh is the one call of the closure
+    let refreshes: Vec<_> = pending.lock().unwrap().drain(..).collect();
+    futures_util::future::join_all(refreshes).await;
+    assert_eq!(cache.get("once-swr").await.unwrap().value, "fresh-value");
+}
+
+#[tokio::test]
+async fn test_get_fresh_value_once_is_not_retried() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+    let attempts = Arc::new(AtomicUsize::new(0));
+
+    let counter = attempts.clone();
+    let result = cachified(
+        CachifiedOptionsBuilder::new(cache, "once-retry")
+            .retry(3, Duration::from_millis(5))
+            .get_fresh_value_once(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err(CachifiedError::fresh_value("upstream down"))
+            }),
+    ).await;
+
+    // Retries are skipped and the error of the only attempt is kept
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    match result.unwrap_err() {
+        CachifiedError::FreshValueError(msg) => assert_eq!(msg, "upstream down"),
+        error => panic!("Wrong error type: {error:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_no_expiry_caches_value() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::starting_now();
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let get = |options: CachifiedOptionsBuilder<String, MokaCache<String>>| {
+        let calls = calls.clone();
+        cachified(options.clock(clock.clone()).get_fresh_value(move || {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok("value".to_string())
+            }
+        }))
+    };
+
+    // Without a TTL, values aren't cached
+    get(CachifiedOptionsBuilder::new(cache.clone(), "no-ttl")).await.unwrap();
+    get(CachifiedOptionsBuilder::new(cache.clone(), "no-ttl")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert!(cache.get("no-ttl").await.is_none());
+
+    // With no_expiry, they are cached forever, even over an earlier TTL
+    let forever = || {
+        CachifiedOptionsBuilder::new(cache.clone(), "no-expiry")
+            .ttl(Duration::from_secs(60))
+            .no_expiry()
+    };
+    get(forever()).await.unwrap();
+    clock.advance(Duration::from_secs(365 * 24 * 60 * 60));
+    assert_eq!(get(forever()).await.unwrap(), "value");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.get("no-expiry").await.unwrap().metadata.ttl, None);
+
+    // max_ttl still caps the lifetime
+    get(CachifiedOptionsBuilder::new(cache.clone(), "no-expiry-capped").no_expiry().max_ttl(Duration::from_secs(60)))
+        .await
+        .unwrap();
+    assert_eq!(cache.get("no-expiry-capped").await.unwrap().metadata.ttl, Some(Duration::from_secs(60)));
+}
+
+#[tokio::test]
+async fn test_extend_ttl_uses_injected_clock() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::new(Duration::from_secs(1_000));
+    let entry = CacheEntry::with_metadata(
+        "session".to_string(),
+        CacheMetadata::with_time(clock.now(), Some(Duration::from_secs(60))),
+    );
+    cache.set("session-1", entry).await.unwrap();
+
+    clock.advance(Duration::from_secs(50));
+    assert!(extend_ttl(&cache, "session-1", Duration::from_secs(3600), &clock).await.unwrap());
+    assert!(!extend_ttl(&cache, "session-2", Duration::from_secs(3600), &clock).await.unwrap());
+
+    let metadata = cache.get_metadata("session-1").await.unwrap();
+    assert_eq!(metadata.created_time, Duration::from_secs(1_000));
+    assert_eq!(metadata.ttl, Some(Duration::from_secs(3650)));
+    assert_eq!(metadata.version, 1);
+}
@@ -1,5 +1,5 @@
-use cachified::{cachified, soft_purge, CachifiedOptionsBuilder, MokaCache, SoftPurgeOptions, Cache, CacheEntry, CacheMetadata};
-use std::time::Duration;
+use cachified::{clock::{Clock, MockClock}, cachified, soft_purge, soft_purge_many, soft_purge_prefix, CachifiedOptionsBuilder, HashMapCache, MokaCache, SoftPurgeOptions, SoftPurgeOutcome, Cache, CacheEntry, CacheMetadata};
+use std::time::{Duration, SystemTime};
 use tokio::time::sleep;
 use std::sync::{Arc, Mutex};
 
@@ -107,14 +107,11 @@ async fn test_soft_purge_already_expired() {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap();
-    
-    let expired_entry = CacheEntry {
-        value: "expired-value".to_string(),
-        metadata: CacheMetadata {
-            created_time: now - Duration::from_secs(100),
-            ttl: Some(Duration::from_secs(50)), // Expired 50 seconds ago
-        },
-    };
+
+    let expired_entry = CacheEntry::builder("expired-value".to_string())
+        .ttl(Duration::from_secs(50)) // Expired 50 seconds ago
+        .created_time(now - Duration::from_secs(100))
+        .build();
     
     cache.set("expired-test", expired_entry).await.unwrap();
 
@@ -137,6 +134,21 @@ async fn test_soft_purge_already_expired() {
     assert!(entry.metadata.created_time >= now - Duration::from_secs(1));
 }
 
+#[tokio::test]
+async fn test_soft_purge_with_system_time() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+    let created_at = SystemTime::now() - Duration::from_secs(10);
+    let metadata = CacheMetadata::from_system_time(created_at, Some(Duration::from_secs(300)));
+    cache.set("system-time", CacheEntry { value: "value".to_string(), metadata }).await.unwrap();
+
+    let entry = cache.get("system-time").await.unwrap();
+    assert_eq!(entry.metadata.created_at(), created_at);
+    assert!(!entry.is_expired_at(SystemTime::now()));
+
+    soft_purge(&cache, SoftPurgeOptions::new("system-time")).await.unwrap();
+    assert!(cache.get("system-time").await.unwrap().is_expired_at(SystemTime::now()));
+}
+
 #[tokio::test] 
 async fn test_soft_purge_options_builder() {
     // Test that SoftPurgeOptions builder pattern works correctly
@@ -151,3 +163,126 @@ async fn test_soft_purge_options_builder() {
     assert_eq!(default_options.key, "another-key");
     assert_eq!(default_options.stale_while_revalidate, None);
 }
+
+#[tokio::test]
+async fn test_soft_purge_prefix() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+
+    for key in ["tenant-1:users", "tenant-1:settings", "tenant-2:users"] {
+        cache.set(key, CacheEntry::new(key.to_string(), Some(Duration::from_secs(300)))).await.unwrap();
+    }
+
+    let report = soft_purge_prefix(&cache, SoftPurgeOptions::new("tenant-1:")).await.unwrap();
+    assert_eq!(report.purged, 2);
+    assert!(report.failed.is_empty());
+
+    let now = SystemTime::now();
+    assert!(cache.get("tenant-1:users").await.unwrap().is_expired_at(now));
+    assert!(cache.get("tenant-1:settings").await.unwrap().is_expired_at(now));
+
+    // Entries of other tenants are left untouched
+    let other = cache.get("tenant-2:users").await.unwrap();
+    assert!(!other.is_expired_at(now));
+}
+
+#[tokio::test]
+async fn test_soft_purge_many() {
+    let cache: MokaCache<String> = MokaCache::new(100);
+
+    for key in ["items", "item:42", "item:43"] {
+        cache.set(key, CacheEntry::new(key.to_string(), Some(Duration::from_secs(300)))).await.unwrap();
+    }
+
+    let outcomes = soft_purge_many(&cache, [
+        SoftPurgeOptions::new("items"),
+        SoftPurgeOptions::new("item:missing"),
+        SoftPurgeOptions::new("item:42"),
+    ]).await;
+
+    let keys: Vec<&str> = outcomes.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["items", "item:missing", "item:42"]);
+    assert!(matches!(outcomes[0].1, SoftPurgeOutcome::Purged));
+    assert!(matches!(outcomes[1].1, SoftPurgeOutcome::Missing));
+    assert!(matches!(outcomes[2].1, SoftPurgeOutcome::Purged));
+
+    let now = SystemTime::now();
+    assert!(cache.get("items").await.unwrap().is_expired_at(now));
+    assert!(cache.get("item:42").await.unwrap().is_expired_at(now));
+    assert!(!cache.get("item:43").await.unwrap().is_expired_at(now));
+    assert!(cache.get("item:missing").await.is_none());
+}
+
+#[tokio::test]
+async fn test_soft_purge_uses_injected_clock() {
+    let cache = MokaCache::new(100);
+    let clock = MockClock::new(Duration::from_secs(1_000));
+
+    // Already expired at the time of the mock clock
+    cache
+        .set("clocked", CacheEntry::with_time("value".to_string(), Duration::from_secs(500), Some(Duration::from_secs(60))))
+        .await
+        .unwrap();
+
+    soft_purge(&cache, SoftPurgeOptions::new("clocked").clock(clock.clone()))
+        .await
+        .unwrap();
+
+    // The stale window starts at the purge time read from the clock
+    let entry = cache.get("clocked").await.unwrap();
+    assert_eq!(entry.metadata.ttl, Some(Duration::ZERO));
+    assert_eq!(entry.metadata.created_time, clock.now());
+}
+
+#[tokio::test]
+async fn test_soft_purge_keeps_entry_through_stale_window() {
+    let clock = MockClock::new(Duration::from_secs(1_000));
+    let cache: HashMapCache<String> = HashMapCache::new().with_clock(clock.clone());
+
+    for key in ["single", "many", "prefix:1"] {
+        cache
+            .set(key, CacheEntry::with_time("original-value".to_string(), clock.now(), Some(Duration::from_secs(600))))
+            .await
+            .unwrap();
+    }
+
+    let swr = Duration::from_secs(300);
+    soft_purge(&cache, SoftPurgeOptions::new("single").stale_while_revalidate(swr).clock(clock.clone()))
+        .await
+        .unwrap();
+    soft_purge_many(&cache, [SoftPurgeOptions::new("many").stale_while_revalidate(swr).clock(clock.clone())]).await;
+    soft_purge_prefix(&cache, SoftPurgeOptions::new("prefix:").stale_while_revalidate(swr).clock(clock.clone()))
+        .await
+        .unwrap();
+
+    // The backend evicts at the end of the stale window, not at the purge time
+    clock.advance(Duration::from_secs(200));
+    for key in ["single", "many", "prefix:1"] {
+        let entry = cache.get(key).await.unwrap();
+        assert_eq!(entry.metadata.swr, Some(swr));
+    }
+
+    // cachified serves the purged value stale while it refreshes
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "single")
+            .ttl(Duration::from_secs(600))
+            .clock(clock.clone())
+            .get_fresh_value(|| async { Ok("refreshed-value".to_string()) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, "original-value");
+
+    clock.advance(Duration::from_secs(200));
+    assert!(cache.get("many").await.is_none());
+}
+
+#[tokio::test]
+async fn test_soft_purge_defaults_to_five_minute_stale_window() {
+    let cache: HashMapCache<String> = HashMapCache::new();
+    cache.set("key", CacheEntry::new("value".to_string(), Some(Duration::from_secs(60)))).await.unwrap();
+
+    soft_purge(&cache, SoftPurgeOptions::new("key")).await.unwrap();
+
+    let entry = cache.get("key").await.unwrap();
+    assert_eq!(entry.metadata.swr, Some(Duration::from_secs(300)));
+}
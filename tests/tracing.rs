@@ -0,0 +1,206 @@
+#![cfg(feature = "tracing")]
+
+use cachified::{cachified, Cache, CacheEntry, CachifiedError, CachifiedOptionsBuilder, HashMapCache, KeyDisplay};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+type Fields = HashMap<String, String>;
+
+/// Layer keeping the fields of all spans and events
+#[derive(Clone, Default)]
+struct RecordingLayer {
+    /// Names and fields of spans, in the order they were created
+    spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl RecordingLayer {
+    /// Fields of all `cachified` spans, in the order they were created
+    fn cachified_spans(&self) -> Vec<Fields> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| *name == "cachified")
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+
+    /// Fields of the events with the given message
+    fn events(&self, message: &str) -> Vec<Fields> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|fields| fields.get("message").is_some_and(|m| m == message))
+            .cloned()
+            .collect()
+    }
+
+    /// Record everything within the current thread until the guard is dropped
+    fn set_default(&self) -> tracing::subscriber::DefaultGuard {
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(self.clone()))
+    }
+}
+
+/// Index of a span in `RecordingLayer::spans`, as span IDs are reused
+struct SpanIndex(usize);
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl<S> Layer<S> for RecordingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::new();
+        attributes.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanIndex(spans.len()));
+        }
+        spans.push((attributes.metadata().name(), fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(SpanIndex(index)) = span.extensions().get::<SpanIndex>()
+        {
+            values.record(&mut FieldVisitor(&mut self.spans.lock().unwrap()[*index].1));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events.lock().unwrap().push(fields);
+    }
+}
+
+#[tokio::test]
+async fn test_cachified_span_records_outcome() {
+    let subscriber = RecordingLayer::default();
+    let _guard = subscriber.set_default();
+    let cache: HashMapCache<String> = HashMapCache::new();
+
+    let get = |key: &'static str| {
+        CachifiedOptionsBuilder::new(cache.clone(), key)
+            .ttl(Duration::from_secs(60))
+            .stale_while_revalidate(Duration::from_secs(300))
+            .key_display(KeyDisplay::Full)
+    };
+
+    // Miss, then hit
+    for _ in 0..2 {
+        let value: String = cachified(get("user:1").get_fresh_value(|| async { Ok("fresh".to_string()) }))
+            .await
+            .unwrap();
+        assert_eq!(value, "fresh");
+    }
+
+    // Expired, but within the stale-while-revalidate window
+    let mut stale = CacheEntry::builder("stale".to_string())
+        .ttl(Duration::from_secs(60))
+        .created_ago(Duration::from_secs(120))
+        .build();
+    stale.metadata.swr = Some(Duration::from_secs(300));
+    cache.set("user:2", stale).await.unwrap();
+    let value: String = cachified(get("user:2").get_fresh_value(|| async { Ok("fresh".to_string()) }))
+        .await
+        .unwrap();
+    assert_eq!(value, "stale");
+
+    // Failing fetch without a cached value
+    let result: Result<String, _> = cachified(
+        get("user:3").get_fresh_value(|| async { Err(CachifiedError::fresh_value("unavailable")) }),
+    )
+    .await;
+    assert!(result.is_err());
+
+    // Forced fetch despite a cached value
+    let value: String = cachified(
+        get("user:1")
+            .force_fresh(true)
+            .get_fresh_value(|| async { Ok("forced".to_string()) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, "forced");
+
+    let spans = subscriber.cachified_spans();
+    let recorded: Vec<(&str, &str, &str)> = spans
+        .iter()
+        .map(|fields| {
+            (
+                fields["cache.key"].as_str(),
+                fields["cache.force_fresh"].as_str(),
+                fields["cache.outcome"].as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            ("user:1", "false", "miss"),
+            ("user:1", "false", "hit"),
+            ("user:2", "false", "stale"),
+            ("user:3", "false", "error"),
+            ("user:1", "true", "miss"),
+        ]
+    );
+    assert!(spans.iter().all(|fields| fields["cache.backend"] == "hash_map"));
+    assert_eq!(spans[3]["cache.stage"], "Getting the fresh value");
+    assert!(!spans[0].contains_key("cache.stage"));
+}
+
+#[tokio::test]
+async fn test_fresh_value_fetch_records_elapsed_time() {
+    let subscriber = RecordingLayer::default();
+    let _guard = subscriber.set_default();
+    let cache: HashMapCache<String> = HashMapCache::new();
+
+    let value: String = cachified(
+        CachifiedOptionsBuilder::new(cache.clone(), "slow")
+            .ttl(Duration::from_secs(60))
+            .get_fresh_value(|| async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok("value".to_string())
+            }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, "value");
+
+    assert_eq!(subscriber.events("cache miss, getting fresh value").len(), 1);
+    let finished = subscriber.events("getting fresh value finished");
+    assert_eq!(finished.len(), 1);
+    assert_eq!(finished[0]["success"], "true");
+    assert!(finished[0].contains_key("elapsed"));
+
+    // Hits don't fetch
+    let _: String = cachified(
+        CachifiedOptionsBuilder::new(cache, "slow").get_fresh_value(|| async { Ok("other".to_string()) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(subscriber.events("cache hit").len(), 1);
+    assert_eq!(subscriber.events("getting fresh value finished").len(), 1);
+}